@@ -7,9 +7,28 @@ CREATE TABLE address_book
     multi_address                NOT NULL
 );
 
+DROP INDEX swaps_role;
+DROP INDEX swaps_counterparty_peer_id;
+DROP INDEX swaps_start_of_swap;
+DROP INDEX hbits_side_swap_id;
+DROP INDEX herc20s_side_swap_id;
+DROP INDEX halbits_side_swap_id;
+DROP INDEX swap_outcomes_outcome;
+
 DROP VIEW swap_contexts;
 
 DROP TABLE orders;
 DROP TABLE btc_dai_orders;
 DROP TABLE order_hbit_params;
 DROP TABLE order_herc20_params;
+DROP TABLE lnd_executions;
+DROP TABLE wallet_executions;
+DROP TABLE wallet_birth_heights;
+DROP TABLE hbit_spends;
+DROP TABLE webhook_deliveries;
+DROP TABLE webhook_events;
+DROP TABLE fund_handouts;
+DROP TABLE swap_cancellations;
+DROP TABLE swap_outcomes;
+DROP TABLE peer_addresses;
+DROP TABLE peers;
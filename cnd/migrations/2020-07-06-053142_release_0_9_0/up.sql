@@ -85,4 +85,114 @@ CREATE TABLE order_swaps
     swap_id UNIQUE NOT NULL,
     FOREIGN KEY (order_id) REFERENCES orders (id),
     FOREIGN KEY (swap_id) REFERENCES swaps (id)
-)
+);
+
+CREATE TABLE peers
+(
+    id INTEGER       NOT NULL PRIMARY KEY,
+    peer_id UNIQUE   NOT NULL,
+    successful_dials NOT NULL,
+    failed_dials     NOT NULL,
+    last_seen_at,
+    last_order_at,
+    violations       NOT NULL,
+    banned           NOT NULL
+);
+
+CREATE TABLE peer_addresses
+(
+    id INTEGER   NOT NULL PRIMARY KEY,
+    peer_id      NOT NULL,
+    address      NOT NULL,
+    last_seen_at NOT NULL,
+    UNIQUE (peer_id, address),
+    FOREIGN KEY (peer_id) REFERENCES peers (id)
+);
+
+CREATE TABLE swap_outcomes
+(
+    id INTEGER     NOT NULL PRIMARY KEY,
+    swap_id UNIQUE NOT NULL,
+    outcome        NOT NULL,
+    FOREIGN KEY (swap_id) REFERENCES swaps (id)
+);
+
+CREATE TABLE swap_cancellations
+(
+    id INTEGER     NOT NULL PRIMARY KEY,
+    swap_id UNIQUE NOT NULL,
+    cancelled_by   NOT NULL,
+    cancelled_at   NOT NULL,
+    FOREIGN KEY (swap_id) REFERENCES swaps (id)
+);
+
+CREATE TABLE fund_handouts
+(
+    id INTEGER     NOT NULL PRIMARY KEY,
+    swap_id UNIQUE NOT NULL,
+    handed_out_at  NOT NULL,
+    FOREIGN KEY (swap_id) REFERENCES swaps (id)
+);
+
+CREATE TABLE webhook_deliveries
+(
+    id INTEGER      NOT NULL PRIMARY KEY,
+    url             NOT NULL,
+    payload         NOT NULL,
+    attempts        NOT NULL,
+    next_attempt_at NOT NULL
+);
+
+CREATE TABLE wallet_birth_heights
+(
+    id INTEGER NOT NULL PRIMARY KEY,
+    wallet     NOT NULL UNIQUE,
+    height     NOT NULL
+);
+
+CREATE TABLE webhook_events
+(
+    id INTEGER NOT NULL PRIMARY KEY,
+    event      NOT NULL,
+    subject    NOT NULL,
+    data       NOT NULL,
+    UNIQUE (event, subject)
+);
+
+CREATE TABLE wallet_executions
+(
+    id INTEGER  NOT NULL PRIMARY KEY,
+    swap_id     NOT NULL,
+    action      NOT NULL,
+    succeeded   NOT NULL,
+    executed_at NOT NULL,
+    FOREIGN KEY (swap_id) REFERENCES swaps (id)
+);
+
+CREATE TABLE lnd_executions
+(
+    id INTEGER  NOT NULL PRIMARY KEY,
+    swap_id     NOT NULL,
+    action      NOT NULL,
+    succeeded   NOT NULL,
+    executed_at NOT NULL,
+    FOREIGN KEY (swap_id) REFERENCES swaps (id)
+);
+
+CREATE TABLE hbit_spends
+(
+    id INTEGER NOT NULL PRIMARY KEY,
+    swap_id    NOT NULL,
+    kind       NOT NULL,
+    fee_rate   NOT NULL,
+    FOREIGN KEY (swap_id) REFERENCES swaps (id)
+);
+
+-- Indices backing the filters of GET /swaps
+CREATE INDEX swaps_role ON swaps (role);
+CREATE INDEX swaps_counterparty_peer_id ON swaps (counterparty_peer_id);
+CREATE INDEX swaps_start_of_swap ON swaps (start_of_swap);
+CREATE INDEX hbits_side_swap_id ON hbits (side, swap_id);
+CREATE INDEX herc20s_side_swap_id ON herc20s (side, swap_id);
+CREATE INDEX halbits_side_swap_id ON halbits (side, swap_id);
+CREATE INDEX swap_outcomes_outcome ON swap_outcomes (outcome);
@@ -2,7 +2,7 @@ mod file;
 mod settings;
 mod validation;
 
-use crate::{ethereum, ethereum::ChainId, fs};
+use crate::{bitcoin::SatPerVbyte, ethereum, ethereum::ChainId, fs};
 use anyhow::{Context, Result};
 use conquer_once::Lazy;
 use libp2p::Multiaddr;
@@ -11,8 +11,8 @@ use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, path::PathBuf, str::FromStr};
 
 pub use self::{
-    file::File,
-    settings::{AllowedOrigins, Settings},
+    file::{File, Tls, Webhook},
+    settings::{AllowedOrigins, Settings, Webhooks},
     validation::validate_connection_to_network,
 };
 use comit::ledger;
@@ -23,6 +23,10 @@ static BITCOIND_RPC_REGTEST: Lazy<Url> = Lazy::new(|| parse_unchecked("http://lo
 
 static LND_URL: Lazy<Url> = Lazy::new(|| parse_unchecked("https://localhost:8080"));
 
+/// How long we wait before asking LND again whether an invoice or payment
+/// changed.
+const LND_RETRY_INTERVAL_MS: u64 = 100;
+
 static WEB3_URL: Lazy<Url> = Lazy::new(|| parse_unchecked("http://localhost:8545"));
 
 /// The DAI token contract on Ethereum mainnet.
@@ -62,6 +66,7 @@ impl Data {
 pub struct Bitcoin {
     pub network: ledger::Bitcoin,
     pub bitcoind: Bitcoind,
+    pub fees: BitcoinFees,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -69,11 +74,21 @@ pub struct Bitcoind {
     pub node_url: Url,
 }
 
+/// How cnd determines the fee rate of the Bitcoin transactions it signs,
+/// i.e. the redeem and refund transactions of hbit.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum BitcoinFees {
+    Static { sat_per_vbyte: SatPerVbyte },
+    Bitcoind { target_blocks: u16 },
+}
+
 impl Bitcoin {
     fn new(network: ledger::Bitcoin) -> Self {
         Self {
             network,
             bitcoind: Bitcoind::new(network),
+            fees: BitcoinFees::new(network),
         }
     }
 
@@ -92,8 +107,13 @@ impl Bitcoin {
 
         let network = bitcoin.network;
         let bitcoind = bitcoin.bitcoind.unwrap_or_else(|| Bitcoind::new(network));
+        let fees = bitcoin.fees.unwrap_or_else(|| BitcoinFees::new(network));
 
-        Ok(Bitcoin { network, bitcoind })
+        Ok(Bitcoin {
+            network,
+            bitcoind,
+            fees,
+        })
     }
 }
 
@@ -109,11 +129,27 @@ impl Bitcoind {
     }
 }
 
+impl BitcoinFees {
+    /// bitcoind cannot estimate fees on regtest, hence we default to a static
+    /// fee rate there.
+    fn new(network: ledger::Bitcoin) -> Self {
+        match network {
+            ledger::Bitcoin::Mainnet | ledger::Bitcoin::Testnet => {
+                BitcoinFees::Bitcoind { target_blocks: 6 }
+            }
+            ledger::Bitcoin::Regtest => BitcoinFees::Static {
+                sat_per_vbyte: crate::bitcoin::FALLBACK_FEE_RATE,
+            },
+        }
+    }
+}
+
 impl From<Bitcoin> for file::Bitcoin {
     fn from(bitcoin: Bitcoin) -> Self {
         file::Bitcoin {
             network: bitcoin.network,
             bitcoind: Some(bitcoin.bitcoind),
+            fees: Some(bitcoin.fees),
         }
     }
 }
@@ -270,6 +306,8 @@ impl From<Lightning> for file::Lightning {
             lnd: Some(file::Lnd {
                 rest_api_url: lightning.lnd.rest_api_url,
                 dir: lightning.lnd.dir,
+                execute_actions: Some(lightning.lnd.execute_actions),
+                retry_interval_ms: Some(lightning.lnd.retry_interval_ms),
             }),
             network: lightning.network,
         }
@@ -282,6 +320,13 @@ pub struct Lnd {
     pub dir: PathBuf,
     pub cert_path: PathBuf,
     pub readonly_macaroon_path: PathBuf,
+    pub admin_macaroon_path: PathBuf,
+    /// Whether cnd should execute lightning actions itself instead of
+    /// returning them to the user.
+    pub execute_actions: bool,
+    /// How long to wait between polling LND for the state of invoices and
+    /// payments.
+    pub retry_interval_ms: u64,
 }
 
 impl Lnd {
@@ -292,11 +337,11 @@ impl Lnd {
     fn from_file(file: file::Lnd, network: ledger::Bitcoin) -> Result<Self> {
         let rest_api_url = assert_lnd_url_https(file.rest_api_url)?;
 
-        Ok(Self::from_url_dir_and_network(
-            rest_api_url,
-            file.dir,
-            network,
-        ))
+        Ok(Lnd {
+            execute_actions: file.execute_actions.unwrap_or_default(),
+            retry_interval_ms: file.retry_interval_ms.unwrap_or(LND_RETRY_INTERVAL_MS),
+            ..Self::from_url_dir_and_network(rest_api_url, file.dir, network)
+        })
     }
 
     fn from_url_dir_and_network(rest_api_url: Url, dir: PathBuf, network: ledger::Bitcoin) -> Self {
@@ -304,7 +349,14 @@ impl Lnd {
             rest_api_url,
             dir: dir.clone(),
             cert_path: default_lnd_cert_path(dir.clone()),
-            readonly_macaroon_path: default_lnd_readonly_macaroon_path(dir, network),
+            readonly_macaroon_path: default_lnd_macaroon_path(
+                dir.clone(),
+                network,
+                "readonly.macaroon",
+            ),
+            admin_macaroon_path: default_lnd_macaroon_path(dir, network, "admin.macaroon"),
+            execute_actions: false,
+            retry_interval_ms: LND_RETRY_INTERVAL_MS,
         }
     }
 }
@@ -325,7 +377,11 @@ fn default_lnd_cert_path(lnd_dir: PathBuf) -> PathBuf {
     lnd_dir.join("tls.cert")
 }
 
-fn default_lnd_readonly_macaroon_path(lnd_dir: PathBuf, network: ledger::Bitcoin) -> PathBuf {
+fn default_lnd_macaroon_path(
+    lnd_dir: PathBuf,
+    network: ledger::Bitcoin,
+    macaroon: &str,
+) -> PathBuf {
     let network_dir = match network {
         ledger::Bitcoin::Mainnet => "mainnet",
         ledger::Bitcoin::Testnet => "testnet",
@@ -336,7 +392,7 @@ fn default_lnd_readonly_macaroon_path(lnd_dir: PathBuf, network: ledger::Bitcoin
         .join("chain")
         .join("bitcoin")
         .join(network_dir)
-        .join("readonly.macaroon")
+        .join(macaroon)
 }
 
 fn parse_unchecked<T>(str: &'static str) -> T
@@ -366,6 +422,8 @@ mod tests {
         let expected = file::Lnd {
             rest_api_url: LND_URL.clone(),
             dir: PathBuf::from("~/.local/share/comit/lnd"),
+            execute_actions: None,
+            retry_interval_ms: None,
         };
 
         assert_eq!(actual, Ok(expected));
@@ -387,18 +445,38 @@ mod tests {
             lnd: Some(file::Lnd {
                 rest_api_url: LND_URL.clone(),
                 dir: PathBuf::from("/path/to/lnd"),
+                execute_actions: None,
+                retry_interval_ms: None,
             }),
         };
 
         assert_eq!(actual, Ok(expected));
     }
 
+    #[test]
+    fn lnd_execute_actions_defaults_to_false() {
+        let file = file::Lnd {
+            rest_api_url: LND_URL.clone(),
+            dir: PathBuf::from("/path/to/lnd"),
+            execute_actions: None,
+            retry_interval_ms: None,
+        };
+
+        let lnd = Lnd::from_file(file, ledger::Bitcoin::Regtest);
+
+        assert_that(&lnd)
+            .is_ok()
+            .map(|lnd| &lnd.execute_actions)
+            .is_false();
+    }
+
     #[test]
     fn given_network_on_cli_when_config_disagrees_then_error() {
         let comit_network = comit::Network::Main;
         let config_file = file::Bitcoin {
             network: ledger::Bitcoin::Testnet,
             bitcoind: None,
+            fees: None,
         };
 
         let result = Bitcoin::from_file(config_file, Some(comit_network));
@@ -411,6 +489,7 @@ mod tests {
         let config_file = file::Bitcoin {
             network: ledger::Bitcoin::Testnet,
             bitcoind: None,
+            fees: None,
         };
 
         let result = Bitcoin::from_file(config_file, None);
@@ -427,6 +506,7 @@ mod tests {
         let config_file = file::Bitcoin {
             network: ledger::Bitcoin::Mainnet,
             bitcoind: None,
+            fees: None,
         };
 
         let result = Bitcoin::from_file(config_file, Some(comit_network));
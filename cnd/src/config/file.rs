@@ -1,9 +1,9 @@
 use crate::{
-    config::{Bitcoind, Data, Geth},
+    config::{BitcoinFees, Bitcoind, Data, Geth},
     ethereum,
     ethereum::ChainId,
 };
-use comit::ledger;
+use comit::{ethereum::gas::GasPriceStrategy, ledger, network::discovery};
 use libp2p::core::Multiaddr;
 use log::LevelFilter;
 use serde::{Deserialize, Serialize};
@@ -27,18 +27,27 @@ pub struct File {
     pub bitcoin: Option<Bitcoin>,
     pub ethereum: Option<Ethereum>,
     pub lightning: Option<Lightning>,
+    pub webhooks: Option<Webhooks>,
+    pub wallet: Option<Wallet>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Network {
     pub listen: Vec<Multiaddr>,
     pub peer_addresses: Option<Vec<Multiaddr>>,
+    /// The smallest quantity in satoshi we are willing to swap when an order
+    /// is only partially filled.
+    pub min_fill_sats: Option<u64>,
+    /// Peers whose reputation score is lower than this are not traded with.
+    pub min_reputation: Option<i64>,
+    pub discovery: Option<discovery::Config>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Bitcoin {
     pub network: ledger::Bitcoin,
     pub bitcoind: Option<Bitcoind>,
+    pub fees: Option<BitcoinFees>,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -63,6 +72,32 @@ pub struct Lightning {
 pub struct Lnd {
     pub rest_api_url: reqwest::Url,
     pub dir: PathBuf,
+    pub execute_actions: Option<bool>,
+    pub retry_interval_ms: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Webhooks {
+    /// How many minutes before a swap expires we warn about it.
+    pub expiry_warning_mins: Option<u32>,
+    pub endpoints: Vec<Webhook>,
+}
+
+/// An endpoint that receives a POST request for every event, signed with the
+/// shared secret.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Webhook {
+    pub url: reqwest::Url,
+    pub secret: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Wallet {
+    /// Execute the Bitcoin and Ethereum actions of swaps with wallets derived
+    /// from the seed of cnd.
+    pub managed: Option<bool>,
+    pub stuck_transaction_timeout_secs: Option<u64>,
+    pub gas_price: Option<GasPriceStrategy>,
 }
 
 impl File {
@@ -75,6 +110,8 @@ impl File {
             bitcoin: Option::None,
             ethereum: Option::None,
             lightning: Option::None,
+            webhooks: Option::None,
+            wallet: Option::None,
         }
     }
 
@@ -133,6 +170,8 @@ impl From<Level> for LevelFilter {
 pub struct HttpApi {
     pub socket: SocketAddr,
     pub cors: Option<Cors>,
+    pub auth: Option<Auth>,
+    pub tls: Option<Tls>,
 }
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
@@ -160,10 +199,26 @@ pub enum None {
     None,
 }
 
+#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct Auth {
+    pub enabled: bool,
+}
+
+/// The identity the HTTP API presents to its clients if served over TLS.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct Tls {
+    /// A PKCS #12 archive holding the certificate chain and private key.
+    pub identity: PathBuf,
+    pub password: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{Bitcoind, Geth, Settings};
+    use crate::{
+        bitcoin::SatPerVbyte,
+        config::{BitcoinFees, Bitcoind, Geth, Settings},
+    };
     use reqwest::Url;
     use spectral::prelude::*;
     use std::{
@@ -189,6 +244,13 @@ mod tests {
                 "/ip4/1.1.1.1/tcp/9939",
                 "/ip4/2.2.2.2/tcp/3456"
             ]
+            min_fill_sats = 10000
+            min_reputation = -5
+
+            [discovery]
+            mdns = true
+            kademlia = true
+            bootstrap_nodes = [ "/ip4/3.3.3.3/tcp/9939/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC" ]
             "#,
         ];
 
@@ -196,6 +258,9 @@ mod tests {
             Network {
                 listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
                 peer_addresses: Some(vec!["/ip4/1.1.1.1/tcp/9939".parse().unwrap()]),
+                min_fill_sats: None,
+                min_reputation: None,
+                discovery: None,
             },
             Network {
                 listen: (vec![
@@ -206,6 +271,17 @@ mod tests {
                     "/ip4/1.1.1.1/tcp/9939".parse().unwrap(),
                     "/ip4/2.2.2.2/tcp/3456".parse().unwrap(),
                 ]),
+                min_fill_sats: Some(10_000),
+                min_reputation: Some(-5),
+                discovery: Some(discovery::Config {
+                    mdns: true,
+                    kademlia: true,
+                    bootstrap_nodes: vec![
+                        "/ip4/3.3.3.3/tcp/9939/p2p/QmcgpsyWgH8Y8ajJz1Cu72KnS5uo2Aa2LpzU7kinSupNKC"
+                            .parse()
+                            .unwrap(),
+                    ],
+                }),
             },
         ];
 
@@ -268,6 +344,13 @@ socket = "127.0.0.1:8000"
 [http_api.cors]
 allowed_origins = "all"
 
+[http_api.auth]
+enabled = true
+
+[http_api.tls]
+identity = "/tmp/comit/identity.p12"
+password = "hunter2"
+
 [data]
 dir = "/tmp/comit/"
 
@@ -280,6 +363,10 @@ network = "regtest"
 [bitcoin.bitcoind]
 node_url = "http://localhost:18443/"
 
+[bitcoin.fees]
+strategy = "bitcoind"
+target_blocks = 3
+
 [ethereum]
 chain_id = 1337
 
@@ -295,17 +382,43 @@ network = "regtest"
 [lightning.lnd]
 rest_api_url = "https://localhost:8080"
 dir = "/foo/bar"
+execute_actions = true
+retry_interval_ms = 250
+
+[webhooks]
+expiry_warning_mins = 30
+
+[[webhooks.endpoints]]
+url = "http://localhost:9000/cnd"
+secret = "correct horse battery staple"
+
+[wallet]
+managed = true
+stuck_transaction_timeout_secs = 300
+
+[wallet.gas_price]
+strategy = "capped"
+multiplier_percent = 120
+max_gwei = 200
 "#;
         let file = File {
             network: Some(Network {
                 listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
                 peer_addresses: None,
+                min_fill_sats: None,
+                min_reputation: None,
+                discovery: None,
             }),
             http_api: Some(HttpApi {
                 socket: SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8000),
                 cors: Some(Cors {
                     allowed_origins: AllowedOrigins::All(All::All),
                 }),
+                auth: Some(Auth { enabled: true }),
+                tls: Some(Tls {
+                    identity: PathBuf::from("/tmp/comit/identity.p12"),
+                    password: Some("hunter2".to_owned()),
+                }),
             }),
             data: Some(Data {
                 dir: PathBuf::from("/tmp/comit/"),
@@ -318,6 +431,7 @@ dir = "/foo/bar"
                 bitcoind: Some(Bitcoind {
                     node_url: "http://localhost:18443".parse().unwrap(),
                 }),
+                fees: Some(BitcoinFees::Bitcoind { target_blocks: 3 }),
             }),
             ethereum: Some(Ethereum {
                 chain_id: ChainId::GETH_DEV,
@@ -337,6 +451,23 @@ dir = "/foo/bar"
                 lnd: Some(Lnd {
                     rest_api_url: "https://localhost:8080".parse().unwrap(),
                     dir: PathBuf::from("/foo/bar"),
+                    execute_actions: Some(true),
+                    retry_interval_ms: Some(250),
+                }),
+            }),
+            webhooks: Some(Webhooks {
+                expiry_warning_mins: Some(30),
+                endpoints: vec![Webhook {
+                    url: "http://localhost:9000/cnd".parse().unwrap(),
+                    secret: "correct horse battery staple".to_owned(),
+                }],
+            }),
+            wallet: Some(Wallet {
+                managed: Some(true),
+                stuck_transaction_timeout_secs: Some(300),
+                gas_price: Some(GasPriceStrategy::Capped {
+                    multiplier_percent: 120,
+                    max_gwei: 200,
                 }),
             }),
         };
@@ -380,6 +511,9 @@ dir = "/foo/bar"
             network = "regtest"
             [bitcoind]
             node_url = "http://example.com:18443"
+            [fees]
+            strategy = "static"
+            sat_per_vbyte = 20
             "#,
         ];
 
@@ -389,18 +523,23 @@ dir = "/foo/bar"
                 bitcoind: Some(Bitcoind {
                     node_url: Url::parse("http://example.com:8332").unwrap(),
                 }),
+                fees: None,
             },
             Bitcoin {
                 network: ledger::Bitcoin::Testnet,
                 bitcoind: Some(Bitcoind {
                     node_url: Url::parse("http://example.com:18332").unwrap(),
                 }),
+                fees: None,
             },
             Bitcoin {
                 network: ledger::Bitcoin::Regtest,
                 bitcoind: Some(Bitcoind {
                     node_url: Url::parse("http://example.com:18443").unwrap(),
                 }),
+                fees: Some(BitcoinFees::Static {
+                    sat_per_vbyte: SatPerVbyte::new(20),
+                }),
             },
         ];
 
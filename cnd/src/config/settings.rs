@@ -1,7 +1,17 @@
-use crate::config::{file, Bitcoin, Data, Ethereum, File, Lightning, COMIT_SOCKET};
+use crate::{
+    asset,
+    config::{file, Bitcoin, Data, Ethereum, File, Lightning, COMIT_SOCKET},
+};
+use comit::{
+    ethereum::gas::{GasPolicy, DEFAULT_STUCK_TRANSACTION_TIMEOUT},
+    network::discovery,
+};
 use libp2p::core::Multiaddr;
 use log::LevelFilter;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
 
 /// This structs represents the settings as they are used through out the code.
 ///
@@ -18,24 +28,40 @@ pub struct Settings {
     pub bitcoin: Bitcoin,
     pub ethereum: Ethereum,
     pub lightning: Lightning,
+    pub webhooks: Webhooks,
+    pub wallet: Wallet,
 }
 
 impl From<Settings> for File {
     fn from(settings: Settings) -> Self {
         let Settings {
             network,
-            http_api: HttpApi { socket, cors },
+            http_api:
+                HttpApi {
+                    socket,
+                    cors,
+                    auth,
+                    tls,
+                },
             data,
             logging: Logging { level },
             bitcoin,
             ethereum,
             lightning,
+            webhooks,
+            wallet: Wallet {
+                managed,
+                gas_policy,
+            },
         } = settings;
 
         File {
             network: Some(file::Network {
                 listen: network.listen,
                 peer_addresses: Some(network.peer_addresses),
+                min_fill_sats: Some(network.min_fill.as_sat()),
+                min_reputation: network.min_reputation,
+                discovery: Some(network.discovery),
             }),
             http_api: Some(file::HttpApi {
                 socket,
@@ -46,6 +72,10 @@ impl From<Settings> for File {
                         AllowedOrigins::Some(origins) => file::AllowedOrigins::Some(origins),
                     },
                 }),
+                auth: Some(file::Auth {
+                    enabled: auth.enabled,
+                }),
+                tls,
             }),
             data: Some(data),
             logging: Some(file::Logging {
@@ -54,6 +84,17 @@ impl From<Settings> for File {
             bitcoin: Some(bitcoin.into()),
             ethereum: Some(ethereum.into()),
             lightning: Some(lightning.into()),
+            webhooks: Some(file::Webhooks {
+                expiry_warning_mins: Some(webhooks.expiry_warning_mins),
+                endpoints: webhooks.endpoints,
+            }),
+            wallet: Some(file::Wallet {
+                managed: Some(managed),
+                stuck_transaction_timeout_secs: Some(
+                    gas_policy.stuck_transaction_timeout.as_secs(),
+                ),
+                gas_price: Some(gas_policy.strategy),
+            }),
         }
     }
 }
@@ -62,6 +103,10 @@ impl From<Settings> for File {
 pub struct Network {
     pub listen: Vec<Multiaddr>,
     pub peer_addresses: Vec<Multiaddr>,
+    pub min_fill: asset::Bitcoin,
+    /// Peers whose reputation score is lower than this are not traded with.
+    pub min_reputation: Option<i64>,
+    pub discovery: discovery::Config,
 }
 
 impl Default for Network {
@@ -69,6 +114,9 @@ impl Default for Network {
         Self {
             listen: vec![COMIT_SOCKET.clone()],
             peer_addresses: vec![],
+            min_fill: asset::Bitcoin::ZERO,
+            min_reputation: None,
+            discovery: discovery::Config::default(),
         }
     }
 }
@@ -77,10 +125,17 @@ impl From<file::Network> for Network {
     fn from(network: file::Network) -> Self {
         let listen = network.listen;
         let peer_addresses = network.peer_addresses.unwrap_or_default();
+        let min_fill = network
+            .min_fill_sats
+            .map_or(asset::Bitcoin::ZERO, asset::Bitcoin::from_sat);
+        let discovery = network.discovery.unwrap_or_default();
 
         Self {
             listen,
             peer_addresses,
+            min_fill,
+            min_reputation: network.min_reputation,
+            discovery,
         }
     }
 }
@@ -89,6 +144,9 @@ impl From<file::Network> for Network {
 pub struct HttpApi {
     pub socket: SocketAddr,
     pub cors: Cors,
+    pub auth: Auth,
+    /// Serve the HTTP API over TLS with this identity instead of plain HTTP.
+    pub tls: Option<file::Tls>,
 }
 
 impl Default for HttpApi {
@@ -96,6 +154,8 @@ impl Default for HttpApi {
         Self {
             socket: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8000),
             cors: Cors::default(),
+            auth: Auth::default(),
+            tls: None,
         }
     }
 }
@@ -104,8 +164,28 @@ impl From<file::HttpApi> for HttpApi {
     fn from(http_api: file::HttpApi) -> Self {
         let socket = http_api.socket;
         let cors = http_api.cors.map_or_else(Cors::default, Cors::from);
+        let auth = http_api.auth.map_or_else(Auth::default, Auth::from);
+
+        HttpApi {
+            socket,
+            cors,
+            auth,
+            tls: http_api.tls,
+        }
+    }
+}
+
+/// Whether requests to the HTTP API need to present one of the API tokens.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Auth {
+    pub enabled: bool,
+}
 
-        HttpApi { socket, cors }
+impl From<file::Auth> for Auth {
+    fn from(auth: file::Auth) -> Self {
+        Auth {
+            enabled: auth.enabled,
+        }
     }
 }
 
@@ -141,6 +221,50 @@ pub enum AllowedOrigins {
     Some(Vec<String>),
 }
 
+#[derive(Clone, Debug, PartialEq, derivative::Derivative)]
+#[derivative(Default)]
+pub struct Webhooks {
+    #[derivative(Default(value = "60"))]
+    pub expiry_warning_mins: u32,
+    pub endpoints: Vec<file::Webhook>,
+}
+
+impl From<file::Webhooks> for Webhooks {
+    fn from(webhooks: file::Webhooks) -> Self {
+        let expiry_warning_mins = webhooks
+            .expiry_warning_mins
+            .unwrap_or_else(|| Webhooks::default().expiry_warning_mins);
+
+        Self {
+            expiry_warning_mins,
+            endpoints: webhooks.endpoints,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Wallet {
+    pub managed: bool,
+    /// Decides on the gas price of the Ethereum transactions of the managed
+    /// wallet and how long we wait for transactions to be confirmed before
+    /// replacing them.
+    pub gas_policy: GasPolicy,
+}
+
+impl From<file::Wallet> for Wallet {
+    fn from(wallet: file::Wallet) -> Self {
+        Self {
+            managed: wallet.managed.unwrap_or_default(),
+            gas_policy: GasPolicy {
+                strategy: wallet.gas_price.unwrap_or_default(),
+                stuck_transaction_timeout: wallet
+                    .stuck_transaction_timeout_secs
+                    .map_or(DEFAULT_STUCK_TRANSACTION_TIMEOUT, Duration::from_secs),
+            },
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, derivative::Derivative)]
 #[derivative(Default)]
 pub struct Logging {
@@ -172,6 +296,8 @@ impl Settings {
             bitcoin,
             ethereum,
             lightning,
+            webhooks,
+            wallet,
         } = config_file;
 
         Ok(Self {
@@ -192,6 +318,8 @@ impl Settings {
                 || Ok(Lightning::new(comit_network.unwrap_or_default().into())),
                 |file| Lightning::from_file(file, comit_network),
             )?,
+            webhooks: webhooks.map_or_else(Webhooks::default, Webhooks::from),
+            wallet: wallet.map_or_else(Wallet::default, Wallet::from),
         })
     }
 }
@@ -200,7 +328,8 @@ impl Settings {
 mod tests {
     use super::*;
     use crate::{
-        config::{file, Bitcoind, Geth, Lnd, Tokens, DAI_MAINNET},
+        bitcoin::SatPerVbyte,
+        config::{file, BitcoinFees, Bitcoind, Geth, Lnd, Tokens, DAI_MAINNET},
         ethereum::ChainId,
     };
     use comit::ledger;
@@ -230,6 +359,8 @@ mod tests {
             http_api: Some(file::HttpApi {
                 socket: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8000),
                 cors: None,
+                auth: None,
+                tls: None,
             }),
             ..File::default()
         };
@@ -261,6 +392,51 @@ mod tests {
                 cors: Cors {
                     allowed_origins: AllowedOrigins::None,
                 },
+                auth: Auth { enabled: false },
+                tls: None,
+            })
+    }
+
+    #[test]
+    fn webhooks_section_defaults() {
+        let config_file = File {
+            webhooks: Some(file::Webhooks {
+                expiry_warning_mins: None,
+                endpoints: vec![],
+            }),
+            ..File::default()
+        };
+
+        let settings = Settings::from_config_file_and_defaults(config_file, None);
+
+        assert_that(&settings)
+            .is_ok()
+            .map(|settings| &settings.webhooks)
+            .is_equal_to(Webhooks {
+                expiry_warning_mins: 60,
+                endpoints: vec![],
+            })
+    }
+
+    #[test]
+    fn wallet_is_not_managed_by_default() {
+        let config_file = File {
+            wallet: Some(file::Wallet {
+                managed: None,
+                stuck_transaction_timeout_secs: None,
+                gas_price: None,
+            }),
+            ..File::default()
+        };
+
+        let settings = Settings::from_config_file_and_defaults(config_file, None);
+
+        assert_that(&settings)
+            .is_ok()
+            .map(|settings| &settings.wallet)
+            .is_equal_to(Wallet {
+                managed: false,
+                gas_policy: GasPolicy::default(),
             })
     }
 
@@ -279,6 +455,9 @@ mod tests {
             .is_equal_to(Network {
                 listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
                 peer_addresses: vec![],
+                min_fill: asset::Bitcoin::ZERO,
+                min_reputation: None,
+                discovery: discovery::Config::default(),
             })
     }
 
@@ -296,22 +475,38 @@ mod tests {
                 bitcoind: Bitcoind {
                     node_url: "http://localhost:8332".parse().unwrap(),
                 },
+                fees: BitcoinFees::Bitcoind { target_blocks: 6 },
             })
     }
 
     #[test]
     fn bitcoin_defaults_network_only() {
         let defaults = vec![
-            (ledger::Bitcoin::Mainnet, "http://localhost:8332"),
-            (ledger::Bitcoin::Testnet, "http://localhost:18332"),
-            (ledger::Bitcoin::Regtest, "http://localhost:18443"),
+            (
+                ledger::Bitcoin::Mainnet,
+                "http://localhost:8332",
+                BitcoinFees::Bitcoind { target_blocks: 6 },
+            ),
+            (
+                ledger::Bitcoin::Testnet,
+                "http://localhost:18332",
+                BitcoinFees::Bitcoind { target_blocks: 6 },
+            ),
+            (
+                ledger::Bitcoin::Regtest,
+                "http://localhost:18443",
+                BitcoinFees::Static {
+                    sat_per_vbyte: SatPerVbyte::new(10),
+                },
+            ),
         ];
 
-        for (network, url) in defaults {
+        for (network, url, fees) in defaults {
             let config_file = File {
                 bitcoin: Some(file::Bitcoin {
                     network,
                     bitcoind: None,
+                    fees: None,
                 }),
                 ..File::default()
             };
@@ -326,6 +521,7 @@ mod tests {
                     bitcoind: Bitcoind {
                         node_url: url.parse().unwrap(),
                     },
+                    fees,
                 })
         }
     }
@@ -392,6 +588,8 @@ mod tests {
                 lnd: Some(file::Lnd {
                     rest_api_url: "http://localhost:8000/".parse().unwrap(),
                     dir: Default::default(),
+                    execute_actions: None,
+                    retry_interval_ms: None,
                 }),
             }),
             ..File::default()
@@ -1,7 +1,9 @@
 use crate::{
+    bitcoin::{EstimateFeeRate, FeeRateEstimator},
     btsieve,
-    btsieve::{bitcoin::BitcoindConnector, ethereum::Web3Connector, LatestBlock},
+    btsieve::{bitcoin::BitcoindConnector, ethereum::Web3Connector, CacheStats, LatestBlock},
     ethereum,
+    metrics::Instrumented,
 };
 use comit::btsieve::{ethereum::ReceiptByHash, BlockByHash};
 use std::sync::Arc;
@@ -9,18 +11,21 @@ use std::sync::Arc;
 /// A facade for accessing various blockchain connectors.
 #[derive(Debug, Clone)]
 pub struct Connectors {
-    bitcoin: Arc<btsieve::bitcoin::Cache<BitcoindConnector>>,
-    ethereum: Arc<btsieve::ethereum::Cache<Web3Connector>>,
+    bitcoin: Arc<btsieve::bitcoin::Cache<Instrumented<BitcoindConnector>>>,
+    ethereum: Arc<btsieve::ethereum::Cache<Instrumented<Web3Connector>>>,
+    bitcoin_fee_rate: Arc<FeeRateEstimator>,
 }
 
 impl Connectors {
     pub fn new(
-        bitcoin: btsieve::bitcoin::Cache<BitcoindConnector>,
-        ethereum: btsieve::ethereum::Cache<Web3Connector>,
+        bitcoin: btsieve::bitcoin::Cache<Instrumented<BitcoindConnector>>,
+        ethereum: btsieve::ethereum::Cache<Instrumented<Web3Connector>>,
+        bitcoin_fee_rate: FeeRateEstimator,
     ) -> Self {
         Self {
             bitcoin: Arc::new(bitcoin),
             ethereum: Arc::new(ethereum),
+            bitcoin_fee_rate: Arc::new(bitcoin_fee_rate),
         }
     }
 
@@ -50,4 +55,23 @@ impl Connectors {
     > {
         self.ethereum.clone()
     }
+
+    /// The statistics of the caches in front of the connectors, by ledger and
+    /// kind of item.
+    pub fn cache_stats(&self) -> Vec<(&'static str, &'static str, Arc<CacheStats>)> {
+        vec![
+            ("bitcoin", "block", self.bitcoin.block_cache_stats.clone()),
+            ("ethereum", "block", self.ethereum.block_cache_stats.clone()),
+            (
+                "ethereum",
+                "receipt",
+                self.ethereum.receipt_cache_stats.clone(),
+            ),
+        ]
+    }
+
+    /// Provides access to a reference of the Bitcoin fee rate estimator.
+    pub fn bitcoin_fee_rate(&self) -> Arc<impl EstimateFeeRate> {
+        self.bitcoin_fee_rate.clone()
+    }
 }
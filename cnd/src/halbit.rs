@@ -135,7 +135,7 @@ pub struct CreatedSwap {
 }
 
 /// Represents states that an invoice can be in.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, strum_macros::Display)]
 pub enum State {
     None,
     Opened(Opened),
@@ -1,4 +1,5 @@
 use crate::{
+    bitcoin::SatPerVbyte,
     btsieve::{BlockByHash, LatestBlock},
     ledger, state,
     state::Update,
@@ -208,6 +209,26 @@ pub enum State {
     },
 }
 
+/// The kind of transaction spending from an hbit HTLC.
+#[derive(Clone, Copy, Debug, PartialEq, strum_macros::Display, strum_macros::EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum SpendKind {
+    Redeem,
+    Refund,
+}
+
+/// The last transaction spending from an hbit HTLC that was handed out to the
+/// user.
+///
+/// We need to remember this to be able to replace the transaction with one
+/// paying a higher fee rate if it doesn't get confirmed, hence it is persisted
+/// in the `hbit_spends` table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spend {
+    pub kind: SpendKind,
+    pub fee_rate: SatPerVbyte,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Identities {
     pub redeem_identity: identity::Bitcoin,
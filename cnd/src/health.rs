@@ -0,0 +1,501 @@
+//! Diagnostics of everything cnd depends on, served by the "/health" and
+//! "/ready" endpoints.
+//!
+//! The checks talk to the nodes directly instead of going through the
+//! [`Connectors`](crate::connectors::Connectors): their caches could hide an
+//! unreachable node and the checks need more than the connector traits offer.
+//!
+//! cnd is healthy if all of its dependencies are. It is ready as long as it
+//! can make progress on the swaps that are in progress, i.e. an unhealthy
+//! ledger that no active swap needs doesn't make cnd unready.
+
+use crate::{
+    btsieve::{bitcoin::BitcoindConnector, ethereum::Web3Connector, BlockByHash, LatestBlock},
+    config::Settings,
+    ethereum::ChainId,
+    network::Swarm,
+    storage::{SortOrder, Storage, Swap, SwapFilter, SwapStatus},
+    LockProtocol,
+};
+use anyhow::Context;
+use comit::{
+    ledger,
+    lnd::{LndActionExecutor, LndConnectorParams, LndDiagnostics},
+};
+use libp2p::Multiaddr;
+use serde::Serialize;
+use std::{
+    collections::BTreeSet,
+    future::Future,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// How long we wait for a single check before we consider it failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The ledgers swaps can lock assets on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Ledger {
+    Bitcoin,
+    Ethereum,
+    Lightning,
+}
+
+impl From<LockProtocol> for Ledger {
+    fn from(protocol: LockProtocol) -> Self {
+        match protocol {
+            LockProtocol::Hbit => Ledger::Bitcoin,
+            LockProtocol::Herc20 => Ledger::Ethereum,
+            LockProtocol::Halbit => Ledger::Lightning,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Health {
+    bitcoind: Arc<BitcoindConnector>,
+    bitcoin_network: ledger::Bitcoin,
+    geth: Arc<Web3Connector>,
+    chain_id: ChainId,
+    /// Absent if we could not read the certificate or macaroon of LND.
+    lnd: Option<LndDiagnostics>,
+    execute_lightning_actions: bool,
+    /// Absent if we could not read the certificate or admin macaroon of LND
+    /// or are not configured to execute lightning actions.
+    lnd_action_executor: Option<LndActionExecutor>,
+}
+
+impl Health {
+    pub fn new(settings: &Settings) -> anyhow::Result<Self> {
+        let lnd = &settings.lightning.lnd;
+        let lnd_params = |macaroon_path| {
+            LndConnectorParams::new(
+                lnd.rest_api_url.clone(),
+                lnd.retry_interval_ms,
+                lnd.cert_path.clone(),
+                macaroon_path,
+            )
+        };
+
+        let lnd_action_executor = if lnd.execute_actions {
+            lnd_params(lnd.admin_macaroon_path.clone())
+                .map(LndActionExecutor::from)
+                .ok()
+        } else {
+            None
+        };
+
+        Ok(Self {
+            bitcoind: Arc::new(BitcoindConnector::new(
+                settings.bitcoin.bitcoind.node_url.clone(),
+            )?),
+            bitcoin_network: settings.bitcoin.network,
+            geth: Arc::new(Web3Connector::new(settings.ethereum.geth.node_url.clone())),
+            chain_id: settings.ethereum.chain_id,
+            lnd: lnd_params(lnd.readonly_macaroon_path.clone())
+                .map(LndDiagnostics::from)
+                .ok(),
+            execute_lightning_actions: lnd.execute_actions,
+            lnd_action_executor,
+        })
+    }
+
+    /// Runs all checks concurrently.
+    pub async fn check(&self, storage: &Storage, swarm: &Swarm) -> Report {
+        let (bitcoin, ethereum, lightning, database, network, active_swap_ledgers) = tokio::join!(
+            self.check_bitcoin(),
+            self.check_ethereum(),
+            self.check_lightning(),
+            check_database(storage),
+            check_network(swarm),
+            ledgers_of_active_swaps(storage),
+        );
+
+        // If we cannot tell which ledgers are needed we assume all of them are.
+        let active_swap_ledgers = active_swap_ledgers.unwrap_or_else(|e| {
+            tracing::warn!("failed to load the ledgers of active swaps: {:#}", e);
+            vec![Ledger::Bitcoin, Ledger::Ethereum, Ledger::Lightning]
+                .into_iter()
+                .collect()
+        });
+
+        Report::new(
+            bitcoin,
+            ethereum,
+            lightning,
+            database,
+            network,
+            active_swap_ledgers,
+        )
+    }
+
+    async fn check_bitcoin(&self) -> NodeStatus {
+        let result = within_timeout(async {
+            let chain_info = self.bitcoind.chain_info().await?;
+            let best_block = self
+                .bitcoind
+                .block_by_hash(chain_info.bestblockhash)
+                .await?;
+
+            Ok((chain_info, best_block))
+        })
+        .await;
+
+        match result {
+            Ok((chain_info, best_block)) => NodeStatus {
+                reachable: true,
+                network_matches: chain_info.chain == self.bitcoin_network,
+                best_block_height: Some(chain_info.blocks.into()),
+                best_block_age: Some(age(best_block.header.time.into())),
+                synced: !chain_info.initialblockdownload && chain_info.blocks == chain_info.headers,
+                error: None,
+            },
+            Err(e) => NodeStatus::unreachable(e),
+        }
+    }
+
+    async fn check_ethereum(&self) -> NodeStatus {
+        let result = within_timeout(async {
+            let chain_id = self.geth.net_version().await?;
+            let number = self.geth.block_number().await?;
+            let best_block = self.geth.latest_block().await?;
+            let syncing = self.geth.is_syncing().await?;
+
+            Ok((chain_id, number, best_block, syncing))
+        })
+        .await;
+
+        match result {
+            Ok((chain_id, number, best_block, syncing)) => NodeStatus {
+                reachable: true,
+                network_matches: chain_id == self.chain_id,
+                best_block_height: Some(number.low_u64()),
+                best_block_age: Some(age(best_block.timestamp.low_u64())),
+                synced: !syncing,
+                error: None,
+            },
+            Err(e) => NodeStatus::unreachable(e),
+        }
+    }
+
+    async fn check_lightning(&self) -> LightningStatus {
+        let lnd = match &self.lnd {
+            Some(lnd) => lnd,
+            None => return LightningStatus::default(),
+        };
+
+        let result = within_timeout(async {
+            let node_info = lnd.node_info().await?;
+            let can_follow_htlcs = lnd.can_read_invoices_and_payments().await?;
+            let can_execute_actions = match &self.lnd_action_executor {
+                _ if !self.execute_lightning_actions => None,
+                Some(executor) => Some(executor.can_execute_actions().await?),
+                None => Some(false),
+            };
+
+            Ok((node_info, can_follow_htlcs, can_execute_actions))
+        })
+        .await;
+
+        match result {
+            Ok((node_info, can_follow_htlcs, can_execute_actions)) => LightningStatus {
+                configured: true,
+                reachable: true,
+                synced: node_info.synced_to_chain,
+                block_height: Some(node_info.block_height),
+                can_follow_htlcs,
+                can_execute_actions,
+                error: None,
+            },
+            Err(e) => LightningStatus {
+                configured: true,
+                error: Some(format!("{:#}", e)),
+                ..LightningStatus::default()
+            },
+        }
+    }
+}
+
+async fn check_database(storage: &Storage) -> DatabaseStatus {
+    match within_timeout(storage.db.check_writable()).await {
+        Ok(()) => DatabaseStatus {
+            writable: true,
+            error: None,
+        },
+        Err(e) => DatabaseStatus {
+            writable: false,
+            error: Some(format!("{:#}", e)),
+        },
+    }
+}
+
+async fn check_network(swarm: &Swarm) -> NetworkStatus {
+    let listen_addresses = swarm.listeners().await;
+    let connected_peers = swarm.connected_peers().await.count();
+
+    NetworkStatus {
+        listening: !listen_addresses.is_empty(),
+        listen_addresses,
+        connected_peers,
+    }
+}
+
+/// The ledgers at least one swap that is still in progress locks assets on.
+async fn ledgers_of_active_swaps(storage: &Storage) -> anyhow::Result<BTreeSet<Ledger>> {
+    storage
+        .db
+        .do_in_transaction(|conn| {
+            let mut ledgers = BTreeSet::new();
+
+            for &protocol in &[
+                LockProtocol::Hbit,
+                LockProtocol::Herc20,
+                LockProtocol::Halbit,
+            ] {
+                let on_alpha = SwapFilter {
+                    status: Some(SwapStatus::InProgress),
+                    alpha: Some(protocol),
+                    ..SwapFilter::default()
+                };
+                let on_beta = SwapFilter {
+                    status: Some(SwapStatus::InProgress),
+                    beta: Some(protocol),
+                    ..SwapFilter::default()
+                };
+
+                for filter in &[on_alpha, on_beta] {
+                    if !Swap::query(conn, filter, SortOrder::Oldest, None, 1)?.is_empty() {
+                        ledgers.insert(Ledger::from(protocol));
+                    }
+                }
+            }
+
+            Ok(ledgers)
+        })
+        .await
+}
+
+async fn within_timeout<F, T>(check: F) -> anyhow::Result<T>
+where
+    F: Future<Output = anyhow::Result<T>>,
+{
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .context("check timed out")?
+}
+
+/// Seconds since the given UNIX timestamp, zero if it lies in the future.
+fn age(timestamp: u64) -> u64 {
+    let then = UNIX_EPOCH + Duration::from_secs(timestamp);
+
+    SystemTime::now()
+        .duration_since(then)
+        .map(|age| age.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    pub healthy: bool,
+    pub ready: bool,
+    pub bitcoin: NodeStatus,
+    pub ethereum: NodeStatus,
+    pub lightning: LightningStatus,
+    pub database: DatabaseStatus,
+    pub network: NetworkStatus,
+    /// The ledgers swaps in progress lock assets on.
+    pub active_swap_ledgers: BTreeSet<Ledger>,
+}
+
+impl Report {
+    fn new(
+        bitcoin: NodeStatus,
+        ethereum: NodeStatus,
+        lightning: LightningStatus,
+        database: DatabaseStatus,
+        network: NetworkStatus,
+        active_swap_ledgers: BTreeSet<Ledger>,
+    ) -> Self {
+        let is_healthy = |ledger| match ledger {
+            Ledger::Bitcoin => bitcoin.is_healthy(),
+            Ledger::Ethereum => ethereum.is_healthy(),
+            Ledger::Lightning => lightning.is_healthy(),
+        };
+
+        let ledgers_healthy = is_healthy(Ledger::Bitcoin)
+            && is_healthy(Ledger::Ethereum)
+            && (!lightning.configured || is_healthy(Ledger::Lightning));
+        let active_swap_ledgers_healthy =
+            active_swap_ledgers.iter().all(|ledger| is_healthy(*ledger));
+        let essentials_healthy = database.writable && network.listening;
+
+        Self {
+            healthy: essentials_healthy && ledgers_healthy,
+            ready: essentials_healthy && active_swap_ledgers_healthy,
+            bitcoin,
+            ethereum,
+            lightning,
+            database,
+            network,
+            active_swap_ledgers,
+        }
+    }
+}
+
+/// The status of a Bitcoin or Ethereum node.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NodeStatus {
+    pub reachable: bool,
+    /// Whether the node is on the network cnd is configured for.
+    pub network_matches: bool,
+    pub best_block_height: Option<u64>,
+    /// Seconds since the best block was mined, according to its timestamp.
+    pub best_block_age: Option<u64>,
+    pub synced: bool,
+    pub error: Option<String>,
+}
+
+impl NodeStatus {
+    fn unreachable(error: anyhow::Error) -> Self {
+        Self {
+            error: Some(format!("{:#}", error)),
+            ..Self::default()
+        }
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.reachable && self.network_matches && self.synced
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct LightningStatus {
+    /// Whether we could read the certificate and macaroon of LND.
+    pub configured: bool,
+    pub reachable: bool,
+    pub synced: bool,
+    pub block_height: Option<u32>,
+    /// Whether the readonly macaroon allows following lightning HTLCs.
+    pub can_follow_htlcs: bool,
+    /// Whether the admin macaroon allows executing lightning actions, only
+    /// checked if cnd is configured to execute them.
+    pub can_execute_actions: Option<bool>,
+    pub error: Option<String>,
+}
+
+impl LightningStatus {
+    fn is_healthy(&self) -> bool {
+        self.reachable
+            && self.synced
+            && self.can_follow_htlcs
+            && self.can_execute_actions != Some(false)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DatabaseStatus {
+    pub writable: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NetworkStatus {
+    /// Whether libp2p listens on at least one address.
+    pub listening: bool,
+    pub listen_addresses: Vec<Multiaddr>,
+    pub connected_peers: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn healthy_node() -> NodeStatus {
+        NodeStatus {
+            reachable: true,
+            network_matches: true,
+            best_block_height: Some(100),
+            best_block_age: Some(60),
+            synced: true,
+            error: None,
+        }
+    }
+
+    fn report(
+        ethereum: NodeStatus,
+        lightning: LightningStatus,
+        active_swap_ledgers: Vec<Ledger>,
+    ) -> Report {
+        Report::new(
+            healthy_node(),
+            ethereum,
+            lightning,
+            DatabaseStatus {
+                writable: true,
+                error: None,
+            },
+            NetworkStatus {
+                listening: true,
+                listen_addresses: vec!["/ip4/127.0.0.1/tcp/9939".parse().unwrap()],
+                connected_peers: 0,
+            },
+            active_swap_ledgers.into_iter().collect(),
+        )
+    }
+
+    #[test]
+    fn unconfigured_lightning_does_not_make_cnd_unhealthy() {
+        let report = report(
+            healthy_node(),
+            LightningStatus::default(),
+            vec![Ledger::Bitcoin],
+        );
+
+        assert!(report.healthy);
+        assert!(report.ready);
+    }
+
+    #[test]
+    fn unhealthy_ledger_only_makes_cnd_unready_if_active_swaps_need_it() {
+        let out_of_sync = NodeStatus {
+            synced: false,
+            ..healthy_node()
+        };
+
+        let unused = report(
+            out_of_sync.clone(),
+            LightningStatus::default(),
+            vec![Ledger::Bitcoin],
+        );
+        let used = report(
+            out_of_sync,
+            LightningStatus::default(),
+            vec![Ledger::Bitcoin, Ledger::Ethereum],
+        );
+
+        assert!(!unused.healthy);
+        assert!(unused.ready);
+        assert!(!used.healthy);
+        assert!(!used.ready);
+    }
+
+    #[test]
+    fn missing_permission_to_execute_actions_makes_lightning_unhealthy() {
+        let lightning = LightningStatus {
+            configured: true,
+            reachable: true,
+            synced: true,
+            block_height: Some(100),
+            can_follow_htlcs: true,
+            can_execute_actions: Some(false),
+            error: None,
+        };
+
+        let report = report(healthy_node(), lightning, vec![Ledger::Lightning]);
+
+        assert!(!report.healthy);
+        assert!(!report.ready);
+    }
+}
@@ -1,24 +1,40 @@
 mod action;
+mod auth;
 mod dial_addr;
 pub mod halbit;
 mod halbit_herc20;
 pub mod hbit;
 mod hbit_herc20;
+mod health;
 pub mod herc20;
 mod herc20_halbit;
 mod herc20_hbit;
 mod info;
 mod markets;
+mod metrics;
+mod openapi;
 mod orders;
 mod peers;
 mod problem;
 mod route_factory;
 mod serde_peer_id;
 mod swaps;
+mod tls;
 mod tokens;
+mod updates;
+mod webhooks;
 
 pub use self::{
-    halbit::Halbit, hbit::Hbit, herc20::Herc20, problem::*, route_factory::create as create_routes,
+    auth::Tokens,
+    halbit::Halbit,
+    hbit::Hbit,
+    herc20::Herc20,
+    problem::*,
+    route_factory::create as create_routes,
+    swaps::LedgerSnapshot,
+    tls::{incoming as tls_incoming, Listener},
+    updates::{publish as publish_updates, Topic, Updates},
+    webhooks::worker as call_webhooks,
 };
 
 pub const PATH: &str = "swaps";
@@ -26,9 +42,10 @@ pub const PATH: &str = "swaps";
 use crate::{
     asset,
     asset::Erc20Quantity,
-    ethereum,
-    storage::{tables, BtcDaiOrder, CreatedSwap, Order},
-    LocalSwapId, Role, Secret, SecretHash, Timestamp,
+    bitcoin::SatPerVbyte,
+    ethereum, lnd_actions,
+    storage::{tables, BtcDaiOrder, CreatedSwap, LndExecution, Order, WalletExecution},
+    wallet_actions, LocalSwapId, Role, Secret, SecretHash, Timestamp,
 };
 use anyhow::Result;
 use chrono::Utc;
@@ -223,6 +240,8 @@ pub enum ActionName {
     Fund,
     Redeem,
     Refund,
+    Bump,
+    Cancel,
 }
 
 pub trait Events {
@@ -288,6 +307,38 @@ pub enum SwapEvent {
     HalbitIncorrectlyFunded,
     HalbitRedeemed,
     HalbitRefunded,
+
+    LndActionExecuted { action: lnd_actions::ActionKind },
+    LndActionFailed { action: lnd_actions::ActionKind },
+
+    WalletActionExecuted { action: wallet_actions::ActionKind },
+    WalletActionFailed { action: wallet_actions::ActionKind },
+
+    Cancelled { by: Role },
+}
+
+impl From<&LndExecution> for SwapEvent {
+    fn from(execution: &LndExecution) -> Self {
+        let action = execution.action;
+
+        if execution.succeeded {
+            SwapEvent::LndActionExecuted { action }
+        } else {
+            SwapEvent::LndActionFailed { action }
+        }
+    }
+}
+
+impl From<&WalletExecution> for SwapEvent {
+    fn from(execution: &WalletExecution) -> Self {
+        let action = execution.action;
+
+        if execution.succeeded {
+            SwapEvent::WalletActionExecuted { action }
+        } else {
+            SwapEvent::WalletActionFailed { action }
+        }
+    }
 }
 
 impl From<&herc20::State> for Vec<SwapEvent> {
@@ -492,6 +543,32 @@ impl From<DialInformation> for PeerId {
 #[error("action not found")]
 pub struct ActionNotFound;
 
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("lightning action is executed by cnd")]
+pub struct LightningActionExecutedByCnd;
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("fee rate {requested} does not exceed the previous fee rate {previous}")]
+pub struct FeeRateTooLow {
+    pub requested: SatPerVbyte,
+    pub previous: SatPerVbyte,
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("fee rate {previous} is already at the maximum of {maximum} for bumped transactions")]
+pub struct FeeRateAtMaximum {
+    pub previous: SatPerVbyte,
+    pub maximum: SatPerVbyte,
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("fee rate {requested} is not between {min} and {max}")]
+pub struct FeeRateOutOfRange {
+    pub requested: SatPerVbyte,
+    pub min: SatPerVbyte,
+    pub max: SatPerVbyte,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -608,6 +685,20 @@ mod tests {
         )
     }
 
+    #[test]
+    fn lnd_action_executed_event_serializes_correctly() {
+        let event = SwapEvent::LndActionExecuted {
+            action: lnd_actions::ActionKind::LndAddHoldInvoice,
+        };
+
+        let result = serde_json::to_string(&event).unwrap();
+
+        assert_eq!(
+            result,
+            r#"{"name":"lnd_action_executed","action":"lnd-add-hold-invoice"}"#
+        )
+    }
+
     #[test]
     fn herc20_protocol_serializes_correctly() {
         let protocol = Protocol::herc20_dai(Erc20Quantity::from_wei(1_000_000_000_000_000u64));
@@ -5,6 +5,7 @@ use crate::{
         lnd::Chain,
     },
     asset,
+    bitcoin::SatPerVbyte,
     ethereum::ChainId,
     identity, transaction, RelativeTime, Secret, SecretHash, Timestamp,
 };
@@ -25,6 +26,12 @@ pub enum ActionResponseBody {
         network: ledger::Bitcoin,
         #[serde(skip_serializing_if = "Option::is_none")]
         min_median_block_time: Option<Timestamp>,
+        fee_rate: SatPerVbyte,
+    },
+    BitcoinPsbt {
+        /// The base64 encoded PSBT as per BIP174.
+        psbt: String,
+        network: ledger::Bitcoin,
     },
     EthereumDeployContract {
         data: EthereumData,
@@ -90,6 +97,7 @@ impl ActionResponseBody {
     pub fn bitcoin_broadcast_signed_transaction(
         transaction: &transaction::Bitcoin,
         network: ledger::Bitcoin,
+        fee_rate: SatPerVbyte,
     ) -> Self {
         let min_median_block_time = if transaction.lock_time == 0 {
             None
@@ -104,10 +112,125 @@ impl ActionResponseBody {
             hex: ::bitcoin::consensus::encode::serialize_hex(transaction),
             network,
             min_median_block_time,
+            fee_rate,
         }
     }
 }
 
+/// Converts the output of one of the action traits into a BIP174 PSBT.
+///
+/// Only the Bitcoin fund action can be represented as a PSBT. Spends of
+/// HTLCs are signed by cnd, hence they are not offered for external signers.
+pub trait IntoPsbt {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody>;
+}
+
+impl IntoPsbt for bitcoin::SendToAddress {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody> {
+        let psbt = self.to_psbt()?;
+
+        Ok(ActionResponseBody::BitcoinPsbt {
+            psbt: base64::encode(::bitcoin::consensus::encode::serialize(&psbt)),
+            network: self.network,
+        })
+    }
+}
+
+impl IntoPsbt for bitcoin::BroadcastSignedTransaction {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody> {
+        anyhow::bail!(PsbtNotSupported)
+    }
+}
+
+impl IntoPsbt for ethereum::DeployContract {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody> {
+        anyhow::bail!(PsbtNotSupported)
+    }
+}
+
+impl IntoPsbt for ethereum::CallContract {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody> {
+        anyhow::bail!(PsbtNotSupported)
+    }
+}
+
+impl IntoPsbt for lnd::AddHoldInvoice {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody> {
+        anyhow::bail!(PsbtNotSupported)
+    }
+}
+
+impl IntoPsbt for lnd::SendPayment {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody> {
+        anyhow::bail!(PsbtNotSupported)
+    }
+}
+
+impl IntoPsbt for lnd::SettleInvoice {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody> {
+        anyhow::bail!(PsbtNotSupported)
+    }
+}
+
+impl IntoPsbt for comit::Never {
+    fn into_psbt(self) -> anyhow::Result<ActionResponseBody> {
+        unreachable!("impl should be removed once ! type is stabilised")
+    }
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("action cannot be represented as a PSBT")]
+pub struct PsbtNotSupported;
+
+/// The output of the redeem and refund action traits.
+///
+/// Spending a Bitcoin HTLC is the only such action that involves a
+/// transaction signed by cnd. Its fee rate is chosen when handing out the
+/// action, all other actions are ready as they are.
+#[derive(Debug)]
+pub enum Spend<A> {
+    Ready(A),
+    BitcoinHtlc(bitcoin::SpendHtlc),
+}
+
+pub trait IntoSpend {
+    type Ready: IntoPsbt + Into<ActionResponseBody>;
+
+    fn into_spend(self) -> Spend<Self::Ready>;
+}
+
+impl IntoSpend for bitcoin::SpendHtlc {
+    type Ready = comit::Never;
+
+    fn into_spend(self) -> Spend<Self::Ready> {
+        Spend::BitcoinHtlc(self)
+    }
+}
+
+impl IntoSpend for ethereum::CallContract {
+    type Ready = Self;
+
+    fn into_spend(self) -> Spend<Self::Ready> {
+        Spend::Ready(self)
+    }
+}
+
+impl IntoSpend for lnd::SettleInvoice {
+    type Ready = Self;
+
+    fn into_spend(self) -> Spend<Self::Ready> {
+        Spend::Ready(self)
+    }
+}
+
+impl IntoSpend for comit::Never {
+    type Ready = Self;
+
+    fn into_spend(self) -> Spend<Self::Ready> {
+        Spend::Ready(self)
+    }
+}
+
 impl From<bitcoin::SendToAddress> for ActionResponseBody {
     fn from(action: SendToAddress) -> Self {
         let SendToAddress {
@@ -128,9 +251,10 @@ impl From<bitcoin::BroadcastSignedTransaction> for ActionResponseBody {
         bitcoin::BroadcastSignedTransaction {
             transaction,
             network,
+            fee_rate,
         }: bitcoin::BroadcastSignedTransaction,
     ) -> Self {
-        Self::bitcoin_broadcast_signed_transaction(&transaction, network)
+        Self::bitcoin_broadcast_signed_transaction(&transaction, network, fee_rate)
     }
 }
 
@@ -325,4 +449,58 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn bitcoin_broadcast_signed_transaction_serializes_fee_rate() {
+        let response_body = ActionResponseBody::BitcoinBroadcastSignedTransaction {
+            hex: "0200".to_owned(),
+            network: ledger::Bitcoin::Regtest,
+            min_median_block_time: None,
+            fee_rate: SatPerVbyte::new(12),
+        };
+
+        let serialized = serde_json::to_string(&response_body).unwrap();
+
+        assert_eq!(
+            serialized,
+            r#"{"type":"bitcoin-broadcast-signed-transaction","payload":{"hex":"0200","network":"regtest","fee_rate":12}}"#
+        );
+    }
+
+    #[test]
+    fn send_amount_to_address_converts_to_psbt() {
+        let to = BitcoinAddress::from_str("2N3pk6v15FrDiRNKYVuxnnugn1Yg7wfQRL9").unwrap();
+        let action = SendToAddress {
+            to: to.into(),
+            amount: asset::Bitcoin::from_sat(100_000_000),
+            network: ledger::Bitcoin::Regtest,
+        };
+
+        let response_body = action.into_psbt().unwrap();
+
+        let psbt = match response_body {
+            ActionResponseBody::BitcoinPsbt {
+                psbt,
+                network: ledger::Bitcoin::Regtest,
+            } => psbt,
+            _ => panic!("expected a PSBT for regtest"),
+        };
+        let psbt: bitcoin::PartiallySignedTransaction =
+            ::bitcoin::consensus::encode::deserialize(&base64::decode(psbt).unwrap()).unwrap();
+        assert_eq!(psbt.global.unsigned_tx.output[0].value, 100_000_000);
+    }
+
+    #[test]
+    fn ethereum_actions_cannot_be_converted_to_psbt() {
+        let action = ethereum::DeployContract {
+            data: vec![],
+            amount: asset::Ether::from_wei(0u32),
+            gas_limit: 0,
+            chain_id: ChainId::from(3),
+        };
+
+        let error = action.into_psbt().unwrap_err();
+
+        assert!(error.is::<PsbtNotSupported>());
+    }
 }
@@ -0,0 +1,314 @@
+//! Token based authentication for the HTTP API.
+//!
+//! cnd generates two tokens into its data directory on first start: One
+//! grants full access to the API, the other one only permits reading it.
+//! Clients present a token in the `Authorization: Bearer <token>` header.
+//!
+//! Handing out a swap action is recorded, e.g. it rules out cancelling the
+//! swap, hence the action endpoints need full access despite being GET routes.
+
+use anyhow::{Context, Result};
+use http_api_problem::HttpApiProblem;
+use rand::Rng;
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::Path,
+    sync::Arc,
+};
+use warp::{
+    http::{Method, StatusCode},
+    path::FullPath,
+    Filter, Rejection,
+};
+
+const FULL_ACCESS_TOKEN_FILE: &str = "api_token";
+const READ_ONLY_TOKEN_FILE: &str = "api_token_read_only";
+const TOKEN_LENGTH: usize = 32;
+/// The swap actions whose GET routes change what cnd does with the swap.
+const RECORDED_ACTIONS: &[&str] = &["fund", "deploy", "redeem", "refund", "bump"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Scope {
+    ReadOnly,
+    Full,
+}
+
+impl Scope {
+    fn permits(self, method: &Method, path: &str) -> bool {
+        match self {
+            Scope::Full => true,
+            Scope::ReadOnly => {
+                (*method == Method::GET || *method == Method::HEAD) && !is_recorded_action(path)
+            }
+        }
+    }
+}
+
+/// Whether the path is the one of a swap action that cnd records handing out.
+fn is_recorded_action(path: &str) -> bool {
+    let segments = path
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+
+    matches!(
+        segments.as_slice(),
+        ["swaps", _, action] if RECORDED_ACTIONS.contains(action)
+    )
+}
+
+#[derive(Clone, Debug)]
+pub struct Tokens {
+    full: String,
+    read_only: String,
+}
+
+impl Tokens {
+    /// Read the tokens from the directory, generating the ones that don't
+    /// exist yet.
+    pub fn from_dir_or_generate<D, R>(data_dir: D, mut rng: R) -> Result<Tokens>
+    where
+        D: AsRef<Path>,
+        R: Rng,
+    {
+        let dir = data_dir.as_ref();
+
+        Ok(Tokens {
+            full: read_or_generate(&dir.join(FULL_ACCESS_TOKEN_FILE), &mut rng)?,
+            read_only: read_or_generate(&dir.join(READ_ONLY_TOKEN_FILE), &mut rng)?,
+        })
+    }
+
+    fn scope(&self, token: &str) -> Option<Scope> {
+        if constant_time_eq(token, &self.full) {
+            Some(Scope::Full)
+        } else if constant_time_eq(token, &self.read_only) {
+            Some(Scope::ReadOnly)
+        } else {
+            None
+        }
+    }
+}
+
+/// Rejects all requests that don't present a token with sufficient scope.
+///
+/// Passing `None` disables authentication, i.e. every request is let through.
+pub fn authorize(tokens: Option<Tokens>) -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    let tokens = Arc::new(tokens);
+
+    warp::method()
+        .and(warp::path::full())
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(
+            move |method: Method, path: FullPath, authorization: Option<String>| {
+                let tokens = tokens.clone();
+
+                async move {
+                    match tokens.as_ref() {
+                        Some(tokens) => {
+                            check(tokens, &method, path.as_str(), authorization.as_deref())
+                                .map_err(warp::reject::custom)
+                        }
+                        None => Ok(()),
+                    }
+                }
+            },
+        )
+        .untuple_one()
+}
+
+fn check(
+    tokens: &Tokens,
+    method: &Method,
+    path: &str,
+    authorization: Option<&str>,
+) -> Result<(), HttpApiProblem> {
+    // Browsers never send credentials with CORS preflight requests
+    if *method == Method::OPTIONS {
+        return Ok(());
+    }
+
+    let scope = authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .and_then(|token| tokens.scope(token))
+        .ok_or_else(|| {
+            HttpApiProblem::new("Missing or invalid API token.")
+                .set_status(StatusCode::UNAUTHORIZED)
+                .set_detail("Present one of the API tokens from the data directory of cnd as `Authorization: Bearer <token>`.")
+        })?;
+
+    if !scope.permits(method, path) {
+        return Err(HttpApiProblem::new("API token lacks permission.")
+            .set_status(StatusCode::FORBIDDEN)
+            .set_detail("The read-only API token only permits GET requests that don't hand out swap actions."));
+    }
+
+    Ok(())
+}
+
+fn read_or_generate<R>(path: &Path, rng: &mut R) -> Result<String>
+where
+    R: Rng,
+{
+    if path.exists() {
+        let token = fs::read_to_string(path)
+            .with_context(|| format!("failed to read API token from {}", path.display()))?;
+        let token = token.trim().to_owned();
+        anyhow::ensure!(
+            !token.is_empty(),
+            "API token in {} is empty",
+            path.display()
+        );
+
+        return Ok(token);
+    }
+
+    let mut bytes = [0u8; TOKEN_LENGTH];
+    rng.fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    create_private(path)
+        .and_then(|mut file| file.write_all(token.as_bytes()))
+        .with_context(|| format!("failed to write API token to {}", path.display()))?;
+
+    tracing::info!("No API token found, creating at: {}", path.display());
+
+    Ok(token)
+}
+
+/// Creates a file only we can read, the tokens must not leak to other users.
+#[cfg(unix)]
+fn create_private(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> io::Result<File> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)
+}
+
+/// Compares without short-circuiting to not leak how much of a token matched.
+fn constant_time_eq(left: &str, right: &str) -> bool {
+    left.len() == right.len()
+        && left
+            .bytes()
+            .zip(right.bytes())
+            .fold(0, |acc, (l, r)| acc | (l ^ r))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::thread_rng;
+
+    fn tokens() -> Tokens {
+        Tokens {
+            full: "full".to_owned(),
+            read_only: "read-only".to_owned(),
+        }
+    }
+
+    async fn is_authorized(tokens: Option<Tokens>, method: &str, token: Option<&str>) -> bool {
+        is_authorized_for(tokens, method, "/swaps", token).await
+    }
+
+    async fn is_authorized_for(
+        tokens: Option<Tokens>,
+        method: &str,
+        path: &str,
+        token: Option<&str>,
+    ) -> bool {
+        let mut request = warp::test::request().method(method).path(path);
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {}", token));
+        }
+
+        request.filter(&authorize(tokens)).await.is_ok()
+    }
+
+    #[test]
+    fn tokens_are_generated_once() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let generated = Tokens::from_dir_or_generate(dir.path(), thread_rng()).unwrap();
+        let read = Tokens::from_dir_or_generate(dir.path(), thread_rng()).unwrap();
+
+        assert_eq!(generated.full, read.full);
+        assert_eq!(generated.read_only, read.read_only);
+        assert_ne!(generated.full, generated.read_only);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn generated_tokens_are_only_readable_by_us() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        Tokens::from_dir_or_generate(dir.path(), thread_rng()).unwrap();
+
+        for file in &[FULL_ACCESS_TOKEN_FILE, READ_ONLY_TOKEN_FILE] {
+            let mode = fs::metadata(dir.path().join(file))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[tokio::test]
+    async fn read_only_token_only_permits_reading() {
+        assert!(is_authorized(Some(tokens()), "GET", Some("read-only")).await);
+        assert!(!is_authorized(Some(tokens()), "POST", Some("read-only")).await);
+        assert!(is_authorized(Some(tokens()), "GET", Some("full")).await);
+        assert!(is_authorized(Some(tokens()), "POST", Some("full")).await);
+    }
+
+    #[tokio::test]
+    async fn handing_out_swap_actions_needs_full_access() {
+        let swap = "/swaps/7f3e1ae1-3d5e-4d0c-9b66-b9c5a5e2c0e7";
+
+        for action in RECORDED_ACTIONS {
+            let path = format!("{}/{}", swap, action);
+            assert!(!is_authorized_for(Some(tokens()), "GET", &path, Some("read-only")).await);
+            assert!(is_authorized_for(Some(tokens()), "GET", &path, Some("full")).await);
+        }
+        assert!(is_authorized_for(Some(tokens()), "GET", swap, Some("read-only")).await);
+        assert!(
+            is_authorized_for(
+                Some(tokens()),
+                "GET",
+                &format!("{}/init", swap),
+                Some("read-only")
+            )
+            .await
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_without_valid_token_are_rejected() {
+        assert!(!is_authorized(Some(tokens()), "GET", None).await);
+        assert!(!is_authorized(Some(tokens()), "GET", Some("")).await);
+        assert!(!is_authorized(Some(tokens()), "GET", Some("fulll")).await);
+        assert!(is_authorized(Some(tokens()), "OPTIONS", None).await);
+    }
+
+    #[tokio::test]
+    async fn every_request_passes_if_authentication_is_disabled() {
+        assert!(is_authorized(None, "GET", None).await);
+        assert!(is_authorized(None, "POST", None).await);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    actions::bitcoin::{BroadcastSignedTransaction, SendToAddress},
+    actions::bitcoin::{SendToAddress, SpendHtlc},
     asset,
     bitcoin::Address,
     ledger, Secret, SecretHash, Timestamp,
@@ -77,10 +77,7 @@ impl FinalizedAsFunder {
         params.build_fund_action()
     }
 
-    pub fn build_refund_action(
-        &self,
-        secret_hash: SecretHash,
-    ) -> anyhow::Result<BroadcastSignedTransaction> {
+    pub fn build_refund_action(&self, secret_hash: SecretHash) -> anyhow::Result<SpendHtlc> {
         let (fund_amount, fund_location) = match &self.state {
             State::Funded {
                 asset: fund_amount,
@@ -93,13 +90,15 @@ impl FinalizedAsFunder {
         let transient_refund_sk = self.transient_refund_identity;
         let refund_address = self.final_refund_identity.clone().into();
         let params = self.build_params(secret_hash);
-        params.build_refund_action(
+        let action = params.build_refund_action(
             &*crate::SECP,
             *fund_amount,
             *fund_location,
             transient_refund_sk,
             refund_address,
-        )
+        );
+
+        Ok(action)
     }
 
     fn build_params(&self, secret_hash: SecretHash) -> Params {
@@ -119,10 +118,7 @@ impl FinalizedAsFunder {
 }
 
 impl FinalizedAsRedeemer {
-    pub fn build_redeem_action(
-        &self,
-        secret: Secret,
-    ) -> anyhow::Result<BroadcastSignedTransaction> {
+    pub fn build_redeem_action(&self, secret: Secret) -> anyhow::Result<SpendHtlc> {
         let (fund_amount, fund_location) = match &self.state {
             State::Funded {
                 asset: fund_amount,
@@ -137,14 +133,16 @@ impl FinalizedAsRedeemer {
 
         let secret_hash = SecretHash::new(secret);
         let params = self.build_params(secret_hash);
-        params.build_redeem_action(
+        let action = params.build_redeem_action(
             &*crate::SECP,
             *fund_amount,
             *fund_location,
             transient_redeem_sk,
             redeem_address,
             secret,
-        )
+        );
+
+        Ok(action)
     }
 
     fn build_params(&self, secret_hash: SecretHash) -> Params {
@@ -1,5 +1,5 @@
 use crate::{
-    actions::bitcoin::{BroadcastSignedTransaction, SendToAddress},
+    actions::bitcoin::{SendToAddress, SpendHtlc},
     http_api::{
         hbit, herc20, ActionNotFound, AliceSwap, AlphaAbsoluteExpiry, AlphaLedger, AlphaProtocol,
         BetaAbsoluteExpiry, BetaLedger, BetaProtocol, Events, Ledger, Protocol, SwapEvent,
@@ -64,7 +64,7 @@ impl RedeemAction
 impl RefundAction
     for AliceSwap<asset::Bitcoin, asset::Erc20, hbit::FinalizedAsFunder, herc20::Finalized>
 {
-    type Output = BroadcastSignedTransaction;
+    type Output = SpendHtlc;
 
     fn refund_action(&self) -> anyhow::Result<Self::Output> {
         match self {
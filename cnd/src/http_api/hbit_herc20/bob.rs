@@ -1,5 +1,5 @@
 use crate::{
-    actions::bitcoin::BroadcastSignedTransaction,
+    actions::bitcoin::SpendHtlc,
     http_api::{
         hbit, herc20, ActionNotFound, AlphaAbsoluteExpiry, AlphaLedger, AlphaProtocol,
         BetaAbsoluteExpiry, BetaLedger, BetaProtocol, BobSwap, Events, Ledger, Protocol, SwapEvent,
@@ -73,7 +73,7 @@ impl FundAction
 impl RedeemAction
     for BobSwap<asset::Bitcoin, asset::Erc20, hbit::FinalizedAsRedeemer, herc20::Finalized>
 {
-    type Output = BroadcastSignedTransaction;
+    type Output = SpendHtlc;
 
     fn redeem_action(&self) -> anyhow::Result<Self::Output> {
         match self {
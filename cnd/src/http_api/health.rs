@@ -0,0 +1,50 @@
+//! The "/health" and "/ready" endpoints report the status of the nodes cnd
+//! connects to, its database and its libp2p listeners, see [`Health`].
+//!
+//! Both respond with the same report. They differ in the status code: "/health"
+//! responds with 503 unless everything is healthy whereas "/ready" only does
+//! so if cnd cannot make progress on the swaps that are in progress.
+
+use crate::{
+    health::{Health, Report},
+    network::Swarm,
+    storage::Storage,
+};
+use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+/// The warp filter serving "/health" and "/ready".
+pub fn routes(
+    health: Health,
+    storage: Storage,
+    swarm: Swarm,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let health = warp::any().map(move || health.clone());
+    let storage = warp::any().map(move || storage.clone());
+    let swarm = warp::any().map(move || swarm.clone());
+    let report = health.and(storage).and(swarm).and_then(
+        |health: Health, storage: Storage, swarm: Swarm| async move {
+            Ok::<_, Rejection>(health.check(&storage, &swarm).await)
+        },
+    );
+
+    let health_route = warp::get()
+        .and(warp::path!("health"))
+        .and(report.clone())
+        .map(|report: Report| reply(report.healthy, &report));
+    let ready_route = warp::get()
+        .and(warp::path!("ready"))
+        .and(report)
+        .map(|report: Report| reply(report.ready, &report));
+
+    health_route.or(ready_route)
+}
+
+fn reply(ok: bool, report: &Report) -> impl Reply {
+    let status = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    warp::reply::with_status(warp::reply::json(report), status)
+}
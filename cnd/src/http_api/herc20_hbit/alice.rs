@@ -1,5 +1,5 @@
 use crate::{
-    actions::bitcoin::BroadcastSignedTransaction,
+    actions::bitcoin::SpendHtlc,
     http_api::{
         hbit, herc20, ActionNotFound, AliceSwap, AlphaAbsoluteExpiry, AlphaLedger, AlphaProtocol,
         BetaAbsoluteExpiry, BetaLedger, BetaProtocol, Events, Ledger, Protocol, SwapEvent,
@@ -75,7 +75,7 @@ impl FundAction
 impl RedeemAction
     for AliceSwap<asset::Erc20, asset::Bitcoin, herc20::Finalized, hbit::FinalizedAsRedeemer>
 {
-    type Output = BroadcastSignedTransaction;
+    type Output = SpendHtlc;
 
     fn redeem_action(&self) -> anyhow::Result<Self::Output> {
         match self {
@@ -1,5 +1,5 @@
 use crate::{
-    actions::bitcoin::{BroadcastSignedTransaction, SendToAddress},
+    actions::bitcoin::{SendToAddress, SpendHtlc},
     http_api::{
         hbit, herc20, ActionNotFound, AlphaAbsoluteExpiry, AlphaLedger, AlphaProtocol,
         BetaAbsoluteExpiry, BetaLedger, BetaProtocol, BobSwap, Events, Ledger, Protocol, SwapEvent,
@@ -71,7 +71,7 @@ impl RedeemAction
 impl RefundAction
     for BobSwap<asset::Erc20, asset::Bitcoin, herc20::Finalized, hbit::FinalizedAsFunder>
 {
-    type Output = BroadcastSignedTransaction;
+    type Output = SpendHtlc;
     fn refund_action(&self) -> anyhow::Result<Self::Output> {
         match self {
             BobSwap::Finalized {
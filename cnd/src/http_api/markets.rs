@@ -1,3 +1,3 @@
 mod get_btc_dai;
 
-pub use get_btc_dai::route as get_btc_dai;
+pub use get_btc_dai::{market_items, route as get_btc_dai};
@@ -23,36 +23,46 @@ pub fn route(
         })
 }
 
-/// Retrieves viable orders: orders that have expiries that match the safe
-/// expiries determined by the expiries module.
 async fn handler(swarm: Swarm, network: comit::Network) -> Result<impl Reply> {
     let mut orders = siren::Entity::default();
-    let local_peer_id = swarm.local_peer_id();
-
-    let viable_orders = swarm
-        .btc_dai_market()
-        .await
-        .into_iter()
-        .filter(|(_, order)| has_viable_expiries(order, network));
-
-    for (maker, order) in viable_orders {
-        let market_item = siren::Entity::default()
-            .with_properties(MarketItem {
-                id: order.id,
-                quantity: Amount::from(order.quantity),
-                price: Amount::from(order.price),
-                ours: maker == local_peer_id,
-                maker,
-                position: order.position,
-            })
-            .context("failed to serialize market item sub entity")?;
 
+    for (_, market_item) in market_items(&swarm, network).await? {
         orders.push_sub_entity(siren::SubEntity::from_entity(market_item, &["item"]))
     }
 
     Ok(reply::json(&orders))
 }
 
+/// Retrieves viable orders: orders that have expiries that match the safe
+/// expiries determined by the expiries module.
+pub async fn market_items(
+    swarm: &Swarm,
+    network: comit::Network,
+) -> Result<Vec<(OrderId, siren::Entity)>> {
+    let local_peer_id = swarm.local_peer_id();
+
+    swarm
+        .btc_dai_market()
+        .await
+        .into_iter()
+        .filter(|(_, order)| has_viable_expiries(order, network))
+        .map(|(maker, order)| {
+            let market_item = siren::Entity::default()
+                .with_properties(MarketItem {
+                    id: order.id,
+                    quantity: Amount::from(order.quantity),
+                    price: Amount::from(order.price),
+                    ours: maker == local_peer_id,
+                    maker,
+                    position: order.position,
+                })
+                .context("failed to serialize market item sub entity")?;
+
+            Ok((order.id, market_item))
+        })
+        .collect()
+}
+
 pub fn has_viable_expiries(order: &BtcDaiOrder, network: comit::Network) -> bool {
     match order.swap_protocol {
         SwapProtocol::HbitHerc20 {
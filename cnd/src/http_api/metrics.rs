@@ -0,0 +1,31 @@
+//! The "/metrics" endpoint exposes the metrics of cnd in the Prometheus text
+//! format, see [`Metrics`].
+
+use crate::{connectors::Connectors, metrics::Metrics, network::Swarm};
+use warp::{http::header::CONTENT_TYPE, Filter, Rejection, Reply};
+
+/// The content type of version 0.0.4 of the Prometheus text format.
+const CONTENT_TYPE_PROMETHEUS: &str = "text/plain; version=0.0.4";
+
+/// The warp filter serving the metrics.
+pub fn route(
+    metrics: Metrics,
+    swarm: Swarm,
+    connectors: Connectors,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::get().and(warp::path!("metrics")).and_then(move || {
+        let metrics = metrics.clone();
+        let swarm = swarm.clone();
+        let connectors = connectors.clone();
+
+        async move {
+            let body = metrics.render(&swarm, &connectors.cache_stats()).await;
+
+            Ok::<_, Rejection>(warp::reply::with_header(
+                body,
+                CONTENT_TYPE,
+                CONTENT_TYPE_PROMETHEUS,
+            ))
+        }
+    })
+}
@@ -0,0 +1,1149 @@
+//! The OpenAPI 3 description of the HTTP API, served on "/openapi.json".
+//!
+//! The schemas of request and response bodies are declared next to each other
+//! through the [`Schema`] trait so client SDKs can be generated from the
+//! document. Tests assert that the documented operations are exactly the ones
+//! served by the routes and that actual bodies are valid as per their schemas.
+
+use crate::{
+    health::Report,
+    http_api::{action::ActionResponseBody, DialInformation, Halbit, Hbit, Herc20},
+};
+use serde_json::{json, Map, Value};
+use warp::{Filter, Rejection, Reply};
+
+/// The warp filter serving the OpenAPI document.
+pub fn route() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("openapi.json"))
+        .map(|| warp::reply::json(&document()))
+}
+
+/// A type whose JSON representation in the HTTP API can be described as an
+/// OpenAPI schema.
+pub trait Schema {
+    /// The name of the schema in the `components` section of the document.
+    const NAME: &'static str;
+
+    fn schema() -> Value;
+
+    fn reference() -> Value {
+        json!({ "$ref": format!("#/components/schemas/{}", Self::NAME) })
+    }
+}
+
+impl Schema for Herc20 {
+    const NAME: &'static str = "Herc20";
+
+    fn schema() -> Value {
+        object(
+            &[
+                ("amount", wei()),
+                ("identity", ethereum_address()),
+                ("chain_id", json!({ "type": "integer" })),
+                ("token_contract", ethereum_address()),
+                ("absolute_expiry", unix_timestamp()),
+            ],
+            &[],
+        )
+    }
+}
+
+impl Schema for Hbit {
+    const NAME: &'static str = "Hbit";
+
+    fn schema() -> Value {
+        object(
+            &[
+                ("amount", satoshis()),
+                ("final_identity", json!({ "type": "string" })),
+                ("network", bitcoin_network()),
+                ("absolute_expiry", unix_timestamp()),
+            ],
+            &[],
+        )
+    }
+}
+
+impl Schema for Halbit {
+    const NAME: &'static str = "Halbit";
+
+    fn schema() -> Value {
+        object(
+            &[
+                ("amount", satoshis()),
+                ("identity", lightning_public_key()),
+                ("network", bitcoin_network()),
+                ("cltv_expiry", json!({ "type": "integer", "minimum": 0 })),
+            ],
+            &[],
+        )
+    }
+}
+
+impl Schema for DialInformation {
+    const NAME: &'static str = "DialInformation";
+
+    fn schema() -> Value {
+        json!({
+            "oneOf": [
+                peer_id(),
+                object(&[("peer_id", peer_id()), ("address_hint", multiaddr())], &[]),
+            ]
+        })
+    }
+}
+
+impl Schema for ActionResponseBody {
+    const NAME: &'static str = "Action";
+
+    fn schema() -> Value {
+        let bitcoin_network = bitcoin_network();
+        let chain_id = json!({ "type": "integer" });
+        let gas_limit = json!({ "type": "string", "description": "Hex encoded." });
+        let hex = json!({ "type": "string", "description": "Hex encoded." });
+        let relative_time = json!({ "type": "integer", "minimum": 0 });
+        let chain = json!({ "type": "string", "enum": ["bitcoin"] });
+
+        let variants = vec![
+            action(
+                "bitcoin-send-amount-to-address",
+                &[
+                    ("to", json!({ "type": "string" })),
+                    ("amount", satoshis()),
+                    ("network", bitcoin_network.clone()),
+                ],
+                &[],
+            ),
+            action(
+                "bitcoin-broadcast-signed-transaction",
+                &[
+                    ("hex", hex.clone()),
+                    ("network", bitcoin_network.clone()),
+                    ("fee_rate", sat_per_vbyte()),
+                ],
+                &[("min_median_block_time", unix_timestamp())],
+            ),
+            action(
+                "bitcoin-psbt",
+                &[
+                    (
+                        "psbt",
+                        json!({ "type": "string", "description": "Base64 encoded as per BIP174." }),
+                    ),
+                    ("network", bitcoin_network.clone()),
+                ],
+                &[],
+            ),
+            action(
+                "ethereum-deploy-contract",
+                &[
+                    ("data", hex.clone()),
+                    ("amount", wei()),
+                    ("gas_limit", gas_limit.clone()),
+                    ("chain_id", chain_id.clone()),
+                ],
+                &[],
+            ),
+            action(
+                "ethereum-call-contract",
+                &[
+                    ("contract_address", ethereum_address()),
+                    ("gas_limit", gas_limit),
+                    ("chain_id", chain_id),
+                ],
+                &[
+                    ("data", hex.clone()),
+                    ("min_block_timestamp", unix_timestamp()),
+                ],
+            ),
+            action(
+                "lnd-add-hold-invoice",
+                &[
+                    ("amount", satoshis()),
+                    ("secret_hash", hex.clone()),
+                    ("expiry", relative_time.clone()),
+                    ("cltv_expiry", relative_time.clone()),
+                    ("chain", chain.clone()),
+                    ("network", bitcoin_network.clone()),
+                    ("self_public_key", lightning_public_key()),
+                ],
+                &[],
+            ),
+            action(
+                "lnd-send-payment",
+                &[
+                    ("to_public_key", lightning_public_key()),
+                    ("amount", satoshis()),
+                    ("secret_hash", hex.clone()),
+                    ("final_cltv_delta", relative_time),
+                    ("chain", chain.clone()),
+                    ("network", bitcoin_network.clone()),
+                    ("self_public_key", lightning_public_key()),
+                ],
+                &[],
+            ),
+            action(
+                "lnd-settle-invoice",
+                &[
+                    ("secret", hex),
+                    ("chain", chain),
+                    ("network", bitcoin_network),
+                    ("self_public_key", lightning_public_key()),
+                ],
+                &[],
+            ),
+        ];
+
+        json!({ "oneOf": variants, "discriminator": { "propertyName": "type" } })
+    }
+}
+
+/// The problem+json body of every error response, see RFC 7807.
+struct Problem;
+
+impl Schema for Problem {
+    const NAME: &'static str = "Problem";
+
+    fn schema() -> Value {
+        object(
+            &[("title", json!({ "type": "string" }))],
+            &[
+                ("type", json!({ "type": "string", "format": "uri" })),
+                ("status", json!({ "type": "integer" })),
+                ("detail", json!({ "type": "string" })),
+            ],
+        )
+    }
+}
+
+/// The hypermedia documents most resources are represented as, see
+/// <https://github.com/kevinswiber/siren>.
+struct Siren;
+
+impl Schema for Siren {
+    const NAME: &'static str = "Siren";
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "class": { "type": "array", "items": { "type": "string" } },
+                "properties": { "type": "object" },
+                "entities": { "type": "array", "items": { "type": "object" } },
+                "actions": { "type": "array", "items": { "type": "object" } },
+                "links": { "type": "array", "items": { "type": "object" } }
+            }
+        })
+    }
+}
+
+impl Schema for Report {
+    const NAME: &'static str = "HealthReport";
+
+    fn schema() -> Value {
+        let boolean = || json!({ "type": "boolean" });
+        let node = object(
+            &[
+                ("reachable", boolean()),
+                ("network_matches", boolean()),
+                ("best_block_height", nullable("integer")),
+                ("best_block_age", nullable("integer")),
+                ("synced", boolean()),
+                ("error", nullable("string")),
+            ],
+            &[],
+        );
+        let lightning = object(
+            &[
+                ("configured", boolean()),
+                ("reachable", boolean()),
+                ("synced", boolean()),
+                ("block_height", nullable("integer")),
+                ("can_follow_htlcs", boolean()),
+                ("can_execute_actions", nullable("boolean")),
+                ("error", nullable("string")),
+            ],
+            &[],
+        );
+        let database = object(
+            &[("writable", boolean()), ("error", nullable("string"))],
+            &[],
+        );
+        let network = object(
+            &[
+                ("listening", boolean()),
+                (
+                    "listen_addresses",
+                    json!({ "type": "array", "items": multiaddr() }),
+                ),
+                ("connected_peers", json!({ "type": "integer" })),
+            ],
+            &[],
+        );
+        let ledger = json!({ "type": "string", "enum": ["bitcoin", "ethereum", "lightning"] });
+
+        object(
+            &[
+                ("healthy", boolean()),
+                ("ready", boolean()),
+                ("bitcoin", node.clone()),
+                ("ethereum", node),
+                ("lightning", lightning),
+                ("database", database),
+                ("network", network),
+                (
+                    "active_swap_ledgers",
+                    json!({ "type": "array", "items": ledger }),
+                ),
+            ],
+            &[],
+        )
+    }
+}
+
+/// The body to create a swap with the protocols `A` and `B`.
+fn post_swap_body<A, B>() -> Value
+where
+    A: Schema,
+    B: Schema,
+{
+    object(
+        &[
+            ("alpha", A::reference()),
+            ("beta", B::reference()),
+            ("peer", DialInformation::reference()),
+            ("role", role()),
+        ],
+        &[],
+    )
+}
+
+pub fn document() -> Value {
+    let schemas = vec![
+        (Herc20::NAME, Herc20::schema()),
+        (Hbit::NAME, Hbit::schema()),
+        (Halbit::NAME, Halbit::schema()),
+        (DialInformation::NAME, DialInformation::schema()),
+        (ActionResponseBody::NAME, ActionResponseBody::schema()),
+        (Problem::NAME, Problem::schema()),
+        (Siren::NAME, Siren::schema()),
+        (Report::NAME, Report::schema()),
+    ]
+    .into_iter()
+    .map(|(name, schema)| (name.to_owned(), schema))
+    .collect::<Map<_, _>>();
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "COMIT network daemon",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "components": {
+            "schemas": schemas,
+            "securitySchemes": {
+                "token": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "One of the API tokens from the data directory, only required if `http_api.auth` is enabled. The read-only token permits GET requests only, except for the fund, deploy, redeem, refund and bump actions of a swap.",
+                },
+            },
+        },
+        "security": [{ "token": [] }],
+        "paths": paths(),
+    })
+}
+
+fn paths() -> Value {
+    let swap_id = path_parameter("id", json!({ "type": "string", "format": "uuid" }));
+    let order_id = path_parameter("order_id", json!({ "type": "string", "format": "uuid" }));
+    let peer_id_parameter = path_parameter("peer_id", peer_id());
+    let fee_rate = query_parameter(
+        "fee_rate",
+        "The fee rate in sat/vbyte to sign Bitcoin transactions with, estimated if absent.",
+        json!({ "type": "integer", "minimum": 1, "maximum": 1000 }),
+    );
+    let format = query_parameter(
+        "format",
+        "Pass `psbt` to receive a Bitcoin fund action as a BIP174 PSBT.",
+        json!({ "type": "string", "enum": ["psbt"] }),
+    );
+
+    let get_action = |name: &str, parameters: Vec<Value>| {
+        let mut parameters = parameters;
+        parameters.insert(0, swap_id.clone());
+
+        json!({
+            "get": {
+                "summary": format!("The {} action of the swap.", name),
+                "parameters": parameters,
+                "responses": responses(ok_json(ActionResponseBody::reference())),
+            }
+        })
+    };
+    let post_swap = |alpha: &str, beta: &str, body: Value| {
+        json!({
+            "post": {
+                "summary": format!("Create a swap of {} on alpha and {} on beta.", alpha, beta),
+                "requestBody": json_body(body),
+                "responses": responses(created("/swaps/{id}")),
+            }
+        })
+    };
+    let post_peer = |summary: &str| {
+        json!({
+            "post": {
+                "summary": summary,
+                "parameters": [peer_id_parameter.clone()],
+                "responses": responses(ok_json(json!({ "type": "object" }))),
+            }
+        })
+    };
+
+    json!({
+        "/": {
+            "get": {
+                "summary": "Our peer id and listen addresses, as siren document if requested with `Accept: application/vnd.siren+json`.",
+                "responses": responses(ok_json(object(
+                    &[
+                        ("id", peer_id()),
+                        ("listen_addresses", json!({ "type": "array", "items": multiaddr() })),
+                    ],
+                    &[],
+                ))),
+            }
+        },
+        "/openapi.json": {
+            "get": {
+                "summary": "This document.",
+                "responses": responses(ok_json(json!({ "type": "object" }))),
+            }
+        },
+        "/peers": {
+            "get": {
+                "summary": "The peers we know of and their reputation.",
+                "responses": responses(ok_json(json!({ "type": "object" }))),
+            }
+        },
+        "/peers/{peer_id}/ban": post_peer("Ban a peer, regardless of its reputation."),
+        "/peers/{peer_id}/unban": post_peer("Lift the ban of a peer."),
+        "/dial": {
+            "post": {
+                "summary": "Dial the given addresses.",
+                "requestBody": json_body(object(
+                    &[("addresses", json!({ "type": "array", "items": multiaddr() }))],
+                    &[],
+                )),
+                "responses": responses(json!({ "200": { "description": "Dialling." } })),
+            }
+        },
+        "/swaps": {
+            "get": {
+                "summary": "One page of our swaps, with a `next` link to the following page.",
+                "parameters": [
+                    query_parameter("status", "How far the swap got.", json!({ "type": "string", "enum": ["in_progress", "redeemed", "refunded", "aborted"] })),
+                    query_parameter("role", "Our role in the swap.", role()),
+                    query_parameter("alpha", "The protocol on the alpha ledger.", lock_protocol()),
+                    query_parameter("beta", "The protocol on the beta ledger.", lock_protocol()),
+                    query_parameter("peer", "The counterparty.", peer_id()),
+                    query_parameter("started_after", "Only swaps that started at or after this point in time.", json!({ "type": "string", "format": "date-time" })),
+                    query_parameter("started_before", "Only swaps that started before this point in time.", json!({ "type": "string", "format": "date-time" })),
+                    query_parameter("sort", "Which swaps come first.", json!({ "type": "string", "enum": ["oldest", "newest"], "default": "oldest" })),
+                    query_parameter("cursor", "The last swap of the previous page.", json!({ "type": "string", "format": "uuid" })),
+                    query_parameter("limit", "The size of the page.", json!({ "type": "integer", "minimum": 0, "maximum": 1000, "default": 100 })),
+                ],
+                "responses": responses(ok_json(Siren::reference())),
+            }
+        },
+        "/swaps/herc20/halbit": post_swap("herc20", "halbit", post_swap_body::<Herc20, Halbit>()),
+        "/swaps/halbit/herc20": post_swap("halbit", "herc20", post_swap_body::<Halbit, Herc20>()),
+        "/swaps/herc20/hbit": post_swap("herc20", "hbit", post_swap_body::<Herc20, Hbit>()),
+        "/swaps/hbit/herc20": post_swap("hbit", "herc20", post_swap_body::<Hbit, Herc20>()),
+        "/swaps/{id}": {
+            "get": {
+                "summary": "The swap with its events and available actions.",
+                "parameters": [swap_id.clone()],
+                "responses": responses(ok_json(Siren::reference())),
+            }
+        },
+        "/swaps/{id}/init": get_action("init", vec![]),
+        "/swaps/{id}/fund": get_action("fund", vec![format.clone()]),
+        "/swaps/{id}/deploy": get_action("deploy", vec![]),
+        "/swaps/{id}/redeem": get_action("redeem", vec![fee_rate.clone()]),
+        "/swaps/{id}/refund": get_action("refund", vec![fee_rate.clone()]),
+        "/swaps/{id}/bump": get_action("bump", vec![fee_rate]),
+        "/swaps/{id}/cancel": {
+            "post": {
+                "summary": "Cancel the swap, possible until Alice funds it and once the counterparty accepts the cancellation.",
+                "parameters": [swap_id],
+                "responses": responses(json!({ "200": { "description": "The swap is cancelled." } })),
+            }
+        },
+        "/orders": {
+            "get": {
+                "summary": "Our open orders.",
+                "responses": responses(ok_json(Siren::reference())),
+            }
+        },
+        "/orders/BTC-DAI": {
+            "post": {
+                "summary": "Make a BTC/DAI order.",
+                "requestBody": json_body(object(
+                    &[
+                        ("position", json!({ "type": "string", "enum": ["buy", "sell"] })),
+                        ("quantity", satoshis()),
+                        ("price", json!({ "type": "string", "description": "Wei per bitcoin, as decimal string." })),
+                        ("swap", object(
+                            &[
+                                ("bitcoin_address", json!({ "type": "string" })),
+                                ("ethereum_address", ethereum_address()),
+                            ],
+                            &[("role", role())],
+                        )),
+                    ],
+                    &[],
+                )),
+                "responses": responses(created("/orders/{order_id}")),
+            }
+        },
+        "/orders/{order_id}": {
+            "get": {
+                "summary": "The order.",
+                "parameters": [order_id.clone()],
+                "responses": responses(ok_json(Siren::reference())),
+            },
+            "patch": {
+                "summary": "Amend the price and/or the remaining quantity of the open order, it keeps its id.",
+                "parameters": [order_id.clone()],
+                "requestBody": json_body(object(
+                    &[],
+                    &[
+                        ("price", json!({ "type": "string", "description": "In the same unit as when making the order." })),
+                        ("quantity", satoshis()),
+                    ],
+                )),
+                "responses": responses(json!({ "200": { "description": "The order is amended." } })),
+            },
+            "delete": {
+                "summary": "Cancel the order.",
+                "parameters": [order_id.clone()],
+                "responses": responses(json!({ "200": { "description": "The order is cancelled." } })),
+            }
+        },
+        "/orders/{order_id}/take": {
+            "post": {
+                "summary": "Take an order of another maker from the BTC/DAI market, responds once the maker set up the swap.",
+                "parameters": [order_id],
+                "requestBody": json_body(object(
+                    &[
+                        ("swap", object(
+                            &[
+                                ("bitcoin_address", json!({ "type": "string" })),
+                                ("ethereum_address", ethereum_address()),
+                            ],
+                            &[],
+                        )),
+                    ],
+                    &[("quantity", satoshis())],
+                )),
+                "responses": responses(created("/swaps/{id}")),
+            }
+        },
+        "/markets/BTC-DAI": {
+            "get": {
+                "summary": "The BTC/DAI orders of all peers we know of.",
+                "responses": responses(ok_json(Siren::reference())),
+            }
+        },
+        "/metrics": {
+            "get": {
+                "summary": "Metrics about swaps, connectors, peers, the orderbook and this API in the Prometheus text format.",
+                "responses": responses(json!({
+                    "200": {
+                        "description": "The metrics.",
+                        "content": { "text/plain": { "schema": { "type": "string" } } },
+                    }
+                })),
+            }
+        },
+        "/health": {
+            "get": {
+                "summary": "The status of the nodes we connect to, our database and our libp2p listeners.",
+                "responses": health_responses("Everything is healthy."),
+            }
+        },
+        "/ready": {
+            "get": {
+                "summary": "The same report as \"/health\", only unavailable if we cannot make progress on the swaps in progress.",
+                "responses": health_responses("The ledgers of all swaps in progress, the database and the libp2p listeners are healthy."),
+            }
+        },
+        "/tokens": {
+            "get": {
+                "summary": "The ERC20 tokens we support.",
+                "responses": responses(ok_json(json!({ "type": "array", "items": { "type": "object" } }))),
+            }
+        },
+        "/updates": {
+            "get": {
+                "summary": "Server-sent events for every change to our swaps, our orders and the BTC/DAI market.",
+                "parameters": [
+                    query_parameter("cursor", "Resume after the event with this id, like the `Last-Event-ID` header.", json!({ "type": "integer", "minimum": 0 })),
+                ],
+                "responses": responses(json!({
+                    "200": {
+                        "description": "The stream of events.",
+                        "content": { "text/event-stream": { "schema": { "type": "string" } } },
+                    }
+                })),
+            }
+        },
+    })
+}
+
+/// Adds the problem+json response every operation can fail with.
+fn responses(success: Value) -> Value {
+    let mut responses = success;
+    responses["default"] = json!({
+        "description": "The request failed.",
+        "content": { "application/problem+json": { "schema": Problem::reference() } },
+    });
+
+    responses
+}
+
+/// Both the successful and the unavailable response carry the report.
+fn health_responses(ok: &str) -> Value {
+    let report = json!({ "application/json": { "schema": Report::reference() } });
+
+    responses(json!({
+        "200": { "description": ok, "content": report },
+        "503": { "description": "Unavailable, see the report for the reason.", "content": report },
+    }))
+}
+
+fn ok_json(schema: Value) -> Value {
+    json!({
+        "200": {
+            "description": "OK",
+            "content": { "application/json": { "schema": schema } },
+        }
+    })
+}
+
+fn created(location: &str) -> Value {
+    json!({
+        "201": {
+            "description": format!("Created, the `Location` header points to {}.", location),
+            "headers": { "Location": { "schema": { "type": "string" } } },
+        }
+    })
+}
+
+fn json_body(schema: Value) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+fn path_parameter(name: &str, schema: Value) -> Value {
+    json!({ "name": name, "in": "path", "required": true, "schema": schema })
+}
+
+fn query_parameter(name: &str, description: &str, schema: Value) -> Value {
+    json!({ "name": name, "in": "query", "description": description, "schema": schema })
+}
+
+fn object(required: &[(&str, Value)], optional: &[(&str, Value)]) -> Value {
+    let properties = required
+        .iter()
+        .chain(optional)
+        .map(|(name, schema)| ((*name).to_owned(), schema.clone()))
+        .collect::<Map<_, _>>();
+    let required = required.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+
+    let mut schema = json!({ "type": "object", "properties": properties });
+    // OpenAPI does not allow an empty list of required properties.
+    if !required.is_empty() {
+        schema["required"] = json!(required);
+    }
+
+    schema
+}
+
+/// A variant of the action enum, tagged with its `type`.
+fn action(name: &str, required: &[(&str, Value)], optional: &[(&str, Value)]) -> Value {
+    object(
+        &[
+            ("type", json!({ "type": "string", "enum": [name] })),
+            ("payload", object(required, optional)),
+        ],
+        &[],
+    )
+}
+
+fn satoshis() -> Value {
+    json!({ "type": "string", "description": "Satoshis, as decimal string." })
+}
+
+fn wei() -> Value {
+    json!({ "type": "string", "description": "Wei, as decimal string." })
+}
+
+fn sat_per_vbyte() -> Value {
+    json!({ "type": "integer", "minimum": 0 })
+}
+
+fn unix_timestamp() -> Value {
+    json!({ "type": "integer", "minimum": 0, "description": "Seconds since the unix epoch." })
+}
+
+fn ethereum_address() -> Value {
+    json!({ "type": "string", "pattern": "^0x[0-9a-fA-F]{40}$" })
+}
+
+fn lightning_public_key() -> Value {
+    json!({ "type": "string", "description": "Hex encoded." })
+}
+
+fn bitcoin_network() -> Value {
+    json!({ "type": "string", "enum": ["mainnet", "testnet", "regtest"] })
+}
+
+fn lock_protocol() -> Value {
+    json!({ "type": "string", "enum": ["hbit", "halbit", "herc20"] })
+}
+
+fn role() -> Value {
+    json!({ "type": "string", "enum": ["Alice", "Bob"] })
+}
+
+fn peer_id() -> Value {
+    json!({ "type": "string" })
+}
+
+fn multiaddr() -> Value {
+    json!({ "type": "string" })
+}
+
+fn nullable(type_: &str) -> Value {
+    json!({ "type": type_, "nullable": true })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        actions::{bitcoin::SendToAddress, lnd::Chain},
+        asset::{self, ethereum::FromWei},
+        bitcoin::{Address as BitcoinAddress, FeeRateEstimator, SatPerVbyte, StaticFeeRate},
+        btsieve::{self, bitcoin::BitcoindConnector, ethereum::Web3Connector},
+        config::{File, Settings},
+        connectors::Connectors,
+        ethereum::{ChainId, U256},
+        health::Health,
+        http_api::{action::EthereumData, create_routes, PostBody, Tokens, Updates},
+        identity,
+        metrics::{Instrumented, Metrics, Node},
+        network::Swarm,
+        protocol_spawner::ProtocolSpawner,
+        storage::{RootSeed, Sqlite, Storage},
+        LocalSwapId, RelativeTime, Secret, SecretHash, Timestamp,
+    };
+    use comit::ledger;
+    use libp2p::PeerId;
+    use rand::thread_rng;
+    use serde::de::DeserializeOwned;
+    use std::str::FromStr;
+    use warp::{filters::BoxedFilter, http::header::CONTENT_TYPE, hyper::Response};
+
+    const METHODS: [&str; 4] = ["GET", "POST", "DELETE", "PATCH"];
+
+    async fn routes() -> BoxedFilter<(impl Reply,)> {
+        let mut settings = Settings::from_config_file_and_defaults(File::default(), None).unwrap();
+        settings.network.listen = vec!["/ip4/127.0.0.1/tcp/0".parse().unwrap()];
+        settings.network.peer_addresses = vec![];
+        settings.network.discovery.mdns = false;
+        settings.network.discovery.kademlia = false;
+
+        let seed = RootSeed::new_random(thread_rng()).unwrap();
+        let storage = Storage::new(Sqlite::test(), seed);
+        let metrics = Metrics::default();
+        let connectors = Connectors::new(
+            btsieve::bitcoin::Cache::new(
+                Instrumented::new(
+                    BitcoindConnector::new(settings.bitcoin.bitcoind.node_url.clone()).unwrap(),
+                    Node::Bitcoind,
+                    metrics.clone(),
+                ),
+                1,
+            ),
+            btsieve::ethereum::Cache::new(
+                Instrumented::new(
+                    Web3Connector::new(settings.ethereum.geth.node_url.clone()),
+                    Node::Geth,
+                    metrics.clone(),
+                ),
+                1,
+                1,
+            ),
+            FeeRateEstimator::Static(StaticFeeRate(SatPerVbyte::new(1))),
+        );
+        let handle = tokio::runtime::Handle::current();
+        let protocol_spawner = ProtocolSpawner::new(
+            connectors.clone(),
+            None,
+            None,
+            None,
+            metrics.clone(),
+            handle.clone(),
+            storage.clone(),
+        );
+        let swarm = Swarm::new(&settings, seed, handle, storage.clone(), protocol_spawner)
+            .await
+            .unwrap();
+        let api_tokens =
+            Tokens::from_dir_or_generate(tempfile::tempdir().unwrap().path(), thread_rng())
+                .unwrap();
+        let health = Health::new(&settings).unwrap();
+
+        create_routes(
+            swarm,
+            storage,
+            connectors,
+            &settings,
+            comit::Network::Dev,
+            Updates::default(),
+            api_tokens,
+            metrics,
+            health,
+        )
+    }
+
+    fn concrete_path(path: &str) -> String {
+        path.replace("{id}", &LocalSwapId::random().to_string())
+            .replace("{order_id}", &LocalSwapId::random().to_string())
+            .replace("{peer_id}", &PeerId::random().to_base58())
+    }
+
+    /// Tells whether no route matched the request, as opposed to a handler
+    /// failing with a problem.
+    fn is_unrouted(response: &Response<warp::hyper::body::Bytes>) -> bool {
+        let is_problem = response.headers().get(CONTENT_TYPE).map_or(false, |value| {
+            *value == http_api_problem::PROBLEM_JSON_MEDIA_TYPE
+        });
+
+        (response.status() == 404 || response.status() == 405) && !is_problem
+    }
+
+    #[tokio::test]
+    async fn document_matches_routes() {
+        let routes = routes().await;
+        let document = document();
+
+        for (path, operations) in document["paths"].as_object().unwrap() {
+            // The event stream never ends, hence cannot be asserted on here.
+            if path == "/updates" {
+                continue;
+            }
+
+            for method in METHODS.iter() {
+                let response = warp::test::request()
+                    .method(method)
+                    .path(&concrete_path(path))
+                    .reply(&routes)
+                    .await;
+                let is_documented = operations.get(method.to_lowercase()).is_some();
+
+                assert_eq!(
+                    is_unrouted(&response),
+                    !is_documented,
+                    "{} {} is documented: {}, but responded with {}",
+                    method,
+                    path,
+                    is_documented,
+                    response.status()
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn undocumented_paths_are_not_routed() {
+        let routes = routes().await;
+
+        for path in &["/swaps/herc20/herc20", "/orders/BTC-ETH", "/openapi.yaml"] {
+            let response = warp::test::request().path(path).reply(&routes).await;
+
+            assert!(is_unrouted(&response), "{} is routed", path);
+        }
+    }
+
+    #[test]
+    fn every_reference_resolves() {
+        fn references(value: &Value, found: &mut Vec<String>) {
+            match value {
+                Value::Object(map) => {
+                    if let Some(Value::String(reference)) = map.get("$ref") {
+                        found.push(reference.clone());
+                    }
+                    map.values().for_each(|value| references(value, found));
+                }
+                Value::Array(values) => values.iter().for_each(|value| references(value, found)),
+                _ => {}
+            }
+        }
+
+        let document = document();
+        let mut found = Vec::new();
+        references(&document, &mut found);
+
+        assert!(!found.is_empty());
+        for reference in found {
+            let pointer = reference.trim_start_matches('#');
+            assert!(
+                document.pointer(pointer).is_some(),
+                "{} does not resolve",
+                reference
+            );
+        }
+    }
+
+    /// Validates the value against the schema, as far as the keywords used in
+    /// the document go. Undocumented properties are an error too.
+    fn validate(document: &Value, schema: &Value, value: &Value) -> Result<(), String> {
+        if let Some(Value::String(reference)) = schema.get("$ref") {
+            let schema = document
+                .pointer(reference.trim_start_matches('#'))
+                .ok_or_else(|| format!("{} does not resolve", reference))?;
+
+            return validate(document, schema, value);
+        }
+
+        if let Some(Value::Array(variants)) = schema.get("oneOf") {
+            let matching = variants
+                .iter()
+                .filter(|variant| validate(document, variant, value).is_ok())
+                .count();
+
+            return match matching {
+                1 => Ok(()),
+                n => Err(format!("{} matches {} variants", value, n)),
+            };
+        }
+
+        if value.is_null() && schema["nullable"] == true {
+            return Ok(());
+        }
+
+        let is_of_type = match schema["type"].as_str() {
+            Some("object") => value.is_object(),
+            Some("array") => value.is_array(),
+            Some("string") => value.is_string(),
+            Some("integer") => value.is_u64() || value.is_i64(),
+            Some("boolean") => value.is_boolean(),
+            Some(other) => return Err(format!("unknown type {}", other)),
+            None => true,
+        };
+        if !is_of_type {
+            return Err(format!("{} is not of type {}", value, schema["type"]));
+        }
+
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !allowed.contains(value) {
+                return Err(format!("{} is not one of {:?}", value, allowed));
+            }
+        }
+        if let (Some(pattern), Some(string)) = (schema["pattern"].as_str(), value.as_str()) {
+            if !regex::Regex::new(pattern).unwrap().is_match(string) {
+                return Err(format!("{} does not match {}", string, pattern));
+            }
+        }
+        if let (Some(minimum), Some(number)) = (schema["minimum"].as_i64(), value.as_i64()) {
+            if number < minimum {
+                return Err(format!("{} is less than {}", number, minimum));
+            }
+        }
+
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for property in required.iter().filter_map(Value::as_str) {
+                if value.get(property).is_none() {
+                    return Err(format!("{} lacks {}", value, property));
+                }
+            }
+        }
+        if let (Some(properties), Some(object)) =
+            (schema["properties"].as_object(), value.as_object())
+        {
+            for (name, value) in object {
+                let schema = properties
+                    .get(name)
+                    .ok_or_else(|| format!("{} is not documented", name))?;
+                validate(document, schema, value).map_err(|e| format!("{}: {}", name, e))?;
+            }
+        }
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            for value in values {
+                validate(document, items, value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn actions() -> Vec<ActionResponseBody> {
+        let network = ledger::Bitcoin::Regtest;
+        let secret = Secret::from([1u8; 32]);
+        let secret_key = ::bitcoin::secp256k1::SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let lightning = identity::Lightning::from_secret_key(&*crate::SECP, &secret_key);
+        let address = BitcoinAddress::from_str("2N3pk6v15FrDiRNKYVuxnnugn1Yg7wfQRL9").unwrap();
+
+        vec![
+            ActionResponseBody::from(SendToAddress {
+                to: address.into(),
+                amount: asset::Bitcoin::from_sat(100_000_000),
+                network,
+            }),
+            ActionResponseBody::BitcoinBroadcastSignedTransaction {
+                hex: "0200".to_owned(),
+                network,
+                min_median_block_time: Some(Timestamp::from(1_600_000_000u32)),
+                fee_rate: SatPerVbyte::new(12),
+            },
+            ActionResponseBody::BitcoinPsbt {
+                psbt: "cHNidP8BAAoCAAAAAAAAAAAAAAAA".to_owned(),
+                network,
+            },
+            ActionResponseBody::EthereumDeployContract {
+                data: EthereumData::from(vec![0x1, 0x2, 0x3]),
+                amount: asset::Ether::from_wei(10000u32),
+                gas_limit: U256::from(1),
+                chain_id: ChainId::from(3),
+            },
+            ActionResponseBody::EthereumCallContract {
+                contract_address: identity::Ethereum::from([1u8; 20]),
+                data: Some(EthereumData::from(vec![0x1])),
+                gas_limit: U256::from(1),
+                chain_id: ChainId::from(3),
+                min_block_timestamp: Some(Timestamp::from(1_600_000_000u32)),
+            },
+            ActionResponseBody::LndAddHoldInvoice {
+                amount: asset::Bitcoin::from_sat(1000),
+                secret_hash: SecretHash::new(secret),
+                expiry: RelativeTime::from(3600),
+                cltv_expiry: RelativeTime::from(144),
+                chain: Chain::Bitcoin,
+                network,
+                self_public_key: lightning,
+            },
+            ActionResponseBody::LndSendPayment {
+                to_public_key: lightning,
+                amount: asset::Bitcoin::from_sat(1000),
+                secret_hash: SecretHash::new(secret),
+                final_cltv_delta: RelativeTime::from(144),
+                chain: Chain::Bitcoin,
+                network,
+                self_public_key: lightning,
+            },
+            ActionResponseBody::LndSettleInvoice {
+                secret,
+                chain: Chain::Bitcoin,
+                network,
+                self_public_key: lightning,
+            },
+        ]
+    }
+
+    #[test]
+    fn actions_are_valid_as_per_their_schema() {
+        let document = document();
+
+        for action in actions() {
+            let value = serde_json::to_value(&action).unwrap();
+
+            if let Err(e) = validate(&document, &ActionResponseBody::reference(), &value) {
+                panic!("{} is invalid: {}", value, e)
+            }
+        }
+    }
+
+    #[test]
+    fn undocumented_properties_are_invalid() {
+        let document = document();
+        let mut value = serde_json::to_value(&actions()[0]).unwrap();
+        value["payload"]["foo"] = json!("bar");
+
+        assert!(validate(&document, &ActionResponseBody::reference(), &value).is_err());
+    }
+
+    /// Asserts that the body is accepted by the route and valid as per the
+    /// documented schema of the request body.
+    fn assert_post_swap_body<A, B>(path: &str, body: Value)
+    where
+        A: DeserializeOwned,
+        B: DeserializeOwned,
+    {
+        let document = document();
+        let request_body = &document["paths"][path]["post"]["requestBody"];
+        let schema = &request_body["content"]["application/json"]["schema"];
+
+        serde_json::from_value::<PostBody<A, B>>(body.clone())
+            .unwrap_or_else(|e| panic!("{} is not accepted on {}: {}", body, path, e));
+        if let Err(e) = validate(&document, schema, &body) {
+            panic!("{} is invalid on {}: {}", body, path, e)
+        }
+    }
+
+    fn swap_body(alpha: &Value, beta: &Value, peer: &Value) -> Value {
+        json!({ "alpha": alpha, "beta": beta, "peer": peer, "role": "Alice" })
+    }
+
+    #[test]
+    fn swap_bodies_are_valid_as_per_their_schema() {
+        let secret_key = ::bitcoin::secp256k1::SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let lightning = identity::Lightning::from_secret_key(&*crate::SECP, &secret_key);
+        let hbit = json!({
+            "amount": "100000000",
+            "final_identity": "2N3pk6v15FrDiRNKYVuxnnugn1Yg7wfQRL9",
+            "network": "regtest",
+            "absolute_expiry": 1_600_000_000u32,
+        });
+        let herc20 = json!({
+            "amount": "1000000000000000000",
+            "identity": "0x0a81e8be41b21f651a71aab1a85c6813b8bbccf8",
+            "chain_id": 1337,
+            "token_contract": "0x0a81e8be41b21f651a71aab1a85c6813b8bbccf8",
+            "absolute_expiry": 1_600_000_000u32,
+        });
+        let halbit = json!({
+            "amount": "100000000",
+            "identity": serde_json::to_value(&lightning).unwrap(),
+            "network": "regtest",
+            "cltv_expiry": 144,
+        });
+        let peer = json!(PeerId::random().to_base58());
+        let peer_with_address_hint = json!({
+            "peer_id": PeerId::random().to_base58(),
+            "address_hint": "/ip4/127.0.0.1/tcp/9939",
+        });
+
+        assert_post_swap_body::<Hbit, Herc20>(
+            "/swaps/hbit/herc20",
+            swap_body(&hbit, &herc20, &peer),
+        );
+        assert_post_swap_body::<Herc20, Hbit>(
+            "/swaps/herc20/hbit",
+            swap_body(&herc20, &hbit, &peer_with_address_hint),
+        );
+        assert_post_swap_body::<Herc20, Halbit>(
+            "/swaps/herc20/halbit",
+            swap_body(&herc20, &halbit, &peer),
+        );
+        assert_post_swap_body::<Halbit, Herc20>(
+            "/swaps/halbit/herc20",
+            swap_body(&halbit, &herc20, &peer_with_address_hint),
+        );
+    }
+}
@@ -1,9 +1,21 @@
+mod amend;
 mod cancel;
 mod get_single;
 mod list_open;
 mod make_btc_dai;
+mod take;
 
+pub use amend::route as amend;
 pub use cancel::route as cancel;
 pub use get_single::route as get_single;
 pub use list_open::route as list_open;
 pub use make_btc_dai::route as make_btc_dai;
+pub use take::{route as take, SwapNotSetUp};
+
+use crate::asset;
+use serde::Deserialize;
+
+/// A quantity in a request body, given in satoshis as a string like in the
+/// order entities.
+#[derive(Debug, Deserialize)]
+struct Sats(#[serde(with = "asset::bitcoin::sats_as_string")] asset::Bitcoin);
@@ -0,0 +1,45 @@
+use crate::{
+    asset::Erc20Quantity,
+    http_api::{orders::Sats, problem},
+    network::Swarm,
+};
+use anyhow::Result;
+use comit::{orderpool::InvalidAmendment, OrderId, Price, Quantity};
+use futures::TryFutureExt;
+use serde::Deserialize;
+use warp::{Filter, Rejection, Reply};
+
+/// The warp filter for amending the price and/or the remaining quantity of an
+/// open order.
+pub fn route(swarm: Swarm) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::patch()
+        .and(warp::path!("orders" / OrderId))
+        .and(warp::body::json())
+        .and_then(move |order_id, body| {
+            handler(order_id, body, swarm.clone())
+                .map_err(problem::from_anyhow)
+                .map_err(warp::reject::custom)
+        })
+}
+
+async fn handler(order_id: OrderId, body: Body, swarm: Swarm) -> Result<impl Reply> {
+    if body.price.as_ref() == Some(&Erc20Quantity::zero()) {
+        anyhow::bail!(InvalidAmendment::ZeroPrice(order_id))
+    }
+
+    let price = body.price.map(Price::from_wei_per_sat);
+    let quantity = body.quantity.map(|Sats(quantity)| Quantity::new(quantity));
+
+    swarm.amend_order(order_id, price, quantity).await?;
+
+    Ok(warp::reply())
+}
+
+#[derive(Debug, Deserialize)]
+struct Body {
+    #[serde(default)]
+    price: Option<Erc20Quantity>,
+    /// The new remaining quantity, i.e. what is open of the order.
+    #[serde(default)]
+    quantity: Option<Sats>,
+}
@@ -20,7 +20,9 @@ use crate::{
     Role,
 };
 use anyhow::Result;
-use comit::{order::SwapProtocol, BtcDaiOrder, Position, Price, Quantity, Side};
+use comit::{
+    order::SwapProtocol, orderpool::BelowMinFill, BtcDaiOrder, Position, Price, Quantity, Side,
+};
 use diesel::SqliteConnection;
 use futures::TryFutureExt;
 use serde::Deserialize;
@@ -58,6 +60,14 @@ async fn handler(
 ) -> Result<impl Reply> {
     let db = storage.db;
 
+    let min_fill = settings.network.min_fill;
+    if body.quantity < min_fill {
+        anyhow::bail!(BelowMinFill {
+            quantity: body.quantity,
+            min_fill,
+        })
+    }
+
     let order = BtcDaiOrder::new(
         body.position,
         Quantity::new(body.quantity),
@@ -66,8 +76,13 @@ async fn handler(
     );
     let order_id = order.id;
 
-    db.do_in_transaction(save_order(order.clone(), body.swap, settings))
-        .await?;
+    db.do_in_transaction(save_order(
+        order.clone(),
+        body.swap.bitcoin_address,
+        body.swap.ethereum_address,
+        settings,
+    ))
+    .await?;
     swarm.publish_order(order).await;
 
     Ok(warp::reply::with_header(
@@ -98,9 +113,11 @@ fn default_role() -> Role {
     Role::Alice
 }
 
-fn save_order(
+/// Save the order together with the addresses to use for its swaps.
+pub(super) fn save_order(
     order: BtcDaiOrder,
-    swap: SwapParams,
+    bitcoin_address: bitcoin::Address,
+    ethereum_address: ethereum::Address,
     settings: Settings,
 ) -> impl FnOnce(&SqliteConnection) -> Result<()> {
     let insertable_order = InsertableOrder::new(order.id, order.position, order.created_at);
@@ -115,7 +132,7 @@ fn save_order(
     let insertable_hbit = {
         let network = settings.bitcoin.network;
         let swap_protocol = order.swap_protocol;
-        let our_final_address = swap.bitcoin_address;
+        let our_final_address = bitcoin_address;
 
         move |order_fk| {
             InsertableOrderHbitParams::new(
@@ -135,7 +152,7 @@ fn save_order(
         let chain_id = settings.ethereum.chain_id;
         let dai_contract = settings.ethereum.tokens.dai;
         let swap_protocol = order.swap_protocol;
-        let our_htlc_identity = swap.ethereum_address;
+        let our_htlc_identity = ethereum_address;
 
         move |order_fk| {
             InsertableOrderHerc20Params::new(
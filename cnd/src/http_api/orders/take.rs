@@ -0,0 +1,141 @@
+//! This file contains the logic for taking an order of another maker through
+//! the HTTP API.
+//!
+//! Taking an order creates an order of our own that mirrors it, which is never
+//! published but records our side of the swap. The swap is then proposed to
+//! the maker directly and we respond once it is set up.
+
+use crate::{
+    config::Settings,
+    ethereum,
+    http_api::{
+        orders::{make_btc_dai::save_order, Sats},
+        problem,
+    },
+    network::Swarm,
+    storage::{BtcDaiOrder, Load, Order, Storage, SwapContext},
+    LocalSwapId,
+};
+use anyhow::Result;
+use comit::{OrderId, Quantity};
+use futures::TryFutureExt;
+use serde::Deserialize;
+use std::time::Duration;
+use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+/// How long we wait for the maker to set up the swap.
+const SETUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often we check whether the swap was set up.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The maker did not set up the swap in time, the order was not taken.
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+#[error("maker of order {0} did not set up the swap in time")]
+pub struct SwapNotSetUp(pub OrderId);
+
+/// The warp filter for taking an order of another maker.
+pub fn route(
+    storage: Storage,
+    swarm: Swarm,
+    settings: Settings,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::post()
+        .and(warp::path!("orders" / OrderId / "take"))
+        .and(warp::body::json())
+        .and_then(move |order_id, body| {
+            handler(
+                order_id,
+                body,
+                storage.clone(),
+                swarm.clone(),
+                settings.clone(),
+            )
+            .map_err(problem::from_anyhow)
+            .map_err(warp::reject::custom)
+        })
+}
+
+async fn handler(
+    order_id: OrderId,
+    body: Body,
+    storage: Storage,
+    swarm: Swarm,
+    settings: Settings,
+) -> Result<impl Reply> {
+    let quantity = body.quantity.map(|Sats(quantity)| Quantity::new(quantity));
+    let (order, new_match) = swarm.take_order(order_id, quantity).await?;
+    let our_order_id = order.id;
+
+    storage
+        .db
+        .do_in_transaction(save_order(
+            order,
+            body.swap.bitcoin_address,
+            body.swap.ethereum_address,
+            settings,
+        ))
+        .await?;
+
+    let swap_id = match swarm.propose_swap_to_maker(new_match.clone()).await {
+        Ok(swap_id) => swap_id,
+        Err(e) => {
+            swarm.abort_take(&new_match).await;
+            cancel(&storage, our_order_id).await;
+            return Err(e);
+        }
+    };
+
+    let timed_out = tokio::time::timeout(SETUP_TIMEOUT, swap_saved(&storage, swap_id))
+        .await
+        .is_err();
+
+    // The swap may have been set up right after we stopped waiting, it is
+    // saved shortly after.
+    if timed_out && swarm.abort_take(&new_match).await {
+        cancel(&storage, our_order_id).await;
+        anyhow::bail!(SwapNotSetUp(order_id))
+    }
+
+    Ok(warp::reply::with_header(
+        StatusCode::CREATED,
+        "Location",
+        format!("/swaps/{}", swap_id),
+    ))
+}
+
+/// Resolves once the swap was set up and saved.
+async fn swap_saved(storage: &Storage, swap_id: LocalSwapId) {
+    while Load::<SwapContext>::load(storage, swap_id).await.is_err() {
+        tokio::time::delay_for(POLL_INTERVAL).await;
+    }
+}
+
+/// Cancel the order we created for taking the maker's order, it will not be
+/// filled anymore.
+async fn cancel(storage: &Storage, order_id: OrderId) {
+    if let Err(e) = storage
+        .db
+        .do_in_transaction(|conn| {
+            let order = Order::by_order_id(conn, order_id)?;
+            BtcDaiOrder::by_order(conn, &order)?.set_to_cancelled(conn)
+        })
+        .await
+    {
+        tracing::warn!("failed to cancel order {}: {:#}", order_id, e);
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Body {
+    /// Defaults to everything that is left of the order.
+    #[serde(default)]
+    quantity: Option<Sats>,
+    swap: SwapParams,
+}
+
+#[derive(Debug, Deserialize)]
+struct SwapParams {
+    bitcoin_address: bitcoin::Address,
+    ethereum_address: ethereum::Address,
+}
@@ -1,22 +1,119 @@
-use crate::{http_api::serde_peer_id, network::Swarm};
+use crate::{
+    http_api::{problem, serde_peer_id},
+    network::Swarm,
+    storage::{self, Storage},
+};
+use comit::reputation::Reputation;
+use futures::TryFutureExt;
 use libp2p::{Multiaddr, PeerId};
 use serde::Serialize;
+use std::collections::HashMap;
 use warp::{Rejection, Reply};
 
 #[allow(clippy::needless_pass_by_value)]
-pub async fn get_peers(swarm: Swarm) -> Result<impl Reply, Rejection> {
-    let peers = swarm
-        .connected_peers()
+pub async fn get_peers(swarm: Swarm, storage: Storage) -> Result<impl Reply, Rejection> {
+    handler(swarm, storage)
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
         .await
-        .map(|(peer, addresses)| Peer {
-            id: peer,
-            endpoints: addresses,
+}
+
+async fn handler(swarm: Swarm, storage: Storage) -> anyhow::Result<impl Reply> {
+    let mut connected = swarm.connected_peers().await.collect::<HashMap<_, _>>();
+
+    let known = storage
+        .db
+        .do_in_transaction(|conn| {
+            let mut known = Vec::new();
+
+            for peer in storage::Peer::all(conn)? {
+                let addresses = peer.addresses(conn)?;
+                let reputation = peer.reputation(conn)?;
+                known.push((peer, addresses, reputation));
+            }
+
+            Ok(known)
+        })
+        .await?;
+
+    let mut peers = known
+        .into_iter()
+        .map(|(peer, addresses, reputation)| Peer {
+            endpoints: connected.remove(&peer.peer_id).unwrap_or_default(),
+            id: peer.peer_id,
+            addresses: addresses
+                .into_iter()
+                .map(|address| KnownAddress {
+                    address: address.address,
+                    last_seen_at: address.last_seen_at,
+                })
+                .collect(),
+            successful_dials: peer.successful_dials,
+            failed_dials: peer.failed_dials,
+            last_seen_at: peer.last_seen_at,
+            last_order_at: peer.last_order_at,
+            reputation: reputation.into(),
         })
-        .collect();
+        .collect::<Vec<_>>();
+
+    // Connections that were established since the address book was last updated
+    peers.extend(connected.into_iter().map(|(peer, endpoints)| Peer {
+        id: peer,
+        endpoints,
+        addresses: Vec::new(),
+        successful_dials: 0,
+        failed_dials: 0,
+        last_seen_at: None,
+        last_order_at: None,
+        reputation: Reputation::default().into(),
+    }));
 
     Ok(warp::reply::json(&PeersResource { peers }))
 }
 
+#[allow(clippy::needless_pass_by_value)]
+pub async fn post_ban(
+    peer: PeerId,
+    swarm: Swarm,
+    storage: Storage,
+) -> Result<impl Reply, Rejection> {
+    set_banned(peer, true, swarm, storage)
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+        .await
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub async fn post_unban(
+    peer: PeerId,
+    swarm: Swarm,
+    storage: Storage,
+) -> Result<impl Reply, Rejection> {
+    set_banned(peer, false, swarm, storage)
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+        .await
+}
+
+async fn set_banned(
+    peer: PeerId,
+    banned: bool,
+    swarm: Swarm,
+    storage: Storage,
+) -> anyhow::Result<impl Reply> {
+    let reputation = storage
+        .db
+        .do_in_transaction(|conn| {
+            storage::Peer::get_or_insert(conn, &peer)?.set_banned(conn, banned)?;
+            storage::Peer::get_or_insert(conn, &peer)?.reputation(conn)
+        })
+        .await?;
+
+    swarm.enforce_reputation(peer, &reputation).await;
+
+    Ok(warp::reply::json(&ReputationResource::from(reputation)))
+}
+
 #[derive(Serialize, Debug)]
 pub struct PeersResource {
     peers: Vec<Peer>,
@@ -26,5 +123,44 @@ pub struct PeersResource {
 pub struct Peer {
     #[serde(with = "serde_peer_id")]
     id: PeerId,
+    /// The addresses of our current connections to this peer.
     endpoints: Vec<Multiaddr>,
+    /// The addresses we reached this peer under before, most recent first.
+    addresses: Vec<KnownAddress>,
+    successful_dials: i32,
+    failed_dials: i32,
+    last_seen_at: Option<i64>,
+    last_order_at: Option<i64>,
+    reputation: ReputationResource,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ReputationResource {
+    score: i64,
+    completed_swaps: u32,
+    refunded_swaps: u32,
+    expired_swaps: u32,
+    failed_swaps: u32,
+    violations: u32,
+    banned: bool,
+}
+
+impl From<Reputation> for ReputationResource {
+    fn from(reputation: Reputation) -> Self {
+        Self {
+            score: reputation.score(),
+            completed_swaps: reputation.completed,
+            refunded_swaps: reputation.refunded,
+            expired_swaps: reputation.expired,
+            failed_swaps: reputation.failed,
+            violations: reputation.violations,
+            banned: reputation.banned,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct KnownAddress {
+    address: Multiaddr,
+    last_seen_at: i64,
 }
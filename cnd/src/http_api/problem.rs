@@ -1,7 +1,12 @@
 use crate::{
-    http_api::ActionNotFound,
-    storage::{NoOrderExists, NoSwapExists, NotOpen},
+    http_api::{
+        action::PsbtNotSupported, orders::SwapNotSetUp, ActionNotFound, FeeRateAtMaximum,
+        FeeRateOutOfRange, FeeRateTooLow, LightningActionExecutedByCnd,
+    },
+    network::CancellationRejected,
+    storage::{AlreadyFunded, NoOrderExists, NoSwapExists, NotOpen, SwapCancelled},
 };
+use comit::orderpool::{BelowMinFill, InsufficientQuantity, InvalidAmendment, UnknownOrder};
 use http_api_problem::HttpApiProblem;
 use std::error::Error;
 use warp::{
@@ -37,11 +42,54 @@ pub fn from_anyhow(e: anyhow::Error) -> HttpApiProblem {
         e if e.is::<NoOrderExists>() => {
             HttpApiProblem::new("Order not found.").set_status(StatusCode::NOT_FOUND)
         }
+        e if e.is::<UnknownOrder>() => HttpApiProblem::new("Order not found.")
+            .set_status(StatusCode::NOT_FOUND)
+            .set_detail(format!("{}", e)),
+        e if e.is::<InsufficientQuantity>() => HttpApiProblem::new("Insufficient quantity.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{}", e)),
+        e if e.is::<InvalidAmendment>() => HttpApiProblem::new("Invalid amendment.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{}", e)),
+        e if e.is::<BelowMinFill>() => HttpApiProblem::new("Quantity below minimum fill.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{}", e)),
+        e if e.is::<SwapNotSetUp>() => HttpApiProblem::new("Swap was not set up.")
+            .set_status(StatusCode::GATEWAY_TIMEOUT)
+            .set_detail("The maker did not agree to the swap in time, the order was not taken."),
         e if e.is::<NotOpen>() => HttpApiProblem::new("Order can no longer be cancelled.")
             .set_status(StatusCode::BAD_REQUEST),
+        e if e.is::<AlreadyFunded>() => HttpApiProblem::new("Swap can no longer be cancelled.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail("Swaps can only be cancelled until Alice funds them."),
+        e if e.is::<CancellationRejected>() => {
+            HttpApiProblem::new("Counterparty rejected the cancellation.")
+                .set_status(StatusCode::BAD_REQUEST)
+                .set_detail(format!("{}", e))
+        }
+        e if e.is::<SwapCancelled>() => {
+            HttpApiProblem::new("Swap was cancelled.").set_status(StatusCode::BAD_REQUEST)
+        }
         e if e.is::<ActionNotFound>() => {
             HttpApiProblem::new("Action not found.").set_status(StatusCode::NOT_FOUND)
         }
+        e if e.is::<LightningActionExecutedByCnd>() => {
+            HttpApiProblem::new("Action is executed by cnd.")
+                .set_status(StatusCode::CONFLICT)
+                .set_detail("cnd executes lightning actions itself until it gives up on them.")
+        }
+        e if e.is::<FeeRateTooLow>() => HttpApiProblem::new("Fee rate too low.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{}", e)),
+        e if e.is::<FeeRateAtMaximum>() => HttpApiProblem::new("Fee rate at maximum.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{}", e)),
+        e if e.is::<FeeRateOutOfRange>() => HttpApiProblem::new("Invalid fee rate.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{}", e)),
+        e if e.is::<PsbtNotSupported>() => HttpApiProblem::new("Action cannot be a PSBT.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail("Only Bitcoin fund actions can be represented as a PSBT."),
         // Use if let here once stable: https://github.com/rust-lang/rust/issues/51114
         e if e.is::<LedgerNotConfigured>() => {
             let e = e
@@ -1,27 +1,36 @@
 use crate::{
     config::{AllowedOrigins, Settings},
     connectors::Connectors,
+    health::Health,
     http_api,
     http_api::{
-        dial_addr, halbit_herc20, hbit_herc20, herc20_halbit, herc20_hbit, info, markets, orders,
-        peers, swaps, tokens,
+        auth, dial_addr, halbit_herc20, hbit_herc20, health, herc20_halbit, herc20_hbit, info,
+        markets, metrics, openapi, orders, peers, swaps, tokens, updates, Tokens, Updates,
     },
+    metrics::Metrics,
     network::Swarm,
     storage::Storage,
     LocalSwapId,
 };
+use libp2p::PeerId;
 use warp::{self, filters::BoxedFilter, Filter, Reply};
 
 pub fn swap_path(id: LocalSwapId) -> String {
     format!("/{}/{}", http_api::PATH, id)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create(
     swarm: Swarm,
     storage: Storage,
     connectors: Connectors,
     settings: &Settings,
     network: comit::Network,
+    updates: Updates,
+    api_tokens: Tokens,
+    metrics: Metrics,
+    health: Health,
+    execute_lightning_actions: bool,
 ) -> BoxedFilter<(impl Reply,)> {
     let swaps = warp::path(http_api::PATH);
     let swarm_filter = warp::any().map({
@@ -32,11 +41,14 @@ pub fn create(
         let storage = storage.clone();
         move || storage.clone()
     });
+    let metrics_route = metrics::route(metrics.clone(), swarm.clone(), connectors.clone());
+    let health_routes = health::routes(health, storage.clone(), swarm.clone());
     let connectors = warp::any().map(move || connectors.clone());
+    let execute_lightning_actions = warp::any().map(move || execute_lightning_actions);
 
     let cors = warp::cors()
-        .allow_methods(vec!["GET", "POST"])
-        .allow_header("content-type");
+        .allow_methods(vec!["GET", "POST", "PATCH", "DELETE"])
+        .allow_headers(vec!["authorization", "content-type"]);
     let cors = match &settings.http_api.cors.allowed_origins {
         AllowedOrigins::None => cors.allow_origins(Vec::<&str>::new()),
         AllowedOrigins::All => cors.allow_any_origin(),
@@ -45,6 +57,12 @@ pub fn create(
         }
     };
 
+    let authorize = auth::authorize(if settings.http_api.auth.enabled {
+        Some(api_tokens)
+    } else {
+        None
+    });
+
     let preflight_cors_route = warp::options().map(warp::reply);
 
     let get_info = warp::get()
@@ -62,8 +80,23 @@ pub fn create(
         .and(warp::path("peers"))
         .and(warp::path::end())
         .and(swarm_filter.clone())
+        .and(storage_filter.clone())
         .and_then(peers::get_peers);
 
+    let post_ban = warp::post()
+        .and(warp::path!("peers" / PeerId / "ban"))
+        .and(warp::path::end())
+        .and(swarm_filter.clone())
+        .and(storage_filter.clone())
+        .and_then(peers::post_ban);
+
+    let post_unban = warp::post()
+        .and(warp::path!("peers" / PeerId / "unban"))
+        .and(warp::path::end())
+        .and(swarm_filter.clone())
+        .and(storage_filter.clone())
+        .and_then(peers::post_unban);
+
     let herc20_halbit = warp::post()
         .and(warp::path!("swaps" / "herc20" / "halbit"))
         .and(warp::path::end())
@@ -101,12 +134,14 @@ pub fn create(
         .and(warp::path::param())
         .and(warp::path::end())
         .and(storage_filter.clone())
-        .and(connectors)
+        .and(connectors.clone())
+        .and(execute_lightning_actions.clone())
         .and_then(swaps::get_swap);
 
     let get_swaps = warp::get()
         .and(swaps)
         .and(warp::path::end())
+        .and(warp::query::<swaps::SwapsQuery>())
         .and(storage_filter.clone())
         .and_then(swaps::get_swaps);
 
@@ -116,6 +151,7 @@ pub fn create(
         .and(warp::path("init"))
         .and(warp::path::end())
         .and(storage_filter.clone())
+        .and(execute_lightning_actions.clone())
         .and_then(swaps::action_init);
 
     let action_fund = swaps
@@ -123,7 +159,9 @@ pub fn create(
         .and(warp::path::param::<LocalSwapId>())
         .and(warp::path("fund"))
         .and(warp::path::end())
+        .and(warp::query::<swaps::FormatQuery>())
         .and(storage_filter.clone())
+        .and(execute_lightning_actions.clone())
         .and_then(swaps::action_fund);
 
     let action_deploy = swaps
@@ -139,7 +177,11 @@ pub fn create(
         .and(warp::path::param::<LocalSwapId>())
         .and(warp::path("redeem"))
         .and(warp::path::end())
+        .and(warp::query::<swaps::FeeRateQuery>())
+        .and(warp::query::<swaps::FormatQuery>())
         .and(storage_filter.clone())
+        .and(connectors.clone())
+        .and(execute_lightning_actions)
         .and_then(swaps::action_redeem);
 
     let action_refund = swaps
@@ -147,9 +189,31 @@ pub fn create(
         .and(warp::path::param::<LocalSwapId>())
         .and(warp::path("refund"))
         .and(warp::path::end())
-        .and(storage_filter)
+        .and(warp::query::<swaps::FeeRateQuery>())
+        .and(warp::query::<swaps::FormatQuery>())
+        .and(storage_filter.clone())
+        .and(connectors.clone())
         .and_then(swaps::action_refund);
 
+    let action_bump = swaps
+        .and(warp::get())
+        .and(warp::path::param::<LocalSwapId>())
+        .and(warp::path("bump"))
+        .and(warp::path::end())
+        .and(warp::query::<swaps::FeeRateQuery>())
+        .and(warp::query::<swaps::FormatQuery>())
+        .and(storage_filter)
+        .and(connectors)
+        .and_then(swaps::action_bump);
+
+    let action_cancel = swaps
+        .and(warp::post())
+        .and(warp::path::param::<LocalSwapId>())
+        .and(warp::path("cancel"))
+        .and(warp::path::end())
+        .and(swarm_filter.clone())
+        .and_then(swaps::action_cancel);
+
     let post_dial_addr = warp::post()
         .and(warp::path!("dial"))
         .and(warp::path::end())
@@ -157,8 +221,10 @@ pub fn create(
         .and(swarm_filter)
         .and_then(dial_addr::post_dial_addr);
 
-    preflight_cors_route
+    let routes = preflight_cors_route
         .or(get_peers)
+        .or(post_ban)
+        .or(post_unban)
         .or(get_info_siren)
         .or(get_info)
         .or(herc20_halbit)
@@ -170,6 +236,8 @@ pub fn create(
         .or(action_deploy)
         .or(action_redeem)
         .or(action_refund)
+        .or(action_bump)
+        .or(action_cancel)
         .or(hbit_herc20)
         .or(herc20_hbit)
         .or(orders::make_btc_dai(
@@ -178,14 +246,34 @@ pub fn create(
             settings.clone(),
             network,
         ))
+        .or(orders::take(
+            storage.clone(),
+            swarm.clone(),
+            settings.clone(),
+        ))
         .or(orders::get_single(storage.clone()))
         .or(orders::list_open(storage.clone()))
         .or(orders::cancel(storage, swarm.clone()))
+        .or(orders::amend(swarm.clone()))
         .or(tokens::list(settings.clone()))
         .or(markets::get_btc_dai(swarm, network))
-        .or(post_dial_addr)
+        .or(updates::route(updates))
+        .or(openapi::route())
+        .or(metrics_route)
+        .or(health_routes)
+        .or(post_dial_addr);
+
+    authorize
+        .and(routes)
         .recover(http_api::unpack_problem)
         .with(warp::log("http"))
+        .with(warp::log::custom(move |info| {
+            metrics.observe_http_request(
+                info.method().as_str(),
+                info.status().as_u16(),
+                info.elapsed(),
+            )
+        }))
         .with(cors)
         .boxed()
 }
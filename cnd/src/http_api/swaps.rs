@@ -8,21 +8,58 @@
 //!
 //! 3. To fetch swap actions: GET requests can fetch an appropriate swap
 //!    action on the action endpoint e.g., "/swaps/:swap_id/fund"
+//!
+//! The redeem and refund action endpoints accept an optional `fee_rate` query
+//! parameter (in sat/vbyte, between 1 and 1000) for signing Bitcoin
+//! transactions. If absent, the fee rate is estimated.
+//!
+//! If a Bitcoin redeem or refund transaction doesn't get confirmed, the "bump"
+//! action endpoint returns a replacement paying a higher fee rate.
+//!
+//! Passing `format=psbt` to the fund action endpoint returns a Bitcoin fund
+//! action as a BIP174 PSBT instead, e.g. for external signers.
+//!
+//! If cnd executes lightning actions itself, they are neither advertised on
+//! the swap nor returned by the action endpoints.
+//!
+//! Until Alice funds a swap, either party can cancel it with a POST request to
+//! the "cancel" action endpoint. Handing out a deploy or fund action rules out
+//! cancelling the swap. Cancelled swaps offer no further actions, cnd stops
+//! watching their ledgers once they are cancelled.
+//!
+//! GET requests on "/swaps" list the swaps one page at a time, see
+//! [`SwapsQuery`] for the available filters. A "next" link points to the
+//! following page, if there is one.
 
 use crate::{
-    bitcoin,
+    actions::bitcoin::SpendHtlc,
+    bitcoin::{self, EstimateFeeRate, SatPerVbyte, MAX_BUMPED_FEE_RATE},
     connectors::Connectors,
-    ethereum,
+    ethereum, hbit,
     http_api::{
-        action::ActionResponseBody, problem, route_factory, ActionName, AlphaAbsoluteExpiry,
-        AlphaLedger, AlphaProtocol, BetaAbsoluteExpiry, BetaLedger, BetaProtocol, Events, GetRole,
-        Ledger, Protocol, SwapEvent,
+        self,
+        action::{ActionResponseBody, IntoPsbt, IntoSpend, Spend},
+        problem, route_factory, ActionName, ActionNotFound, AlphaAbsoluteExpiry, AlphaLedger,
+        AlphaProtocol, BetaAbsoluteExpiry, BetaLedger, BetaProtocol, Events, FeeRateAtMaximum,
+        FeeRateOutOfRange, FeeRateTooLow, GetRole, Ledger, LightningActionExecutedByCnd, Protocol,
+        SwapEvent,
+    },
+    lnd_actions::{ActionKind, IntoLndAction, Progress},
+    network::Swarm,
+    storage::{
+        FundHandout, HbitSpend, LndExecution, Load, SortOrder, Storage, Swap, SwapCancellation,
+        SwapCancelled, SwapContext, SwapFilter, SwapStatus, WalletExecution,
     },
-    storage::{Load, LoadAll, Storage},
-    DeployAction, FundAction, InitAction, LocalSwapId, RedeemAction, RefundAction, Role,
+    DeployAction, FundAction, InitAction, LocalSwapId, LockProtocol, RedeemAction, RefundAction,
+    Role,
 };
+use chrono::{DateTime, SecondsFormat, Utc};
 use comit::Timestamp;
-use serde::Serialize;
+use libp2p::PeerId;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use std::{convert::TryFrom, fmt, str::FromStr};
+use time::OffsetDateTime;
+use url::form_urlencoded;
 use warp::{http, Rejection, Reply};
 
 #[allow(clippy::needless_pass_by_value)]
@@ -30,61 +67,354 @@ pub async fn get_swap(
     id: LocalSwapId,
     storage: Storage,
     connectors: Connectors,
+    execute_lightning_actions: bool,
 ) -> Result<impl Reply, Rejection> {
-    handle_get_swap(id, storage, connectors)
+    handle_get_swap(id, storage, connectors, execute_lightning_actions)
         .await
         .map(|swap_resource| warp::reply::json(&swap_resource))
         .map_err(problem::from_anyhow)
         .map_err(warp::reject::custom)
 }
 
-pub async fn get_swaps(storage: Storage) -> Result<impl Reply, Rejection> {
-    let swaps = async {
-        let mut swaps = siren::Entity::default().with_class_member("swaps");
+pub async fn get_swaps(query: SwapsQuery, storage: Storage) -> Result<impl Reply, Rejection> {
+    let swaps = handle_get_swaps(query, storage)
+        .await
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)?;
+
+    Ok(warp::reply::json(&swaps))
+}
+
+async fn handle_get_swaps(query: SwapsQuery, storage: Storage) -> anyhow::Result<siren::Entity> {
+    let filter = query.filter();
+    let order = query.sort.unwrap_or_default();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE);
+
+    // We load one more swap than requested to know whether there is a next page
+    let mut page = storage
+        .db
+        .do_in_transaction(|conn| {
+            Swap::query(conn, &filter, order, query.cursor, i64::from(limit) + 1)
+        })
+        .await?;
+    let page_size = usize::try_from(limit)?;
+    let next = if page.len() > page_size {
+        page.truncate(page_size);
+        page.last().map(|swap| swap.local_swap_id)
+    } else {
+        None
+    };
+
+    let mut swaps = siren::Entity::default().with_class_member("swaps");
+
+    for swap in page {
+        swaps.push_sub_entity(siren::SubEntity::from_link(siren::EntityLink {
+            class: vec![],
+            title: None,
+            rel: vec![String::from("item")],
+            href: format!("/swaps/{}", swap.local_swap_id),
+            _type: None,
+        }));
+    }
+
+    if let Some(cursor) = next {
+        swaps = swaps.with_link(siren::NavigationalLink::new(
+            &["next"],
+            query.next_page(cursor),
+        ));
+    }
+
+    Ok(swaps)
+}
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
 
-        for context in storage.load_all().await? {
-            swaps.push_sub_entity(siren::SubEntity::from_link(siren::EntityLink {
-                class: vec![],
-                title: None,
-                rel: vec![String::from("item")],
-                href: format!("/swaps/{}", context.id),
-                _type: None,
-            }));
+/// The query parameters of "/swaps", all of them are optional.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct SwapsQuery {
+    /// One of `in_progress`, `redeemed`, `refunded` or `aborted`.
+    pub status: Option<SwapStatus>,
+    /// Our role in the swap, `Alice` or `Bob`.
+    pub role: Option<Role>,
+    /// The protocol on the alpha ledger, e.g. `herc20`.
+    #[serde(default, deserialize_with = "from_str")]
+    pub alpha: Option<LockProtocol>,
+    /// The protocol on the beta ledger, e.g. `hbit`.
+    #[serde(default, deserialize_with = "from_str")]
+    pub beta: Option<LockProtocol>,
+    #[serde(default, deserialize_with = "from_str")]
+    pub peer: Option<PeerId>,
+    /// Only swaps that started at or after this point in time (RFC 3339).
+    pub started_after: Option<DateTime<Utc>>,
+    /// Only swaps that started before this point in time (RFC 3339).
+    pub started_before: Option<DateTime<Utc>>,
+    /// `oldest` (the default) or `newest` first.
+    pub sort: Option<SortOrder>,
+    /// The last swap of the previous page.
+    pub cursor: Option<LocalSwapId>,
+    pub limit: Option<u32>,
+}
+
+impl SwapsQuery {
+    fn filter(&self) -> SwapFilter {
+        SwapFilter {
+            status: self.status,
+            role: self.role,
+            alpha: self.alpha,
+            beta: self.beta,
+            counterparty: self.peer.clone(),
+            started_after: self.started_after.map(|time| time.naive_utc()),
+            started_before: self.started_before.map(|time| time.naive_utc()),
         }
+    }
+
+    /// The link to the page following the swap `cursor`, with the same
+    /// filters applied.
+    fn next_page(&self, cursor: LocalSwapId) -> String {
+        let mut params = form_urlencoded::Serializer::new(String::new());
+        let time = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::AutoSi, true);
 
-        Ok(swaps)
+        if let Some(status) = self.status {
+            params.append_pair("status", &status.to_string());
+        }
+        if let Some(role) = self.role {
+            params.append_pair("role", &role.to_string());
+        }
+        if let Some(alpha) = self.alpha {
+            params.append_pair("alpha", &alpha.to_string());
+        }
+        if let Some(beta) = self.beta {
+            params.append_pair("beta", &beta.to_string());
+        }
+        if let Some(peer) = &self.peer {
+            params.append_pair("peer", &peer.to_string());
+        }
+        if let Some(started_after) = self.started_after {
+            params.append_pair("started_after", &time(started_after));
+        }
+        if let Some(started_before) = self.started_before {
+            params.append_pair("started_before", &time(started_before));
+        }
+        if let Some(sort) = self.sort {
+            params.append_pair("sort", &sort.to_string());
+        }
+        if let Some(limit) = self.limit {
+            params.append_pair("limit", &limit.to_string());
+        }
+        params.append_pair("cursor", &cursor.to_string());
+
+        format!("/{}?{}", http_api::PATH, params.finish())
     }
-    .await
-    .map_err(problem::from_anyhow)
-    .map_err(warp::reject::custom)?;
+}
 
-    Ok(warp::reply::json(&swaps))
+fn from_str<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let string = String::deserialize(deserializer)?;
+    let value = string.parse().map_err(D::Error::custom)?;
+
+    Ok(Some(value))
 }
 
 async fn handle_get_swap(
     id: LocalSwapId,
     storage: Storage,
     connectors: Connectors,
+    execute_lightning_actions: bool,
 ) -> anyhow::Result<siren::Entity> {
-    let swap_context = storage.load(id).await?;
-    within_swap_context!(swap_context, {
-        let swap: ActorSwap = storage.load(id).await?;
+    let ledgers = LedgerSnapshot::fetch(&connectors).await?;
+
+    swap_entity(id, &storage, ledgers, execute_lightning_actions).await
+}
+
+/// The state of the ledgers that decides which actions are available.
+#[derive(Clone, Copy, Debug)]
+pub struct LedgerSnapshot {
+    bitcoin_median_time_past: Timestamp,
+    ethereum_latest_time: Timestamp,
+}
+
+impl LedgerSnapshot {
+    pub async fn fetch(connectors: &Connectors) -> anyhow::Result<Self> {
         let bitcoin_median_time_past =
             bitcoin::median_time_past(connectors.bitcoin().as_ref()).await?;
         let ethereum_latest_time = ethereum::latest_time(connectors.ethereum().as_ref()).await?;
 
-        let swap_entity =
-            make_swap_entity(id, swap, bitcoin_median_time_past, ethereum_latest_time)?;
+        Ok(Self {
+            bitcoin_median_time_past,
+            ethereum_latest_time,
+        })
+    }
+
+    /// The time on the given ledger that the expiries of HTLCs are compared
+    /// against.
+    pub fn time(&self, ledger: Ledger) -> Timestamp {
+        match ledger {
+            Ledger::Bitcoin => self.bitcoin_median_time_past,
+            Ledger::Ethereum => self.ethereum_latest_time,
+        }
+    }
+
+    /// The action of the swap that is available next, as advertised on the
+    /// swap entity.
+    pub fn next_action<S>(&self, swap: &S) -> anyhow::Result<Option<ActionName>>
+    where
+        S: GetRole
+            + DeployAction
+            + InitAction
+            + FundAction
+            + RedeemAction
+            + RefundAction
+            + Clone
+            + AlphaLedger
+            + BetaLedger
+            + AlphaAbsoluteExpiry
+            + BetaAbsoluteExpiry,
+    {
+        next_available_action(
+            swap,
+            self.bitcoin_median_time_past,
+            self.ethereum_latest_time,
+        )
+    }
+}
+
+/// The swap as returned by `GET /swaps/:id`.
+///
+/// If we execute lightning actions ourselves, they are not advertised unless
+/// we gave up on them.
+pub async fn swap_entity(
+    id: LocalSwapId,
+    storage: &Storage,
+    ledgers: LedgerSnapshot,
+    execute_lightning_actions: bool,
+) -> anyhow::Result<siren::Entity> {
+    let swap_context = storage.load(id).await?;
+    let cancellation = load_cancellation(storage, swap_context).await?;
+    within_swap_context!(swap_context, {
+        let swap: ActorSwap = storage.load(id).await?;
+        let (lnd_executions, wallet_executions, spend) = storage
+            .db
+            .do_in_transaction(|conn| {
+                Ok((
+                    LndExecution::by_swap_id(conn, id)?,
+                    WalletExecution::by_swap_id(conn, id)?,
+                    HbitSpend::latest(conn, id)?,
+                ))
+            })
+            .await?;
+        let executions = lnd_executions
+            .iter()
+            .map(SwapEvent::from)
+            .chain(wallet_executions.iter().map(SwapEvent::from))
+            .collect::<Vec<_>>();
+
+        let swap_entity = make_swap_entity(
+            id,
+            swap,
+            &executions,
+            cancellation,
+            spend,
+            ledgers,
+            if execute_lightning_actions {
+                Some(lnd_executions.as_slice())
+            } else {
+                None
+            },
+        )?;
 
         Ok(swap_entity)
     })
 }
 
+/// The earliest expiry of the swap's HTLCs that has not passed yet.
+pub async fn next_expiry(
+    id: LocalSwapId,
+    storage: &Storage,
+    now: Timestamp,
+) -> anyhow::Result<Option<Timestamp>> {
+    let swap_context = storage.load(id).await?;
+    within_swap_context!(swap_context, {
+        let swap: ActorSwap = storage.load(id).await?;
+        let next_expiry = vec![swap.alpha_absolute_expiry(), swap.beta_absolute_expiry()]
+            .into_iter()
+            .flatten()
+            .filter(|expiry| *expiry > now)
+            .min();
+
+        Ok(next_expiry)
+    })
+}
+
+/// Whether a swap was or can still be cancelled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Cancellation {
+    /// The party with this role cancelled the swap.
+    Cancelled(Role),
+    /// Alice did not fund the swap yet.
+    Possible,
+    /// Alice funded the swap already or we handed out a fund action.
+    NotPossible,
+}
+
+async fn load_cancellation(storage: &Storage, swap: SwapContext) -> anyhow::Result<Cancellation> {
+    let id = swap.id;
+    let cancellation = storage
+        .db
+        .do_in_transaction(|conn| SwapCancellation::by_swap_id(conn, id))
+        .await?;
+
+    if let Some(cancellation) = cancellation {
+        return Ok(Cancellation::Cancelled(cancellation.cancelled_by));
+    }
+
+    let handed_out = storage
+        .db
+        .do_in_transaction(|conn| FundHandout::by_swap_id(conn, id))
+        .await?;
+
+    if handed_out.is_some() || storage.alpha_funded(swap).await? {
+        Ok(Cancellation::NotPossible)
+    } else {
+        Ok(Cancellation::Possible)
+    }
+}
+
+/// Cancelled swaps must not be acted on anymore.
+async fn ensure_not_cancelled(storage: &Storage, id: LocalSwapId) -> anyhow::Result<()> {
+    let cancellation = storage
+        .db
+        .do_in_transaction(|conn| SwapCancellation::by_swap_id(conn, id))
+        .await?;
+
+    if cancellation.is_some() {
+        anyhow::bail!(SwapCancelled(id))
+    }
+
+    Ok(())
+}
+
+/// Once a deploy or fund action is handed out the swap can no longer be
+/// cancelled. Fails if it was cancelled in the meantime.
+async fn record_fund_handout(storage: &Storage, id: LocalSwapId) -> anyhow::Result<()> {
+    storage
+        .db
+        .do_in_transaction(|conn| FundHandout::record(conn, id, OffsetDateTime::now_utc()))
+        .await
+}
+
 fn make_swap_entity<S>(
     id: LocalSwapId,
     swap: S,
-    bitcoin_median_time_past: Timestamp,
-    ethereum_latest_time: Timestamp,
+    executions: &[SwapEvent],
+    cancellation: Cancellation,
+    spend: Option<hbit::Spend>,
+    ledgers: LedgerSnapshot,
+    lnd_executions: Option<&[LndExecution]>,
 ) -> anyhow::Result<siren::Entity>
 where
     S: GetRole
@@ -101,26 +431,68 @@ where
         + BetaLedger
         + AlphaAbsoluteExpiry
         + BetaAbsoluteExpiry,
+    <S as InitAction>::Output: IntoLndAction,
+    <S as FundAction>::Output: IntoLndAction,
+    <S as RedeemAction>::Output: IntoLndAction,
 {
-    let entity = create_swap_entity(id, &swap)?;
+    let mut entity = create_swap_entity(id, &swap, executions, cancellation)?;
 
-    match next_available_action(&swap, bitcoin_median_time_past, ethereum_latest_time)? {
-        None => Ok(entity),
-        Some(action) => {
-            let siren_action = make_siren_action(id, action);
-            Ok(entity.with_action(siren_action))
+    // We no longer watch the ledgers of cancelled swaps, hence their state
+    // cannot tell which action would be next.
+    let next_action = match cancellation {
+        Cancellation::Cancelled(_) => None,
+        _ => ledgers.next_action(&swap)?,
+    };
+
+    if let Some(action) = next_action {
+        let executed_by_cnd = match (lnd_executions, lightning_action_kind(&swap, action)) {
+            (Some(executions), Some(kind)) => Progress::of(executions, kind) != Progress::GaveUp,
+            _ => false,
+        };
+
+        if !executed_by_cnd {
+            entity = entity.with_action(make_siren_action(id, action));
+        }
+
+        // A redeem or refund transaction was handed out already but the HTLC
+        // is not spent yet, replacing it might get it confirmed.
+        let bumpable = match (spend, action) {
+            (Some(spend), ActionName::Redeem) => spend.kind == hbit::SpendKind::Redeem,
+            (Some(spend), ActionName::Refund) => spend.kind == hbit::SpendKind::Refund,
+            _ => false,
+        };
+        if bumpable {
+            entity = entity.with_action(make_siren_action(id, ActionName::Bump));
         }
     }
+
+    if cancellation == Cancellation::Possible {
+        entity = entity.with_action(make_siren_action(id, ActionName::Cancel));
+    }
+
+    Ok(entity)
 }
 
-fn create_swap_entity<S>(id: LocalSwapId, swap: &S) -> anyhow::Result<siren::Entity>
+fn create_swap_entity<S>(
+    id: LocalSwapId,
+    swap: &S,
+    executions: &[SwapEvent],
+    cancellation: Cancellation,
+) -> anyhow::Result<siren::Entity>
 where
     S: GetRole + Events + AlphaProtocol + BetaProtocol,
 {
+    // TODO: These events should be sorted by timestamp but we are not recording
+    // any ...
+    let mut events = swap.events();
+    events.extend_from_slice(executions);
+    if let Cancellation::Cancelled(by) = cancellation {
+        events.push(SwapEvent::Cancelled { by });
+    }
+
     let swap_resource = SwapResource {
         role: swap.get_role(),
-        events: swap.events(), /* TODO: These events should be sorted by timestamp but we are not
-                                * recording any ... */
+        events,
         alpha: swap.alpha_protocol(),
         beta: swap.beta_protocol(),
     };
@@ -199,11 +571,86 @@ where
     Ok(None)
 }
 
+/// The kind of the given action of the swap if it is to be executed against
+/// LND.
+fn lightning_action_kind<S>(swap: &S, action: ActionName) -> Option<ActionKind>
+where
+    S: InitAction + FundAction + RedeemAction,
+    <S as InitAction>::Output: IntoLndAction,
+    <S as FundAction>::Output: IntoLndAction,
+    <S as RedeemAction>::Output: IntoLndAction,
+{
+    let lnd_action = match action {
+        ActionName::Init => swap
+            .init_action()
+            .ok()
+            .and_then(IntoLndAction::into_lnd_action),
+        ActionName::Fund => swap
+            .fund_action()
+            .ok()
+            .and_then(IntoLndAction::into_lnd_action),
+        ActionName::Redeem => swap
+            .redeem_action()
+            .ok()
+            .and_then(IntoLndAction::into_lnd_action),
+        ActionName::Deploy | ActionName::Refund | ActionName::Bump | ActionName::Cancel => None,
+    };
+
+    lnd_action.map(|action| action.kind())
+}
+
+/// Lightning actions that cnd executes itself are not handed out, unless it
+/// gave up on them.
+async fn ensure_not_executed_by_cnd<A>(
+    storage: &Storage,
+    id: LocalSwapId,
+    action: &A,
+    execute_lightning_actions: bool,
+) -> anyhow::Result<()>
+where
+    A: IntoLndAction + Clone,
+{
+    let kind = match action.clone().into_lnd_action() {
+        Some(action) if execute_lightning_actions => action.kind(),
+        _ => return Ok(()),
+    };
+    let executions = storage
+        .db
+        .do_in_transaction(|conn| LndExecution::by_swap_id(conn, id))
+        .await?;
+
+    if Progress::of(&executions, kind) != Progress::GaveUp {
+        anyhow::bail!(LightningActionExecutedByCnd)
+    }
+
+    Ok(())
+}
+
+/// Estimates the Bitcoin fee rate for bumping a transaction.
+///
+/// The bumped fee rate is at least a quarter higher than the previous one
+/// anyway, hence a failed estimation is not a reason to fail the request.
+async fn estimate_fee_rate_or_fallback(connectors: &Connectors) -> SatPerVbyte {
+    connectors
+        .bitcoin_fee_rate()
+        .estimate_fee_rate()
+        .await
+        .unwrap_or_else(|e| {
+            tracing::debug!("failed to estimate bitcoin fee rate: {:#}", e);
+            bitcoin::FALLBACK_FEE_RATE
+        })
+}
+
 fn make_siren_action(id: LocalSwapId, action_name: ActionName) -> siren::Action {
+    let method = match action_name {
+        ActionName::Cancel => http::Method::POST,
+        _ => http::Method::GET,
+    };
+
     siren::Action {
         name: action_name.to_string(),
         class: vec![],
-        method: Some(http::Method::GET),
+        method: Some(method),
         href: format!("/swaps/{}/{}", id, action_name),
         title: None,
         _type: None,
@@ -219,6 +666,8 @@ impl std::fmt::Display for ActionName {
             ActionName::Fund => "fund",
             ActionName::Redeem => "redeem",
             ActionName::Refund => "refund",
+            ActionName::Bump => "bump",
+            ActionName::Cancel => "cancel",
         };
         write!(f, "{}", str)
     }
@@ -232,9 +681,89 @@ struct SwapResource {
     pub beta: Protocol,
 }
 
+/// The query parameters of the redeem and refund action endpoints.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct FeeRateQuery {
+    pub fee_rate: Option<SatPerVbyte>,
+}
+
+/// The bounds of the fee rates we accept through the `fee_rate` query
+/// parameter, anything outside is most likely a mistake.
+const MIN_FEE_RATE: SatPerVbyte = SatPerVbyte::new(1);
+const MAX_FEE_RATE: SatPerVbyte = SatPerVbyte::new(1000);
+
+impl FeeRateQuery {
+    fn requested(self) -> anyhow::Result<Option<SatPerVbyte>> {
+        match self.fee_rate {
+            Some(requested) if requested < MIN_FEE_RATE || requested > MAX_FEE_RATE => {
+                anyhow::bail!(FeeRateOutOfRange {
+                    requested,
+                    min: MIN_FEE_RATE,
+                    max: MAX_FEE_RATE,
+                })
+            }
+            fee_rate => Ok(fee_rate),
+        }
+    }
+}
+
+/// The query parameters selecting the format of an action.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct FormatQuery {
+    pub format: Option<ActionFormat>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionFormat {
+    Psbt,
+}
+
+impl FormatQuery {
+    fn render<A>(self, action: A) -> anyhow::Result<ActionResponseBody>
+    where
+        A: IntoPsbt + Into<ActionResponseBody>,
+    {
+        match self.format {
+            Some(ActionFormat::Psbt) => action.into_psbt(),
+            None => Ok(action.into()),
+        }
+    }
+
+    /// Renders the output of the redeem and refund action traits.
+    ///
+    /// Spends of Bitcoin HTLCs are signed with the requested fee rate or, if
+    /// none was requested, an estimated one.
+    async fn render_spend<A>(
+        self,
+        action: A,
+        requested_fee_rate: Option<SatPerVbyte>,
+        connectors: &Connectors,
+    ) -> anyhow::Result<ActionResponseBody>
+    where
+        A: IntoSpend,
+    {
+        match action.into_spend() {
+            Spend::Ready(action) => self.render(action),
+            Spend::BitcoinHtlc(action) => {
+                let fee_rate = match requested_fee_rate {
+                    Some(fee_rate) => fee_rate,
+                    None => connectors.bitcoin_fee_rate().estimate_fee_rate().await?,
+                };
+
+                self.render(action.sign(&*crate::SECP, fee_rate)?)
+            }
+        }
+    }
+}
+
 #[allow(clippy::needless_pass_by_value)]
-pub async fn action_init(id: LocalSwapId, storage: Storage) -> Result<impl Reply, Rejection> {
-    handle_action_init(id, storage)
+pub async fn action_init(
+    id: LocalSwapId,
+    storage: Storage,
+    execute_lightning_actions: bool,
+) -> Result<impl Reply, Rejection> {
+    handle_action_init(id, storage, execute_lightning_actions)
         .await
         .map(|body| warp::reply::json(&body))
         .map_err(problem::from_anyhow)
@@ -245,11 +774,14 @@ pub async fn action_init(id: LocalSwapId, storage: Storage) -> Result<impl Reply
 async fn handle_action_init(
     id: LocalSwapId,
     storage: Storage,
+    execute_lightning_actions: bool,
 ) -> anyhow::Result<ActionResponseBody> {
     let swap_context = storage.load(id).await?;
+    ensure_not_cancelled(&storage, id).await?;
     let response = within_swap_context!(swap_context, {
         let swap: ActorSwap = storage.load(id).await?;
         let action = swap.init_action()?;
+        ensure_not_executed_by_cnd(&storage, id, &action, execute_lightning_actions).await?;
         ActionResponseBody::from(action)
     });
 
@@ -271,18 +803,25 @@ async fn handle_action_deploy(
     storage: Storage,
 ) -> anyhow::Result<ActionResponseBody> {
     let swap_context = storage.load(id).await?;
+    ensure_not_cancelled(&storage, id).await?;
     let response = within_swap_context!(swap_context, {
         let swap: ActorSwap = storage.load(id).await?;
         let action = swap.deploy_action()?;
         ActionResponseBody::from(action)
     });
+    record_fund_handout(&storage, id).await?;
 
     Ok(response)
 }
 
 #[allow(clippy::needless_pass_by_value)]
-pub async fn action_fund(id: LocalSwapId, storage: Storage) -> Result<impl Reply, Rejection> {
-    handle_action_fund(id, storage)
+pub async fn action_fund(
+    id: LocalSwapId,
+    format: FormatQuery,
+    storage: Storage,
+    execute_lightning_actions: bool,
+) -> Result<impl Reply, Rejection> {
+    handle_action_fund(id, format, storage, execute_lightning_actions)
         .await
         .map(|body| warp::reply::json(&body))
         .map_err(problem::from_anyhow)
@@ -292,45 +831,89 @@ pub async fn action_fund(id: LocalSwapId, storage: Storage) -> Result<impl Reply
 #[allow(clippy::unit_arg, clippy::let_unit_value, clippy::cognitive_complexity)]
 async fn handle_action_fund(
     id: LocalSwapId,
+    format: FormatQuery,
     storage: Storage,
+    execute_lightning_actions: bool,
 ) -> anyhow::Result<ActionResponseBody> {
     let swap_context = storage.load(id).await?;
+    ensure_not_cancelled(&storage, id).await?;
     let response = within_swap_context!(swap_context, {
         let swap: ActorSwap = storage.load(id).await?;
         let action = swap.fund_action()?;
-        ActionResponseBody::from(action)
+        ensure_not_executed_by_cnd(&storage, id, &action, execute_lightning_actions).await?;
+        format.render(action)?
     });
+    record_fund_handout(&storage, id).await?;
 
     Ok(response)
 }
 
 #[allow(clippy::needless_pass_by_value)]
-pub async fn action_redeem(id: LocalSwapId, storage: Storage) -> Result<impl Reply, Rejection> {
-    handle_action_redeem(id, storage)
+pub async fn action_cancel(id: LocalSwapId, swarm: Swarm) -> Result<impl Reply, Rejection> {
+    swarm
+        .cancel_swap(id)
         .await
-        .map(|body| warp::reply::json(&body))
+        .map(|()| warp::reply())
         .map_err(problem::from_anyhow)
         .map_err(warp::reject::custom)
 }
 
+#[allow(clippy::needless_pass_by_value)]
+pub async fn action_redeem(
+    id: LocalSwapId,
+    query: FeeRateQuery,
+    format: FormatQuery,
+    storage: Storage,
+    connectors: Connectors,
+    execute_lightning_actions: bool,
+) -> Result<impl Reply, Rejection> {
+    handle_action_redeem(
+        id,
+        query,
+        format,
+        storage,
+        connectors,
+        execute_lightning_actions,
+    )
+    .await
+    .map(|body| warp::reply::json(&body))
+    .map_err(problem::from_anyhow)
+    .map_err(warp::reject::custom)
+}
+
 #[allow(clippy::unit_arg, clippy::let_unit_value, clippy::cognitive_complexity)]
 async fn handle_action_redeem(
     id: LocalSwapId,
+    query: FeeRateQuery,
+    format: FormatQuery,
     storage: Storage,
+    connectors: Connectors,
+    execute_lightning_actions: bool,
 ) -> anyhow::Result<ActionResponseBody> {
     let swap_context = storage.load(id).await?;
+    let requested_fee_rate = query.requested()?;
     let response = within_swap_context!(swap_context, {
         let swap: ActorSwap = storage.load(id).await?;
         let action = swap.redeem_action()?;
-        ActionResponseBody::from(action)
+        ensure_not_executed_by_cnd(&storage, id, &action, execute_lightning_actions).await?;
+        format
+            .render_spend(action, requested_fee_rate, &connectors)
+            .await?
     });
+    remember_spend(&storage, id, hbit::SpendKind::Redeem, &response).await;
 
     Ok(response)
 }
 
 #[allow(clippy::needless_pass_by_value)]
-pub async fn action_refund(id: LocalSwapId, storage: Storage) -> Result<impl Reply, Rejection> {
-    handle_action_refund(id, storage)
+pub async fn action_refund(
+    id: LocalSwapId,
+    query: FeeRateQuery,
+    format: FormatQuery,
+    storage: Storage,
+    connectors: Connectors,
+) -> Result<impl Reply, Rejection> {
+    handle_action_refund(id, query, format, storage, connectors)
         .await
         .map(|body| warp::reply::json(&body))
         .map_err(problem::from_anyhow)
@@ -340,14 +923,117 @@ pub async fn action_refund(id: LocalSwapId, storage: Storage) -> Result<impl Rep
 #[allow(clippy::unit_arg, clippy::let_unit_value, clippy::cognitive_complexity)]
 async fn handle_action_refund(
     id: LocalSwapId,
+    query: FeeRateQuery,
+    format: FormatQuery,
     storage: Storage,
+    connectors: Connectors,
 ) -> anyhow::Result<ActionResponseBody> {
     let swap_context = storage.load(id).await?;
+    ensure_not_cancelled(&storage, id).await?;
+    let requested_fee_rate = query.requested()?;
     let response = within_swap_context!(swap_context, {
         let swap: ActorSwap = storage.load(id).await?;
         let action = swap.refund_action()?;
-        ActionResponseBody::from(action)
+        format
+            .render_spend(action, requested_fee_rate, &connectors)
+            .await?
+    });
+    remember_spend(&storage, id, hbit::SpendKind::Refund, &response).await;
+
+    Ok(response)
+}
+
+#[allow(clippy::needless_pass_by_value)]
+pub async fn action_bump(
+    id: LocalSwapId,
+    query: FeeRateQuery,
+    format: FormatQuery,
+    storage: Storage,
+    connectors: Connectors,
+) -> Result<impl Reply, Rejection> {
+    handle_action_bump(id, query, format, storage, connectors)
+        .await
+        .map(|body| warp::reply::json(&body))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+/// Re-signs the last redeem or refund transaction handed out for this swap
+/// with a higher fee rate.
+///
+/// This action is only available as long as the HTLC is not yet spent, i.e.
+/// none of the previously handed out transactions has been confirmed.
+#[allow(clippy::unit_arg, clippy::let_unit_value, clippy::cognitive_complexity)]
+async fn handle_action_bump(
+    id: LocalSwapId,
+    query: FeeRateQuery,
+    format: FormatQuery,
+    storage: Storage,
+    connectors: Connectors,
+) -> anyhow::Result<ActionResponseBody> {
+    let previous = storage
+        .db
+        .do_in_transaction(|conn| HbitSpend::latest(conn, id))
+        .await?
+        .ok_or(ActionNotFound)?;
+    let fee_rate = match query.requested()? {
+        Some(requested) if requested > previous.fee_rate => requested,
+        Some(requested) => anyhow::bail!(FeeRateTooLow {
+            requested,
+            previous: previous.fee_rate,
+        }),
+        None if previous.fee_rate >= MAX_BUMPED_FEE_RATE => anyhow::bail!(FeeRateAtMaximum {
+            previous: previous.fee_rate,
+            maximum: MAX_BUMPED_FEE_RATE,
+        }),
+        None => {
+            let estimate = estimate_fee_rate_or_fallback(&connectors).await;
+            std::cmp::min(previous.fee_rate.bump(estimate), MAX_BUMPED_FEE_RATE)
+        }
+    };
+
+    let swap_context = storage.load(id).await?;
+    let action = within_swap_context!(swap_context, {
+        let swap: ActorSwap = storage.load(id).await?;
+        match previous.kind {
+            hbit::SpendKind::Redeem => bitcoin_htlc_spend(swap.redeem_action()?)?,
+            hbit::SpendKind::Refund => bitcoin_htlc_spend(swap.refund_action()?)?,
+        }
     });
+    let response = format.render(action.sign(&*crate::SECP, fee_rate)?)?;
+    remember_spend(&storage, id, previous.kind, &response).await;
 
     Ok(response)
 }
+
+/// Only spends of Bitcoin HTLCs can be bumped.
+fn bitcoin_htlc_spend<A>(action: A) -> anyhow::Result<SpendHtlc>
+where
+    A: IntoSpend,
+{
+    match action.into_spend() {
+        Spend::BitcoinHtlc(action) => Ok(action),
+        Spend::Ready(_) => anyhow::bail!(ActionNotFound),
+    }
+}
+
+async fn remember_spend(
+    storage: &Storage,
+    id: LocalSwapId,
+    kind: hbit::SpendKind,
+    response: &ActionResponseBody,
+) {
+    let fee_rate = match response {
+        ActionResponseBody::BitcoinBroadcastSignedTransaction { fee_rate, .. } => *fee_rate,
+        _ => return,
+    };
+
+    let spend = hbit::Spend { kind, fee_rate };
+    if let Err(e) = storage
+        .db
+        .do_in_transaction(|conn| HbitSpend::record(conn, id, spend))
+        .await
+    {
+        tracing::warn!("failed to record {} spend for swap {}: {:#}", kind, id, e);
+    }
+}
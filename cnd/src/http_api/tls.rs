@@ -0,0 +1,82 @@
+use crate::config::Tls;
+use anyhow::{Context, Result};
+use futures::{channel::mpsc, Stream, StreamExt};
+use std::{fs, io, net::SocketAddr, time::Duration};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tls::{TlsAcceptor, TlsStream};
+
+/// How long a client has to complete the TLS handshake, otherwise clients
+/// that never finish it would pile up.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The socket the HTTP API listens on, optionally serving it over TLS.
+pub enum Listener {
+    Plain(TcpListener),
+    Tls(TcpListener, TlsAcceptor),
+}
+
+impl Listener {
+    pub fn new(listener: TcpListener, tls: Option<&Tls>) -> Result<Self> {
+        let tls = match tls {
+            Some(tls) => tls,
+            None => return Ok(Listener::Plain(listener)),
+        };
+
+        let archive = fs::read(&tls.identity).with_context(|| {
+            format!(
+                "failed to read TLS identity from {}",
+                tls.identity.display()
+            )
+        })?;
+        let password = tls.password.as_deref().unwrap_or_default();
+        let identity = native_tls::Identity::from_pkcs12(&archive, password)
+            .context("failed to decode TLS identity")?;
+        let acceptor = native_tls::TlsAcceptor::new(identity)?;
+
+        Ok(Listener::Tls(listener, acceptor.into()))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            Listener::Plain(listener) | Listener::Tls(listener, _) => listener.local_addr(),
+        }
+    }
+}
+
+/// Accepts connections on the listener and completes their TLS handshakes.
+///
+/// Handshakes happen in the background so a slow client cannot hold up
+/// everyone else.
+pub fn incoming(
+    mut listener: TcpListener,
+    acceptor: TlsAcceptor,
+) -> impl Stream<Item = io::Result<TlsStream<TcpStream>>> {
+    let (sender, receiver) = mpsc::unbounded();
+
+    tokio::spawn(async move {
+        while !sender.is_closed() {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    tracing::warn!("failed to accept connection: {}", e);
+                    tokio::time::delay_for(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+
+            let acceptor = acceptor.clone();
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => {
+                        let _ = sender.unbounded_send(stream);
+                    }
+                    Ok(Err(e)) => tracing::debug!("TLS handshake failed: {}", e),
+                    Err(_) => tracing::debug!("TLS handshake timed out"),
+                }
+            });
+        }
+    });
+
+    receiver.map(Ok)
+}
@@ -0,0 +1,610 @@
+//! The "/updates" endpoint streams changes to our swaps, our orders and the
+//! BTC/DAI market as server-sent events, hence clients don't have to poll the
+//! other endpoints to learn about them.
+//!
+//! Every update carries the document the respective GET endpoint would return
+//! at that time, and a cursor as its event id. A client that reconnects with
+//! the cursor of the last update it saw, either in the `Last-Event-ID` header
+//! or as `cursor` query parameter, first receives all updates it missed. If
+//! they are no longer retained, e.g. because cnd restarted in the meantime, it
+//! receives a `reset` event instead and should fetch the current state from the
+//! GET endpoints.
+//!
+//! Swaps are published while they are in progress and once more when they
+//! finished.
+
+use crate::{
+    connectors::Connectors,
+    http_api::{
+        make_order_entity, markets, problem,
+        swaps::{self, LedgerSnapshot},
+        OrderProperties,
+    },
+    network::Swarm,
+    storage::{
+        all_open_btc_dai_orders, BtcDaiOrder, Order, SortOrder, Storage, Swap, SwapFilter,
+        SwapStatus,
+    },
+    LocalSwapId,
+};
+use anyhow::Result;
+use comit::OrderId;
+use futures::{stream, StreamExt, TryFutureExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    convert::Infallible,
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{broadcast, Mutex};
+use warp::{sse, Filter, Rejection, Reply};
+
+/// How many updates we keep around for clients that reconnect.
+const RETAINED_UPDATES: usize = 1024;
+
+/// How often we look for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The warp filter for streaming updates.
+pub fn route(updates: Updates) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    warp::get()
+        .and(warp::path!("updates"))
+        .and(sse::last_event_id::<String>())
+        .and(warp::query::<CursorQuery>())
+        .and_then(move |last_event_id: Option<String>, query: CursorQuery| {
+            handler(updates.clone(), last_event_id.or(query.cursor))
+                .map_err(problem::from_anyhow)
+                .map_err(warp::reject::custom)
+        })
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct CursorQuery {
+    cursor: Option<String>,
+}
+
+async fn handler(updates: Updates, cursor: Option<String>) -> Result<impl Reply> {
+    let (resume, receiver) = updates.subscribe(cursor.as_deref()).await;
+
+    let resumed = match resume {
+        Resume::Missed(missed) => missed.into_iter().map(Update::into_event).collect(),
+        Resume::Reset { cursor } => vec![Event {
+            cursor,
+            name: "reset",
+            document: json!({}),
+        }],
+    };
+    let live = stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await {
+            Ok(update) => Some((update.into_event(), receiver)),
+            // The client resumes from the last update it received once it reconnects
+            Err(broadcast::RecvError::Lagged(_)) => {
+                tracing::debug!("closing update stream that fell behind");
+                None
+            }
+            Err(broadcast::RecvError::Closed) => None,
+        }
+    });
+    let events = stream::iter(resumed)
+        .chain(live)
+        .map(|event| Ok::<_, Infallible>(event.into_sse()));
+
+    Ok(sse::reply(sse::keep_alive().stream(events)))
+}
+
+/// What an update is about.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Topic {
+    Swap,
+    Order,
+    MarketOrder,
+    MarketOrderRemoved,
+}
+
+impl Topic {
+    pub fn name(self) -> &'static str {
+        match self {
+            Topic::Swap => "swap",
+            Topic::Order => "order",
+            Topic::MarketOrder => "market_order",
+            Topic::MarketOrderRemoved => "market_order_removed",
+        }
+    }
+}
+
+/// The position of an update in the stream.
+///
+/// The sequence numbers start over whenever cnd starts, the epoch tells
+/// cursors handed out by earlier processes apart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Cursor {
+    epoch: u32,
+    sequence: u64,
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.epoch, self.sequence)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(2, '-');
+        let epoch = parts.next().unwrap_or_default().parse()?;
+        let sequence = parts
+            .next()
+            .ok_or_else(|| anyhow::anyhow!("cursor {} lacks a sequence number", s))?
+            .parse()?;
+
+        Ok(Self { epoch, sequence })
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Update {
+    cursor: Cursor,
+    topic: Topic,
+    /// The id of the swap or order the update is about.
+    subject: String,
+    document: Value,
+}
+
+impl Update {
+    pub fn topic(&self) -> Topic {
+        self.topic
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn document(&self) -> &Value {
+        &self.document
+    }
+
+    fn into_event(self) -> Event {
+        Event {
+            cursor: self.cursor,
+            name: self.topic.name(),
+            document: self.document,
+        }
+    }
+}
+
+/// A server-sent event as it goes out to the client.
+#[derive(Debug)]
+struct Event {
+    cursor: Cursor,
+    name: &'static str,
+    document: Value,
+}
+
+impl Event {
+    fn into_sse(self) -> impl sse::ServerSentEvent {
+        (
+            sse::id(self.cursor.to_string()),
+            sse::event(self.name),
+            sse::json(self.document),
+        )
+    }
+}
+
+/// Where a subscriber continues from.
+#[derive(Debug)]
+enum Resume {
+    /// The updates published after the subscriber's cursor.
+    Missed(Vec<Update>),
+    /// The updates after the subscriber's cursor are gone, the subscriber
+    /// has to start over from the current state and this cursor.
+    Reset { cursor: Cursor },
+}
+
+/// The updates published so far, shared between the worker publishing them
+/// and the update streams.
+#[derive(Clone, Debug)]
+pub struct Updates(Arc<Mutex<Log>>);
+
+#[derive(Debug)]
+struct Log {
+    epoch: u32,
+    next_sequence: u64,
+    retained: VecDeque<Update>,
+    /// The latest update of every subject, regardless of whether it is still
+    /// retained.
+    latest: HashMap<(Topic, String), Update>,
+    sender: broadcast::Sender<Update>,
+}
+
+impl Default for Updates {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(RETAINED_UPDATES);
+
+        Self(Arc::new(Mutex::new(Log {
+            epoch: rand::random(),
+            next_sequence: 1,
+            retained: VecDeque::with_capacity(RETAINED_UPDATES),
+            latest: HashMap::new(),
+            sender,
+        })))
+    }
+}
+
+impl Updates {
+    pub async fn publish(&self, topic: Topic, subject: String, document: Value) {
+        let mut log = self.0.lock().await;
+
+        let update = Update {
+            cursor: Cursor {
+                epoch: log.epoch,
+                sequence: log.next_sequence,
+            },
+            topic,
+            subject,
+            document,
+        };
+        log.next_sequence += 1;
+
+        if log.retained.len() == RETAINED_UPDATES {
+            log.retained.pop_front();
+        }
+        log.retained.push_back(update.clone());
+        log.latest
+            .insert((topic, update.subject.clone()), update.clone());
+
+        // Nobody listening is fine, the update is retained for later
+        let _ = log.sender.send(update);
+    }
+
+    /// Receive all updates published from now on.
+    pub async fn listen(&self) -> broadcast::Receiver<Update> {
+        self.0.lock().await.sender.subscribe()
+    }
+
+    /// The latest update of every subject, e.g. to catch up after falling
+    /// behind.
+    pub async fn latest(&self) -> Vec<Update> {
+        let log = self.0.lock().await;
+        let mut latest = log.latest.values().cloned().collect::<Vec<_>>();
+        latest.sort_by_key(|update| update.cursor.sequence);
+
+        latest
+    }
+
+    /// Subscribe to all updates after the given cursor.
+    ///
+    /// Without a cursor, only updates published from now on are received.
+    async fn subscribe(&self, cursor: Option<&str>) -> (Resume, broadcast::Receiver<Update>) {
+        let log = self.0.lock().await;
+        let receiver = log.sender.subscribe();
+        let latest = Cursor {
+            epoch: log.epoch,
+            sequence: log.next_sequence - 1,
+        };
+
+        let cursor = match cursor {
+            Some(cursor) => cursor,
+            None => return (Resume::Missed(Vec::new()), receiver),
+        };
+        // Cursors we cannot make sense of were handed out by another process
+        let sequence = match Cursor::from_str(cursor) {
+            Ok(cursor) if cursor.epoch == log.epoch => cursor.sequence,
+            _ => return (Resume::Reset { cursor: latest }, receiver),
+        };
+        let oldest = log
+            .retained
+            .front()
+            .map(|update| update.cursor.sequence)
+            .unwrap_or(log.next_sequence);
+
+        if sequence + 1 < oldest || sequence > latest.sequence {
+            return (Resume::Reset { cursor: latest }, receiver);
+        }
+
+        let missed = log
+            .retained
+            .iter()
+            .filter(|update| update.cursor.sequence > sequence)
+            .cloned()
+            .collect();
+
+        (Resume::Missed(missed), receiver)
+    }
+}
+
+/// The documents we published last, to tell what changed since.
+#[derive(Debug, Default)]
+struct Published {
+    swaps: HashMap<LocalSwapId, Value>,
+    orders: HashMap<OrderId, Value>,
+    market: HashMap<OrderId, Value>,
+}
+
+/// Publishes changes to our swaps, our orders and the BTC/DAI market.
+///
+/// Besides through ledger events, actions become available as time passes,
+/// hence we look at the current state of everything periodically rather than
+/// hooking into the places where it changes.
+pub async fn publish(
+    updates: Updates,
+    swarm: Swarm,
+    storage: Storage,
+    connectors: Connectors,
+    network: comit::Network,
+    execute_lightning_actions: bool,
+) {
+    let mut published = Published::default();
+
+    loop {
+        if let Err(e) = publish_swaps(
+            &updates,
+            &storage,
+            &connectors,
+            execute_lightning_actions,
+            &mut published,
+        )
+        .await
+        {
+            tracing::debug!("failed to publish swap updates: {:#}", e);
+        }
+        if let Err(e) = publish_orders(&updates, &storage, &mut published).await {
+            tracing::debug!("failed to publish order updates: {:#}", e);
+        }
+        if let Err(e) = publish_market(&updates, &swarm, network, &mut published).await {
+            tracing::debug!("failed to publish market updates: {:#}", e);
+        }
+
+        tokio::time::delay_for(POLL_INTERVAL).await;
+    }
+}
+
+async fn publish_swaps(
+    updates: &Updates,
+    storage: &Storage,
+    connectors: &Connectors,
+    execute_lightning_actions: bool,
+    published: &mut Published,
+) -> Result<()> {
+    let in_progress = SwapFilter {
+        status: Some(SwapStatus::InProgress),
+        ..SwapFilter::default()
+    };
+    let in_progress = storage
+        .db
+        .do_in_transaction(|conn| {
+            Swap::query(conn, &in_progress, SortOrder::Oldest, None, i64::MAX)
+        })
+        .await?
+        .into_iter()
+        .map(|swap| swap.local_swap_id)
+        .collect::<HashSet<_>>();
+
+    // Swaps that are no longer in progress finished, we publish their final
+    // state once.
+    let finished = published
+        .swaps
+        .keys()
+        .filter(|id| !in_progress.contains(id))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if in_progress.is_empty() && finished.is_empty() {
+        return Ok(());
+    }
+    let ledgers = LedgerSnapshot::fetch(connectors).await?;
+
+    for id in in_progress.iter().chain(finished.iter()).copied() {
+        let entity = match swaps::swap_entity(id, storage, ledgers, execute_lightning_actions).await
+        {
+            Ok(entity) => entity,
+            Err(e) => {
+                tracing::debug!("failed to load swap {}: {:#}", id, e);
+                continue;
+            }
+        };
+        let document = serde_json::to_value(&entity)?;
+
+        if published.swaps.get(&id) != Some(&document) {
+            published.swaps.insert(id, document.clone());
+            updates.publish(Topic::Swap, id.to_string(), document).await;
+        }
+    }
+
+    for id in finished {
+        published.swaps.remove(&id);
+    }
+
+    Ok(())
+}
+
+async fn publish_orders(
+    updates: &Updates,
+    storage: &Storage,
+    published: &mut Published,
+) -> Result<()> {
+    let open_orders = storage
+        .db
+        .do_in_transaction(|conn| all_open_btc_dai_orders(conn))
+        .await?;
+    let mut documents = HashMap::with_capacity(open_orders.len());
+    for (order, btc_dai_order) in open_orders {
+        let id = order.order_id;
+        let entity = make_order_entity(OrderProperties::from((order, btc_dai_order)))?;
+        documents.insert(id, serde_json::to_value(&entity)?);
+    }
+
+    // Orders that are no longer open were filled or cancelled, we publish
+    // their final state once.
+    let done = published
+        .orders
+        .keys()
+        .filter(|id| !documents.contains_key(id))
+        .copied()
+        .collect::<Vec<_>>();
+    for id in done {
+        let properties = storage
+            .db
+            .do_in_transaction(|conn| {
+                let order = Order::by_order_id(conn, id)?;
+                let btc_dai_order = BtcDaiOrder::by_order(conn, &order)?;
+
+                Ok((order, btc_dai_order))
+            })
+            .await?
+            .into();
+        let document = serde_json::to_value(&make_order_entity(properties)?)?;
+
+        if published.orders.remove(&id) != Some(document.clone()) {
+            updates
+                .publish(Topic::Order, id.to_string(), document)
+                .await;
+        }
+    }
+
+    for (id, document) in documents {
+        if published.orders.get(&id) != Some(&document) {
+            published.orders.insert(id, document.clone());
+            updates
+                .publish(Topic::Order, id.to_string(), document)
+                .await;
+        }
+    }
+
+    Ok(())
+}
+
+async fn publish_market(
+    updates: &Updates,
+    swarm: &Swarm,
+    network: comit::Network,
+    published: &mut Published,
+) -> Result<()> {
+    let mut documents = HashMap::new();
+    for (id, entity) in markets::market_items(swarm, network).await? {
+        documents.insert(id, serde_json::to_value(&entity)?);
+    }
+
+    let removed = published
+        .market
+        .keys()
+        .filter(|id| !documents.contains_key(id))
+        .copied()
+        .collect::<Vec<_>>();
+    for id in removed {
+        published.market.remove(&id);
+        updates
+            .publish(
+                Topic::MarketOrderRemoved,
+                id.to_string(),
+                json!({ "id": id }),
+            )
+            .await;
+    }
+
+    for (id, document) in documents {
+        if published.market.get(&id) != Some(&document) {
+            published.market.insert(id, document.clone());
+            updates
+                .publish(Topic::MarketOrder, id.to_string(), document)
+                .await;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn publish_swaps(updates: &Updates, n: usize) {
+        for i in 0..n {
+            updates
+                .publish(Topic::Swap, i.to_string(), json!({ "swap": i }))
+                .await;
+        }
+    }
+
+    fn cursors(resume: Resume) -> Vec<u64> {
+        match resume {
+            Resume::Missed(missed) => missed
+                .into_iter()
+                .map(|update| update.cursor.sequence)
+                .collect(),
+            Resume::Reset { .. } => panic!("expected to resume, got {:?}", resume),
+        }
+    }
+
+    /// The cursor of the update with the given sequence number.
+    async fn cursor(updates: &Updates, sequence: u64) -> String {
+        let epoch = updates.0.lock().await.epoch;
+
+        Cursor { epoch, sequence }.to_string()
+    }
+
+    fn reset_to(resume: Resume) -> u64 {
+        match resume {
+            Resume::Reset { cursor } => cursor.sequence,
+            Resume::Missed(_) => panic!("expected to reset, got {:?}", resume),
+        }
+    }
+
+    #[tokio::test]
+    async fn reconnecting_subscriber_receives_missed_updates() {
+        let updates = Updates::default();
+        publish_swaps(&updates, 3).await;
+
+        let (resume, _) = updates.subscribe(Some(&cursor(&updates, 1).await)).await;
+        assert_eq!(cursors(resume), vec![2, 3]);
+
+        let (resume, _) = updates.subscribe(Some(&cursor(&updates, 3).await)).await;
+        assert_eq!(cursors(resume), Vec::<u64>::new());
+    }
+
+    #[tokio::test]
+    async fn new_subscriber_receives_live_updates_only() {
+        let updates = Updates::default();
+        publish_swaps(&updates, 2).await;
+
+        let (resume, mut receiver) = updates.subscribe(None).await;
+        assert_eq!(cursors(resume), Vec::<u64>::new());
+
+        publish_swaps(&updates, 1).await;
+        assert_eq!(receiver.recv().await.unwrap().cursor.sequence, 3);
+    }
+
+    #[tokio::test]
+    async fn subscriber_is_reset_if_missed_updates_are_gone() {
+        let updates = Updates::default();
+        publish_swaps(&updates, RETAINED_UPDATES + 2).await;
+        let latest = RETAINED_UPDATES as u64 + 2;
+
+        let (resume, _) = updates.subscribe(Some(&cursor(&updates, 1).await)).await;
+        assert_eq!(reset_to(resume), latest);
+
+        let (resume, _) = updates.subscribe(Some(&cursor(&updates, 2).await)).await;
+        assert_eq!(cursors(resume).len(), RETAINED_UPDATES);
+    }
+
+    #[tokio::test]
+    async fn subscriber_is_reset_if_cursor_is_from_another_process() {
+        let updates = Updates::default();
+        let before_restart = Updates::default();
+        publish_swaps(&updates, 3).await;
+        publish_swaps(&before_restart, 5).await;
+
+        let (resume, _) = updates
+            .subscribe(Some(&cursor(&before_restart, 2).await))
+            .await;
+        assert_eq!(reset_to(resume), 3);
+
+        let (resume, _) = updates.subscribe(Some("2")).await;
+        assert_eq!(reset_to(resume), 3);
+    }
+}
@@ -0,0 +1,466 @@
+//! Webhooks let backends learn about changes to our swaps and orders without
+//! polling cnd: Every update of a swap or order that we publish on the
+//! "/updates" stream results in a POST request to each configured endpoint.
+//! So does a swap that still offers actions once its next expiry is less than
+//! `expiry_warning_mins` away.
+//!
+//! The body of each request is a JSON object with the `event` name, the
+//! `data` the update stream would carry and the unix timestamp the event was
+//! `created_at`. The `X-Cnd-Signature` header carries the hex encoded
+//! HMAC-SHA256 of the body, keyed with the secret of the endpoint.
+//!
+//! Deliveries are queued in the database, hence they survive restarts. Failed
+//! deliveries are retried with an exponential backoff. We also remember what
+//! we queued about every swap and order, hence unchanged documents are not
+//! delivered again after a restart and every swap is warned about only once.
+
+use crate::{
+    config::{Webhook, Webhooks},
+    connectors::Connectors,
+    http_api::{
+        swaps::{self, LedgerSnapshot},
+        Topic, Updates,
+    },
+    storage::{LoadAll, Sqlite, Storage, SwapContext, WebhookDelivery, WebhookEvent},
+    LocalSwapId, Timestamp,
+};
+use anyhow::{Context, Result};
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use reqwest::{header::CONTENT_TYPE, Url};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{collections::HashMap, convert::TryFrom, time::Duration};
+use time::OffsetDateTime;
+use tokio::sync::broadcast;
+
+/// The header carrying the signature of the request body.
+const SIGNATURE_HEADER: &str = "X-Cnd-Signature";
+
+const EXPIRY_WARNING_EVENT: &str = "swap_expiry_near";
+
+/// How often we look for deliveries that are due.
+const DELIVERY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often we look for swaps that are about to expire.
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long an endpoint has to respond.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// We give up on a delivery after this many failed attempts.
+const MAX_ATTEMPTS: i32 = 20;
+
+const FIRST_RETRY_DELAY_SECS: i64 = 5;
+const MAX_RETRY_DELAY_SECS: i64 = 60 * 60;
+
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    event: &'a str,
+    data: &'a Value,
+    created_at: i64,
+}
+
+/// Queues and delivers the webhook calls for the given endpoints.
+pub async fn worker(
+    settings: Webhooks,
+    updates: Updates,
+    storage: Storage,
+    connectors: Connectors,
+    execute_lightning_actions: bool,
+) {
+    if settings.endpoints.is_empty() {
+        return;
+    }
+
+    let endpoints = settings.endpoints;
+    let expiry_warning = settings.expiry_warning_mins * 60;
+
+    tokio::join!(
+        queue_updates(&endpoints, updates, &storage.db),
+        queue_expiry_warnings(
+            &endpoints,
+            expiry_warning,
+            &storage,
+            &connectors,
+            execute_lightning_actions
+        ),
+        deliver(&endpoints, &storage.db),
+    );
+}
+
+async fn queue_updates(endpoints: &[Webhook], updates: Updates, db: &Sqlite) {
+    let mut receiver = updates.listen().await;
+
+    loop {
+        let missed = match receiver.recv().await {
+            Ok(update) => vec![update],
+            // Unchanged documents are not queued again, hence we catch up by
+            // going through the latest update of everything
+            Err(broadcast::RecvError::Lagged(n)) => {
+                tracing::debug!("missed {} updates for webhooks, catching up", n);
+                updates.latest().await
+            }
+            Err(broadcast::RecvError::Closed) => return,
+        };
+
+        for update in missed {
+            match update.topic() {
+                Topic::Swap | Topic::Order => {}
+                Topic::MarketOrder | Topic::MarketOrderRemoved => continue,
+            }
+
+            if let Err(e) = queue(
+                endpoints,
+                db,
+                update.topic().name(),
+                update.subject(),
+                update.document(),
+            )
+            .await
+            {
+                tracing::error!("failed to queue webhook calls: {:#}", e);
+            }
+        }
+    }
+}
+
+async fn queue_expiry_warnings(
+    endpoints: &[Webhook],
+    expiry_warning: u32,
+    storage: &Storage,
+    connectors: &Connectors,
+    execute_lightning_actions: bool,
+) {
+    loop {
+        if let Err(e) = queue_expiring_swaps(
+            endpoints,
+            expiry_warning,
+            storage,
+            connectors,
+            execute_lightning_actions,
+        )
+        .await
+        {
+            tracing::debug!("failed to check for expiring swaps: {:#}", e);
+        }
+
+        tokio::time::delay_for(EXPIRY_CHECK_INTERVAL).await;
+    }
+}
+
+async fn queue_expiring_swaps(
+    endpoints: &[Webhook],
+    expiry_warning: u32,
+    storage: &Storage,
+    connectors: &Connectors,
+    execute_lightning_actions: bool,
+) -> Result<()> {
+    let ledgers = LedgerSnapshot::fetch(connectors).await?;
+    let now = Timestamp::now();
+    let contexts: Vec<SwapContext> = storage.load_all().await?;
+
+    for SwapContext { id, .. } in contexts {
+        if warned(&storage.db, id).await? {
+            continue;
+        }
+
+        let expiry = match swaps::next_expiry(id, storage, now).await? {
+            Some(expiry) if expiry <= now.plus(expiry_warning) => expiry,
+            _ => continue,
+        };
+
+        // Without actions there is nothing to do before the expiry
+        let swap = swaps::swap_entity(id, storage, ledgers, execute_lightning_actions).await?;
+        let swap = serde_json::to_value(swap)?;
+        let has_actions = swap["actions"]
+            .as_array()
+            .map_or(false, |actions| !actions.is_empty());
+        if !has_actions {
+            continue;
+        }
+
+        let data = json!({ "id": id, "expiry": expiry, "swap": swap });
+        queue(
+            endpoints,
+            &storage.db,
+            EXPIRY_WARNING_EVENT,
+            &id.to_string(),
+            &data,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// We warn about every swap once.
+async fn warned(db: &Sqlite, id: LocalSwapId) -> Result<bool> {
+    let warning = db
+        .do_in_transaction(|conn| {
+            WebhookEvent::by_subject(conn, EXPIRY_WARNING_EVENT, &id.to_string())
+        })
+        .await?;
+
+    Ok(warning.is_some())
+}
+
+/// Queue the event for every endpoint, unless the same event was queued about
+/// the subject before.
+async fn queue(
+    endpoints: &[Webhook],
+    db: &Sqlite,
+    event: &str,
+    subject: &str,
+    data: &Value,
+) -> Result<()> {
+    let now = OffsetDateTime::now_utc();
+    let recorded = serde_json::to_string(data)?;
+    let payload = serde_json::to_string(&Payload {
+        event,
+        data,
+        created_at: now.timestamp(),
+    })?;
+
+    db.do_in_transaction(|conn| {
+        if !WebhookEvent::record(conn, event, subject, &recorded)? {
+            return Ok(());
+        }
+
+        for endpoint in endpoints {
+            WebhookDelivery::enqueue(conn, &endpoint.url, payload.clone(), now)?;
+        }
+
+        Ok(())
+    })
+    .await
+}
+
+async fn deliver(endpoints: &[Webhook], db: &Sqlite) {
+    let client = reqwest::Client::new();
+    let secrets = endpoints
+        .iter()
+        .map(|endpoint| (endpoint.url.clone(), endpoint.secret.clone()))
+        .collect::<HashMap<_, _>>();
+
+    loop {
+        if let Err(e) = deliver_due(&client, &secrets, db, OffsetDateTime::now_utc()).await {
+            tracing::error!("failed to deliver webhook calls: {:#}", e);
+        }
+
+        tokio::time::delay_for(DELIVERY_INTERVAL).await;
+    }
+}
+
+async fn deliver_due(
+    client: &reqwest::Client,
+    secrets: &HashMap<Url, String>,
+    db: &Sqlite,
+    now: OffsetDateTime,
+) -> Result<()> {
+    let due = db
+        .do_in_transaction(|conn| WebhookDelivery::due(conn, now))
+        .await?;
+
+    for delivery in due {
+        let secret = match secrets.get(&delivery.url) {
+            Some(secret) => secret,
+            None => {
+                tracing::info!("dropping call to webhook {} that was removed", delivery.url);
+                db.do_in_transaction(|conn| delivery.delete(conn)).await?;
+                continue;
+            }
+        };
+
+        match call(client, &delivery.url, secret, &delivery.payload).await {
+            Ok(()) => {
+                db.do_in_transaction(|conn| delivery.delete(conn)).await?;
+            }
+            Err(e) if delivery.attempts + 1 >= MAX_ATTEMPTS => {
+                tracing::warn!(
+                    "giving up on calling webhook {} after {} attempts: {:#}",
+                    delivery.url,
+                    MAX_ATTEMPTS,
+                    e
+                );
+                db.do_in_transaction(|conn| delivery.delete(conn)).await?;
+            }
+            Err(e) => {
+                tracing::debug!("failed to call webhook {}: {:#}", delivery.url, e);
+                let next_attempt_at = now + retry_delay(delivery.attempts);
+                db.do_in_transaction(|conn| delivery.reschedule(conn, next_attempt_at))
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn call(client: &reqwest::Client, url: &Url, secret: &str, payload: &str) -> Result<()> {
+    let response = client
+        .post(url.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(secret, payload))
+        .body(payload.to_owned())
+        .timeout(REQUEST_TIMEOUT)
+        .send()
+        .await
+        .with_context(|| format!("failed to reach {}", url))?;
+
+    let status = response.status();
+    if !status.is_success() {
+        anyhow::bail!("{} responded with {}", url, status)
+    }
+
+    Ok(())
+}
+
+/// The hex encoded HMAC-SHA256 of the payload, keyed with the secret.
+fn sign(secret: &str, payload: &str) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(payload.as_bytes());
+
+    hex::encode(hmac::Hmac::<sha256::Hash>::from_engine(engine).into_inner())
+}
+
+fn retry_delay(attempts: i32) -> time::Duration {
+    let delay = u32::try_from(attempts)
+        .ok()
+        .and_then(|attempts| 2i64.checked_pow(attempts))
+        .and_then(|factor| FIRST_RETRY_DELAY_SECS.checked_mul(factor))
+        .map_or(MAX_RETRY_DELAY_SECS, |delay| {
+            delay.min(MAX_RETRY_DELAY_SECS)
+        });
+
+    time::Duration::seconds(delay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{channel::mpsc, StreamExt};
+    use std::net::SocketAddr;
+    use warp::{http::StatusCode, hyper::body::Bytes, Filter};
+
+    const SECRET: &str = "correct horse battery staple";
+
+    /// Serves webhook calls with the given status, forwarding signature and
+    /// body of every call.
+    fn stand_in(status: StatusCode) -> (Url, mpsc::UnboundedReceiver<(String, String)>) {
+        let (sender, receiver) = mpsc::unbounded();
+        let route = warp::post()
+            .and(warp::header::<String>(SIGNATURE_HEADER))
+            .and(warp::body::bytes())
+            .map(move |signature: String, body: Bytes| {
+                let body = String::from_utf8(body.to_vec()).unwrap();
+                sender.unbounded_send((signature, body)).unwrap();
+
+                warp::reply::with_status(warp::reply(), status)
+            });
+        let (address, server): (SocketAddr, _) =
+            warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let url = format!("http://{}/cnd", address).parse().unwrap();
+
+        (url, receiver)
+    }
+
+    fn endpoint(url: &Url) -> Webhook {
+        Webhook {
+            url: url.clone(),
+            secret: SECRET.to_owned(),
+        }
+    }
+
+    fn secrets(url: &Url) -> HashMap<Url, String> {
+        vec![(url.clone(), SECRET.to_owned())].into_iter().collect()
+    }
+
+    #[tokio::test]
+    async fn delivered_calls_are_signed_and_removed_from_the_queue() {
+        let db = Sqlite::test();
+        let (url, mut calls) = stand_in(StatusCode::OK);
+        let data = json!({ "id": "foo" });
+
+        queue(&[endpoint(&url)], &db, "order", "foo", &data)
+            .await
+            .unwrap();
+        let now = OffsetDateTime::now_utc();
+        deliver_due(&reqwest::Client::new(), &secrets(&url), &db, now)
+            .await
+            .unwrap();
+
+        let (signature, body) = calls.next().await.unwrap();
+        assert_eq!(signature, sign(SECRET, &body));
+        let body = serde_json::from_str::<Value>(&body).unwrap();
+        assert_eq!(body["event"], "order");
+        assert_eq!(body["data"], data);
+
+        let remaining = db
+            .do_in_transaction(|conn| WebhookDelivery::due(conn, now))
+            .await
+            .unwrap();
+        assert!(remaining.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_calls_are_retried_later() {
+        let db = Sqlite::test();
+        let (url, mut calls) = stand_in(StatusCode::INTERNAL_SERVER_ERROR);
+
+        queue(&[endpoint(&url)], &db, "swap", "foo", &json!({}))
+            .await
+            .unwrap();
+        let now = OffsetDateTime::now_utc();
+        deliver_due(&reqwest::Client::new(), &secrets(&url), &db, now)
+            .await
+            .unwrap();
+        assert!(calls.next().await.is_some());
+
+        let due_now = db
+            .do_in_transaction(|conn| WebhookDelivery::due(conn, now))
+            .await
+            .unwrap();
+        let due_later = db
+            .do_in_transaction(|conn| WebhookDelivery::due(conn, now + retry_delay(0)))
+            .await
+            .unwrap();
+        assert!(due_now.is_empty());
+        assert_eq!(due_later.len(), 1);
+        assert_eq!(due_later[0].attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn unchanged_documents_are_queued_once() {
+        let db = Sqlite::test();
+        let url = "http://localhost:9000/cnd".parse::<Url>().unwrap();
+        let endpoints = [endpoint(&url)];
+
+        queue(&endpoints, &db, "swap", "foo", &json!({ "a": 1 }))
+            .await
+            .unwrap();
+        queue(&endpoints, &db, "swap", "foo", &json!({ "a": 1 }))
+            .await
+            .unwrap();
+        queue(&endpoints, &db, "swap", "foo", &json!({ "a": 2 }))
+            .await
+            .unwrap();
+
+        let queued = db
+            .do_in_transaction(|conn| WebhookDelivery::due(conn, OffsetDateTime::now_utc()))
+            .await
+            .unwrap();
+        assert_eq!(queued.len(), 2);
+    }
+
+    #[test]
+    fn retry_delay_grows_until_capped() {
+        assert_eq!(retry_delay(0), time::Duration::seconds(5));
+        assert_eq!(retry_delay(1), time::Duration::seconds(10));
+        assert_eq!(retry_delay(MAX_ATTEMPTS), time::Duration::hours(1));
+        assert_eq!(retry_delay(62), time::Duration::hours(1));
+        assert_eq!(retry_delay(i32::MAX), time::Duration::hours(1));
+        assert_eq!(retry_delay(-1), time::Duration::hours(1));
+    }
+}
@@ -0,0 +1,355 @@
+//! This module deals with executing lightning actions on behalf of the user.
+//!
+//! If enabled in the configuration, cnd executes the lightning actions of a
+//! halbit swap itself against LND instead of returning them through the HTTP
+//! API. The outcome of each execution is persisted so that it can be reported
+//! to the user as part of the swap's events. Failed executions are retried a
+//! few times, after that the action is handed out to the user again.
+
+use crate::{
+    actions::{bitcoin, ethereum, lnd, FundAction, InitAction, RedeemAction},
+    halbit,
+    metrics::{Metrics, Node},
+    state::Get,
+    storage::{LndExecution, Load, Storage},
+    LocalSwapId, Never,
+};
+use comit::lnd::{LndActionExecutor, PaymentStatus};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use time::OffsetDateTime;
+
+/// How often we check whether there is a new lightning action to execute.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often we try to execute a lightning action before leaving it to the
+/// user.
+const MAX_ATTEMPTS: usize = 5;
+
+/// How long we wait before retrying a failed action, doubled with every
+/// further failure.
+const RETRY_BACKOFF: Duration = Duration::from_secs(10);
+
+/// The kinds of lightning actions cnd can execute.
+///
+/// These are named after the corresponding action types of the HTTP API.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    strum_macros::Display,
+    strum_macros::EnumString,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ActionKind {
+    #[strum(serialize = "lnd-add-hold-invoice")]
+    LndAddHoldInvoice,
+    #[strum(serialize = "lnd-send-payment")]
+    LndSendPayment,
+    #[strum(serialize = "lnd-settle-invoice")]
+    LndSettleInvoice,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Action {
+    AddHoldInvoice(lnd::AddHoldInvoice),
+    SendPayment(lnd::SendPayment),
+    SettleInvoice(lnd::SettleInvoice),
+}
+
+impl Action {
+    pub fn kind(&self) -> ActionKind {
+        match self {
+            Action::AddHoldInvoice(_) => ActionKind::LndAddHoldInvoice,
+            Action::SendPayment(_) => ActionKind::LndSendPayment,
+            Action::SettleInvoice(_) => ActionKind::LndSettleInvoice,
+        }
+    }
+}
+
+/// How far we got with executing one kind of lightning action of a swap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Progress {
+    /// Not executed yet or due for another attempt at the given time.
+    Pending {
+        retry_at: Option<OffsetDateTime>,
+    },
+    Succeeded,
+    /// All attempts failed, the action is left to the user.
+    GaveUp,
+}
+
+impl Progress {
+    pub fn of(executions: &[LndExecution], kind: ActionKind) -> Self {
+        let attempts = executions
+            .iter()
+            .filter(|execution| execution.action == kind)
+            .collect::<Vec<_>>();
+
+        if attempts.iter().any(|execution| execution.succeeded) {
+            return Progress::Succeeded;
+        }
+
+        match attempts.last() {
+            None => Progress::Pending { retry_at: None },
+            Some(_) if attempts.len() >= MAX_ATTEMPTS => Progress::GaveUp,
+            Some(last) => {
+                let backoff = (1..attempts.len()).fold(RETRY_BACKOFF, |backoff, _| backoff * 2);
+
+                Progress::Pending {
+                    retry_at: Some(OffsetDateTime::from_unix_timestamp(last.executed_at) + backoff),
+                }
+            }
+        }
+    }
+}
+
+/// Extracts the lightning action, if any, out of the output of one of the
+/// action traits.
+pub trait IntoLndAction {
+    fn into_lnd_action(self) -> Option<Action>;
+}
+
+impl IntoLndAction for lnd::AddHoldInvoice {
+    fn into_lnd_action(self) -> Option<Action> {
+        Some(Action::AddHoldInvoice(self))
+    }
+}
+
+impl IntoLndAction for lnd::SendPayment {
+    fn into_lnd_action(self) -> Option<Action> {
+        Some(Action::SendPayment(self))
+    }
+}
+
+impl IntoLndAction for lnd::SettleInvoice {
+    fn into_lnd_action(self) -> Option<Action> {
+        Some(Action::SettleInvoice(self))
+    }
+}
+
+impl IntoLndAction for bitcoin::SendToAddress {
+    fn into_lnd_action(self) -> Option<Action> {
+        None
+    }
+}
+
+impl IntoLndAction for bitcoin::SpendHtlc {
+    fn into_lnd_action(self) -> Option<Action> {
+        None
+    }
+}
+
+impl IntoLndAction for ethereum::DeployContract {
+    fn into_lnd_action(self) -> Option<Action> {
+        None
+    }
+}
+
+impl IntoLndAction for ethereum::CallContract {
+    fn into_lnd_action(self) -> Option<Action> {
+        None
+    }
+}
+
+impl IntoLndAction for Never {
+    fn into_lnd_action(self) -> Option<Action> {
+        None
+    }
+}
+
+/// Executes the lightning actions of the given swap as soon as they become
+/// available.
+///
+/// Every action is executed until it succeeds once, also across restarts
+/// because the executions are persisted. Failed executions are retried with
+/// an increasing backoff until we give up, see [`Progress`]. The task
+/// finishes as soon as the invoice of the swap is either settled or cancelled.
+pub async fn execute(
+    id: LocalSwapId,
+    storage: Storage,
+    executor: LndActionExecutor,
+    metrics: Metrics,
+) {
+    // Payments we sent but LND did not respond to yet, they are only recorded
+    // once it does.
+    let in_flight = Arc::new(Mutex::new(HashSet::new()));
+    let mut given_up = HashSet::new();
+
+    loop {
+        tokio::time::delay_for(POLL_INTERVAL).await;
+
+        match storage.halbit_states.get(&id).await {
+            Ok(Some(halbit::State::Settled(_))) | Ok(Some(halbit::State::Cancelled(_))) => break,
+            _ => {}
+        }
+
+        let executions = match storage
+            .db
+            .do_in_transaction(|conn| LndExecution::by_swap_id(conn, id))
+            .await
+        {
+            Ok(executions) => executions,
+            Err(e) => {
+                tracing::debug!("failed to load lightning executions of {}: {:?}", id, e);
+                continue;
+            }
+        };
+
+        let actions = match available_actions(id, &storage).await {
+            Ok(actions) => actions,
+            Err(e) => {
+                tracing::debug!("failed to determine lightning actions of {}: {:?}", id, e);
+                continue;
+            }
+        };
+
+        let now = OffsetDateTime::now_utc();
+
+        for action in actions {
+            let kind = action.kind();
+
+            match Progress::of(&executions, kind) {
+                Progress::Succeeded => continue,
+                Progress::GaveUp => {
+                    if given_up.insert(kind) {
+                        tracing::warn!("gave up executing {} for swap {}", kind, id);
+                    }
+                    continue;
+                }
+                Progress::Pending {
+                    retry_at: Some(retry_at),
+                } if retry_at > now => continue,
+                Progress::Pending { .. } => {}
+            }
+            if in_flight
+                .lock()
+                .expect("no other thread panicked while holding the lock")
+                .contains(&kind)
+            {
+                continue;
+            }
+
+            match action {
+                Action::AddHoldInvoice(action) => {
+                    let result = metrics
+                        .time(
+                            Node::Lnd,
+                            "add_hold_invoice",
+                            executor.add_hold_invoice(action),
+                        )
+                        .await;
+                    record(&storage, id, kind, result).await;
+                }
+                Action::SettleInvoice(action) => {
+                    let result = metrics
+                        .time(Node::Lnd, "settle_invoice", executor.settle_invoice(action))
+                        .await;
+                    record(&storage, id, kind, result).await;
+                }
+                Action::SendPayment(action) => {
+                    // LND only responds once the payment is settled or failed,
+                    // hence we must not block on it.
+                    in_flight
+                        .lock()
+                        .expect("no other thread panicked while holding the lock")
+                        .insert(kind);
+
+                    let executor = executor.clone();
+                    let storage = storage.clone();
+                    let metrics = metrics.clone();
+                    let in_flight = in_flight.clone();
+
+                    tokio::spawn(async move {
+                        let result = send_payment(&executor, &metrics, action).await;
+                        record(&storage, id, kind, result).await;
+
+                        in_flight
+                            .lock()
+                            .expect("no other thread panicked while holding the lock")
+                            .remove(&kind);
+                    });
+                }
+            }
+        }
+    }
+
+    tracing::info!("finished executing lightning actions of {}", id);
+}
+
+/// Sends the payment unless LND knows about it already, e.g. because we sent
+/// it before a restart. LND refuses to pay the same hash again unless the
+/// earlier payment failed.
+async fn send_payment(
+    executor: &LndActionExecutor,
+    metrics: &Metrics,
+    action: lnd::SendPayment,
+) -> anyhow::Result<()> {
+    loop {
+        match executor.payment_status(action.secret_hash).await? {
+            Some(PaymentStatus::Succeeded) => return Ok(()),
+            Some(PaymentStatus::InFlight) => tokio::time::delay_for(POLL_INTERVAL).await,
+            Some(PaymentStatus::Failed) | Some(PaymentStatus::Unknown) | None => {
+                return metrics
+                    .time(Node::Lnd, "send_payment", executor.send_payment(action))
+                    .await
+            }
+        }
+    }
+}
+
+async fn available_actions(id: LocalSwapId, storage: &Storage) -> anyhow::Result<Vec<Action>> {
+    let swap_context = storage.load(id).await?;
+    let actions = within_swap_context!(swap_context, {
+        let swap: ActorSwap = storage.load(id).await?;
+
+        vec![
+            swap.init_action()
+                .ok()
+                .and_then(IntoLndAction::into_lnd_action),
+            swap.fund_action()
+                .ok()
+                .and_then(IntoLndAction::into_lnd_action),
+            swap.redeem_action()
+                .ok()
+                .and_then(IntoLndAction::into_lnd_action),
+        ]
+    });
+
+    Ok(actions.into_iter().flatten().collect())
+}
+
+async fn record(storage: &Storage, id: LocalSwapId, kind: ActionKind, result: anyhow::Result<()>) {
+    let succeeded = match result {
+        Ok(()) => {
+            tracing::info!("executed {} for swap {}", kind, id);
+            true
+        }
+        Err(e) => {
+            tracing::warn!("failed to execute {} for swap {}: {:?}", kind, id, e);
+            false
+        }
+    };
+
+    let executed_at = OffsetDateTime::now_utc();
+    if let Err(e) = storage
+        .db
+        .do_in_transaction(|conn| LndExecution::record(conn, id, kind, succeeded, executed_at))
+        .await
+    {
+        tracing::warn!(
+            "failed to record execution of {} for swap {}: {:#}",
+            kind,
+            id,
+            e
+        );
+    }
+}
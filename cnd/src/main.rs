@@ -13,6 +13,7 @@
 )]
 #![forbid(unsafe_code)]
 #![type_length_limit = "1049479"] // Regressed with Rust 1.46.0 :(
+#![recursion_limit = "256"] // The `json!` describing the paths of the OpenAPI document is big
 
 #[macro_use]
 extern crate diesel;
@@ -35,9 +36,12 @@ mod file_lock;
 mod fs;
 mod halbit;
 mod hbit;
+mod health;
 mod herc20;
 mod http_api;
+mod lnd_actions;
 mod local_swap_id;
+mod metrics;
 mod protocol_spawner;
 mod respawn;
 mod spawn;
@@ -45,6 +49,8 @@ mod state;
 mod storage;
 mod trace;
 mod tracing_ext;
+mod wallet;
+mod wallet_actions;
 mod htlc_location {
     pub use comit::htlc_location::*;
 }
@@ -72,11 +78,14 @@ mod btsieve {
 
 use self::{
     actions::*,
+    bitcoin::{BitcoindFeeRateEstimator, FeeRateEstimator, StaticFeeRate},
     btsieve::{bitcoin::BitcoindConnector, ethereum::Web3Connector},
     config::{validate_connection_to_network, Settings},
     connectors::Connectors,
     file_lock::TryLockExclusive,
+    health::Health,
     local_swap_id::LocalSwapId,
+    metrics::{Instrumented, Metrics, Node},
     network::{Swarm, SwarmWorker},
     protocol_spawner::{ProtocolSpawner, *},
     respawn::respawn,
@@ -85,8 +94,9 @@ use self::{
 };
 use ::bitcoin::secp256k1::{All, Secp256k1};
 use comit::{
-    ledger, lnd::LndConnectorParams, LockProtocol, Never, RelativeTime, Role, Secret, SecretHash,
-    Side, Timestamp,
+    ledger,
+    lnd::{LndActionExecutor, LndConnectorParams},
+    LockProtocol, Never, RelativeTime, Role, Secret, SecretHash, Side, Timestamp,
 };
 use conquer_once::Lazy;
 use rand::rngs::OsRng;
@@ -128,15 +138,22 @@ fn main() -> anyhow::Result<()> {
 
     let seed = RootSeed::from_dir_or_generate(&settings.data.dir, OsRng)?;
 
+    let api_tokens = http_api::Tokens::from_dir_or_generate(&settings.data.dir, OsRng)?;
+
     let _locked_datadir = &settings.data.dir.try_lock_exclusive()?;
 
+    let metrics = Metrics::default();
+    let health = Health::new(&settings)?;
+
     let mut runtime = runtime::Builder::new()
         .enable_all()
         .threaded_scheduler()
         .build()?;
 
     let bitcoin_connector = {
-        let config::Bitcoin { bitcoind, network } = &settings.bitcoin;
+        let config::Bitcoin {
+            bitcoind, network, ..
+        } = &settings.bitcoin;
         let connector = BitcoindConnector::new(bitcoind.node_url.clone())?;
 
         runtime.block_on(async {
@@ -152,7 +169,10 @@ fn main() -> anyhow::Result<()> {
 
         const BITCOIN_BLOCK_CACHE_CAPACITY: usize = 144;
 
-        btsieve::bitcoin::Cache::new(connector, BITCOIN_BLOCK_CACHE_CAPACITY)
+        btsieve::bitcoin::Cache::new(
+            Instrumented::new(connector, Node::Bitcoind, metrics.clone()),
+            BITCOIN_BLOCK_CACHE_CAPACITY,
+        )
     };
 
     let ethereum_connector = {
@@ -174,7 +194,7 @@ fn main() -> anyhow::Result<()> {
         const ETHEREUM_RECEIPT_CACHE_CAPACITY: usize = 720;
 
         btsieve::ethereum::Cache::new(
-            connector,
+            Instrumented::new(connector, Node::Geth, metrics.clone()),
             ETHEREUM_BLOCK_CACHE_CAPACITY,
             ETHEREUM_RECEIPT_CACHE_CAPACITY,
         )
@@ -182,7 +202,7 @@ fn main() -> anyhow::Result<()> {
 
     let lnd_connector_params = LndConnectorParams::new(
         settings.lightning.lnd.rest_api_url.clone(),
-        100,
+        settings.lightning.lnd.retry_interval_ms,
         settings.lightning.lnd.cert_path.clone(),
         settings.lightning.lnd.readonly_macaroon_path.clone(),
     )
@@ -194,12 +214,53 @@ fn main() -> anyhow::Result<()> {
     })
     .ok();
 
-    let connectors = Connectors::new(bitcoin_connector, ethereum_connector);
+    let lnd_action_executor = if settings.lightning.lnd.execute_actions {
+        LndConnectorParams::new(
+            settings.lightning.lnd.rest_api_url.clone(),
+            settings.lightning.lnd.retry_interval_ms,
+            settings.lightning.lnd.cert_path.clone(),
+            settings.lightning.lnd.admin_macaroon_path.clone(),
+        )
+        .map(LndActionExecutor::from)
+        .map_err(|err| {
+            tracing::warn!(
+                "Could not initialise lnd action executor, lightning actions will not be executed: {:?}",
+                err
+            );
+        })
+        .ok()
+    } else {
+        None
+    };
+
+    let bitcoin_fee_rate = match settings.bitcoin.fees {
+        config::BitcoinFees::Static { sat_per_vbyte } => {
+            FeeRateEstimator::Static(StaticFeeRate(sat_per_vbyte))
+        }
+        config::BitcoinFees::Bitcoind { target_blocks } => {
+            FeeRateEstimator::Bitcoind(BitcoindFeeRateEstimator::new(
+                settings.bitcoin.bitcoind.node_url.clone(),
+                target_blocks,
+            ))
+        }
+    };
+
+    let connectors = Connectors::new(bitcoin_connector, ethereum_connector, bitcoin_fee_rate);
     let storage = Storage::new(database, seed);
 
+    let wallets = if settings.wallet.managed {
+        Some(runtime.block_on(wallet::Wallets::new(&seed, &settings, &storage.db))?)
+    } else {
+        None
+    };
+
+    let execute_lightning_actions = lnd_action_executor.is_some();
     let protocol_spawner = ProtocolSpawner::new(
         connectors.clone(),
         lnd_connector_params,
+        lnd_action_executor,
+        wallets,
+        metrics.clone(),
         runtime.handle().clone(),
         storage.clone(),
     );
@@ -225,6 +286,10 @@ fn main() -> anyhow::Result<()> {
         storage,
         connectors,
         http_api_listener,
+        api_tokens,
+        metrics,
+        health,
+        execute_lightning_actions,
     ));
     runtime.spawn(make_network_api_worker(swarm));
 
@@ -246,34 +311,80 @@ fn version() {
 
 /// Binds to the socket for the HTTP API specified in the settings
 ///
-/// Fails if we cannot bind to the socket.
+/// Fails if we cannot bind to the socket or load the configured TLS identity.
 /// We do this ourselves so we can shut down if this fails and don't just panic
 /// some worker thread in tokio.
-async fn bind_http_api_socket(settings: &Settings) -> anyhow::Result<tokio::net::TcpListener> {
+async fn bind_http_api_socket(settings: &Settings) -> anyhow::Result<http_api::Listener> {
     let listen_addr = settings.http_api.socket;
     let listener = TcpListener::bind(listen_addr).await?;
 
-    Ok(listener)
+    http_api::Listener::new(listener, settings.http_api.tls.as_ref())
 }
 
 /// Construct the worker that is going to process HTTP API requests.
+#[allow(clippy::too_many_arguments)]
 async fn make_http_api_worker(
     settings: Settings,
     network: comit::Network,
     swarm: Swarm,
     storage: Storage,
     connectors: Connectors,
-    incoming_requests: tokio::net::TcpListener,
+    incoming_requests: http_api::Listener,
+    api_tokens: http_api::Tokens,
+    metrics: Metrics,
+    health: Health,
+    execute_lightning_actions: bool,
 ) {
-    let routes = http_api::create_routes(swarm, storage, connectors, &settings, network);
+    tokio::spawn(metrics::track_swaps(metrics.clone(), storage.clone()));
 
-    match incoming_requests.local_addr() {
-        Ok(socket) => {
-            tracing::info!("Starting HTTP server on {} ...", socket);
-            warp::serve(routes).serve_incoming(incoming_requests).await;
-        }
+    let updates = http_api::Updates::default();
+    tokio::spawn(http_api::publish_updates(
+        updates.clone(),
+        swarm.clone(),
+        storage.clone(),
+        connectors.clone(),
+        network,
+        execute_lightning_actions,
+    ));
+    tokio::spawn(http_api::call_webhooks(
+        settings.webhooks.clone(),
+        updates.clone(),
+        storage.clone(),
+        connectors.clone(),
+        execute_lightning_actions,
+    ));
+
+    let routes = http_api::create_routes(
+        swarm,
+        storage,
+        connectors,
+        &settings,
+        network,
+        updates,
+        api_tokens,
+        metrics,
+        health,
+        execute_lightning_actions,
+    );
+
+    let socket = match incoming_requests.local_addr() {
+        Ok(socket) => socket,
         Err(e) => {
             tracing::error!("Cannot start HTTP server because {:?}", e);
+            return;
+        }
+    };
+
+    match incoming_requests {
+        http_api::Listener::Plain(listener) => {
+            tracing::info!("Starting HTTP server on {} ...", socket);
+            warp::serve(routes).serve_incoming(listener).await;
+        }
+        http_api::Listener::Tls(listener, acceptor) => {
+            tracing::info!("Starting HTTPS server on {} ...", socket);
+            warp::serve(routes)
+                .serve_incoming(http_api::tls_incoming(listener, acceptor))
+                .await;
         }
     }
 }
@@ -0,0 +1,676 @@
+//! Metrics about cnd in the Prometheus text exposition format.
+//!
+//! Request metrics are recorded as requests happen: [`Instrumented`] wraps the
+//! blockchain connectors, LND actions are timed where they are executed and
+//! the HTTP API records every request it answers. The states of the HTLCs of
+//! our swaps are sampled by [`track_swaps`] because the protocol tasks don't
+//! report their transitions anywhere else. Everything else, e.g. connected
+//! peers, is read when the metrics are rendered.
+
+use crate::{
+    btsieve::{ethereum::ReceiptByHash, BlockByHash, CacheStats, LatestBlock},
+    ethereum::{Hash, TransactionReceipt},
+    network::Swarm,
+    state::Get,
+    storage::{LoadAll, Storage, SwapContext},
+    LocalSwapId, LockProtocol,
+};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{self, Write},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// How often we sample the states of the HTLCs.
+const TRACK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Buckets in seconds for requests to nodes and to our HTTP API.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Buckets in seconds for the time HTLCs spend in a state, from a minute to two
+/// days.
+const STATE_DURATION_BUCKETS: &[f64] = &[
+    60.0, 300.0, 900.0, 1800.0, 3600.0, 7200.0, 14400.0, 43200.0, 86400.0, 172_800.0,
+];
+
+/// The nodes cnd sends requests to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Node {
+    Bitcoind,
+    Geth,
+    Lnd,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Metrics(Arc<Mutex<Registry>>);
+
+#[derive(Debug, Default)]
+struct Registry {
+    node_requests: BTreeMap<(Node, &'static str), NodeRequests>,
+    last_success: BTreeMap<Node, SystemTime>,
+    http_requests: BTreeMap<(String, u16), Histogram>,
+    htlcs: HashMap<(LocalSwapId, LockProtocol), Htlc>,
+    state_durations: BTreeMap<(String, String), Histogram>,
+}
+
+#[derive(Debug)]
+struct NodeRequests {
+    errors: u64,
+    duration: Histogram,
+}
+
+#[derive(Debug)]
+struct Htlc {
+    state: String,
+    since: Instant,
+}
+
+impl Metrics {
+    /// Records a request to one of the nodes that took the given time.
+    pub fn observe_request(
+        &self,
+        node: Node,
+        method: &'static str,
+        duration: Duration,
+        succeeded: bool,
+    ) {
+        let mut registry = self.lock();
+
+        let requests = registry
+            .node_requests
+            .entry((node, method))
+            .or_insert_with(|| NodeRequests {
+                errors: 0,
+                duration: Histogram::new(LATENCY_BUCKETS),
+            });
+        requests.duration.observe(duration);
+
+        if succeeded {
+            registry.last_success.insert(node, SystemTime::now());
+        } else {
+            requests.errors += 1;
+        }
+    }
+
+    /// Awaits the request and records how long it took and whether it failed.
+    pub async fn time<F, T>(&self, node: Node, method: &'static str, request: F) -> F::Output
+    where
+        F: Future<Output = anyhow::Result<T>>,
+    {
+        let started = Instant::now();
+        let result = request.await;
+        self.observe_request(node, method, started.elapsed(), result.is_ok());
+
+        result
+    }
+
+    /// Records a request answered by our HTTP API.
+    pub fn observe_http_request(&self, method: &str, status: u16, duration: Duration) {
+        self.lock()
+            .http_requests
+            .entry((method.to_owned(), status))
+            .or_insert_with(|| Histogram::new(LATENCY_BUCKETS))
+            .observe(duration);
+    }
+
+    /// Records the state the HTLC of a swap is in right now.
+    ///
+    /// If the HTLC left its previous state, the time it spent in there is
+    /// recorded.
+    fn observe_htlc_state(
+        &self,
+        id: LocalSwapId,
+        protocol: LockProtocol,
+        state: String,
+        now: Instant,
+    ) {
+        let mut registry = self.lock();
+
+        let previous = match registry.htlcs.get_mut(&(id, protocol)) {
+            Some(htlc) if htlc.state == state => return,
+            Some(htlc) => {
+                let previous = (std::mem::replace(&mut htlc.state, state), htlc.since);
+                htlc.since = now;
+                previous
+            }
+            None => {
+                registry
+                    .htlcs
+                    .insert((id, protocol), Htlc { state, since: now });
+                return;
+            }
+        };
+
+        let (previous_state, since) = previous;
+        registry
+            .state_durations
+            .entry((protocol.to_string(), previous_state))
+            .or_insert_with(|| Histogram::new(STATE_DURATION_BUCKETS))
+            .observe(now.saturating_duration_since(since));
+    }
+
+    /// Forgets about the HTLCs of swaps that no longer exist.
+    fn retain_htlcs(&self, ids: &HashSet<LocalSwapId>) {
+        self.lock().htlcs.retain(|(id, _), _| ids.contains(id));
+    }
+
+    /// Renders all metrics in the Prometheus text exposition format.
+    pub async fn render(
+        &self,
+        swarm: &Swarm,
+        cache_stats: &[(&str, &str, Arc<CacheStats>)],
+    ) -> String {
+        let connected_peers = swarm.connected_peers().await.count();
+        let local_peer_id = swarm.local_peer_id();
+        let mut orders = BTreeMap::<(&str, String), u64>::new();
+        for (maker, order) in swarm.btc_dai_market().await {
+            let owner = if maker == local_peer_id {
+                "ours"
+            } else {
+                "theirs"
+            };
+            *orders
+                .entry((owner, order.position.to_string()))
+                .or_default() += 1;
+        }
+
+        let mut out = String::new();
+        self.lock()
+            .render(&mut out, Instant::now())
+            .expect("writing to a string does not fail");
+        render_cache_stats(&mut out, cache_stats).expect("writing to a string does not fail");
+        render_network(&mut out, connected_peers, &orders)
+            .expect("writing to a string does not fail");
+
+        out
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.0
+            .lock()
+            .expect("no other thread panicked while holding the lock")
+    }
+}
+
+impl Registry {
+    fn render(&self, out: &mut String, now: Instant) -> fmt::Result {
+        let mut htlcs = BTreeMap::<(String, &str), (u64, Duration)>::new();
+        for ((_, protocol), htlc) in &self.htlcs {
+            let entry = htlcs
+                .entry((protocol.to_string(), htlc.state.as_str()))
+                .or_default();
+            entry.0 += 1;
+            entry.1 = entry.1.max(now.saturating_duration_since(htlc.since));
+        }
+
+        header(
+            out,
+            "cnd_swap_htlcs",
+            "gauge",
+            "Number of HTLCs of our swaps in each state.",
+        )?;
+        for ((protocol, state), (count, _)) in &htlcs {
+            sample(
+                out,
+                "cnd_swap_htlcs",
+                &[("protocol", protocol), ("state", state)],
+                count,
+            )?;
+        }
+
+        header(
+            out,
+            "cnd_swap_htlc_oldest_state_age_seconds",
+            "gauge",
+            "How long the HTLC that entered its state first has been in it.",
+        )?;
+        for ((protocol, state), (_, oldest)) in &htlcs {
+            sample(
+                out,
+                "cnd_swap_htlc_oldest_state_age_seconds",
+                &[("protocol", protocol), ("state", state)],
+                oldest.as_secs(),
+            )?;
+        }
+
+        header(
+            out,
+            "cnd_swap_htlc_state_duration_seconds",
+            "histogram",
+            "Time HTLCs spent in a state before leaving it.",
+        )?;
+        for ((protocol, state), histogram) in &self.state_durations {
+            histogram.render(
+                out,
+                "cnd_swap_htlc_state_duration_seconds",
+                &[("protocol", protocol), ("state", state)],
+            )?;
+        }
+
+        header(
+            out,
+            "cnd_connector_request_duration_seconds",
+            "histogram",
+            "Duration of requests to bitcoind, geth and LND.",
+        )?;
+        for ((node, method), requests) in &self.node_requests {
+            requests.duration.render(
+                out,
+                "cnd_connector_request_duration_seconds",
+                &[("node", &node.to_string()), ("method", method)],
+            )?;
+        }
+
+        header(
+            out,
+            "cnd_connector_request_errors_total",
+            "counter",
+            "Number of failed requests to bitcoind, geth and LND.",
+        )?;
+        for ((node, method), requests) in &self.node_requests {
+            sample(
+                out,
+                "cnd_connector_request_errors_total",
+                &[("node", &node.to_string()), ("method", method)],
+                requests.errors,
+            )?;
+        }
+
+        header(
+            out,
+            "cnd_connector_last_success_timestamp_seconds",
+            "gauge",
+            "Unix time of the last successful request to the node.",
+        )?;
+        for (node, at) in &self.last_success {
+            let seconds = at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            sample(
+                out,
+                "cnd_connector_last_success_timestamp_seconds",
+                &[("node", &node.to_string())],
+                seconds,
+            )?;
+        }
+
+        header(
+            out,
+            "cnd_http_request_duration_seconds",
+            "histogram",
+            "Duration of requests to the HTTP API.",
+        )?;
+        for ((method, status), histogram) in &self.http_requests {
+            histogram.render(
+                out,
+                "cnd_http_request_duration_seconds",
+                &[("method", method), ("status", &status.to_string())],
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn render_cache_stats(
+    out: &mut String,
+    cache_stats: &[(&str, &str, Arc<CacheStats>)],
+) -> fmt::Result {
+    header(
+        out,
+        "cnd_btsieve_cache_hits_total",
+        "counter",
+        "Number of lookups answered by the cache in front of a connector.",
+    )?;
+    for (ledger, cache, stats) in cache_stats {
+        let labels = [("ledger", *ledger), ("cache", *cache)];
+        sample(out, "cnd_btsieve_cache_hits_total", &labels, stats.hits())?;
+    }
+
+    header(
+        out,
+        "cnd_btsieve_cache_misses_total",
+        "counter",
+        "Number of lookups the cache in front of a connector forwarded to the node.",
+    )?;
+    for (ledger, cache, stats) in cache_stats {
+        let labels = [("ledger", *ledger), ("cache", *cache)];
+        sample(
+            out,
+            "cnd_btsieve_cache_misses_total",
+            &labels,
+            stats.misses(),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn render_network(
+    out: &mut String,
+    connected_peers: usize,
+    orders: &BTreeMap<(&str, String), u64>,
+) -> fmt::Result {
+    header(
+        out,
+        "cnd_connected_peers",
+        "gauge",
+        "Number of peers we are connected to.",
+    )?;
+    sample(out, "cnd_connected_peers", &[], connected_peers)?;
+
+    header(
+        out,
+        "cnd_orderbook_orders",
+        "gauge",
+        "Number of BTC/DAI orders in the orderbook, by owner and position.",
+    )?;
+    for ((owner, position), count) in orders {
+        sample(
+            out,
+            "cnd_orderbook_orders",
+            &[("owner", owner), ("position", position)],
+            *count,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &[(&str, &str)]) -> fmt::Result {
+        let bucket = format!("{}_bucket", name);
+
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            let le = bound.to_string();
+            sample(out, &bucket, &with_le(labels, &le), count)?;
+        }
+        sample(out, &bucket, &with_le(labels, "+Inf"), self.count)?;
+        sample(out, &format!("{}_sum", name), labels, self.sum)?;
+        sample(out, &format!("{}_count", name), labels, self.count)
+    }
+}
+
+fn with_le<'a>(labels: &[(&'a str, &'a str)], le: &'a str) -> Vec<(&'a str, &'a str)> {
+    labels.iter().copied().chain(Some(("le", le))).collect()
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) -> fmt::Result {
+    writeln!(out, "# HELP {} {}", name, help)?;
+    writeln!(out, "# TYPE {} {}", name, kind)
+}
+
+fn sample(
+    out: &mut String,
+    name: &str,
+    labels: &[(&str, &str)],
+    value: impl fmt::Display,
+) -> fmt::Result {
+    write!(out, "{}", name)?;
+
+    if !labels.is_empty() {
+        let labels = labels
+            .iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+            .collect::<Vec<_>>();
+        write!(out, "{{{}}}", labels.join(","))?;
+    }
+
+    writeln!(out, " {}", value)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Samples the states of the HTLCs of all swaps, forever.
+pub async fn track_swaps(metrics: Metrics, storage: Storage) {
+    loop {
+        if let Err(e) = sample_htlc_states(&metrics, &storage).await {
+            tracing::debug!("failed to sample states of HTLCs: {:#}", e);
+        }
+
+        tokio::time::delay_for(TRACK_INTERVAL).await;
+    }
+}
+
+async fn sample_htlc_states(metrics: &Metrics, storage: &Storage) -> anyhow::Result<()> {
+    let swaps: Vec<SwapContext> = storage.load_all().await?;
+    let now = Instant::now();
+
+    for swap in &swaps {
+        for protocol in &[swap.alpha, swap.beta] {
+            let state = match protocol {
+                LockProtocol::Herc20 => storage
+                    .herc20_states
+                    .get(&swap.id)
+                    .await?
+                    .map(|state| state.to_string()),
+                LockProtocol::Hbit => storage
+                    .hbit_states
+                    .get(&swap.id)
+                    .await?
+                    .map(|state| state.to_string()),
+                LockProtocol::Halbit => storage
+                    .halbit_states
+                    .get(&swap.id)
+                    .await?
+                    .map(|state| state.to_string()),
+            };
+
+            // The protocol task of the swap did not start yet
+            if let Some(state) = state {
+                metrics.observe_htlc_state(swap.id, *protocol, state, now);
+            }
+        }
+    }
+
+    metrics.retain_htlcs(&swaps.iter().map(|swap| swap.id).collect());
+
+    Ok(())
+}
+
+/// A connector that records the requests it sends to its node.
+#[derive(Debug)]
+pub struct Instrumented<C> {
+    inner: C,
+    node: Node,
+    metrics: Metrics,
+}
+
+impl<C> Instrumented<C> {
+    pub fn new(inner: C, node: Node, metrics: Metrics) -> Self {
+        Self {
+            inner,
+            node,
+            metrics,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> LatestBlock for Instrumented<C>
+where
+    C: LatestBlock,
+    C::Block: Send,
+{
+    type Block = C::Block;
+
+    async fn latest_block(&self) -> anyhow::Result<Self::Block> {
+        self.metrics
+            .time(self.node, "latest_block", self.inner.latest_block())
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> BlockByHash for Instrumented<C>
+where
+    C: BlockByHash,
+    C::Block: Send,
+    C::BlockHash: Send + 'static,
+{
+    type Block = C::Block;
+    type BlockHash = C::BlockHash;
+
+    async fn block_by_hash(&self, block_hash: Self::BlockHash) -> anyhow::Result<Self::Block> {
+        self.metrics
+            .time(
+                self.node,
+                "block_by_hash",
+                self.inner.block_by_hash(block_hash),
+            )
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl<C> ReceiptByHash for Instrumented<C>
+where
+    C: ReceiptByHash,
+{
+    async fn receipt_by_hash(&self, transaction_hash: Hash) -> anyhow::Result<TransactionReceipt> {
+        self.metrics
+            .time(
+                self.node,
+                "receipt_by_hash",
+                self.inner.receipt_by_hash(transaction_hash),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[0.25, 1.0]);
+        histogram.observe(Duration::from_millis(250));
+        histogram.observe(Duration::from_millis(500));
+        histogram.observe(Duration::from_secs(4));
+
+        let mut out = String::new();
+        histogram
+            .render(&mut out, "requests", &[("node", "geth")])
+            .unwrap();
+
+        assert_eq!(
+            out,
+            "requests_bucket{node=\"geth\",le=\"0.25\"} 1\n\
+             requests_bucket{node=\"geth\",le=\"1\"} 2\n\
+             requests_bucket{node=\"geth\",le=\"+Inf\"} 3\n\
+             requests_sum{node=\"geth\"} 4.75\n\
+             requests_count{node=\"geth\"} 3\n"
+        );
+    }
+
+    #[test]
+    fn time_spent_in_a_state_is_recorded_once_the_htlc_leaves_it() {
+        let metrics = Metrics::default();
+        let id = LocalSwapId::random();
+        let start = Instant::now();
+
+        metrics.observe_htlc_state(id, LockProtocol::Herc20, "None".to_owned(), start);
+        metrics.observe_htlc_state(
+            id,
+            LockProtocol::Herc20,
+            "None".to_owned(),
+            start + Duration::from_secs(30),
+        );
+        metrics.observe_htlc_state(
+            id,
+            LockProtocol::Herc20,
+            "Funded".to_owned(),
+            start + Duration::from_secs(90),
+        );
+
+        let mut out = String::new();
+        metrics
+            .lock()
+            .render(&mut out, start + Duration::from_secs(100))
+            .unwrap();
+
+        assert!(out.contains("cnd_swap_htlcs{protocol=\"herc20\",state=\"Funded\"} 1\n"));
+        assert!(out.contains(
+            "cnd_swap_htlc_oldest_state_age_seconds{protocol=\"herc20\",state=\"Funded\"} 10\n"
+        ));
+        assert!(out.contains(
+            "cnd_swap_htlc_state_duration_seconds_sum{protocol=\"herc20\",state=\"None\"} 90\n"
+        ));
+        assert!(!out.contains("state=\"Funded\",le="));
+    }
+
+    #[test]
+    fn failed_requests_are_counted_as_errors() {
+        let metrics = Metrics::default();
+
+        metrics.observe_request(
+            Node::Bitcoind,
+            "latest_block",
+            Duration::from_millis(1),
+            true,
+        );
+        metrics.observe_request(
+            Node::Bitcoind,
+            "latest_block",
+            Duration::from_millis(1),
+            false,
+        );
+
+        let mut out = String::new();
+        metrics.lock().render(&mut out, Instant::now()).unwrap();
+
+        assert!(out.contains(
+            "cnd_connector_request_errors_total{node=\"bitcoind\",method=\"latest_block\"} 1\n"
+        ));
+        assert!(out.contains(
+            "cnd_connector_request_duration_seconds_count{node=\"bitcoind\",method=\"latest_block\"} 2\n"
+        ));
+        assert!(out.contains("cnd_connector_last_success_timestamp_seconds{node=\"bitcoind\"}"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let mut out = String::new();
+        sample(&mut out, "metric", &[("label", "a \"b\"\\")], 1).unwrap();
+
+        assert_eq!(out, "metric{label=\"a \\\"b\\\"\\\\\"} 1\n");
+    }
+}
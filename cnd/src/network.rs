@@ -1,9 +1,13 @@
+mod address_book;
+mod cancellation;
 mod comit_node;
 mod peer_tracker;
+mod reputation;
 mod swarm;
 mod transport;
 
 // Export comit network types while maintaining the module abstraction.
 pub use ::comit::{asset, ledger, network::*};
+pub use cancellation::CancellationRejected;
 pub use swarm::{Swarm, SwarmWorker};
 pub use transport::ComitTransport;
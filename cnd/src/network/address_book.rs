@@ -0,0 +1,208 @@
+//! This module keeps the address book in the database up to date and
+//! reconnects to peers we still need.
+//!
+//! Peers are worth reconnecting to if we have active swaps with them or saw
+//! their orders recently. Peers that cannot be reached are retried with an
+//! exponential backoff.
+
+use crate::{
+    network::{comit_node::ComitNode, peer_tracker::DialOutcome},
+    storage::{counterparties_of_active_swaps, Peer, Storage},
+};
+use libp2p::PeerId;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+
+/// How often we update the address book and check for peers to reconnect to.
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long after we last saw orders of a peer we still reconnect to it.
+const RECENT_ORDERS: Duration = Duration::from_secs(60 * 60);
+
+const MIN_BACKOFF: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// A background worker that records the outcome of dial attempts and the
+/// makers in our orderbook in the address book and redials the peers we
+/// still need.
+pub async fn worker(
+    swarm: Arc<Mutex<libp2p::Swarm<ComitNode>>>,
+    storage: Storage,
+    local_peer_id: PeerId,
+) {
+    let mut backoffs = HashMap::<PeerId, Backoff>::new();
+
+    loop {
+        tokio::time::delay_for(INTERVAL).await;
+
+        let (outcomes, makers) = {
+            let mut guard = swarm.lock().await;
+            let outcomes = guard.peer_tracker.take_dial_outcomes();
+            let makers = guard
+                .orderbook
+                .orderpool()
+                .all()
+                .map(|(maker, _)| maker.clone())
+                .filter(|maker| maker != &local_peer_id)
+                .collect::<HashSet<_>>();
+
+            (outcomes, makers)
+        };
+
+        for outcome in &outcomes {
+            match outcome {
+                DialOutcome::Connected { peer, .. } => {
+                    backoffs.remove(peer);
+                }
+                DialOutcome::Failed { peer } => backoffs.entry(peer.clone()).or_default().fail(),
+            }
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let wanted = match storage
+            .db
+            .do_in_transaction(|conn| {
+                for outcome in &outcomes {
+                    match outcome {
+                        DialOutcome::Connected { peer, address } => {
+                            Peer::get_or_insert(conn, peer)?
+                                .record_connection(conn, address, now)?
+                        }
+                        DialOutcome::Failed { peer } => {
+                            Peer::get_or_insert(conn, peer)?.record_dial_failure(conn)?
+                        }
+                    }
+                }
+
+                for maker in &makers {
+                    Peer::get_or_insert(conn, maker)?.record_orders(conn, now)?;
+                }
+
+                let mut wanted = Vec::new();
+                let counterparties = counterparties_of_active_swaps(conn, now)?;
+                let with_recent_orders = Peer::with_orders_since(conn, now - RECENT_ORDERS)?
+                    .into_iter()
+                    .map(|peer| peer.peer_id);
+
+                for peer in counterparties.into_iter().chain(with_recent_orders) {
+                    let addresses = Peer::get_or_insert(conn, &peer)?
+                        .addresses(conn)?
+                        .into_iter()
+                        .map(|address| address.address)
+                        .collect::<Vec<_>>();
+
+                    wanted.push((peer, addresses));
+                }
+
+                Ok(wanted)
+            })
+            .await
+        {
+            Ok(wanted) => wanted,
+            Err(e) => {
+                tracing::warn!("failed to update address book: {:#}", e);
+                continue;
+            }
+        };
+
+        let mut guard = swarm.lock().await;
+
+        for (peer, addresses) in wanted {
+            if peer == local_peer_id || libp2p::Swarm::connection_info(&mut guard, &peer).is_some()
+            {
+                continue;
+            }
+
+            let backoff = backoffs.entry(peer.clone()).or_default();
+            if !backoff.is_due() {
+                continue;
+            }
+            backoff.start_attempt();
+
+            // Add the oldest address first so the most recent one ends up in front.
+            for address in addresses.into_iter().rev() {
+                guard
+                    .peer_tracker
+                    .add_recent_address_hint(peer.clone(), address);
+            }
+
+            tracing::debug!("reconnecting to {}", peer);
+            if let Err(e) = libp2p::Swarm::dial(&mut guard, &peer) {
+                tracing::warn!("failed to dial {}: {:?}", peer, e);
+            }
+        }
+    }
+}
+
+/// Tracks when to try reaching a peer again.
+#[derive(Clone, Copy, Debug)]
+struct Backoff {
+    failures: u32,
+    next_attempt: Instant,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            failures: 0,
+            next_attempt: Instant::now(),
+        }
+    }
+}
+
+impl Backoff {
+    fn is_due(&self) -> bool {
+        Instant::now() >= self.next_attempt
+    }
+
+    /// Don't try again until the current attempt had a chance to finish.
+    fn start_attempt(&mut self) {
+        self.next_attempt = Instant::now() + self.delay();
+    }
+
+    fn fail(&mut self) {
+        self.failures = self.failures.saturating_add(1);
+        self.next_attempt = Instant::now() + self.delay();
+    }
+
+    fn delay(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.failures);
+
+        MIN_BACKOFF
+            .checked_mul(factor)
+            .map_or(MAX_BACKOFF, |delay| Duration::min(delay, MAX_BACKOFF))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_with_every_failure() {
+        let mut backoff = Backoff::default();
+        assert_eq!(backoff.delay(), MIN_BACKOFF);
+
+        backoff.fail();
+        backoff.fail();
+
+        assert_eq!(backoff.delay(), MIN_BACKOFF * 4);
+        assert!(!backoff.is_due());
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let mut backoff = Backoff::default();
+
+        for _ in 0..100 {
+            backoff.fail();
+        }
+
+        assert_eq!(backoff.delay(), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,157 @@
+//! This module cancels swaps that Alice did not fund yet.
+//!
+//! Either party can cancel a swap until the alpha HTLC is funded and as long
+//! as we did not hand out a deploy or fund action for it. We record the
+//! cancellation, stop watching the ledgers and give the quantity the swap
+//! filled back to the order it was set up from. If we cancel a swap ourselves,
+//! we only do so once our counterparty accepted the cancellation.
+
+use crate::{
+    network::comit_node::ComitNode,
+    storage::{
+        cancel_swap, AlreadyFunded, FundHandout, Load, Storage, Swap, SwapCancellation,
+        SwapCancelled, SwapContext,
+    },
+    LocalSwapId, ProtocolSpawner,
+};
+use anyhow::{Context, Result};
+use comit::{network::protocols::cancel_swap::Response, Role, SecretHash};
+use diesel::SqliteConnection;
+use futures::{channel::mpsc, StreamExt};
+use libp2p::PeerId;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tokio::sync::Mutex;
+
+#[derive(thiserror::Error, Debug, Clone)]
+#[error("counterparty rejected the cancellation: {0}")]
+pub struct CancellationRejected(pub String);
+
+/// A background worker that cancels the swaps our counterparties tell us they
+/// are not going to fund.
+pub async fn worker(
+    swarm: Arc<Mutex<libp2p::Swarm<ComitNode>>>,
+    mut receiver: mpsc::Receiver<(PeerId, SecretHash)>,
+    storage: Storage,
+    spawner: ProtocolSpawner,
+) {
+    while let Some((peer, secret_hash)) = receiver.next().await {
+        let result = cancelled_by_peer(&swarm, &storage, &spawner, &peer, secret_hash).await;
+        let response = match result {
+            Ok(()) => Response::Accepted,
+            Err(e) => {
+                tracing::warn!("failed to cancel swap on behalf of {}: {:#}", peer, e);
+                Response::Rejected {
+                    reason: e.to_string(),
+                }
+            }
+        };
+
+        swarm
+            .lock()
+            .await
+            .cancel_swap
+            .respond(&peer, secret_hash, response);
+    }
+}
+
+async fn cancelled_by_peer(
+    swarm: &Mutex<libp2p::Swarm<ComitNode>>,
+    storage: &Storage,
+    spawner: &ProtocolSpawner,
+    peer: &PeerId,
+    secret_hash: SecretHash,
+) -> Result<()> {
+    let id = storage
+        .swap_by_secret_hash(secret_hash)
+        .await?
+        .with_context(|| format!("no swap with secret hash {}", secret_hash))?;
+    let swap = storage
+        .db
+        .do_in_transaction(|conn| Swap::by_swap_id(conn, id))
+        .await?;
+
+    if swap.counterparty_peer_id != *peer {
+        anyhow::bail!("{} is not the counterparty of swap {}", peer, id)
+    }
+
+    let peer_role = match swap.role {
+        Role::Alice => Role::Bob,
+        Role::Bob => Role::Alice,
+    };
+
+    cancel(swarm, storage, spawner, id, peer_role).await
+}
+
+/// Cancel the swap on behalf of the given party.
+///
+/// If we cancel the swap ourselves, we ask our counterparty to accept the
+/// cancellation first and leave the swap untouched if they don't.
+pub async fn cancel(
+    swarm: &Mutex<libp2p::Swarm<ComitNode>>,
+    storage: &Storage,
+    spawner: &ProtocolSpawner,
+    id: LocalSwapId,
+    cancelled_by: Role,
+) -> Result<()> {
+    let context: SwapContext = storage.load(id).await?;
+
+    if storage.alpha_funded(context).await? {
+        anyhow::bail!(AlreadyFunded(id))
+    }
+
+    let swap = storage
+        .db
+        .do_in_transaction(|conn| {
+            ensure_cancellable(conn, id)?;
+
+            Swap::by_swap_id(conn, id)
+        })
+        .await?;
+
+    // Our counterparty already knows if they cancelled the swap
+    if cancelled_by == context.role {
+        let secret_hash = storage.secret_hash(id, context.role).await?;
+        let answer = swarm
+            .lock()
+            .await
+            .cancel_swap
+            .cancel(&swap.counterparty_peer_id, secret_hash)?;
+
+        match answer.await {
+            Ok(Response::Accepted) => {}
+            Ok(Response::Rejected { reason }) => anyhow::bail!(CancellationRejected(reason)),
+            Err(_) => anyhow::bail!("counterparty did not answer the cancellation of {}", id),
+        }
+    }
+
+    let order = storage
+        .db
+        .do_in_transaction(|conn| {
+            ensure_cancellable(conn, id)?;
+
+            cancel_swap(conn, id, cancelled_by, OffsetDateTime::now_utc())
+        })
+        .await?;
+    spawner.abort(id);
+
+    if let Some(order) = order {
+        swarm.lock().await.orderbook.publish(order);
+    }
+
+    tracing::info!("swap {} was cancelled by {}", id, cancelled_by);
+
+    Ok(())
+}
+
+fn ensure_cancellable(conn: &SqliteConnection, id: LocalSwapId) -> Result<()> {
+    if SwapCancellation::by_swap_id(conn, id)?.is_some() {
+        anyhow::bail!(SwapCancelled(id))
+    }
+    // The watchers might not have seen the funding transaction yet
+    if FundHandout::by_swap_id(conn, id)?.is_some() {
+        anyhow::bail!(AlreadyFunded(id))
+    }
+
+    Ok(())
+}
@@ -13,13 +13,19 @@ use comit::{
     lightning,
     network::{
         comit::{Comit, LocalData, RemoteData},
+        discovery,
+        discovery::Discovery,
         orderbook,
         orderbook::Orderbook,
-        protocols::{announce, announce::Announce, setup_swap::SetupSwap},
+        protocols::{
+            announce, announce::Announce, cancel_swap, cancel_swap::CancelSwap,
+            setup_swap::SetupSwap,
+        },
         setup_swap,
         swap_digest::SwapDigest,
         Identities, SharedSwapId, WhatAliceLearnedFromBob, WhatBobLearnedFromAlice,
     },
+    order::SwapProtocol,
     orderpool, LockProtocol, Never, OrderId, Quantity, Role, SecretHash, Side,
 };
 use futures::{channel::mpsc, SinkExt, TryFutureExt};
@@ -34,9 +40,11 @@ use tokio::runtime::Handle;
 pub struct ComitNode {
     pub announce: Announce<LocalSwapId>,
     pub setup_swap: SetupSwap<SetupSwapContext>,
+    pub cancel_swap: CancelSwap,
     pub orderbook: Orderbook,
     pub comit: Comit,
     pub peer_tracker: PeerTracker,
+    pub discovery: Discovery,
 
     #[behaviour(ignore)]
     seed: RootSeed,
@@ -58,9 +66,12 @@ pub struct ComitNode {
     protocol_spawner: ProtocolSpawner,
     #[behaviour(ignore)]
     matches_sender: mpsc::Sender<orderpool::Match>,
+    #[behaviour(ignore)]
+    cancellations_sender: mpsc::Sender<(PeerId, SecretHash)>,
 }
 
 impl ComitNode {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         seed: RootSeed,
         task_executor: Handle,
@@ -68,14 +79,18 @@ impl ComitNode {
         protocol_spawner: ProtocolSpawner,
         peer_id: PeerId,
         key: Keypair,
+        discovery: Discovery,
         matches_sender: mpsc::Sender<orderpool::Match>,
+        cancellations_sender: mpsc::Sender<(PeerId, SecretHash)>,
     ) -> Self {
         Self {
             announce: Announce::default(),
             setup_swap: Default::default(),
+            cancel_swap: CancelSwap::new(key.clone()),
             orderbook: Orderbook::new(peer_id, key),
             comit: Comit::default(),
             peer_tracker: PeerTracker::default(),
+            discovery,
             seed,
             task_executor,
             local_data: HashMap::default(),
@@ -83,6 +98,7 @@ impl ComitNode {
             storage,
             protocol_spawner,
             matches_sender,
+            cancellations_sender,
         }
     }
 
@@ -124,6 +140,51 @@ impl ComitNode {
         Ok(())
     }
 
+    /// Reserve the quantity a taker asked for of one of our orders, provided
+    /// the swap they propose fits the order.
+    ///
+    /// The taker derived the expiries from the match reference point in the
+    /// same way we do, hence we recover it from the bitcoin expiry.
+    fn reserve_for_taker(
+        &mut self,
+        peer: PeerId,
+        take: setup_swap::Take,
+        common: &setup_swap::CommonParams,
+        swap_protocol: setup_swap::SwapProtocol,
+        their_role: Role,
+    ) -> anyhow::Result<orderpool::Match> {
+        let order = self
+            .orderbook
+            .orderpool()
+            .ours()
+            .find(|order| order.id == take.order)
+            .cloned()
+            .ok_or(orderpool::UnknownOrder(take.order))?;
+
+        let order_swap_protocol = match order.swap_protocol {
+            SwapProtocol::HbitHerc20 { .. } => setup_swap::SwapProtocol::HbitHerc20,
+            SwapProtocol::Herc20Hbit { .. } => setup_swap::SwapProtocol::Herc20Hbit,
+        };
+        if order_swap_protocol != swap_protocol
+            || order.swap_protocol.role(order.position) == their_role
+        {
+            anyhow::bail!("proposed swap does not fit order {}", order.id)
+        }
+
+        let match_reference_point =
+            OffsetDateTime::from_unix_timestamp(i64::from(common.bitcoin_absolute_expiry))
+                - order.swap_protocol.hbit_expiry_offset();
+
+        self.orderbook.orderpool_mut().reserve_for_taker(
+            peer,
+            take.order,
+            take.taker_order,
+            Quantity::new(common.bitcoin),
+            &common.erc20.quantity,
+            match_reference_point,
+        )
+    }
+
     fn assert_have_lnd_if_needed(
         &self,
         identity: Option<lightning::PublicKey>,
@@ -223,6 +284,18 @@ impl libp2p::swarm::NetworkBehaviourEventProcess<announce::BehaviourOutEvent<Loc
     }
 }
 
+impl libp2p::swarm::NetworkBehaviourEventProcess<discovery::BehaviourOutEvent> for ComitNode {
+    fn inject_event(&mut self, event: discovery::BehaviourOutEvent) {
+        match event {
+            discovery::BehaviourOutEvent::Discovered { peer, address } => {
+                self.peer_tracker
+                    .add_recent_address_hint(peer.clone(), address);
+                self.orderbook.connect(peer);
+            }
+        }
+    }
+}
+
 impl libp2p::swarm::NetworkBehaviourEventProcess<orderbook::BehaviourOutEvent> for ComitNode {
     fn inject_event(&mut self, event: orderbook::BehaviourOutEvent) {
         match event {
@@ -241,6 +314,40 @@ impl libp2p::swarm::NetworkBehaviourEventProcess<orderbook::BehaviourOutEvent> f
                     }
                 });
             }
+            orderbook::BehaviourOutEvent::ProtocolViolation { peer } => {
+                let storage = self.storage.clone();
+
+                self.task_executor.spawn(async move {
+                    if let Err(e) = storage.record_violation(peer).await {
+                        tracing::warn!("failed to record protocol violation: {:#}", e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+impl libp2p::swarm::NetworkBehaviourEventProcess<cancel_swap::BehaviourOutEvent> for ComitNode {
+    fn inject_event(&mut self, event: cancel_swap::BehaviourOutEvent) {
+        match event {
+            cancel_swap::BehaviourOutEvent::Cancelled { peer, secret_hash } => {
+                let mut sender = self.cancellations_sender.clone();
+
+                self.task_executor.spawn(async move {
+                    if sender.send((peer, secret_hash)).await.is_err() {
+                        tracing::error!("failed to dispatch swap cancellation");
+                    }
+                });
+            }
+            cancel_swap::BehaviourOutEvent::ProtocolViolation { peer } => {
+                let storage = self.storage.clone();
+
+                self.task_executor.spawn(async move {
+                    if let Err(e) = storage.record_violation(peer).await {
+                        tracing::warn!("failed to record protocol violation: {:#}", e);
+                    }
+                });
+            }
         }
     }
 }
@@ -269,6 +376,7 @@ impl libp2p::swarm::NetworkBehaviourEventProcess<setup_swap::BehaviourOutEvent<S
                     InsertableSwap::new(swap_id, exec_swap.peer_id, role, start_of_swap);
 
                 let hbit_params = exec_swap.hbit;
+                let quantity = Quantity::new(hbit_params.asset);
                 let insertable_hbit = move |swap_fk, our_final_address| {
                     InsertableHbit::new(
                         swap_fk,
@@ -320,7 +428,8 @@ impl libp2p::swarm::NetworkBehaviourEventProcess<setup_swap::BehaviourOutEvent<S
                                 insertable_herc20(swap_pk).insert(conn)?;
 
                                 let order = Order::by_order_id(conn, order_id)?;
-                                BtcDaiOrder::by_order(conn, &order)?.set_to_settling(conn)?;
+                                BtcDaiOrder::by_order(conn, &order)?
+                                    .set_to_settling(conn, quantity)?;
 
                                 InsertableOrderSwap::new(swap_pk, order.id).insert(conn)?;
                                 let order_hbit_params = OrderHbitParams::by_order(conn, &order)?;
@@ -357,7 +466,7 @@ impl libp2p::swarm::NetworkBehaviourEventProcess<setup_swap::BehaviourOutEvent<S
                 if let Err(e) = self
                     .orderbook
                     .orderpool_mut()
-                    .notify_swap_setup_successful(order_id, Quantity::new(hbit_params.asset))
+                    .notify_swap_setup_successful(order_id, quantity)
                 {
                     tracing::error!(
                         "failed to notify orderpool about successful swap setup: {:#}",
@@ -365,10 +474,81 @@ impl libp2p::swarm::NetworkBehaviourEventProcess<setup_swap::BehaviourOutEvent<S
                     );
                 }
             }
+            setup_swap::BehaviourOutEvent::TakeRequested {
+                peer,
+                take,
+                common,
+                swap_protocol,
+                their_role,
+            } => {
+                let new_match = match self.reserve_for_taker(
+                    peer.clone(),
+                    take,
+                    &common,
+                    swap_protocol,
+                    their_role,
+                ) {
+                    Ok(new_match) => new_match,
+                    Err(e) => {
+                        tracing::warn!("{} cannot take order {}: {:#}", peer, take.order, e);
+                        self.setup_swap.decline(
+                            &peer,
+                            take.into(),
+                            &common,
+                            swap_protocol,
+                            format!("cannot take order {}: {:#}", take.order, e),
+                        );
+                        return;
+                    }
+                };
+                tracing::info!(
+                    "{} takes {} of order {}",
+                    peer,
+                    new_match.quantity.to_inner(),
+                    take.order
+                );
+                let mut sender = self.matches_sender.clone();
+
+                self.task_executor.spawn(async move {
+                    if sender.send(new_match).await.is_err() {
+                        tracing::error!("failed to dispatch taken order");
+                    }
+                });
+            }
             setup_swap::BehaviourOutEvent::AlreadyHaveRoleParams { peer, .. } => tracing::error!(
                 "Already have role dependent parameters from this peer: {}",
                 peer
             ),
+            setup_swap::BehaviourOutEvent::ExpiriesRejected {
+                peer,
+                rejected_by,
+                reason,
+                context,
+            } => tracing::warn!(
+                "Failed to set up swap {} for order {} with {}, {} rejected the expiries: {}",
+                context.swap,
+                context.order,
+                peer,
+                rejected_by,
+                reason
+            ),
+            setup_swap::BehaviourOutEvent::TimedOut { peer, context } => tracing::warn!(
+                "Failed to set up swap {} for order {} with {}, the negotiation timed out",
+                context.swap,
+                context.order,
+                peer
+            ),
+            setup_swap::BehaviourOutEvent::Declined {
+                peer,
+                reason,
+                context,
+            } => tracing::warn!(
+                "Failed to set up swap {} for order {} with {}, they declined: {}",
+                context.swap,
+                context.order,
+                peer,
+                reason
+            ),
         }
     }
 }
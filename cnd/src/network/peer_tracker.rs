@@ -13,11 +13,19 @@ use std::{
     task::Poll,
 };
 
+/// The outcome of dialling a peer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DialOutcome {
+    Connected { peer: PeerId, address: Multiaddr },
+    Failed { peer: PeerId },
+}
+
 /// A NetworkBehaviour that tracks connections to other peers.
 #[derive(Default, Debug)]
 pub struct PeerTracker {
     connected_peers: HashMap<PeerId, Vec<Multiaddr>>,
     address_hints: HashMap<PeerId, VecDeque<Multiaddr>>,
+    dial_outcomes: Vec<DialOutcome>,
 }
 
 impl PeerTracker {
@@ -38,10 +46,16 @@ impl PeerTracker {
                 self.address_hints.insert(id, hints);
             }
             Some(hints) => {
+                hints.retain(|hint| hint != &addr);
                 hints.push_front(addr);
             }
         }
     }
+
+    /// Takes the outcomes of all dial attempts since the last call.
+    pub fn take_dial_outcomes(&mut self) -> Vec<DialOutcome> {
+        std::mem::take(&mut self.dial_outcomes)
+    }
 }
 
 impl NetworkBehaviour for PeerTracker {
@@ -91,6 +105,10 @@ impl NetworkBehaviour for PeerTracker {
                 .entry(peer.clone())
                 .or_default()
                 .push(address.clone());
+            self.dial_outcomes.push(DialOutcome::Connected {
+                peer: peer.clone(),
+                address: address.clone(),
+            });
         }
     }
 
@@ -114,6 +132,11 @@ impl NetworkBehaviour for PeerTracker {
         }
     }
 
+    fn inject_dial_failure(&mut self, peer: &PeerId) {
+        self.dial_outcomes
+            .push(DialOutcome::Failed { peer: peer.clone() });
+    }
+
     fn inject_event(&mut self, _: PeerId, _: ConnectionId, _: void::Void) {}
 
     fn poll(
@@ -0,0 +1,67 @@
+//! This module refuses to trade with peers of bad reputation.
+//!
+//! We stop matching the orders of peers that were banned explicitly or whose
+//! score dropped below the configured minimum. Without a match, no swap is set
+//! up with them.
+
+use crate::{
+    network::comit_node::ComitNode,
+    storage::{Peer, Storage},
+};
+use comit::reputation::Reputation;
+use libp2p::PeerId;
+use std::{sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+/// How often we check the reputation of all peers we know.
+const INTERVAL: Duration = Duration::from_secs(10);
+
+/// A background worker that blocks or unblocks the orders of all peers in our
+/// address book according to their reputation.
+pub async fn worker(
+    swarm: Arc<Mutex<libp2p::Swarm<ComitNode>>>,
+    storage: Storage,
+    min_reputation: Option<i64>,
+) {
+    loop {
+        let reputations = storage
+            .db
+            .do_in_transaction(|conn| {
+                let mut reputations = Vec::new();
+
+                for peer in Peer::all(conn)? {
+                    let reputation = peer.reputation(conn)?;
+                    reputations.push((peer.peer_id, reputation));
+                }
+
+                Ok(reputations)
+            })
+            .await;
+
+        match reputations {
+            Ok(reputations) => {
+                let mut guard = swarm.lock().await;
+
+                for (peer, reputation) in reputations {
+                    enforce(&mut guard, peer, &reputation, min_reputation);
+                }
+            }
+            Err(e) => tracing::warn!("failed to load reputations: {:#}", e),
+        }
+
+        tokio::time::delay_for(INTERVAL).await;
+    }
+}
+
+pub fn enforce(
+    node: &mut ComitNode,
+    peer: PeerId,
+    reputation: &Reputation,
+    min_reputation: Option<i64>,
+) {
+    if reputation.is_acceptable(min_reputation) {
+        node.orderbook.unblock(&peer);
+    } else {
+        node.orderbook.block(peer);
+    }
+}
@@ -3,23 +3,27 @@ use crate::{
     config::Settings,
     local_swap_id::LocalSwapId,
     network::{
+        address_book, cancellation,
         comit_node::{ComitNode, SetupSwapContext},
-        setup_swap,
+        reputation, setup_swap,
         setup_swap::{AliceParams, BobParams},
         transport,
     },
     protocol_spawner::ProtocolSpawner,
-    storage::{RootSeed, Storage},
+    storage::{Load, RootSeed, Storage, SwapContext},
 };
 use anyhow::{Context as _, Result};
 use comit::{
     network::{
-        protocols::setup_swap::{CommonParams, RoleDependentParams},
+        discovery::Discovery,
+        protocols::setup_swap::{CommonParams, NegotiationId, RoleDependentParams, Take},
         swap_digest::SwapDigest,
         Identities,
     },
     order::SwapProtocol,
-    orderpool, BtcDaiOrder, OrderId, Role, SecretHash, Side,
+    orderpool,
+    reputation::Reputation,
+    BtcDaiOrder, OrderId, Price, Quantity, Role, SecretHash, Side, Timestamp,
 };
 use futures::{channel::mpsc, stream::StreamExt};
 use libp2p::{
@@ -36,6 +40,7 @@ use std::{
     sync::Arc,
     task::{Context, Poll},
 };
+use time::OffsetDateTime;
 use tokio::sync::Mutex;
 
 #[derive(Clone, derivative::Derivative)]
@@ -44,6 +49,11 @@ pub struct Swarm {
     #[derivative(Debug = "ignore")]
     inner: Arc<Mutex<libp2p::Swarm<ComitNode>>>,
     local_peer_id: PeerId,
+    min_reputation: Option<i64>,
+    storage: Storage,
+    protocol_spawner: ProtocolSpawner,
+    #[derivative(Debug = "ignore")]
+    seed: RootSeed,
 }
 
 impl Swarm {
@@ -61,16 +71,25 @@ impl Swarm {
         let transport = transport::build(local_key_pair.clone(), settings.network.listen.clone())?;
 
         let (sender, receiver) = mpsc::channel(1);
+        let (cancellations_sender, cancellations_receiver) = mpsc::channel(1);
 
-        let behaviour = ComitNode::new(
+        let discovery = Discovery::new(local_peer_id.clone(), &settings.network.discovery)?;
+
+        let mut behaviour = ComitNode::new(
             seed,
             task_executor.clone(),
             storage.clone(),
-            protocol_spawner,
+            protocol_spawner.clone(),
             local_peer_id.clone(),
             local_key_pair,
+            discovery,
             sender,
+            cancellations_sender,
         );
+        behaviour
+            .orderbook
+            .orderpool_mut()
+            .set_min_fill(settings.network.min_fill);
 
         let mut swarm = SwarmBuilder::new(transport, behaviour, local_peer_id.clone())
             .executor(Box::new(TokioExecutor {
@@ -92,11 +111,36 @@ impl Swarm {
 
         let swarm = Arc::new(Mutex::new(swarm));
 
-        task_executor.spawn(new_match_worker(swarm.clone(), receiver, storage, seed));
+        task_executor.spawn(address_book::worker(
+            swarm.clone(),
+            storage.clone(),
+            local_peer_id.clone(),
+        ));
+        task_executor.spawn(reputation::worker(
+            swarm.clone(),
+            storage.clone(),
+            settings.network.min_reputation,
+        ));
+        task_executor.spawn(cancellation::worker(
+            swarm.clone(),
+            cancellations_receiver,
+            storage.clone(),
+            protocol_spawner.clone(),
+        ));
+        task_executor.spawn(new_match_worker(
+            swarm.clone(),
+            receiver,
+            storage.clone(),
+            seed,
+        ));
 
         Ok(Self {
             inner: swarm,
             local_peer_id,
+            min_reputation: settings.network.min_reputation,
+            storage,
+            protocol_spawner,
+            seed,
         })
     }
 
@@ -147,6 +191,153 @@ impl Swarm {
         self.inner.lock().await.orderbook.cancel(order_id);
     }
 
+    /// Amend the price and/or the remaining quantity of one of our open
+    /// orders in the database and the order pool.
+    ///
+    /// We hold on to the swarm while updating the database so that no swap
+    /// can be set up for the order in between.
+    pub async fn amend_order(
+        &self,
+        order_id: OrderId,
+        price: Option<Price<asset::Bitcoin, asset::Erc20Quantity>>,
+        quantity: Option<Quantity<asset::Bitcoin>>,
+    ) -> Result<()> {
+        let mut guard = self.inner.lock().await;
+        let previous = guard
+            .orderbook
+            .orderpool_mut()
+            .amend(order_id, price.clone(), quantity)?;
+
+        let saved = self
+            .storage
+            .db
+            .do_in_transaction(|conn| {
+                use crate::storage::{BtcDaiOrder, Order};
+
+                let order = Order::by_order_id(conn, order_id)?;
+                let btc_dai_order = BtcDaiOrder::by_order(conn, &order)?;
+
+                if let Some(price) = &price {
+                    btc_dai_order.set_price(conn, price)?;
+                }
+                if let Some(quantity) = quantity {
+                    btc_dai_order.resize(conn, previous.quantity, quantity)?;
+                }
+
+                Ok(())
+            })
+            .await;
+
+        if saved.is_err() {
+            guard.orderbook.orderpool_mut().amend(
+                order_id,
+                Some(previous.price),
+                Some(previous.quantity),
+            )?;
+        }
+
+        saved
+    }
+
+    /// Reserve the given quantity of another maker's order, or all of it that
+    /// is left, for taking it.
+    ///
+    /// Returns the order recording our side of the swap, it needs to be saved
+    /// before the swap is proposed with
+    /// [`propose_swap_to_maker`](Swarm::propose_swap_to_maker).
+    pub async fn take_order(
+        &self,
+        order_id: OrderId,
+        quantity: Option<Quantity<asset::Bitcoin>>,
+    ) -> Result<(BtcDaiOrder, orderpool::Match)> {
+        self.inner
+            .lock()
+            .await
+            .orderbook
+            .orderpool_mut()
+            .take(order_id, quantity)
+    }
+
+    /// Propose the swap for an order we took to its maker.
+    ///
+    /// The swap is saved under the returned id once the maker agrees to it.
+    pub async fn propose_swap_to_maker(&self, new_match: orderpool::Match) -> Result<LocalSwapId> {
+        let take = Take {
+            order: new_match.theirs,
+            taker_order: new_match.ours,
+        };
+        let peer = new_match.peer.clone();
+        let match_reference_point = new_match.match_reference_point;
+
+        let (swap_id, common, role, protocol) =
+            handle_new_match(&self.seed, &self.storage, new_match).await?;
+
+        self.inner.lock().await.setup_swap.take(
+            &peer,
+            role,
+            common,
+            protocol,
+            start_of_swap(match_reference_point),
+            take,
+            SetupSwapContext {
+                swap: swap_id,
+                order: take.taker_order,
+                match_reference_point,
+            },
+        )?;
+
+        Ok(swap_id)
+    }
+
+    /// Give up on taking an order whose swap the maker did not set up in time.
+    ///
+    /// The maker is told that we declined and the quantities reserved for the
+    /// swap are released. Returns `false` without aborting anything if the
+    /// swap was set up in the meantime.
+    pub async fn abort_take(&self, new_match: &orderpool::Match) -> bool {
+        let mut guard = self.inner.lock().await;
+        let orderpool = guard.orderbook.orderpool_mut();
+
+        // Our reservation is only gone if the swap was set up.
+        if orderpool
+            .notify_swap_setup_failed(new_match.ours, new_match.quantity)
+            .is_err()
+        {
+            return false;
+        }
+        if let Err(e) = orderpool.notify_swap_setup_failed(new_match.theirs, new_match.quantity) {
+            tracing::debug!("no reservation to release: {:#}", e);
+        }
+
+        guard
+            .setup_swap
+            .abort(|context| context.order == new_match.ours);
+
+        true
+    }
+
+    /// Cancel a swap that was not funded yet and tell our counterparty about
+    /// it.
+    pub async fn cancel_swap(&self, id: LocalSwapId) -> anyhow::Result<()> {
+        let swap: SwapContext = self.storage.load(id).await?;
+
+        cancellation::cancel(
+            &self.inner,
+            &self.storage,
+            &self.protocol_spawner,
+            id,
+            swap.role,
+        )
+        .await
+    }
+
+    /// Block or unblock the orders of this peer right away, depending on its
+    /// changed reputation.
+    pub async fn enforce_reputation(&self, peer: PeerId, reputation: &Reputation) {
+        let mut guard = self.inner.lock().await;
+        reputation::enforce(&mut guard, peer, reputation, self.min_reputation);
+    }
+
     pub async fn dial_addr(&self, addr: Multiaddr) -> anyhow::Result<()> {
         let mut guard = self.inner.lock().await;
         let _ = libp2p::Swarm::dial_addr(&mut *guard, addr)?;
@@ -171,6 +362,14 @@ impl Swarm {
         Box::new(swarm.peer_tracker.connected_peers())
     }
 
+    /// The addresses we are actually listening on, without the external
+    /// addresses we learned about.
+    pub async fn listeners(&self) -> Vec<Multiaddr> {
+        let swarm = self.inner.lock().await;
+
+        libp2p::Swarm::listeners(&swarm).cloned().collect()
+    }
+
     pub async fn listen_addresses(&self) -> Vec<Multiaddr> {
         let swarm = self.inner.lock().await;
 
@@ -249,6 +448,7 @@ async fn new_match_worker(
 ) {
     while let Some(new_match) = receiver.next().await {
         let order_id = new_match.ours;
+        let negotiation_id = NegotiationId::new(new_match.ours, new_match.theirs);
         let peer = new_match.peer.clone();
         let match_reference_point = new_match.match_reference_point;
 
@@ -263,19 +463,30 @@ async fn new_match_worker(
 
         let mut guard = swarm.lock().await;
 
-        if let Err(e) = guard
-            .setup_swap
-            .send(&peer, role, common, protocol, SetupSwapContext {
+        if let Err(e) = guard.setup_swap.send(
+            &peer,
+            negotiation_id,
+            role,
+            common,
+            protocol,
+            start_of_swap(match_reference_point),
+            SetupSwapContext {
                 swap: swap_id,
                 order: order_id,
                 match_reference_point,
-            })
-        {
+            },
+        ) {
             tracing::warn!("failed to setup swap for order {}: {:#}", order_id, e);
         }
     }
 }
 
+fn start_of_swap(match_reference_point: OffsetDateTime) -> Timestamp {
+    #[allow(clippy::cast_sign_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    Timestamp::from(match_reference_point.timestamp() as u32)
+}
+
 async fn handle_new_match(
     seed: &RootSeed,
     storage: &Storage,
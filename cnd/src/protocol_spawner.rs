@@ -1,9 +1,25 @@
 use crate::{
-    connectors::Connectors, halbit, hbit, herc20, http_api::LedgerNotConfigured, storage::Storage,
-    LocalSwapId, Role, Side,
+    connectors::Connectors,
+    halbit, hbit, herc20,
+    http_api::LedgerNotConfigured,
+    lnd_actions,
+    metrics::Metrics,
+    state::Get,
+    storage::{Load, Storage, SwapContext},
+    wallet::Wallets,
+    wallet_actions, LocalSwapId, Role, Side, Timestamp,
 };
 use chrono::{DateTime, Utc};
-use comit::lnd::{LndConnectorAsReceiver, LndConnectorAsSender, LndConnectorParams};
+use comit::{
+    lnd::{LndActionExecutor, LndConnectorAsReceiver, LndConnectorAsSender, LndConnectorParams},
+    reputation::Outcome,
+};
+use futures::future::{self, AbortHandle, Either, Future};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 use tokio::runtime::Handle;
 
 /// ProtocolSpawner acts as a bundle for all dependencies needed to spawn
@@ -12,8 +28,21 @@ use tokio::runtime::Handle;
 pub struct ProtocolSpawner {
     connectors: Connectors,
     lnd_connector_params: Option<LndConnectorParams>,
+    lnd_action_executor: Option<LndActionExecutor>,
+    /// Present in managed-wallet mode.
+    wallets: Option<Wallets>,
+    metrics: Metrics,
     runtime_handle: Handle,
     storage: Storage,
+    /// Handles to abort the protocols of a swap in case it gets cancelled.
+    tasks: Arc<Mutex<HashMap<LocalSwapId, SwapTasks>>>,
+}
+
+/// The protocols spawned for a swap, forgotten once all of them finished.
+#[derive(Debug, Default)]
+struct SwapTasks {
+    running: usize,
+    handles: Vec<AbortHandle>,
 }
 
 /// The `Spawn` trait abstracts over the functionality of spawning a particular
@@ -33,14 +62,21 @@ impl ProtocolSpawner {
     pub fn new(
         connectors: Connectors,
         lnd_connector_params: Option<LndConnectorParams>,
+        lnd_action_executor: Option<LndActionExecutor>,
+        wallets: Option<Wallets>,
+        metrics: Metrics,
         runtime_handle: Handle,
         storage: Storage,
     ) -> Self {
         Self {
             connectors,
             lnd_connector_params,
+            lnd_action_executor,
+            wallets,
+            metrics,
             runtime_handle,
             storage,
+            tasks: Arc::default(),
         }
     }
 
@@ -52,6 +88,51 @@ impl ProtocolSpawner {
             })),
         }
     }
+
+    /// Stop watching the ledgers for this swap.
+    pub fn abort(&self, id: LocalSwapId) {
+        let handles = self
+            .tasks
+            .lock()
+            .expect("no other thread panicked while holding the lock")
+            .remove(&id)
+            .map(|tasks| tasks.handles)
+            .unwrap_or_default();
+
+        for handle in handles {
+            handle.abort();
+        }
+    }
+
+    fn spawn_abortable(&self, id: LocalSwapId, task: impl Future<Output = ()> + Send + 'static) {
+        let (task, handle) = future::abortable(task);
+
+        {
+            let mut tasks = self
+                .tasks
+                .lock()
+                .expect("no other thread panicked while holding the lock");
+            let swap_tasks = tasks.entry(id).or_default();
+            swap_tasks.running += 1;
+            swap_tasks.handles.push(handle);
+        }
+
+        let tasks = Arc::clone(&self.tasks);
+        self.runtime_handle.spawn(async move {
+            let _ = task.await;
+
+            let mut tasks = tasks
+                .lock()
+                .expect("no other thread panicked while holding the lock");
+            // The entry is gone if the swap was aborted
+            if let Some(swap_tasks) = tasks.get_mut(&id) {
+                swap_tasks.running -= 1;
+                if swap_tasks.running == 0 {
+                    tasks.remove(&id);
+                }
+            }
+        });
+    }
 }
 
 impl Spawn<herc20::Params> for ProtocolSpawner {
@@ -63,6 +144,7 @@ impl Spawn<herc20::Params> for ProtocolSpawner {
         side: Side,
         role: Role,
     ) {
+        let expiry = params.expiry;
         let task = herc20::new(
             id,
             params,
@@ -73,7 +155,126 @@ impl Spawn<herc20::Params> for ProtocolSpawner {
             self.connectors.ethereum(),
         );
 
-        self.runtime_handle.spawn(task);
+        self.spawn_abortable(
+            id,
+            track_outcome(id, expiry, role, side, task, self.storage.clone()),
+        );
+
+        // Every swap involves a herc20 HTLC, hence this spawns the wallet
+        // actions exactly once per swap.
+        if let Some(wallets) = &self.wallets {
+            self.spawn_abortable(
+                id,
+                wallet_actions::execute(
+                    id,
+                    self.storage.clone(),
+                    self.connectors.clone(),
+                    wallets.clone(),
+                ),
+            );
+        }
+    }
+}
+
+/// Drives the herc20 protocol of a swap and records the outcome of the swap in
+/// the reputation of the counterparty.
+///
+/// Every swap we support involves exactly one herc20 HTLC, hence its final
+/// state tells us how the swap ended. The counterparty is only blamed for
+/// actions it missed itself, see [`missed_funding`] and [`missed_redeem`].
+async fn track_outcome(
+    id: LocalSwapId,
+    expiry: Timestamp,
+    role: Role,
+    side: Side,
+    task: impl Future<Output = ()>,
+    storage: Storage,
+) {
+    let seconds_left = u32::from(expiry).saturating_sub(u32::from(Timestamp::now()));
+    let expired = tokio::time::delay_for(Duration::from_secs(u64::from(seconds_left)));
+    futures::pin_mut!(task);
+
+    let outcome = match future::select(task, expired).await {
+        Either::Left(((), _)) => final_outcome(&storage, id, role, side).await,
+        Either::Right(((), task)) => match storage.herc20_states.get(&id).await {
+            Ok(Some(herc20::State::None)) | Ok(Some(herc20::State::Deployed { .. })) => {
+                if missed_funding(&storage, id, role, side).await {
+                    record_outcome(&storage, id, Outcome::Expired).await;
+                }
+                task.await;
+                return;
+            }
+            _ => {
+                task.await;
+                final_outcome(&storage, id, role, side).await
+            }
+        },
+    };
+
+    if let Some(outcome) = outcome {
+        record_outcome(&storage, id, outcome).await;
+    }
+}
+
+async fn final_outcome(
+    storage: &Storage,
+    id: LocalSwapId,
+    role: Role,
+    side: Side,
+) -> Option<Outcome> {
+    match storage.herc20_states.get(&id).await {
+        Ok(Some(herc20::State::Redeemed { .. })) => Some(Outcome::Completed),
+        Ok(Some(herc20::State::Refunded { .. })) if missed_redeem(role, side) => {
+            Some(Outcome::Refunded)
+        }
+        Ok(Some(herc20::State::Refunded { .. })) => None,
+        _ => Some(Outcome::Failed),
+    }
+}
+
+/// Whether the herc20 HTLC expired unfunded because the counterparty missed
+/// one of its fund actions.
+///
+/// Alice funds alpha first and Bob only funds beta after that. Hence, if Alice
+/// didn't fund alpha, the swap expired because of her.
+async fn missed_funding(storage: &Storage, id: LocalSwapId, role: Role, side: Side) -> bool {
+    match (role, side) {
+        (Role::Alice, Side::Alpha) => false,
+        (Role::Bob, Side::Alpha) => true,
+        (Role::Alice, Side::Beta) | (Role::Bob, Side::Beta) => {
+            match alpha_funded(storage, id).await {
+                Ok(alpha_funded) => alpha_funded == (role == Role::Alice),
+                Err(e) => {
+                    tracing::warn!(
+                        "failed to determine whether swap {} was funded: {:#}",
+                        id,
+                        e
+                    );
+                    false
+                }
+            }
+        }
+    }
+}
+
+async fn alpha_funded(storage: &Storage, id: LocalSwapId) -> anyhow::Result<bool> {
+    let swap: SwapContext = storage.load(id).await?;
+
+    storage.alpha_funded(swap).await
+}
+
+/// Whether a refunded herc20 HTLC means that the counterparty missed its
+/// redeem action.
+///
+/// Alice has to redeem beta first, only then can Bob redeem alpha. A refunded
+/// beta HTLC is therefore never Bob's fault.
+fn missed_redeem(role: Role, side: Side) -> bool {
+    !matches!((role, side), (Role::Alice, Side::Beta))
+}
+
+async fn record_outcome(storage: &Storage, id: LocalSwapId, outcome: Outcome) {
+    if let Err(e) = storage.record_swap_outcome(id, outcome).await {
+        tracing::warn!("failed to record outcome of swap {}: {:#}", id, e);
     }
 }
 
@@ -96,7 +297,7 @@ impl Spawn<hbit::Params> for ProtocolSpawner {
             self.connectors.bitcoin(),
         );
 
-        self.runtime_handle.spawn(task);
+        self.spawn_abortable(id, task);
     }
 }
 
@@ -122,25 +323,43 @@ impl Spawn<halbit::Params> for ProtocolSpawner {
 
         match (role, side) {
             (Role::Alice, Side::Alpha) | (Role::Bob, Side::Beta) => {
-                self.runtime_handle.spawn(halbit::new(
+                self.spawn_abortable(
                     id,
-                    params,
-                    role,
-                    side,
-                    self.storage.halbit_states.clone(),
-                    LndConnectorAsSender::from(lnd_connector_params.clone()),
-                ));
+                    halbit::new(
+                        id,
+                        params,
+                        role,
+                        side,
+                        self.storage.halbit_states.clone(),
+                        LndConnectorAsSender::from(lnd_connector_params.clone()),
+                    ),
+                );
             }
             (Role::Bob, Side::Alpha) | (Role::Alice, Side::Beta) => {
-                self.runtime_handle.spawn(halbit::new(
+                self.spawn_abortable(
                     id,
-                    params,
-                    role,
-                    side,
-                    self.storage.halbit_states.clone(),
-                    LndConnectorAsReceiver::from(lnd_connector_params.clone()),
-                ));
+                    halbit::new(
+                        id,
+                        params,
+                        role,
+                        side,
+                        self.storage.halbit_states.clone(),
+                        LndConnectorAsReceiver::from(lnd_connector_params.clone()),
+                    ),
+                );
             }
         }
+
+        if let Some(executor) = &self.lnd_action_executor {
+            self.spawn_abortable(
+                id,
+                lnd_actions::execute(
+                    id,
+                    self.storage.clone(),
+                    executor.clone(),
+                    self.metrics.clone(),
+                ),
+            );
+        }
     }
 }
@@ -2,19 +2,24 @@
 //!
 //! "Respawning" spawns refers to the feature of _spawning_ tasks into a runtime
 //! for watching the necessary ledgers of all swaps that we know about in the
-//! database which have not been completed yet.
+//! database which have not been completed yet. Cancelled swaps are not
+//! respawned.
 
 use crate::{
     protocol_spawner::ProtocolSpawner,
     spawn::spawn,
-    storage::{LoadAll, Storage},
+    storage::{cancelled_swaps, LoadAll, Storage, SwapContext},
 };
 
 /// Respawn the protocols for all swaps that are not yet done.
 pub async fn respawn(storage: Storage, spawner: ProtocolSpawner) -> anyhow::Result<()> {
-    let swaps = storage.load_all().await?;
+    let swaps: Vec<SwapContext> = storage.load_all().await?;
+    let cancelled = storage.db.do_in_transaction(cancelled_swaps).await?;
 
-    for swap in swaps {
+    for swap in swaps
+        .into_iter()
+        .filter(|swap| !cancelled.contains(&swap.id))
+    {
         let id = swap.id;
         match spawn(&spawner, &storage, swap).await {
             Err(e) => {
@@ -5,11 +5,15 @@ mod seed;
 use crate::{
     asset, halbit, hbit, herc20, identity,
     network::{WhatAliceLearnedFromBob, WhatBobLearnedFromAlice},
-    spawn, LocalSwapId, LockProtocol, Role, Side,
+    spawn,
+    state::Get,
+    LocalSwapId, LockProtocol, Role, Side,
 };
 use anyhow::Context;
 use async_trait::async_trait;
+use comit::reputation::Outcome;
 use diesel::{BelongingToDsl, ExpressionMethods, OptionalExtension, QueryDsl, RunQueryDsl};
+use libp2p::PeerId;
 use std::sync::Arc;
 
 use chrono::{DateTime, Utc};
@@ -82,6 +86,137 @@ impl Storage {
 
         identity::Bitcoin::from_secret_key(&*crate::SECP, &sk)
     }
+
+    /// Record that the given peer sent us malformed messages or invalid
+    /// orders.
+    pub async fn record_violation(&self, peer: PeerId) -> anyhow::Result<()> {
+        self.db
+            .do_in_transaction(|conn| Peer::get_or_insert(conn, &peer)?.record_violation(conn))
+            .await
+    }
+
+    /// Record the outcome of a swap in the reputation of its counterparty.
+    pub async fn record_swap_outcome(
+        &self,
+        id: LocalSwapId,
+        outcome: Outcome,
+    ) -> anyhow::Result<()> {
+        self.db
+            .do_in_transaction(|conn| db::record_swap_outcome(conn, id, outcome))
+            .await
+    }
+
+    /// Whether Alice funded the alpha HTLC of this swap, as far as we know.
+    ///
+    /// Swaps can only be cancelled until then.
+    pub async fn alpha_funded(&self, swap: SwapContext) -> anyhow::Result<bool> {
+        let funded = match swap.alpha {
+            LockProtocol::Herc20 => match self.herc20_states.get(&swap.id).await? {
+                None | Some(herc20::State::None) | Some(herc20::State::Deployed { .. }) => false,
+                Some(_) => true,
+            },
+            LockProtocol::Hbit => match self.hbit_states.get(&swap.id).await? {
+                None | Some(hbit::State::None) => false,
+                Some(_) => true,
+            },
+            LockProtocol::Halbit => match self.halbit_states.get(&swap.id).await? {
+                None | Some(halbit::State::None) | Some(halbit::State::Opened(_)) => false,
+                Some(_) => true,
+            },
+        };
+
+        Ok(funded)
+    }
+
+    /// Whether we redeemed or refunded this swap, as far as we know.
+    ///
+    /// That is the case once the HTLC we redeem from is redeemed or the HTLC we
+    /// funded is refunded.
+    pub async fn redeemed_or_refunded(&self, swap: SwapContext) -> anyhow::Result<bool> {
+        let (funded, redeemed) = match swap.role {
+            Role::Alice => (swap.alpha, swap.beta),
+            Role::Bob => (swap.beta, swap.alpha),
+        };
+
+        let refunded = match funded {
+            LockProtocol::Herc20 => matches!(
+                self.herc20_states.get(&swap.id).await?,
+                Some(herc20::State::Refunded { .. })
+            ),
+            LockProtocol::Hbit => matches!(
+                self.hbit_states.get(&swap.id).await?,
+                Some(hbit::State::Refunded { .. })
+            ),
+            LockProtocol::Halbit => matches!(
+                self.halbit_states.get(&swap.id).await?,
+                Some(halbit::State::Cancelled(_))
+            ),
+        };
+        let redeemed = match redeemed {
+            LockProtocol::Herc20 => matches!(
+                self.herc20_states.get(&swap.id).await?,
+                Some(herc20::State::Redeemed { .. })
+            ),
+            LockProtocol::Hbit => matches!(
+                self.hbit_states.get(&swap.id).await?,
+                Some(hbit::State::Redeemed { .. })
+            ),
+            LockProtocol::Halbit => matches!(
+                self.halbit_states.get(&swap.id).await?,
+                Some(halbit::State::Settled(_))
+            ),
+        };
+
+        Ok(refunded || redeemed)
+    }
+
+    /// The secret hash of a swap, derived from our seed if we are Alice.
+    pub async fn secret_hash(
+        &self,
+        id: LocalSwapId,
+        role: Role,
+    ) -> anyhow::Result<comit::SecretHash> {
+        let seed = self.seed;
+        self.db
+            .do_in_transaction(|conn| {
+                let secret_hash = schema::secret_hashes::table
+                    .filter(schema::secret_hashes::swap_id.eq_any(swap_id_fk!(id)))
+                    .first::<SecretHash>(conn)
+                    .optional()?;
+
+                derive_or_unwrap_secret_hash(id, seed, role, secret_hash)
+            })
+            .await
+    }
+
+    /// Find the swap that uses the given secret hash.
+    ///
+    /// Alice derives her secret hashes instead of storing them, hence we also
+    /// compare against the secret hashes of all swaps in which we are Alice.
+    pub async fn swap_by_secret_hash(
+        &self,
+        secret_hash: comit::SecretHash,
+    ) -> anyhow::Result<Option<LocalSwapId>> {
+        if let Some(id) = self
+            .db
+            .do_in_transaction(|conn| db::swap_by_secret_hash(conn, secret_hash))
+            .await?
+        {
+            return Ok(Some(id));
+        }
+
+        let swaps: Vec<SwapContext> = self.load_all().await?;
+        let id = swaps
+            .into_iter()
+            .filter(|swap| swap.role == Role::Alice)
+            .map(|swap| swap.id)
+            .find(|id| {
+                comit::SecretHash::new(self.seed.derive_swap_seed(*id).derive_secret())
+                    == secret_hash
+            });
+
+        Ok(id)
+    }
 }
 
 #[cfg(test)]
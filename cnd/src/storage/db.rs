@@ -73,6 +73,19 @@ impl Sqlite {
 
         Ok(result)
     }
+
+    /// Fails unless we can write to the database.
+    ///
+    /// The update doesn't match any row but SQLite still has to lock the
+    /// database for writing to execute it.
+    pub async fn check_writable(&self) -> anyhow::Result<()> {
+        self.do_in_transaction(|conn| {
+            diesel::sql_query("UPDATE swaps SET id = id WHERE 0").execute(conn)?;
+
+            Ok(())
+        })
+        .await
+    }
 }
 
 #[cfg(test)]
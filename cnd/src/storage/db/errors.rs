@@ -55,3 +55,11 @@ pub struct NoRefundIdentity;
 #[derive(thiserror::Error, Debug, Clone, Copy)]
 #[error("Order {0} is no longer open and can therefore not be cancelled")]
 pub struct NotOpen(pub OrderId);
+
+#[derive(thiserror::Error, Debug, Clone, Copy)]
+#[error("swap {0} was already funded and can therefore not be cancelled")]
+pub struct AlreadyFunded(pub LocalSwapId);
+
+#[derive(thiserror::Error, Debug, Clone, Copy)]
+#[error("swap {0} was cancelled")]
+pub struct SwapCancelled(pub LocalSwapId);
@@ -124,10 +124,127 @@ table! {
     }
 }
 
+table! {
+    peers {
+        id -> Integer,
+        peer_id -> Text,
+        successful_dials -> Integer,
+        failed_dials -> Integer,
+        last_seen_at -> Nullable<BigInt>,
+        last_order_at -> Nullable<BigInt>,
+        violations -> Integer,
+        banned -> Bool,
+    }
+}
+
+table! {
+    peer_addresses {
+        id -> Integer,
+        peer_id -> Integer,
+        address -> Text,
+        last_seen_at -> BigInt,
+    }
+}
+
+table! {
+    swap_outcomes {
+        id -> Integer,
+        swap_id -> Integer,
+        outcome -> Text,
+    }
+}
+
+table! {
+    swap_cancellations {
+        id -> Integer,
+        swap_id -> Integer,
+        cancelled_by -> Text,
+        cancelled_at -> BigInt,
+    }
+}
+
+table! {
+    fund_handouts {
+        id -> Integer,
+        swap_id -> Integer,
+        handed_out_at -> BigInt,
+    }
+}
+
+table! {
+    webhook_deliveries {
+        id -> Integer,
+        url -> Text,
+        payload -> Text,
+        attempts -> Integer,
+        next_attempt_at -> BigInt,
+    }
+}
+
+table! {
+    wallet_birth_heights {
+        id -> Integer,
+        wallet -> Text,
+        height -> BigInt,
+    }
+}
+
+table! {
+    webhook_events {
+        id -> Integer,
+        event -> Text,
+        subject -> Text,
+        data -> Text,
+    }
+}
+
+table! {
+    wallet_executions {
+        id -> Integer,
+        swap_id -> Integer,
+        action -> Text,
+        succeeded -> Bool,
+        executed_at -> BigInt,
+    }
+}
+
+table! {
+    hbit_spends {
+        id -> Integer,
+        swap_id -> Integer,
+        kind -> Text,
+        fee_rate -> BigInt,
+    }
+}
+
+table! {
+    lnd_executions {
+        id -> Integer,
+        swap_id -> Integer,
+        action -> Text,
+        succeeded -> Bool,
+        executed_at -> BigInt,
+    }
+}
+
 allow_tables_to_appear_in_same_query!(swaps, halbits);
 allow_tables_to_appear_in_same_query!(swaps, herc20s);
 allow_tables_to_appear_in_same_query!(swaps, hbits);
 allow_tables_to_appear_in_same_query!(halbits, herc20s);
 allow_tables_to_appear_in_same_query!(hbits, herc20s);
 allow_tables_to_appear_in_same_query!(orders, btc_dai_orders);
+allow_tables_to_appear_in_same_query!(peers, peer_addresses);
+allow_tables_to_appear_in_same_query!(swaps, swap_outcomes);
+allow_tables_to_appear_in_same_query!(swaps, swap_cancellations);
+allow_tables_to_appear_in_same_query!(swaps, fund_handouts);
+allow_tables_to_appear_in_same_query!(swaps, wallet_executions);
+allow_tables_to_appear_in_same_query!(swaps, lnd_executions);
+allow_tables_to_appear_in_same_query!(swaps, hbit_spends);
 joinable!(btc_dai_orders -> orders (order_id));
+joinable!(peer_addresses -> peers (peer_id));
+joinable!(swap_outcomes -> swaps (swap_id));
+joinable!(swap_cancellations -> swaps (swap_id));
+joinable!(fund_handouts -> swaps (swap_id));
+joinable!(wallet_executions -> swaps (swap_id));
+joinable!(lnd_executions -> swaps (swap_id));
+joinable!(hbit_spends -> swaps (swap_id));
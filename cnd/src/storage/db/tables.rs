@@ -17,28 +17,46 @@ macro_rules! swap_id_fk {
 }
 
 mod btc_dai_orders;
+mod fund_handouts;
 mod halbits;
+mod hbit_spends;
 mod hbits;
 mod herc20s;
+mod lnd_executions;
 mod order_hbit_params;
 mod order_herc20_params;
 mod order_swaps;
 mod orders;
+mod peers;
 mod secret_hashes;
+mod swap_cancellations;
 mod swap_contexts;
 mod swaps;
+mod wallet_birth_heights;
+mod wallet_executions;
+mod webhook_deliveries;
+mod webhook_events;
 
 pub use btc_dai_orders::{all_open_btc_dai_orders, BtcDaiOrder, InsertableBtcDaiOrder};
+pub use fund_handouts::FundHandout;
 pub use halbits::{Halbit, InsertableHalbit};
+pub use hbit_spends::HbitSpend;
 pub use hbits::{Hbit, InsertableHbit};
 pub use herc20s::{Herc20, InsertableHerc20};
+pub use lnd_executions::LndExecution;
 pub use order_hbit_params::{InsertableOrderHbitParams, OrderHbitParams};
 pub use order_herc20_params::{InsertableOrderHerc20Params, OrderHerc20Params};
 pub use order_swaps::{InsertableOrderSwap, OrderSwap};
 pub use orders::{InsertableOrder, Order};
-pub use secret_hashes::{InsertableSecretHash, SecretHash};
+pub use peers::{counterparties_of_active_swaps, record_swap_outcome, Peer, PeerAddress};
+pub use secret_hashes::{swap_by_secret_hash, InsertableSecretHash, SecretHash};
+pub use swap_cancellations::{cancel_swap, cancelled_swaps, SwapCancellation};
 pub use swap_contexts::SwapContext;
-pub use swaps::{InsertableSwap, Swap};
+pub use swaps::{InsertableSwap, SortOrder, Swap, SwapFilter, SwapStatus};
+pub use wallet_birth_heights::WalletBirthHeight;
+pub use wallet_executions::WalletExecution;
+pub use webhook_deliveries::WebhookDelivery;
+pub use webhook_events::WebhookEvent;
 
 pub trait IntoInsertable {
     type Insertable;
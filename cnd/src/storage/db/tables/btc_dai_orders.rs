@@ -47,18 +47,34 @@ impl BtcDaiOrder {
         Ok(params)
     }
 
-    /// Move the amount that is settling from open to settling.
+    /// Move the quantity of a fill from open to settling.
     ///
-    /// Whilst we don't have partial order matching, this simply means updating
-    /// `settling` to the amount of `open` and updating `open` to `0`.
-    ///
-    /// Once we implement partial order matching, this will need to get more
-    /// sophisticated.
-    pub fn set_to_settling(&self, conn: &SqliteConnection) -> Result<()> {
+    /// With partial order matching, an order may be filled by several swaps.
+    /// Each of them moves its quantity from `open` to `settling`.
+    pub fn set_to_settling(
+        &self,
+        conn: &SqliteConnection,
+        quantity: Quantity<bitcoin::Bitcoin>,
+    ) -> Result<()> {
+        let quantity = quantity.to_inner();
+        let open = self
+            .open
+            .to_inner()
+            .checked_sub(quantity)
+            .with_context(|| {
+                format!(
+                    "cannot settle {} of order {} because only {} are open",
+                    quantity,
+                    self.order_id,
+                    self.open.to_inner()
+                )
+            })?;
+        let settling = self.settling.to_inner() + quantity;
+
         let affected_rows = diesel::update(self)
             .set((
-                btc_dai_orders::settling.eq(Text::<Satoshis>(self.open.to_inner().into())),
-                btc_dai_orders::open.eq(Text::<Satoshis>(bitcoin::Bitcoin::ZERO.into())),
+                btc_dai_orders::settling.eq(Text::<Satoshis>(settling.into())),
+                btc_dai_orders::open.eq(Text::<Satoshis>(open.into())),
             ))
             .execute(conn)?;
 
@@ -69,6 +85,121 @@ impl BtcDaiOrder {
         Ok(())
     }
 
+    /// Move the quantity of a cancelled swap back from settling.
+    ///
+    /// The quantity is open again unless the order was cancelled in the
+    /// meantime, in which case it is cancelled as well.
+    pub fn release_settling(
+        &self,
+        conn: &SqliteConnection,
+        quantity: Quantity<bitcoin::Bitcoin>,
+    ) -> Result<()> {
+        let quantity = quantity.to_inner();
+        let settling = self
+            .settling
+            .to_inner()
+            .checked_sub(quantity)
+            .with_context(|| {
+                format!(
+                    "cannot release {} of order {} because only {} are settling",
+                    quantity,
+                    self.order_id,
+                    self.settling.to_inner()
+                )
+            })?;
+        let settling = btc_dai_orders::settling.eq(Text::<Satoshis>(settling.into()));
+
+        let affected_rows = if self.cancelled == Quantity::new(bitcoin::Bitcoin::ZERO) {
+            let open = self.open.to_inner() + quantity;
+
+            diesel::update(self)
+                .set((
+                    settling,
+                    btc_dai_orders::open.eq(Text::<Satoshis>(open.into())),
+                ))
+                .execute(conn)?
+        } else {
+            let cancelled = self.cancelled.to_inner() + quantity;
+
+            diesel::update(self)
+                .set((
+                    settling,
+                    btc_dai_orders::cancelled.eq(Text::<Satoshis>(cancelled.into())),
+                ))
+                .execute(conn)?
+        };
+
+        if affected_rows == 0 {
+            anyhow::bail!(
+                "failed to release settling quantity of order {}",
+                self.order_id
+            )
+        }
+
+        Ok(())
+    }
+
+    /// Change the price of the order.
+    ///
+    /// Swaps that are already set up keep the price they were set up with.
+    pub fn set_price(
+        &self,
+        conn: &SqliteConnection,
+        price: &Price<bitcoin::Bitcoin, Erc20Quantity>,
+    ) -> Result<()> {
+        let affected_rows = diesel::update(self)
+            .set(btc_dai_orders::price.eq(Text::<Erc20Amount>(price.wei_per_sat().into())))
+            .execute(conn)?;
+
+        if affected_rows == 0 {
+            anyhow::bail!("failed to change price of order {}", self.order_id)
+        }
+
+        Ok(())
+    }
+
+    /// Change the open quantity of the order by the difference between `from`
+    /// and `to`, the total quantity changes by the same amount.
+    ///
+    /// `from` is the quantity the order pool knew of. The open quantity in
+    /// here may still be larger if a swap was set up but not saved yet.
+    pub fn resize(
+        &self,
+        conn: &SqliteConnection,
+        from: Quantity<bitcoin::Bitcoin>,
+        to: Quantity<bitcoin::Bitcoin>,
+    ) -> Result<()> {
+        let resize = |quantity: Quantity<bitcoin::Bitcoin>| {
+            quantity
+                .to_inner()
+                .checked_sub(from.to_inner())
+                .map(|rest| rest + to.to_inner())
+                .with_context(|| {
+                    format!(
+                        "cannot resize order {} from {} because only {} are open",
+                        self.order_id,
+                        from.to_inner(),
+                        self.open.to_inner()
+                    )
+                })
+        };
+        let open = resize(self.open)?;
+        let quantity = resize(self.quantity)?;
+
+        let affected_rows = diesel::update(self)
+            .set((
+                btc_dai_orders::quantity.eq(Text::<Satoshis>(quantity.into())),
+                btc_dai_orders::open.eq(Text::<Satoshis>(open.into())),
+            ))
+            .execute(conn)?;
+
+        if affected_rows == 0 {
+            anyhow::bail!("failed to resize order {}", self.order_id)
+        }
+
+        Ok(())
+    }
+
     pub fn set_to_cancelled(&self, conn: &SqliteConnection) -> Result<()> {
         if self.open == Quantity::new(bitcoin::Bitcoin::ZERO) {
             let order = Order::by_id(conn, self.order_id)?;
@@ -135,3 +266,119 @@ pub fn all_open_btc_dai_orders(conn: &SqliteConnection) -> Result<Vec<(Order, Bt
 
     Ok(orders)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{InsertableOrder, Sqlite};
+    use comit::{asset::ethereum::FromWei, OrderId, Position};
+    use time::OffsetDateTime;
+    use tokio::runtime::Runtime;
+
+    fn sats(sats: u64) -> Quantity<bitcoin::Bitcoin> {
+        Quantity::new(bitcoin::Bitcoin::from_sat(sats))
+    }
+
+    fn wei_per_sat(wei: u64) -> Price<bitcoin::Bitcoin, Erc20Quantity> {
+        Price::from_wei_per_sat(Erc20Quantity::from_wei(wei))
+    }
+
+    fn insert_order(db: &Sqlite, runtime: &mut Runtime, quantity: u64) -> Order {
+        runtime
+            .block_on(db.do_in_transaction(|conn| {
+                let order_pk = InsertableOrder::new(
+                    OrderId::random(),
+                    Position::Buy,
+                    OffsetDateTime::now_utc(),
+                )
+                .insert(conn)?;
+                InsertableBtcDaiOrder::new(
+                    order_pk,
+                    bitcoin::Bitcoin::from_sat(quantity),
+                    Erc20Quantity::from_wei(90u64),
+                )
+                .insert(conn)?;
+
+                Order::by_id(conn, order_pk)
+            }))
+            .unwrap()
+    }
+
+    fn load(db: &Sqlite, runtime: &mut Runtime, order: Order) -> BtcDaiOrder {
+        runtime
+            .block_on(db.do_in_transaction(|conn| BtcDaiOrder::by_order(conn, &order)))
+            .unwrap()
+    }
+
+    #[test]
+    fn set_price_only_changes_the_price() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let order = insert_order(&db, &mut runtime, 100_000);
+        let before = load(&db, &mut runtime, order);
+
+        runtime
+            .block_on(db.do_in_transaction(|conn| {
+                BtcDaiOrder::by_order(conn, &order)?.set_price(conn, &wei_per_sat(100))
+            }))
+            .unwrap();
+
+        let after = load(&db, &mut runtime, order);
+        assert_eq!(after.price, wei_per_sat(100));
+        assert_eq!(after.quantity, before.quantity);
+        assert_eq!(after.open, before.open);
+    }
+
+    #[test]
+    fn resize_changes_open_and_total_quantity_by_the_difference() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let order = insert_order(&db, &mut runtime, 100_000);
+
+        runtime
+            .block_on(db.do_in_transaction(|conn| {
+                BtcDaiOrder::by_order(conn, &order)?.set_to_settling(conn, sats(40_000))?;
+                BtcDaiOrder::by_order(conn, &order)?.resize(conn, sats(60_000), sats(30_000))
+            }))
+            .unwrap();
+
+        let after = load(&db, &mut runtime, order);
+        assert_eq!(after.quantity, sats(70_000));
+        assert_eq!(after.open, sats(30_000));
+        assert_eq!(after.settling, sats(40_000));
+    }
+
+    #[test]
+    fn resize_keeps_open_quantity_of_swaps_not_yet_saved() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let order = insert_order(&db, &mut runtime, 100_000);
+
+        // The order pool already moved 40_000 to a swap that is not saved yet.
+        runtime
+            .block_on(db.do_in_transaction(|conn| {
+                BtcDaiOrder::by_order(conn, &order)?.resize(conn, sats(60_000), sats(80_000))
+            }))
+            .unwrap();
+
+        let after = load(&db, &mut runtime, order);
+        assert_eq!(after.quantity, sats(120_000));
+        assert_eq!(after.open, sats(120_000));
+    }
+
+    #[test]
+    fn resize_fails_if_less_is_open_than_known_to_the_order_pool() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let order = insert_order(&db, &mut runtime, 100_000);
+
+        let result = runtime.block_on(db.do_in_transaction(|conn| {
+            BtcDaiOrder::by_order(conn, &order)?.resize(conn, sats(200_000), sats(50_000))
+        }));
+
+        assert!(result.is_err());
+        let after = load(&db, &mut runtime, order);
+        assert_eq!(after.quantity, sats(100_000));
+        assert_eq!(after.open, sats(100_000));
+    }
+}
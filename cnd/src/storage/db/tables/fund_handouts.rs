@@ -0,0 +1,120 @@
+use crate::{
+    local_swap_id::LocalSwapId,
+    storage::{
+        db::schema::fund_handouts,
+        tables::{Swap, SwapCancellation},
+        NoSwapExists, SwapCancelled,
+    },
+};
+use anyhow::{Context, Result};
+use diesel::{prelude::*, SqliteConnection};
+use time::OffsetDateTime;
+
+/// The first deploy or fund action of a swap that was handed out to the user
+/// or executed by our wallets.
+///
+/// From then on our funds might be locked up in an HTLC, hence the swap can
+/// no longer be cancelled.
+#[derive(Associations, Clone, Copy, Debug, Identifiable, Queryable, PartialEq)]
+#[belongs_to(Swap)]
+#[table_name = "fund_handouts"]
+pub struct FundHandout {
+    id: i32,
+    swap_id: i32,
+    pub handed_out_at: i64,
+}
+
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "fund_handouts"]
+struct InsertableFundHandout {
+    swap_id: i32,
+    handed_out_at: i64,
+}
+
+impl FundHandout {
+    /// Record that a deploy or fund action of the swap is handed out.
+    ///
+    /// Fails if the swap was cancelled, in which case the action must not be
+    /// handed out anymore.
+    pub fn record(
+        conn: &SqliteConnection,
+        swap_id: LocalSwapId,
+        handed_out_at: OffsetDateTime,
+    ) -> Result<()> {
+        if SwapCancellation::by_swap_id(conn, swap_id)?.is_some() {
+            anyhow::bail!(SwapCancelled(swap_id))
+        }
+
+        if Self::by_swap_id(conn, swap_id)?.is_some() {
+            return Ok(());
+        }
+
+        let swap_fk = swap_id_fk!(swap_id)
+            .first::<i32>(conn)
+            .context(NoSwapExists(swap_id))?;
+
+        diesel::insert_into(fund_handouts::table)
+            .values(InsertableFundHandout {
+                swap_id: swap_fk,
+                handed_out_at: handed_out_at.timestamp(),
+            })
+            .execute(conn)
+            .with_context(|| format!("failed to record fund handout for {}", swap_id))?;
+
+        Ok(())
+    }
+
+    pub fn by_swap_id(conn: &SqliteConnection, swap_id: LocalSwapId) -> Result<Option<Self>> {
+        let handout = fund_handouts::table
+            .filter(fund_handouts::swap_id.eq_any(swap_id_fk!(swap_id)))
+            .first::<Self>(conn)
+            .optional()?;
+
+        Ok(handout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{cancel_swap, tables::InsertableSwap, Sqlite};
+    use chrono::NaiveDateTime;
+    use comit::Role;
+    use libp2p::PeerId;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn fund_actions_of_cancelled_swaps_are_not_handed_out() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let swap_id = LocalSwapId::random();
+        let cancelled_swap_id = LocalSwapId::random();
+        let now = OffsetDateTime::now_utc();
+
+        runtime
+            .block_on(db.do_in_transaction(|conn| {
+                for id in &[swap_id, cancelled_swap_id] {
+                    let start_of_swap = NaiveDateTime::from_timestamp(now.timestamp(), 0);
+                    InsertableSwap::new(*id, PeerId::random(), Role::Alice, start_of_swap)
+                        .insert(conn)?;
+                }
+                cancel_swap(conn, cancelled_swap_id, Role::Bob, now)?;
+
+                Ok(())
+            }))
+            .unwrap();
+
+        let handed_out = runtime.block_on(db.do_in_transaction(|conn| {
+            FundHandout::record(conn, swap_id, now)?;
+            FundHandout::record(conn, swap_id, now)?;
+
+            FundHandout::by_swap_id(conn, swap_id)
+        }));
+        let cancelled = runtime.block_on(
+            db.do_in_transaction(|conn| FundHandout::record(conn, cancelled_swap_id, now)),
+        );
+
+        assert!(handed_out.unwrap().is_some());
+        assert!(cancelled.unwrap_err().is::<SwapCancelled>());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    bitcoin::SatPerVbyte,
+    hbit::{Spend, SpendKind},
+    local_swap_id::LocalSwapId,
+    storage::{db::schema::hbit_spends, tables::Swap, NoSwapExists, Text},
+};
+use anyhow::{Context, Result};
+use diesel::{prelude::*, SqliteConnection};
+use std::convert::TryFrom;
+
+/// A transaction spending from an hbit HTLC that was handed out to the user.
+#[derive(Associations, Clone, Copy, Debug, Identifiable, Queryable, PartialEq)]
+#[belongs_to(Swap)]
+#[table_name = "hbit_spends"]
+pub struct HbitSpend {
+    id: i32,
+    swap_id: i32,
+    #[diesel(deserialize_as = "Text<SpendKind>")]
+    pub kind: SpendKind,
+    pub fee_rate: i64,
+}
+
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "hbit_spends"]
+struct InsertableHbitSpend {
+    swap_id: i32,
+    kind: Text<SpendKind>,
+    fee_rate: i64,
+}
+
+impl HbitSpend {
+    pub fn record(conn: &SqliteConnection, swap_id: LocalSwapId, spend: Spend) -> Result<()> {
+        let swap_fk = swap_id_fk!(swap_id)
+            .first::<i32>(conn)
+            .context(NoSwapExists(swap_id))?;
+
+        diesel::insert_into(hbit_spends::table)
+            .values(InsertableHbitSpend {
+                swap_id: swap_fk,
+                kind: Text(spend.kind),
+                fee_rate: i64::try_from(spend.fee_rate.as_u64())?,
+            })
+            .execute(conn)
+            .with_context(|| format!("failed to record {} spend for {}", spend.kind, swap_id))?;
+
+        Ok(())
+    }
+
+    /// The spend that was handed out last for the swap, if any.
+    pub fn latest(conn: &SqliteConnection, swap_id: LocalSwapId) -> Result<Option<Spend>> {
+        let latest = hbit_spends::table
+            .filter(hbit_spends::swap_id.eq_any(swap_id_fk!(swap_id)))
+            .order(hbit_spends::id.desc())
+            .first::<Self>(conn)
+            .optional()?;
+
+        match latest {
+            Some(spend) => Ok(Some(Spend {
+                kind: spend.kind,
+                fee_rate: SatPerVbyte::new(u64::try_from(spend.fee_rate)?),
+            })),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{tables::InsertableSwap, Sqlite};
+    use chrono::NaiveDateTime;
+    use comit::Role;
+    use libp2p::PeerId;
+    use time::OffsetDateTime;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn latest_spend_is_the_one_recorded_last_for_the_swap() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let swap_id = LocalSwapId::random();
+        let other_swap_id = LocalSwapId::random();
+        let now = OffsetDateTime::now_utc();
+        let spend = |fee_rate| Spend {
+            kind: SpendKind::Redeem,
+            fee_rate: SatPerVbyte::new(fee_rate),
+        };
+
+        let (latest, other_latest) = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                for id in &[swap_id, other_swap_id] {
+                    let start_of_swap = NaiveDateTime::from_timestamp(now.timestamp(), 0);
+                    InsertableSwap::new(*id, PeerId::random(), Role::Alice, start_of_swap)
+                        .insert(conn)?;
+                }
+
+                HbitSpend::record(conn, swap_id, spend(10))?;
+                HbitSpend::record(conn, swap_id, spend(13))?;
+
+                Ok((
+                    HbitSpend::latest(conn, swap_id)?,
+                    HbitSpend::latest(conn, other_swap_id)?,
+                ))
+            }))
+            .unwrap();
+
+        assert_eq!(latest, Some(spend(13)));
+        assert_eq!(other_latest, None);
+    }
+}
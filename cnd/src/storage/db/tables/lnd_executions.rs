@@ -0,0 +1,184 @@
+use crate::{
+    lnd_actions::ActionKind,
+    local_swap_id::LocalSwapId,
+    storage::{db::schema::lnd_executions, tables::Swap, NoSwapExists, Text},
+};
+use anyhow::{Context, Result};
+use diesel::{prelude::*, SqliteConnection};
+use time::OffsetDateTime;
+
+/// A lightning action of a swap that cnd executed against LND.
+#[derive(Associations, Clone, Copy, Debug, Identifiable, Queryable, PartialEq)]
+#[belongs_to(Swap)]
+#[table_name = "lnd_executions"]
+pub struct LndExecution {
+    id: i32,
+    swap_id: i32,
+    #[diesel(deserialize_as = "Text<ActionKind>")]
+    pub action: ActionKind,
+    pub succeeded: bool,
+    pub executed_at: i64,
+}
+
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "lnd_executions"]
+struct InsertableLndExecution {
+    swap_id: i32,
+    action: Text<ActionKind>,
+    succeeded: bool,
+    executed_at: i64,
+}
+
+impl LndExecution {
+    pub fn record(
+        conn: &SqliteConnection,
+        swap_id: LocalSwapId,
+        action: ActionKind,
+        succeeded: bool,
+        executed_at: OffsetDateTime,
+    ) -> Result<()> {
+        let swap_fk = swap_id_fk!(swap_id)
+            .first::<i32>(conn)
+            .context(NoSwapExists(swap_id))?;
+
+        diesel::insert_into(lnd_executions::table)
+            .values(InsertableLndExecution {
+                swap_id: swap_fk,
+                action: Text(action),
+                succeeded,
+                executed_at: executed_at.timestamp(),
+            })
+            .execute(conn)
+            .with_context(|| format!("failed to record execution of {} for {}", action, swap_id))?;
+
+        Ok(())
+    }
+
+    /// All executions of lightning actions of the swap, in the order they
+    /// happened.
+    pub fn by_swap_id(conn: &SqliteConnection, swap_id: LocalSwapId) -> Result<Vec<Self>> {
+        let executions = lnd_executions::table
+            .filter(lnd_executions::swap_id.eq_any(swap_id_fk!(swap_id)))
+            .order(lnd_executions::id.asc())
+            .load::<Self>(conn)?;
+
+        Ok(executions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lnd_actions::Progress,
+        storage::{tables::InsertableSwap, Sqlite},
+    };
+    use chrono::NaiveDateTime;
+    use comit::Role;
+    use libp2p::PeerId;
+    use std::time::Duration;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn executions_survive_reloading_the_swap() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let swap_id = LocalSwapId::random();
+        let now = OffsetDateTime::now_utc();
+
+        runtime
+            .block_on(db.do_in_transaction(|conn| {
+                let start_of_swap = NaiveDateTime::from_timestamp(now.timestamp(), 0);
+                InsertableSwap::new(swap_id, PeerId::random(), Role::Bob, start_of_swap)
+                    .insert(conn)?;
+
+                LndExecution::record(conn, swap_id, ActionKind::LndAddHoldInvoice, true, now)?;
+                LndExecution::record(conn, swap_id, ActionKind::LndSettleInvoice, false, now)
+            }))
+            .unwrap();
+
+        let executions = runtime
+            .block_on(db.do_in_transaction(|conn| LndExecution::by_swap_id(conn, swap_id)))
+            .unwrap();
+
+        let executions = executions
+            .iter()
+            .map(|execution| (execution.action, execution.succeeded))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            executions,
+            vec![
+                (ActionKind::LndAddHoldInvoice, true),
+                (ActionKind::LndSettleInvoice, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_actions_are_retried_until_cnd_gives_up() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let swap_id = LocalSwapId::random();
+        let now = OffsetDateTime::now_utc();
+        let kind = ActionKind::LndSendPayment;
+
+        let progress = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                let start_of_swap = NaiveDateTime::from_timestamp(now.timestamp(), 0);
+                InsertableSwap::new(swap_id, PeerId::random(), Role::Bob, start_of_swap)
+                    .insert(conn)?;
+
+                let mut progress = vec![progress_of(conn, swap_id, kind)?];
+                for _ in 0..5 {
+                    LndExecution::record(conn, swap_id, kind, false, now)?;
+                    progress.push(progress_of(conn, swap_id, kind)?);
+                }
+
+                Ok(progress)
+            }))
+            .unwrap();
+
+        let failed_at = OffsetDateTime::from_unix_timestamp(now.timestamp());
+        assert_eq!(progress[0], Progress::Pending { retry_at: None });
+        assert_eq!(progress[1], Progress::Pending {
+            retry_at: Some(failed_at + Duration::from_secs(10))
+        });
+        assert_eq!(progress[4], Progress::Pending {
+            retry_at: Some(failed_at + Duration::from_secs(80))
+        });
+        assert_eq!(progress[5], Progress::GaveUp);
+    }
+
+    #[test]
+    fn action_that_succeeded_once_is_not_retried() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let swap_id = LocalSwapId::random();
+        let now = OffsetDateTime::now_utc();
+        let kind = ActionKind::LndAddHoldInvoice;
+
+        let executions = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                let start_of_swap = NaiveDateTime::from_timestamp(now.timestamp(), 0);
+                InsertableSwap::new(swap_id, PeerId::random(), Role::Bob, start_of_swap)
+                    .insert(conn)?;
+
+                LndExecution::record(conn, swap_id, kind, false, now)?;
+                LndExecution::record(conn, swap_id, kind, true, now)?;
+                LndExecution::by_swap_id(conn, swap_id)
+            }))
+            .unwrap();
+
+        assert_eq!(Progress::of(&executions, kind), Progress::Succeeded);
+    }
+
+    fn progress_of(
+        conn: &SqliteConnection,
+        swap_id: LocalSwapId,
+        kind: ActionKind,
+    ) -> Result<Progress> {
+        let executions = LndExecution::by_swap_id(conn, swap_id)?;
+
+        Ok(Progress::of(&executions, kind))
+    }
+}
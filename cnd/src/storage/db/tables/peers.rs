@@ -0,0 +1,328 @@
+use crate::{
+    local_swap_id::LocalSwapId,
+    storage::{
+        db::schema::{herc20s, peer_addresses, peers, swap_outcomes, swaps},
+        NoSwapExists, Text,
+    },
+};
+use anyhow::{Context, Result};
+use comit::reputation::{Outcome, Reputation};
+use diesel::{prelude::*, SqliteConnection};
+use libp2p::{Multiaddr, PeerId};
+use std::convert::TryFrom;
+use time::OffsetDateTime;
+
+/// An entry of our address book.
+#[derive(Associations, Clone, Debug, Identifiable, Queryable, PartialEq)]
+#[table_name = "peers"]
+pub struct Peer {
+    id: i32,
+    #[diesel(deserialize_as = "Text<PeerId>")]
+    pub peer_id: PeerId,
+    pub successful_dials: i32,
+    pub failed_dials: i32,
+    /// Unix timestamp of the last time we successfully dialled this peer.
+    pub last_seen_at: Option<i64>,
+    /// Unix timestamp of the last time we saw orders of this peer.
+    pub last_order_at: Option<i64>,
+    /// How many malformed messages or invalid orders this peer sent us.
+    pub violations: i32,
+    pub banned: bool,
+}
+
+/// An address under which we reached a peer before.
+#[derive(Associations, Clone, Debug, Identifiable, Queryable, PartialEq)]
+#[belongs_to(Peer)]
+#[table_name = "peer_addresses"]
+pub struct PeerAddress {
+    id: i32,
+    pub peer_id: i32,
+    #[diesel(deserialize_as = "Text<Multiaddr>")]
+    pub address: Multiaddr,
+    pub last_seen_at: i64,
+}
+
+impl Peer {
+    /// Load the entry of the given peer, creating an empty one if we don't
+    /// know the peer yet.
+    pub fn get_or_insert(conn: &SqliteConnection, peer: &PeerId) -> Result<Self> {
+        diesel::insert_or_ignore_into(peers::table)
+            .values(InsertablePeer::new(peer.clone()))
+            .execute(conn)?;
+
+        let peer = peers::table
+            .filter(peers::peer_id.eq(Text(peer.clone())))
+            .first::<Peer>(conn)
+            .with_context(|| format!("failed to load address book entry of {}", peer))?;
+
+        Ok(peer)
+    }
+
+    pub fn all(conn: &SqliteConnection) -> Result<Vec<Self>> {
+        let peers = peers::table.load::<Peer>(conn)?;
+
+        Ok(peers)
+    }
+
+    /// All peers we saw orders of at or after the given point in time.
+    pub fn with_orders_since(conn: &SqliteConnection, since: OffsetDateTime) -> Result<Vec<Self>> {
+        let peers = peers::table
+            .filter(peers::last_order_at.ge(since.timestamp()))
+            .load::<Peer>(conn)?;
+
+        Ok(peers)
+    }
+
+    /// The addresses we reached this peer under, most recent first.
+    pub fn addresses(&self, conn: &SqliteConnection) -> Result<Vec<PeerAddress>> {
+        let addresses = PeerAddress::belonging_to(self)
+            .order(peer_addresses::last_seen_at.desc())
+            .load::<PeerAddress>(conn)?;
+
+        Ok(addresses)
+    }
+
+    /// Record that we successfully dialled this peer under the given address.
+    pub fn record_connection(
+        &self,
+        conn: &SqliteConnection,
+        address: &Multiaddr,
+        at: OffsetDateTime,
+    ) -> Result<()> {
+        diesel::update(self)
+            .set((
+                peers::successful_dials.eq(peers::successful_dials + 1),
+                peers::last_seen_at.eq(at.timestamp()),
+            ))
+            .execute(conn)?;
+
+        diesel::insert_or_ignore_into(peer_addresses::table)
+            .values(InsertablePeerAddress {
+                peer_id: self.id,
+                address: Text(address.clone()),
+                last_seen_at: at.timestamp(),
+            })
+            .execute(conn)?;
+        diesel::update(
+            PeerAddress::belonging_to(self)
+                .filter(peer_addresses::address.eq(Text(address.clone()))),
+        )
+        .set(peer_addresses::last_seen_at.eq(at.timestamp()))
+        .execute(conn)
+        .with_context(|| format!("failed to record address {} of {}", address, self.peer_id))?;
+
+        Ok(())
+    }
+
+    /// The reputation of this peer, based on the outcomes of our swaps with
+    /// them and the protocol violations they committed.
+    pub fn reputation(&self, conn: &SqliteConnection) -> Result<Reputation> {
+        let swaps_with_peer = swaps::table
+            .filter(swaps::counterparty_peer_id.eq(Text(self.peer_id.clone())))
+            .select(swaps::id);
+
+        let outcomes = swap_outcomes::table
+            .filter(swap_outcomes::swap_id.eq_any(swaps_with_peer))
+            .select(swap_outcomes::outcome)
+            .load::<Text<Outcome>>(conn)?;
+
+        let mut reputation = Reputation {
+            violations: u32::try_from(self.violations).unwrap_or_default(),
+            banned: self.banned,
+            ..Reputation::default()
+        };
+        for outcome in outcomes {
+            reputation.record(outcome.0);
+        }
+
+        Ok(reputation)
+    }
+
+    pub fn record_violation(&self, conn: &SqliteConnection) -> Result<()> {
+        diesel::update(self)
+            .set(peers::violations.eq(peers::violations + 1))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub fn set_banned(&self, conn: &SqliteConnection, banned: bool) -> Result<()> {
+        diesel::update(self)
+            .set(peers::banned.eq(banned))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub fn record_dial_failure(&self, conn: &SqliteConnection) -> Result<()> {
+        diesel::update(self)
+            .set(peers::failed_dials.eq(peers::failed_dials + 1))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Record that this peer had orders in our orderbook at the given point in
+    /// time.
+    pub fn record_orders(&self, conn: &SqliteConnection, at: OffsetDateTime) -> Result<()> {
+        diesel::update(self)
+            .set(peers::last_order_at.eq(at.timestamp()))
+            .execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "peers"]
+struct InsertablePeer {
+    peer_id: Text<PeerId>,
+    successful_dials: i32,
+    failed_dials: i32,
+    violations: i32,
+    banned: bool,
+}
+
+impl InsertablePeer {
+    fn new(peer: PeerId) -> Self {
+        Self {
+            peer_id: Text(peer),
+            successful_dials: 0,
+            failed_dials: 0,
+            violations: 0,
+            banned: false,
+        }
+    }
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "peer_addresses"]
+struct InsertablePeerAddress {
+    peer_id: i32,
+    address: Text<Multiaddr>,
+    last_seen_at: i64,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "swap_outcomes"]
+struct InsertableSwapOutcome {
+    swap_id: i32,
+    outcome: Text<Outcome>,
+}
+
+/// Record how a swap ended, unless we already did so.
+///
+/// Swaps are respawned on every startup, only recording the first outcome
+/// keeps them from being counted several times.
+pub fn record_swap_outcome(
+    conn: &SqliteConnection,
+    swap_id: LocalSwapId,
+    outcome: Outcome,
+) -> Result<()> {
+    let swap_fk = swap_id_fk!(swap_id)
+        .first::<i32>(conn)
+        .context(NoSwapExists(swap_id))?;
+
+    diesel::insert_or_ignore_into(swap_outcomes::table)
+        .values(InsertableSwapOutcome {
+            swap_id: swap_fk,
+            outcome: Text(outcome),
+        })
+        .execute(conn)?;
+
+    Ok(())
+}
+
+/// The counterparties of all swaps whose herc20 HTLC has not expired yet.
+///
+/// Every swap we support involves a herc20 HTLC, we consider a swap to be
+/// active until it expires.
+pub fn counterparties_of_active_swaps(
+    conn: &SqliteConnection,
+    now: OffsetDateTime,
+) -> Result<Vec<PeerId>> {
+    let active_swaps = herc20s::table
+        .filter(herc20s::expiry.gt(now.timestamp()))
+        .select(herc20s::swap_id);
+
+    let peers = swaps::table
+        .filter(swaps::id.eq_any(active_swaps))
+        .select(swaps::counterparty_peer_id)
+        .distinct()
+        .load::<Text<PeerId>>(conn)?;
+
+    Ok(peers.into_iter().map(PeerId::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{InsertableSwap, Sqlite};
+    use chrono::NaiveDateTime;
+    use comit::Role;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn records_connections_and_failures() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let peer_id = PeerId::random();
+        let address: Multiaddr = "/ip4/127.0.0.1/tcp/9939".parse().unwrap();
+        let now = OffsetDateTime::now_utc();
+
+        let (peer, addresses) = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                let peer = Peer::get_or_insert(conn, &peer_id)?;
+                peer.record_connection(conn, &address, now)?;
+                peer.record_connection(conn, &address, now)?;
+                peer.record_dial_failure(conn)?;
+
+                let peer = Peer::get_or_insert(conn, &peer_id)?;
+                let addresses = peer.addresses(conn)?;
+
+                Ok((peer, addresses))
+            }))
+            .unwrap();
+
+        assert_eq!(peer.successful_dials, 2);
+        assert_eq!(peer.failed_dials, 1);
+        assert_eq!(peer.last_seen_at, Some(now.timestamp()));
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0].address, address);
+    }
+
+    #[test]
+    fn reputation_counts_every_swap_once() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let peer_id = PeerId::random();
+        let swap_id = LocalSwapId::random();
+
+        let reputation = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                InsertableSwap::new(
+                    swap_id,
+                    peer_id.clone(),
+                    Role::Alice,
+                    NaiveDateTime::from_timestamp(0, 0),
+                )
+                .insert(conn)?;
+                record_swap_outcome(conn, swap_id, Outcome::Completed)?;
+                record_swap_outcome(conn, swap_id, Outcome::Failed)?;
+
+                let peer = Peer::get_or_insert(conn, &peer_id)?;
+                peer.record_violation(conn)?;
+                peer.set_banned(conn, true)?;
+
+                Peer::get_or_insert(conn, &peer_id)?.reputation(conn)
+            }))
+            .unwrap();
+
+        assert_eq!(reputation, Reputation {
+            completed: 1,
+            violations: 1,
+            banned: true,
+            ..Reputation::default()
+        });
+    }
+}
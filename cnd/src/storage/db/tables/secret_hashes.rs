@@ -1,4 +1,11 @@
-use crate::storage::{db::schema::secret_hashes, tables::Swap, Text};
+use crate::{
+    local_swap_id::LocalSwapId,
+    storage::{
+        db::schema::{secret_hashes, swaps},
+        tables::Swap,
+        Text,
+    },
+};
 use anyhow::Result;
 use diesel::{prelude::*, sqlite::SqliteConnection};
 
@@ -34,3 +41,21 @@ impl InsertableSecretHash {
         Ok(())
     }
 }
+
+/// The swap that uses the given secret hash, if we stored it.
+pub fn swap_by_secret_hash(
+    conn: &SqliteConnection,
+    secret_hash: comit::SecretHash,
+) -> Result<Option<LocalSwapId>> {
+    let swap_fk = secret_hashes::table
+        .filter(secret_hashes::secret_hash.eq(Text(secret_hash)))
+        .select(secret_hashes::swap_id);
+
+    let id = swaps::table
+        .filter(swaps::id.eq_any(swap_fk))
+        .select(swaps::local_swap_id)
+        .first::<Text<LocalSwapId>>(conn)
+        .optional()?;
+
+    Ok(id.map(|id| id.0))
+}
@@ -0,0 +1,173 @@
+use crate::{
+    asset,
+    local_swap_id::LocalSwapId,
+    storage::{
+        db::schema::{hbits, order_swaps, swap_cancellations, swaps},
+        tables::{BtcDaiOrder, Hbit, Order, OrderHbitParams, OrderHerc20Params, Swap},
+        NoSwapExists, Text,
+    },
+};
+use anyhow::{Context, Result};
+use comit::{
+    expiries::{AlphaOffset, BetaOffset},
+    order::SwapProtocol,
+    Quantity, Role, Side,
+};
+use diesel::{prelude::*, SqliteConnection};
+use time::{Duration, OffsetDateTime};
+
+/// A swap that one of the parties cancelled before it was funded.
+#[derive(Associations, Clone, Copy, Debug, Identifiable, Queryable, PartialEq)]
+#[belongs_to(Swap)]
+#[table_name = "swap_cancellations"]
+pub struct SwapCancellation {
+    id: i32,
+    swap_id: i32,
+    #[diesel(deserialize_as = "Text<Role>")]
+    pub cancelled_by: Role,
+    pub cancelled_at: i64,
+}
+
+impl SwapCancellation {
+    pub fn by_swap_id(conn: &SqliteConnection, swap_id: LocalSwapId) -> Result<Option<Self>> {
+        let cancellation = swap_cancellations::table
+            .filter(swap_cancellations::swap_id.eq_any(swap_id_fk!(swap_id)))
+            .first::<Self>(conn)
+            .optional()?;
+
+        Ok(cancellation)
+    }
+}
+
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "swap_cancellations"]
+struct InsertableSwapCancellation {
+    swap_id: i32,
+    cancelled_by: Text<Role>,
+    cancelled_at: i64,
+}
+
+/// Record that the swap was cancelled and release the quantity it filled of
+/// the order it was set up from, if any.
+///
+/// Returns the order if it has an open quantity again so it can be published
+/// once more.
+pub fn cancel_swap(
+    conn: &SqliteConnection,
+    swap_id: LocalSwapId,
+    cancelled_by: Role,
+    cancelled_at: OffsetDateTime,
+) -> Result<Option<comit::BtcDaiOrder>> {
+    let swap_fk = swap_id_fk!(swap_id)
+        .first::<i32>(conn)
+        .context(NoSwapExists(swap_id))?;
+
+    diesel::insert_into(swap_cancellations::table)
+        .values(InsertableSwapCancellation {
+            swap_id: swap_fk,
+            cancelled_by: Text(cancelled_by),
+            cancelled_at: cancelled_at.timestamp(),
+        })
+        .execute(conn)
+        .with_context(|| format!("failed to cancel swap {}", swap_id))?;
+
+    let order_fk = order_swaps::table
+        .filter(order_swaps::swap_id.eq(swap_fk))
+        .select(order_swaps::order_id)
+        .first::<i32>(conn)
+        .optional()?;
+    let order_fk = match order_fk {
+        Some(order_fk) => order_fk,
+        None => return Ok(None),
+    };
+
+    // Swaps set up from an order always involve hbit, its amount is the
+    // quantity of the order that was filled.
+    let hbit = hbits::table
+        .filter(hbits::swap_id.eq(swap_fk))
+        .first::<Hbit>(conn)?;
+    let order = Order::by_id(conn, order_fk)?;
+    BtcDaiOrder::by_order(conn, &order)?.release_settling(conn, Quantity::from(hbit.amount))?;
+
+    let btc_dai_order = BtcDaiOrder::by_order(conn, &order)?;
+    if btc_dai_order.open == Quantity::new(asset::Bitcoin::ZERO) {
+        return Ok(None);
+    }
+
+    let hbit_params = OrderHbitParams::by_order(conn, &order)?;
+    let herc20_params = OrderHerc20Params::by_order(conn, &order)?;
+    let hbit_expiry_offset = Duration::seconds(hbit_params.expiry_offset);
+    let herc20_expiry_offset = Duration::seconds(herc20_params.expiry_offset);
+
+    let swap_protocol = match hbit_params.side {
+        Side::Alpha => SwapProtocol::HbitHerc20 {
+            hbit_expiry_offset: AlphaOffset::from(hbit_expiry_offset),
+            herc20_expiry_offset: BetaOffset::from(herc20_expiry_offset),
+        },
+        Side::Beta => SwapProtocol::Herc20Hbit {
+            herc20_expiry_offset: AlphaOffset::from(herc20_expiry_offset),
+            hbit_expiry_offset: BetaOffset::from(hbit_expiry_offset),
+        },
+    };
+
+    Ok(Some(comit::BtcDaiOrder {
+        id: order.order_id,
+        position: order.position,
+        swap_protocol,
+        created_at: OffsetDateTime::from_unix_timestamp(order.created_at),
+        quantity: btc_dai_order.open,
+        price: btc_dai_order.price,
+    }))
+}
+
+/// The ids of all swaps that were cancelled.
+pub fn cancelled_swaps(conn: &SqliteConnection) -> Result<Vec<LocalSwapId>> {
+    let ids = swaps::table
+        .filter(swaps::id.eq_any(swap_cancellations::table.select(swap_cancellations::swap_id)))
+        .select(swaps::local_swap_id)
+        .load::<Text<LocalSwapId>>(conn)?;
+
+    Ok(ids.into_iter().map(|id| id.0).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{InsertableSwap, Sqlite};
+    use chrono::NaiveDateTime;
+    use libp2p::PeerId;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn swaps_can_only_be_cancelled_once() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let swap_id = LocalSwapId::random();
+        let now = OffsetDateTime::now_utc();
+
+        let (order, cancellation, cancelled) = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                InsertableSwap::new(
+                    swap_id,
+                    PeerId::random(),
+                    Role::Alice,
+                    NaiveDateTime::from_timestamp(0, 0),
+                )
+                .insert(conn)?;
+
+                let order = cancel_swap(conn, swap_id, Role::Bob, now)?;
+                let cancellation = SwapCancellation::by_swap_id(conn, swap_id)?;
+
+                Ok((order, cancellation, cancelled_swaps(conn)?))
+            }))
+            .unwrap();
+
+        assert_eq!(order, None);
+        assert_eq!(cancellation.map(|c| c.cancelled_by), Some(Role::Bob));
+        assert_eq!(cancelled, vec![swap_id]);
+
+        let again = runtime
+            .block_on(db.do_in_transaction(|conn| cancel_swap(conn, swap_id, Role::Alice, now)));
+        assert!(again.is_err());
+    }
+}
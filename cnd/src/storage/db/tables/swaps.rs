@@ -1,12 +1,16 @@
 use crate::{
     local_swap_id::LocalSwapId,
-    storage::{db::schema::swaps, Text},
+    storage::{
+        db::schema::{halbits, hbits, herc20s, swap_cancellations, swap_outcomes, swaps},
+        NoSwapExists, Text,
+    },
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
 use chrono::NaiveDateTime;
-use comit::Role;
-use diesel::{prelude::*, SqliteConnection};
+use comit::{reputation::Outcome, LockProtocol, Role, Side};
+use diesel::{prelude::*, sqlite::Sqlite, SqliteConnection};
 use libp2p::PeerId;
+use serde::Deserialize;
 
 #[derive(Identifiable, Queryable, PartialEq, Debug)]
 #[table_name = "swaps"]
@@ -21,6 +25,172 @@ pub struct Swap {
     pub start_of_swap: NaiveDateTime,
 }
 
+impl Swap {
+    pub fn by_swap_id(conn: &SqliteConnection, swap_id: LocalSwapId) -> Result<Self> {
+        let swap = swaps::table
+            .filter(swaps::local_swap_id.eq(Text(swap_id)))
+            .first::<Self>(conn)
+            .with_context(|| NoSwapExists(swap_id))?;
+
+        Ok(swap)
+    }
+
+    /// Up to `limit` swaps that match the filter, continuing after the swap
+    /// `cursor` if given.
+    pub fn query(
+        conn: &SqliteConnection,
+        filter: &SwapFilter,
+        order: SortOrder,
+        cursor: Option<LocalSwapId>,
+        limit: i64,
+    ) -> Result<Vec<Self>> {
+        let mut query = swaps::table.into_boxed();
+
+        if let Some(status) = filter.status {
+            query = with_status(query, status);
+        }
+        if let Some(role) = filter.role {
+            query = query.filter(swaps::role.eq(Text(role)));
+        }
+        if let Some(alpha) = filter.alpha {
+            query = with_protocol(query, alpha, Side::Alpha);
+        }
+        if let Some(beta) = filter.beta {
+            query = with_protocol(query, beta, Side::Beta);
+        }
+        if let Some(counterparty) = &filter.counterparty {
+            query = query.filter(swaps::counterparty_peer_id.eq(Text(counterparty.clone())));
+        }
+        if let Some(started_after) = filter.started_after {
+            query = query.filter(swaps::start_of_swap.ge(started_after));
+        }
+        if let Some(started_before) = filter.started_before {
+            query = query.filter(swaps::start_of_swap.lt(started_before));
+        }
+
+        if let Some(cursor) = cursor {
+            let cursor = swap_id_fk!(cursor)
+                .first::<i32>(conn)
+                .with_context(|| NoSwapExists(cursor))?;
+
+            query = match order {
+                SortOrder::Oldest => query.filter(swaps::id.gt(cursor)),
+                SortOrder::Newest => query.filter(swaps::id.lt(cursor)),
+            };
+        }
+
+        let query = match order {
+            SortOrder::Oldest => query.order(swaps::id.asc()),
+            SortOrder::Newest => query.order(swaps::id.desc()),
+        };
+
+        let swaps = query.limit(limit).load::<Self>(conn)?;
+
+        Ok(swaps)
+    }
+}
+
+/// How far a swap got, as far as the database knows.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, strum_macros::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SwapStatus {
+    InProgress,
+    Redeemed,
+    Refunded,
+    /// Cancelled, expired before it was funded or failed otherwise.
+    Aborted,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, strum_macros::Display)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SortOrder {
+    Oldest,
+    Newest,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        SortOrder::Oldest
+    }
+}
+
+/// The criteria a swap has to meet to be returned by [`Swap::query`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapFilter {
+    pub status: Option<SwapStatus>,
+    pub role: Option<Role>,
+    pub alpha: Option<LockProtocol>,
+    pub beta: Option<LockProtocol>,
+    pub counterparty: Option<PeerId>,
+    pub started_after: Option<NaiveDateTime>,
+    pub started_before: Option<NaiveDateTime>,
+}
+
+fn with_status(
+    query: swaps::BoxedQuery<'static, Sqlite>,
+    status: SwapStatus,
+) -> swaps::BoxedQuery<'static, Sqlite> {
+    let with_outcomes = |outcomes: Vec<Outcome>| {
+        swap_outcomes::table
+            .filter(swap_outcomes::outcome.eq_any(outcomes.into_iter().map(Text)))
+            .select(swap_outcomes::swap_id)
+    };
+    let cancelled = swap_cancellations::table.select(swap_cancellations::swap_id);
+
+    match status {
+        SwapStatus::InProgress => query
+            .filter(swaps::id.ne_all(swap_outcomes::table.select(swap_outcomes::swap_id)))
+            .filter(swaps::id.ne_all(cancelled)),
+        SwapStatus::Redeemed => {
+            query.filter(swaps::id.eq_any(with_outcomes(vec![Outcome::Completed])))
+        }
+        SwapStatus::Refunded => {
+            query.filter(swaps::id.eq_any(with_outcomes(vec![Outcome::Refunded])))
+        }
+        SwapStatus::Aborted => query.filter(
+            swaps::id
+                .eq_any(with_outcomes(vec![
+                    Outcome::Expired,
+                    Outcome::Failed,
+                    Outcome::ProtocolViolation,
+                ]))
+                .or(swaps::id.eq_any(cancelled)),
+        ),
+    }
+}
+
+fn with_protocol(
+    query: swaps::BoxedQuery<'static, Sqlite>,
+    protocol: LockProtocol,
+    side: Side,
+) -> swaps::BoxedQuery<'static, Sqlite> {
+    match protocol {
+        LockProtocol::Hbit => query.filter(
+            swaps::id.eq_any(
+                hbits::table
+                    .filter(hbits::side.eq(Text(side)))
+                    .select(hbits::swap_id),
+            ),
+        ),
+        LockProtocol::Herc20 => query.filter(
+            swaps::id.eq_any(
+                herc20s::table
+                    .filter(herc20s::side.eq(Text(side)))
+                    .select(herc20s::swap_id),
+            ),
+        ),
+        LockProtocol::Halbit => query.filter(
+            swaps::id.eq_any(
+                halbits::table
+                    .filter(halbits::side.eq(Text(side)))
+                    .select(halbits::swap_id),
+            ),
+        ),
+    }
+}
+
 #[derive(Insertable, Debug, Clone)]
 #[table_name = "swaps"]
 pub struct InsertableSwap {
@@ -59,3 +229,105 @@ impl InsertableSwap {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{record_swap_outcome, Sqlite};
+    use tokio::runtime::Runtime;
+
+    fn ids(swaps: Vec<Swap>) -> Vec<LocalSwapId> {
+        swaps.into_iter().map(|swap| swap.local_swap_id).collect()
+    }
+
+    #[test]
+    fn query_filters_and_pages_swaps() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let peer_id = PeerId::random();
+        let swap_ids = vec![
+            LocalSwapId::random(),
+            LocalSwapId::random(),
+            LocalSwapId::random(),
+            LocalSwapId::random(),
+        ];
+
+        runtime
+            .block_on(db.do_in_transaction(|conn| {
+                for (i, id) in swap_ids.iter().enumerate() {
+                    let role = if i % 2 == 0 { Role::Alice } else { Role::Bob };
+                    let start_of_swap = NaiveDateTime::from_timestamp(i as i64, 0);
+                    InsertableSwap::new(*id, peer_id.clone(), role, start_of_swap).insert(conn)?;
+                }
+                record_swap_outcome(conn, swap_ids[0], Outcome::Completed)?;
+                record_swap_outcome(conn, swap_ids[1], Outcome::Refunded)?;
+                record_swap_outcome(conn, swap_ids[2], Outcome::Expired)?;
+
+                Ok(())
+            }))
+            .unwrap();
+
+        let mut query = |filter: SwapFilter, order, cursor, limit| {
+            let swaps = runtime
+                .block_on(
+                    db.do_in_transaction(|conn| Swap::query(conn, &filter, order, cursor, limit)),
+                )
+                .unwrap();
+
+            ids(swaps)
+        };
+        let status = |status| SwapFilter {
+            status: Some(status),
+            ..SwapFilter::default()
+        };
+
+        assert_eq!(
+            query(status(SwapStatus::InProgress), SortOrder::Oldest, None, 10),
+            vec![swap_ids[3]]
+        );
+        assert_eq!(
+            query(status(SwapStatus::Redeemed), SortOrder::Oldest, None, 10),
+            vec![swap_ids[0]]
+        );
+        assert_eq!(
+            query(status(SwapStatus::Refunded), SortOrder::Oldest, None, 10),
+            vec![swap_ids[1]]
+        );
+        assert_eq!(
+            query(status(SwapStatus::Aborted), SortOrder::Oldest, None, 10),
+            vec![swap_ids[2]]
+        );
+
+        let bob = SwapFilter {
+            role: Some(Role::Bob),
+            ..SwapFilter::default()
+        };
+        assert_eq!(
+            query(bob, SortOrder::Newest, None, 10),
+            vec![swap_ids[3], swap_ids[1]]
+        );
+
+        let recent = SwapFilter {
+            started_after: Some(NaiveDateTime::from_timestamp(2, 0)),
+            ..SwapFilter::default()
+        };
+        assert_eq!(
+            query(recent, SortOrder::Oldest, None, 10),
+            vec![swap_ids[2], swap_ids[3]]
+        );
+
+        let all = SwapFilter::default();
+        assert_eq!(
+            query(all.clone(), SortOrder::Oldest, None, 2),
+            vec![swap_ids[0], swap_ids[1]]
+        );
+        assert_eq!(
+            query(all.clone(), SortOrder::Oldest, Some(swap_ids[1]), 2),
+            vec![swap_ids[2], swap_ids[3]]
+        );
+        assert_eq!(
+            query(all, SortOrder::Newest, Some(swap_ids[2]), 2),
+            vec![swap_ids[1], swap_ids[0]]
+        );
+    }
+}
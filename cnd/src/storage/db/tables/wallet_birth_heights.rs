@@ -0,0 +1,86 @@
+use crate::storage::db::schema::wallet_birth_heights;
+use anyhow::{Context, Result};
+use diesel::{prelude::*, SqliteConnection};
+use std::convert::TryFrom;
+
+/// The block height at which a wallet managed by cnd was created.
+///
+/// No transaction of the wallet can be older than that, hence it is where a
+/// rescan starts if the wallet has to be recovered from the seed.
+#[derive(Clone, Debug, Identifiable, Queryable, PartialEq)]
+#[table_name = "wallet_birth_heights"]
+pub struct WalletBirthHeight {
+    id: i32,
+    pub wallet: String,
+    pub height: i64,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "wallet_birth_heights"]
+struct InsertableWalletBirthHeight<'a> {
+    wallet: &'a str,
+    height: i64,
+}
+
+impl WalletBirthHeight {
+    pub fn get(conn: &SqliteConnection, wallet: &str) -> Result<Option<u64>> {
+        let recorded = wallet_birth_heights::table
+            .filter(wallet_birth_heights::wallet.eq(wallet))
+            .first::<Self>(conn)
+            .optional()?;
+
+        match recorded {
+            Some(recorded) => Ok(Some(u64::try_from(recorded.height)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Record the birth height of the wallet unless it is known already.
+    pub fn record(conn: &SqliteConnection, wallet: &str, height: u64) -> Result<()> {
+        if Self::get(conn, wallet)?.is_some() {
+            return Ok(());
+        }
+
+        diesel::insert_into(wallet_birth_heights::table)
+            .values(InsertableWalletBirthHeight {
+                wallet,
+                height: i64::try_from(height)?,
+            })
+            .execute(conn)
+            .with_context(|| format!("failed to record birth height of wallet {}", wallet))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Sqlite;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn first_recorded_birth_height_is_kept() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+
+        let (unknown, height, other_wallet) = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                let unknown = WalletBirthHeight::get(conn, "foo")?;
+                WalletBirthHeight::record(conn, "foo", 100)?;
+                WalletBirthHeight::record(conn, "foo", 200)?;
+                WalletBirthHeight::record(conn, "bar", 300)?;
+
+                Ok((
+                    unknown,
+                    WalletBirthHeight::get(conn, "foo")?,
+                    WalletBirthHeight::get(conn, "bar")?,
+                ))
+            }))
+            .unwrap();
+
+        assert_eq!(unknown, None);
+        assert_eq!(height, Some(100));
+        assert_eq!(other_wallet, Some(300));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{
+    local_swap_id::LocalSwapId,
+    storage::{db::schema::wallet_executions, tables::Swap, NoSwapExists, Text},
+    wallet_actions::ActionKind,
+};
+use anyhow::{Context, Result};
+use diesel::{prelude::*, SqliteConnection};
+use time::OffsetDateTime;
+
+/// An action of a swap that cnd executed with its managed wallets.
+#[derive(Associations, Clone, Copy, Debug, Identifiable, Queryable, PartialEq)]
+#[belongs_to(Swap)]
+#[table_name = "wallet_executions"]
+pub struct WalletExecution {
+    id: i32,
+    swap_id: i32,
+    #[diesel(deserialize_as = "Text<ActionKind>")]
+    pub action: ActionKind,
+    pub succeeded: bool,
+    pub executed_at: i64,
+}
+
+#[derive(Insertable, Clone, Copy, Debug)]
+#[table_name = "wallet_executions"]
+struct InsertableWalletExecution {
+    swap_id: i32,
+    action: Text<ActionKind>,
+    succeeded: bool,
+    executed_at: i64,
+}
+
+impl WalletExecution {
+    pub fn record(
+        conn: &SqliteConnection,
+        swap_id: LocalSwapId,
+        action: ActionKind,
+        succeeded: bool,
+        executed_at: OffsetDateTime,
+    ) -> Result<()> {
+        let swap_fk = swap_id_fk!(swap_id)
+            .first::<i32>(conn)
+            .context(NoSwapExists(swap_id))?;
+
+        diesel::insert_into(wallet_executions::table)
+            .values(InsertableWalletExecution {
+                swap_id: swap_fk,
+                action: Text(action),
+                succeeded,
+                executed_at: executed_at.timestamp(),
+            })
+            .execute(conn)
+            .with_context(|| format!("failed to record execution of {} for {}", action, swap_id))?;
+
+        Ok(())
+    }
+
+    /// All executions of actions of the swap, in the order they happened.
+    pub fn by_swap_id(conn: &SqliteConnection, swap_id: LocalSwapId) -> Result<Vec<Self>> {
+        let executions = wallet_executions::table
+            .filter(wallet_executions::swap_id.eq_any(swap_id_fk!(swap_id)))
+            .order(wallet_executions::id.asc())
+            .load::<Self>(conn)?;
+
+        Ok(executions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{tables::InsertableSwap, Sqlite};
+    use chrono::NaiveDateTime;
+    use comit::Role;
+    use libp2p::PeerId;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn executions_are_loaded_per_swap_in_order() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let swap_id = LocalSwapId::random();
+        let other_swap_id = LocalSwapId::random();
+        let now = OffsetDateTime::now_utc();
+
+        let executions = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                for id in &[swap_id, other_swap_id] {
+                    let start_of_swap = NaiveDateTime::from_timestamp(now.timestamp(), 0);
+                    InsertableSwap::new(*id, PeerId::random(), Role::Alice, start_of_swap)
+                        .insert(conn)?;
+                }
+
+                WalletExecution::record(conn, swap_id, ActionKind::Fund, true, now)?;
+                WalletExecution::record(conn, other_swap_id, ActionKind::Fund, false, now)?;
+                WalletExecution::record(conn, swap_id, ActionKind::Redeem, false, now)?;
+
+                WalletExecution::by_swap_id(conn, swap_id)
+            }))
+            .unwrap();
+
+        let executions = executions
+            .iter()
+            .map(|execution| (execution.action, execution.succeeded))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            executions,
+            vec![(ActionKind::Fund, true), (ActionKind::Redeem, false)]
+        );
+    }
+}
@@ -0,0 +1,128 @@
+use crate::storage::{db::schema::webhook_deliveries, Text};
+use anyhow::{Context, Result};
+use diesel::{prelude::*, SqliteConnection};
+use time::OffsetDateTime;
+use url::Url;
+
+/// A webhook call that was not delivered yet.
+#[derive(Clone, Debug, Identifiable, Queryable, PartialEq)]
+#[table_name = "webhook_deliveries"]
+pub struct WebhookDelivery {
+    id: i32,
+    #[diesel(deserialize_as = "Text<Url>")]
+    pub url: Url,
+    pub payload: String,
+    /// How many times we tried to deliver the payload already.
+    pub attempts: i32,
+    /// Unix timestamp of when we try to deliver the payload next.
+    pub next_attempt_at: i64,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "webhook_deliveries"]
+struct InsertableWebhookDelivery {
+    url: Text<Url>,
+    payload: String,
+    attempts: i32,
+    next_attempt_at: i64,
+}
+
+impl WebhookDelivery {
+    /// Queue the payload for delivery to the given endpoint.
+    pub fn enqueue(
+        conn: &SqliteConnection,
+        url: &Url,
+        payload: String,
+        at: OffsetDateTime,
+    ) -> Result<()> {
+        diesel::insert_into(webhook_deliveries::table)
+            .values(InsertableWebhookDelivery {
+                url: Text(url.clone()),
+                payload,
+                attempts: 0,
+                next_attempt_at: at.timestamp(),
+            })
+            .execute(conn)
+            .with_context(|| format!("failed to queue webhook delivery to {}", url))?;
+
+        Ok(())
+    }
+
+    /// All deliveries that are due at the given point in time, in the order
+    /// they were queued.
+    pub fn due(conn: &SqliteConnection, at: OffsetDateTime) -> Result<Vec<Self>> {
+        let deliveries = webhook_deliveries::table
+            .filter(webhook_deliveries::next_attempt_at.le(at.timestamp()))
+            .order(webhook_deliveries::id.asc())
+            .load::<Self>(conn)?;
+
+        Ok(deliveries)
+    }
+
+    /// Record a failed attempt and schedule the next one.
+    pub fn reschedule(
+        &self,
+        conn: &SqliteConnection,
+        next_attempt_at: OffsetDateTime,
+    ) -> Result<()> {
+        diesel::update(self)
+            .set((
+                webhook_deliveries::attempts.eq(webhook_deliveries::attempts + 1),
+                webhook_deliveries::next_attempt_at.eq(next_attempt_at.timestamp()),
+            ))
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    /// Remove the delivery once it succeeded or we gave up on it.
+    pub fn delete(&self, conn: &SqliteConnection) -> Result<()> {
+        diesel::delete(self).execute(conn)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Sqlite;
+    use time::Duration;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn deliveries_are_due_until_they_are_removed() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+        let url = "http://localhost:9000/cnd".parse::<Url>().unwrap();
+        let now = OffsetDateTime::now_utc();
+
+        let (due, due_after_failure, due_later) = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                WebhookDelivery::enqueue(conn, &url, "{}".to_owned(), now)?;
+                let due = WebhookDelivery::due(conn, now)?;
+
+                due[0].reschedule(conn, now + Duration::minutes(1))?;
+                let due_after_failure = WebhookDelivery::due(conn, now)?;
+                let due_later = WebhookDelivery::due(conn, now + Duration::minutes(1))?;
+
+                due_later[0].delete(conn)?;
+
+                Ok((due, due_after_failure, due_later))
+            }))
+            .unwrap();
+
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].url, url);
+        assert_eq!(due[0].attempts, 0);
+        assert!(due_after_failure.is_empty());
+        assert_eq!(due_later[0].attempts, 1);
+
+        let remaining = runtime
+            .block_on(
+                db.do_in_transaction(|conn| WebhookDelivery::due(conn, now + Duration::minutes(1))),
+            )
+            .unwrap();
+        assert!(remaining.is_empty());
+    }
+}
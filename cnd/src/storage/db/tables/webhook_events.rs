@@ -0,0 +1,96 @@
+use crate::storage::db::schema::webhook_events;
+use anyhow::{Context, Result};
+use diesel::{prelude::*, SqliteConnection};
+
+/// The latest event about a swap or order that was queued for the webhooks.
+///
+/// This tells after a restart or after falling behind which events were
+/// already delivered.
+#[derive(Clone, Debug, Identifiable, Queryable, PartialEq)]
+#[table_name = "webhook_events"]
+pub struct WebhookEvent {
+    id: i32,
+    pub event: String,
+    pub subject: String,
+    pub data: String,
+}
+
+#[derive(Insertable, Clone, Debug)]
+#[table_name = "webhook_events"]
+struct InsertableWebhookEvent<'a> {
+    event: &'a str,
+    subject: &'a str,
+    data: &'a str,
+}
+
+impl WebhookEvent {
+    pub fn by_subject(conn: &SqliteConnection, event: &str, subject: &str) -> Result<Option<Self>> {
+        let recorded = webhook_events::table
+            .filter(webhook_events::event.eq(event))
+            .filter(webhook_events::subject.eq(subject))
+            .first::<Self>(conn)
+            .optional()?;
+
+        Ok(recorded)
+    }
+
+    /// Record the data of the latest event about the subject.
+    ///
+    /// Returns `false` if the same data was recorded already.
+    pub fn record(conn: &SqliteConnection, event: &str, subject: &str, data: &str) -> Result<bool> {
+        match Self::by_subject(conn, event, subject)? {
+            Some(recorded) if recorded.data == data => return Ok(false),
+            Some(recorded) => {
+                diesel::update(&recorded)
+                    .set(webhook_events::data.eq(data))
+                    .execute(conn)?;
+            }
+            None => {
+                diesel::insert_into(webhook_events::table)
+                    .values(InsertableWebhookEvent {
+                        event,
+                        subject,
+                        data,
+                    })
+                    .execute(conn)
+                    .with_context(|| format!("failed to record {} event of {}", event, subject))?;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Sqlite;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn only_changed_data_is_recorded() {
+        let db = Sqlite::test();
+        let mut runtime = Runtime::new().unwrap();
+
+        let (first, same, changed, other_subject, recorded) = runtime
+            .block_on(db.do_in_transaction(|conn| {
+                Ok((
+                    WebhookEvent::record(conn, "swap", "foo", "{}")?,
+                    WebhookEvent::record(conn, "swap", "foo", "{}")?,
+                    WebhookEvent::record(conn, "swap", "foo", "{\"bar\":1}")?,
+                    WebhookEvent::record(conn, "swap", "baz", "{}")?,
+                    WebhookEvent::by_subject(conn, "swap", "foo")?,
+                ))
+            }))
+            .unwrap();
+
+        assert!(first);
+        assert!(!same);
+        assert!(changed);
+        assert!(other_subject);
+        assert_eq!(
+            recorded.map(|event| event.data),
+            Some("{\"bar\":1}".to_owned())
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{ethereum, ledger, LocalSwapId};
+use crate::{ethereum, hbit, ledger, lnd_actions, wallet_actions, LocalSwapId};
 use comit::{OrderId, Position, Role, Side};
 use diesel::{
     backend::Backend,
@@ -6,7 +6,7 @@ use diesel::{
     serialize::{self, Output, ToSql},
     sql_types,
 };
-use libp2p::PeerId;
+use libp2p::{Multiaddr, PeerId};
 use std::{fmt, ops::Deref, str::FromStr};
 
 /// Custom diesel new-type that works as long as T implements `Display` and
@@ -66,9 +66,14 @@ macro_rules! impl_from_text {
 impl_from_text!(LocalSwapId);
 impl_from_text!(Role);
 impl_from_text!(PeerId);
+impl_from_text!(Multiaddr);
 impl_from_text!(ledger::Bitcoin);
 impl_from_text!(Side);
 impl_from_text!(ethereum::Address);
 impl_from_text!(::bitcoin::Address);
 impl_from_text!(OrderId);
 impl_from_text!(Position);
+impl_from_text!(url::Url);
+impl_from_text!(wallet_actions::ActionKind);
+impl_from_text!(lnd_actions::ActionKind);
+impl_from_text!(hbit::SpendKind);
@@ -0,0 +1,53 @@
+//! Wallets derived from the seed of cnd.
+//!
+//! In managed-wallet mode, cnd uses these wallets to execute the Bitcoin and
+//! Ethereum actions of swaps itself, see [`wallet_actions`](crate::wallet_actions).
+
+mod bitcoin;
+mod ethereum;
+
+pub use self::{bitcoin::Wallet as BitcoinWallet, ethereum::Wallet as EthereumWallet};
+
+use crate::{
+    config::Settings,
+    storage::{RootSeed, Sqlite},
+};
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+pub struct Wallets {
+    pub bitcoin: Arc<BitcoinWallet>,
+    pub ethereum: Arc<EthereumWallet>,
+}
+
+impl Wallets {
+    /// Opens the wallets on the nodes configured in the settings, creating the
+    /// Bitcoin wallet in bitcoind if it doesn't exist yet.
+    pub async fn new(seed: &RootSeed, settings: &Settings, db: &Sqlite) -> anyhow::Result<Self> {
+        let bitcoin = BitcoinWallet::new(
+            seed,
+            settings.bitcoin.bitcoind.node_url.clone(),
+            settings.bitcoin.network,
+            db,
+        )
+        .await?;
+        let ethereum = EthereumWallet::new(
+            seed,
+            settings.ethereum.geth.node_url.clone(),
+            settings.ethereum.chain_id,
+            settings.wallet.gas_policy,
+        )?;
+
+        tracing::info!(
+            "Managing bitcoind wallet {} with address {}",
+            bitcoin.name(),
+            bitcoin.new_address().await?
+        );
+        tracing::info!("Managing Ethereum account {}", ethereum.account());
+
+        Ok(Self {
+            bitcoin: Arc::new(bitcoin),
+            ethereum: Arc::new(ethereum),
+        })
+    }
+}
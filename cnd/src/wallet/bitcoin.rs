@@ -0,0 +1,200 @@
+use crate::{
+    btsieve::jsonrpc,
+    storage::{RootSeed, Sqlite, WalletBirthHeight},
+};
+use ::bitcoin::{
+    consensus::encode::serialize_hex, hashes::hex::FromHex, secp256k1::SecretKey, Address,
+    PrivateKey, Txid,
+};
+use anyhow::{Context, Result};
+use comit::{
+    actions::bitcoin::{BroadcastSignedTransaction, SendToAddress},
+    ledger,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use url::Url;
+
+/// A wallet in bitcoind whose HD seed is derived from our seed.
+///
+/// Deriving the HD seed means the wallet, including its funds, can be
+/// recreated from the seed of cnd alone. We remember the block height at which
+/// the wallet was created so that recovering it only needs to rescan the
+/// blockchain from there.
+#[derive(Debug)]
+pub struct Wallet {
+    name: String,
+    node: jsonrpc::Client,
+    wallet: Arc<jsonrpc::Client>,
+    network: ledger::Bitcoin,
+}
+
+#[derive(Debug, Deserialize)]
+struct WalletInfo {
+    hdseedid: Option<String>,
+}
+
+impl Wallet {
+    pub async fn new(
+        seed: &RootSeed,
+        node_url: Url,
+        network: ledger::Bitcoin,
+        db: &Sqlite,
+    ) -> Result<Self> {
+        let name = format!(
+            "cnd_{}",
+            hex::encode(&seed.sha256_with_seed(&[b"BITCOIN_WALLET_NAME"])[..4])
+        );
+        let wallet_url = node_url
+            .join(&format!("wallet/{}", name))
+            .context("failed to build URL of bitcoind wallet")?;
+
+        let wallet = Self {
+            name,
+            node: jsonrpc::Client::new(node_url),
+            wallet: Arc::new(jsonrpc::Client::new(wallet_url)),
+            network,
+        };
+
+        let hd_seed = SecretKey::from_slice(&seed.sha256_with_seed(&[b"BITCOIN_WALLET_HD_SEED"]))
+            .context("failed to derive HD seed of bitcoind wallet")?;
+        wallet.init(hd_seed, db).await?;
+
+        Ok(wallet)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn init(&self, hd_seed: SecretKey, db: &Sqlite) -> Result<()> {
+        if self.info().await.is_err() {
+            // The wallet may exist but not be loaded after bitcoind restarted
+            if let Err(e) = self
+                .node
+                .send::<_, Value>(jsonrpc::Request::new("loadwallet", vec![&self.name]))
+                .await
+            {
+                tracing::debug!("failed to load wallet {}: {:#}", self.name, e);
+
+                self.node
+                    .send::<_, Value>(jsonrpc::Request::new(
+                        "createwallet",
+                        (&self.name, false, true),
+                    ))
+                    .await
+                    .with_context(|| format!("failed to create wallet {}", self.name))?;
+            }
+        }
+
+        let height: u64 = self
+            .node
+            .send(jsonrpc::Request::new("getblockcount", Vec::<Value>::new()))
+            .await
+            .context("failed to get block height")?;
+        let birth_height = db
+            .do_in_transaction(|conn| WalletBirthHeight::get(conn, &self.name))
+            .await?;
+
+        if self.info().await?.hdseedid.is_none() {
+            let wif = PrivateKey {
+                compressed: true,
+                network: self.network.into(),
+                key: hd_seed,
+            }
+            .to_wif();
+
+            self.wallet
+                .send::<_, Value>(jsonrpc::Request::new("sethdseed", (true, wif)))
+                .await
+                .with_context(|| format!("failed to set HD seed of wallet {}", self.name))?;
+
+            // We created the wallet before, hence bitcoind lost it and we are
+            // recovering it from the seed
+            if let Some(birth_height) = birth_height {
+                self.spawn_rescan(birth_height);
+            }
+        }
+
+        db.do_in_transaction(|conn| WalletBirthHeight::record(conn, &self.name, height))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Picks up the funds of a recovered wallet in the background, rescanning
+    /// the whole blockchain can take hours.
+    fn spawn_rescan(&self, start_height: u64) {
+        let wallet = self.wallet.clone();
+        let name = self.name.clone();
+
+        tracing::info!(
+            "recovering wallet {}, rescanning blockchain from block {}",
+            name,
+            start_height
+        );
+        tokio::spawn(async move {
+            let request = jsonrpc::Request::new("rescanblockchain", vec![start_height]);
+
+            match wallet.send::<_, Value>(request).await {
+                Ok(_) => tracing::info!("finished rescanning blockchain for wallet {}", name),
+                Err(e) => tracing::warn!("failed to rescan wallet {}: {:#}", name, e),
+            }
+        });
+    }
+
+    async fn info(&self) -> Result<WalletInfo> {
+        self.wallet
+            .send(jsonrpc::Request::new("getwalletinfo", Vec::<Value>::new()))
+            .await
+    }
+
+    pub async fn new_address(&self) -> Result<Address> {
+        self.wallet
+            .send(jsonrpc::Request::new("getnewaddress", ("", "bech32")))
+            .await
+            .context("failed to get new address")
+    }
+
+    pub async fn send_to_address(&self, action: SendToAddress) -> Result<Txid> {
+        self.assert_network(action.network)?;
+
+        let txid: String = self
+            .wallet
+            .send(jsonrpc::Request::new(
+                "sendtoaddress",
+                (action.to.to_string(), action.amount.as_btc()),
+            ))
+            .await
+            .with_context(|| format!("failed to send {} to {}", action.amount, action.to))?;
+
+        Ok(Txid::from_hex(&txid)?)
+    }
+
+    pub async fn broadcast(&self, action: BroadcastSignedTransaction) -> Result<Txid> {
+        self.assert_network(action.network)?;
+
+        let txid: String = self
+            .node
+            .send(jsonrpc::Request::new(
+                "sendrawtransaction",
+                vec![serialize_hex(&action.transaction)],
+            ))
+            .await
+            .context("failed to broadcast transaction")?;
+
+        Ok(Txid::from_hex(&txid)?)
+    }
+
+    fn assert_network(&self, network: ledger::Bitcoin) -> Result<()> {
+        anyhow::ensure!(
+            network == self.network,
+            "wallet is on {} but action is for {}",
+            self.network,
+            network
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{
+    btsieve::jsonrpc,
+    ethereum::{
+        gas::GasPolicy,
+        nonce::{NonceTracker, Nonces},
+        Address, ChainId, Hash,
+    },
+    storage::RootSeed,
+};
+use anyhow::{Context, Result};
+use clarity::Uint256;
+use comit::actions::ethereum::{CallContract, DeployContract};
+use std::{collections::HashMap, convert::TryFrom};
+use tokio::sync::Mutex;
+use url::Url;
+
+/// An Ethereum account whose private key is derived from our seed.
+///
+/// Transactions are signed locally and sent through geth as raw transactions,
+/// hence geth never needs to know the private key.
+///
+/// Sending the same transaction again, i.e. one with the same recipient and
+/// data, while the previous one is not mined replaces the previous one with a
+/// higher gas price.
+#[derive(derivative::Derivative)]
+#[derivative(Debug)]
+pub struct Wallet {
+    #[derivative(Debug = "ignore")]
+    private_key: clarity::PrivateKey,
+    account: Address,
+    chain_id: ChainId,
+    gas_policy: GasPolicy,
+    client: jsonrpc::Client,
+    /// Held while sending a transaction so every transaction gets its own
+    /// nonce.
+    #[derivative(Debug = "ignore")]
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    nonces: NonceTracker,
+    pending: HashMap<(Option<Address>, Vec<u8>), Pending>,
+}
+
+/// A transaction we sent that is not mined yet.
+#[derive(Debug)]
+struct Pending {
+    nonce: u32,
+    gas_price: u64,
+    hash: Hash,
+    /// The signed transaction, hex encoded.
+    raw: String,
+}
+
+impl Wallet {
+    pub fn new(
+        seed: &RootSeed,
+        node_url: Url,
+        chain_id: ChainId,
+        gas_policy: GasPolicy,
+    ) -> Result<Self> {
+        let private_key =
+            clarity::PrivateKey::from_slice(&seed.sha256_with_seed(&[b"ETHEREUM_WALLET"]))
+                .map_err(|e| anyhow::anyhow!("{}", e))
+                .context("failed to derive Ethereum private key")?;
+        let public_key = private_key
+            .to_public_key()
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        let mut account = [0u8; 20];
+        account.copy_from_slice(public_key.as_bytes());
+
+        Ok(Self {
+            private_key,
+            account: Address::from(account),
+            chain_id,
+            gas_policy,
+            client: jsonrpc::Client::new(node_url),
+            state: Mutex::new(State {
+                nonces: NonceTracker::new(Nonces::default()),
+                pending: HashMap::new(),
+            }),
+        })
+    }
+
+    pub fn account(&self) -> Address {
+        self.account
+    }
+
+    pub fn gas_policy(&self) -> GasPolicy {
+        self.gas_policy
+    }
+
+    pub async fn deploy_contract(&self, action: DeployContract) -> Result<Hash> {
+        self.assert_chain(action.chain_id)?;
+
+        let value = Uint256::from_bytes_le(&action.amount.to_bytes());
+
+        self.send_transaction(None, value, action.gas_limit, action.data)
+            .await
+            .context("failed to deploy contract")
+    }
+
+    pub async fn call_contract(&self, action: CallContract) -> Result<Hash> {
+        self.assert_chain(action.chain_id)?;
+
+        self.send_transaction(
+            Some(action.to),
+            Uint256::from(0u64),
+            action.gas_limit,
+            action.data.unwrap_or_default(),
+        )
+        .await
+        .with_context(|| format!("failed to call contract {}", action.to))
+    }
+
+    /// Signs and sends the transaction with a gas price according to our gas
+    /// price strategy.
+    ///
+    /// If we sent the same transaction before and it is not mined yet, it is
+    /// replaced with a bumped gas price, or broadcast again as is if the gas
+    /// price cannot be bumped any further.
+    ///
+    /// Sending a transaction without a recipient deploys a contract.
+    async fn send_transaction(
+        &self,
+        to: Option<Address>,
+        value: Uint256,
+        gas_limit: u64,
+        data: Vec<u8>,
+    ) -> Result<Hash> {
+        let mut guard = self.state.lock().await;
+        let state = &mut *guard;
+
+        let mined_count = self
+            .transaction_count("latest")
+            .await
+            .context("failed to get number of mined transactions")?;
+        let State { nonces, pending } = &mut *state;
+        pending.retain(|_, transaction| {
+            let mined = transaction.nonce < mined_count;
+            if mined {
+                nonces.confirm(transaction.nonce);
+            }
+            !mined
+        });
+
+        let node_gas_price = self
+            .quantity(jsonrpc::Request::new("eth_gasPrice", Vec::<String>::new()))
+            .await
+            .context("failed to get gas price")?;
+
+        let key = (to, data.clone());
+        let (nonce, gas_price, replacing) = match state.pending.get(&key) {
+            Some(previous) => match self
+                .gas_policy
+                .strategy
+                .bump(previous.gas_price, node_gas_price)
+            {
+                Some(gas_price) => (previous.nonce, gas_price, true),
+                None => {
+                    tracing::warn!(
+                        "gas price of transaction {} is capped, broadcasting it again",
+                        previous.hash
+                    );
+                    let raw = previous.raw.clone();
+
+                    return self.send_raw_transaction(raw).await;
+                }
+            },
+            None => {
+                // The pending transaction count includes the transactions
+                // that are not mined yet, be it ours or not.
+                let pending_count = self
+                    .transaction_count("pending")
+                    .await
+                    .context("failed to get nonce")?;
+                state.nonces.sync(pending_count);
+                let nonce = state.nonces.allocate();
+                let gas_price = self.gas_policy.strategy.gas_price(node_gas_price);
+
+                (nonce, gas_price, false)
+            }
+        };
+
+        let to_address = match to {
+            Some(to) => {
+                clarity::Address::from_slice(to.as_bytes()).map_err(|e| anyhow::anyhow!("{}", e))?
+            }
+            None => clarity::Address::default(),
+        };
+        let transaction = clarity::Transaction {
+            nonce: u64::from(nonce).into(),
+            gas_price: gas_price.into(),
+            gas_limit: gas_limit.into(),
+            to: to_address,
+            value,
+            data,
+            signature: None,
+        }
+        .sign(&self.private_key, Some(u64::from(u32::from(self.chain_id))));
+        let raw = transaction
+            .to_bytes()
+            .map_err(|e| anyhow::anyhow!("{}", e))
+            .context("failed to serialize signed transaction")?;
+        let raw = format!("0x{}", hex::encode(raw));
+
+        match self.send_raw_transaction(raw.clone()).await {
+            Ok(hash) => {
+                state.pending.insert(key, Pending {
+                    nonce,
+                    gas_price,
+                    hash,
+                    raw,
+                });
+
+                Ok(hash)
+            }
+            Err(e) => {
+                if !replacing {
+                    state.nonces.release(nonce);
+                }
+
+                Err(e)
+            }
+        }
+    }
+
+    async fn send_raw_transaction(&self, raw: String) -> Result<Hash> {
+        self.client
+            .send(jsonrpc::Request::new("eth_sendRawTransaction", vec![raw]))
+            .await
+    }
+
+    /// The number of transactions sent from our account that are included in
+    /// the given block, e.g. "latest" or "pending".
+    async fn transaction_count(&self, block: &str) -> Result<u32> {
+        let count = self
+            .quantity(jsonrpc::Request::new(
+                "eth_getTransactionCount",
+                vec![self.account.to_string(), block.to_owned()],
+            ))
+            .await?;
+
+        Ok(u32::try_from(count)?)
+    }
+
+    /// Sends a request whose response is a hex encoded quantity.
+    async fn quantity(&self, request: jsonrpc::Request<Vec<String>>) -> Result<u64> {
+        let quantity: String = self.client.send(request).await?;
+        let quantity = u64::from_str_radix(quantity.trim_start_matches("0x"), 16)
+            .with_context(|| format!("invalid quantity {}", quantity))?;
+
+        Ok(quantity)
+    }
+
+    fn assert_chain(&self, chain_id: ChainId) -> Result<()> {
+        anyhow::ensure!(
+            chain_id == self.chain_id,
+            "wallet is on chain {} but action is for chain {}",
+            u32::from(self.chain_id),
+            u32::from(chain_id)
+        );
+
+        Ok(())
+    }
+}
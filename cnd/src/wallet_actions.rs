@@ -0,0 +1,451 @@
+//! This module deals with executing Bitcoin and Ethereum actions on behalf of
+//! the user.
+//!
+//! In managed-wallet mode, cnd executes the deploy, fund, redeem and refund
+//! actions of a swap itself with the wallets derived from its seed as soon as
+//! they are available, see [`Wallets`]. Every execution is persisted so that
+//! actions are not repeated after a restart and can be reported to the user as
+//! part of the swap's events.
+//!
+//! Deploying and funding an HTLC moves our funds, hence these actions rule out
+//! cancelling the swap from then on and are attempted at most once: if sending the transaction failed we cannot tell
+//! for sure whether it reached the network. Redeeming and refunding can only
+//! ever succeed once on the ledger and are therefore retried. Redeem and refund
+//! transactions that do not get confirmed are replaced with ones paying a
+//! higher fee until the protocol sees the HTLC spent, see [`GasPolicy`].
+
+use crate::{
+    actions::{bitcoin, ethereum, lnd, DeployAction, FundAction, RedeemAction, RefundAction},
+    bitcoin::EstimateFeeRate,
+    connectors::Connectors,
+    ethereum::gas::{GasPolicy, OnPending},
+    hbit,
+    http_api::{
+        ActionName, AlphaAbsoluteExpiry, AlphaLedger, BetaAbsoluteExpiry, BetaLedger, GetRole,
+        LedgerSnapshot,
+    },
+    storage::{FundHandout, HbitSpend, Load, Storage, SwapCancelled, SwapContext, WalletExecution},
+    wallet::Wallets,
+    LocalSwapId, Never, Role, Timestamp,
+};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    time::{Duration, Instant},
+};
+use time::OffsetDateTime;
+
+/// How often we check whether there is a new action to execute.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long we wait before retrying a failed redeem or refund.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The kinds of actions cnd executes with its managed wallets.
+///
+/// These are named after the corresponding action endpoints of the HTTP API.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    strum_macros::Display,
+    strum_macros::EnumString,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum ActionKind {
+    Deploy,
+    Fund,
+    Redeem,
+    Refund,
+}
+
+impl ActionKind {
+    fn is_retryable(self) -> bool {
+        match self {
+            ActionKind::Deploy | ActionKind::Fund => false,
+            ActionKind::Redeem | ActionKind::Refund => true,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Action {
+    SendToAddress(bitcoin::SendToAddress),
+    SpendHtlc(bitcoin::SpendHtlc),
+    DeployContract(ethereum::DeployContract),
+    CallContract(ethereum::CallContract),
+}
+
+/// Extracts the wallet action, if any, out of the output of one of the action
+/// traits.
+pub trait IntoWalletAction {
+    fn into_wallet_action(self) -> Option<Action>;
+}
+
+impl IntoWalletAction for bitcoin::SendToAddress {
+    fn into_wallet_action(self) -> Option<Action> {
+        Some(Action::SendToAddress(self))
+    }
+}
+
+impl IntoWalletAction for bitcoin::SpendHtlc {
+    fn into_wallet_action(self) -> Option<Action> {
+        Some(Action::SpendHtlc(self))
+    }
+}
+
+impl IntoWalletAction for ethereum::DeployContract {
+    fn into_wallet_action(self) -> Option<Action> {
+        Some(Action::DeployContract(self))
+    }
+}
+
+impl IntoWalletAction for ethereum::CallContract {
+    fn into_wallet_action(self) -> Option<Action> {
+        Some(Action::CallContract(self))
+    }
+}
+
+impl IntoWalletAction for lnd::AddHoldInvoice {
+    fn into_wallet_action(self) -> Option<Action> {
+        None
+    }
+}
+
+impl IntoWalletAction for lnd::SendPayment {
+    fn into_wallet_action(self) -> Option<Action> {
+        None
+    }
+}
+
+impl IntoWalletAction for lnd::SettleInvoice {
+    fn into_wallet_action(self) -> Option<Action> {
+        None
+    }
+}
+
+impl IntoWalletAction for Never {
+    fn into_wallet_action(self) -> Option<Action> {
+        None
+    }
+}
+
+/// What there is to do for a swap in the current state of the ledgers.
+#[derive(Debug)]
+enum Next {
+    /// The action needs to be confirmed before the deadline, if any.
+    Execute(ActionKind, Action, Option<Timestamp>),
+    Wait,
+    /// Nothing is available and all HTLCs expired, hence nothing will ever be
+    /// available again.
+    Finished,
+}
+
+/// Executes the wallet actions of the given swap as soon as they become
+/// available.
+///
+/// The task finishes once the protocol sees that we redeemed or refunded or
+/// once there is nothing left to do.
+pub async fn execute(id: LocalSwapId, storage: Storage, connectors: Connectors, wallets: Wallets) {
+    let mut failed_at = HashMap::<ActionKind, Instant>::new();
+    let gas_policy = wallets.ethereum.gas_policy();
+
+    loop {
+        tokio::time::delay_for(POLL_INTERVAL).await;
+
+        match redeemed_or_refunded(id, &storage).await {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(e) => {
+                tracing::debug!("failed to load protocol state of {}: {:?}", id, e);
+                continue;
+            }
+        }
+
+        let executions = match storage
+            .db
+            .do_in_transaction(|conn| WalletExecution::by_swap_id(conn, id))
+            .await
+        {
+            Ok(executions) => executions,
+            Err(e) => {
+                tracing::debug!("failed to load wallet executions of {}: {:?}", id, e);
+                continue;
+            }
+        };
+
+        let (kind, action, deadline) = match next(id, &storage, &connectors).await {
+            Ok(Next::Execute(kind, action, deadline)) => (kind, action, deadline),
+            Ok(Next::Wait) => continue,
+            Ok(Next::Finished) => break,
+            Err(e) => {
+                tracing::debug!("failed to determine wallet action of {}: {:?}", id, e);
+                continue;
+            }
+        };
+
+        let attempts = executions
+            .iter()
+            .filter(|execution| execution.action == kind)
+            .collect::<Vec<_>>();
+
+        // The action stays available until its transaction is picked up by
+        // the protocol task.
+        let last_sent_at = attempts
+            .iter()
+            .filter(|execution| execution.succeeded)
+            .map(|execution| execution.executed_at)
+            .max();
+        if let Some(sent_at) = last_sent_at {
+            if !kind.is_retryable() {
+                continue;
+            }
+
+            match on_pending(gas_policy, sent_at, deadline) {
+                OnPending::Wait | OnPending::GiveUp => continue,
+                OnPending::Replace => {
+                    tracing::info!("replacing {} transaction of swap {}", kind, id)
+                }
+            }
+        }
+
+        if !attempts.is_empty() && !kind.is_retryable() {
+            continue;
+        }
+
+        if failed_at
+            .get(&kind)
+            .map_or(false, |at| at.elapsed() < RETRY_INTERVAL)
+        {
+            continue;
+        }
+
+        if !kind.is_retryable() {
+            if let Err(e) = storage
+                .db
+                .do_in_transaction(|conn| FundHandout::record(conn, id, OffsetDateTime::now_utc()))
+                .await
+            {
+                tracing::warn!("not executing {} for swap {}: {:#}", kind, id, e);
+                if e.is::<SwapCancelled>() {
+                    break;
+                }
+                continue;
+            }
+        }
+
+        let result = execute_action(id, kind, action, &wallets, &storage, &connectors).await;
+        if result.is_err() {
+            failed_at.insert(kind, Instant::now());
+            if !kind.is_retryable() {
+                tracing::warn!(
+                    "not retrying {} for swap {}, the action has to be executed manually",
+                    kind,
+                    id
+                );
+            }
+        }
+
+        record(&storage, id, kind, result).await;
+    }
+
+    tracing::info!("finished executing wallet actions of {}", id);
+}
+
+async fn redeemed_or_refunded(id: LocalSwapId, storage: &Storage) -> anyhow::Result<bool> {
+    let swap: SwapContext = storage.load(id).await?;
+
+    storage.redeemed_or_refunded(swap).await
+}
+
+/// What to do about our transaction sent at the given time that is not
+/// confirmed yet.
+///
+/// We are as patient with Bitcoin transactions as with Ethereum ones.
+fn on_pending(gas_policy: GasPolicy, sent_at: i64, deadline: Option<Timestamp>) -> OnPending {
+    let now = Timestamp::now();
+    let since_sent = u64::try_from(i64::from(now) - sent_at).unwrap_or_default();
+
+    gas_policy.on_pending(Duration::from_secs(since_sent), deadline, now)
+}
+
+async fn next(id: LocalSwapId, storage: &Storage, connectors: &Connectors) -> anyhow::Result<Next> {
+    let ledgers = LedgerSnapshot::fetch(connectors).await?;
+    let swap_context = storage.load(id).await?;
+
+    within_swap_context!(swap_context, {
+        let swap: ActorSwap = storage.load(id).await?;
+
+        let next = match ledgers.next_action(&swap)? {
+            Some(ActionName::Deploy) => swap
+                .deploy_action()
+                .ok()
+                .and_then(IntoWalletAction::into_wallet_action)
+                .map(|action| (ActionKind::Deploy, action, None)),
+            Some(ActionName::Fund) if our_htlc_expired(&swap, &ledgers) => {
+                tracing::warn!("not funding swap {} because its HTLC expired", id);
+                None
+            }
+            Some(ActionName::Fund) => swap
+                .fund_action()
+                .ok()
+                .and_then(IntoWalletAction::into_wallet_action)
+                .map(|action| (ActionKind::Fund, action, None)),
+            Some(ActionName::Redeem) => swap
+                .redeem_action()
+                .ok()
+                .and_then(IntoWalletAction::into_wallet_action)
+                .map(|action| (ActionKind::Redeem, action, redeem_deadline(&swap))),
+            Some(ActionName::Refund) => swap
+                .refund_action()
+                .ok()
+                .and_then(IntoWalletAction::into_wallet_action)
+                .map(|action| (ActionKind::Refund, action, None)),
+            Some(ActionName::Init) | Some(ActionName::Bump) | Some(ActionName::Cancel) | None => {
+                None
+            }
+        };
+
+        let next = match next {
+            Some((kind, action, deadline)) => Next::Execute(kind, action, deadline),
+            None if all_htlcs_expired(&swap, &ledgers) => Next::Finished,
+            None => Next::Wait,
+        };
+
+        Ok(next)
+    })
+}
+
+/// Whether the HTLC we fund has expired.
+fn our_htlc_expired<S>(swap: &S, ledgers: &LedgerSnapshot) -> bool
+where
+    S: GetRole + AlphaLedger + BetaLedger + AlphaAbsoluteExpiry + BetaAbsoluteExpiry,
+{
+    let (expiry, ledger) = match swap.get_role() {
+        Role::Alice => (swap.alpha_absolute_expiry(), swap.alpha_ledger()),
+        Role::Bob => (swap.beta_absolute_expiry(), swap.beta_ledger()),
+    };
+
+    expiry.map_or(false, |expiry| expiry < ledgers.time(ledger))
+}
+
+/// The expiry of the HTLC we redeem, from then on the counterparty can refund
+/// it.
+fn redeem_deadline<S>(swap: &S) -> Option<Timestamp>
+where
+    S: GetRole + AlphaAbsoluteExpiry + BetaAbsoluteExpiry,
+{
+    match swap.get_role() {
+        Role::Alice => swap.beta_absolute_expiry(),
+        Role::Bob => swap.alpha_absolute_expiry(),
+    }
+}
+
+/// Whether both HTLCs of the swap have expired. HTLCs without an expiry, i.e.
+/// lightning ones, are not executed by the wallets and therefore ignored.
+fn all_htlcs_expired<S>(swap: &S, ledgers: &LedgerSnapshot) -> bool
+where
+    S: AlphaLedger + BetaLedger + AlphaAbsoluteExpiry + BetaAbsoluteExpiry,
+{
+    let alpha = swap
+        .alpha_absolute_expiry()
+        .map_or(true, |expiry| expiry < ledgers.time(swap.alpha_ledger()));
+    let beta = swap
+        .beta_absolute_expiry()
+        .map_or(true, |expiry| expiry < ledgers.time(swap.beta_ledger()));
+
+    alpha && beta
+}
+
+async fn execute_action(
+    id: LocalSwapId,
+    kind: ActionKind,
+    action: Action,
+    wallets: &Wallets,
+    storage: &Storage,
+    connectors: &Connectors,
+) -> anyhow::Result<()> {
+    match action {
+        Action::SendToAddress(action) => {
+            let txid = wallets.bitcoin.send_to_address(action).await?;
+            tracing::debug!("sent bitcoin transaction {}", txid);
+        }
+        Action::SpendHtlc(action) => {
+            let spend_kind = match kind {
+                ActionKind::Redeem => hbit::SpendKind::Redeem,
+                ActionKind::Refund => hbit::SpendKind::Refund,
+                ActionKind::Deploy | ActionKind::Fund => {
+                    anyhow::bail!("{} does not spend an HTLC", kind)
+                }
+            };
+
+            // A replacement has to pay a higher fee rate than the transaction
+            // it replaces
+            let estimate = connectors.bitcoin_fee_rate().estimate_fee_rate().await?;
+            let previous = storage
+                .db
+                .do_in_transaction(|conn| HbitSpend::latest(conn, id))
+                .await?;
+            let fee_rate = match previous {
+                Some(previous) if previous.kind == spend_kind => previous.fee_rate.bump(estimate),
+                _ => estimate,
+            };
+
+            let action = action.sign(&*crate::SECP, fee_rate)?;
+            let txid = wallets.bitcoin.broadcast(action).await?;
+            tracing::debug!("broadcast bitcoin transaction {}", txid);
+
+            let spend = hbit::Spend {
+                kind: spend_kind,
+                fee_rate,
+            };
+            storage
+                .db
+                .do_in_transaction(|conn| HbitSpend::record(conn, id, spend))
+                .await?;
+        }
+        Action::DeployContract(action) => {
+            let hash = wallets.ethereum.deploy_contract(action).await?;
+            tracing::debug!("sent ethereum transaction {}", hash);
+        }
+        Action::CallContract(action) => {
+            let hash = wallets.ethereum.call_contract(action).await?;
+            tracing::debug!("sent ethereum transaction {}", hash);
+        }
+    }
+
+    Ok(())
+}
+
+async fn record(storage: &Storage, id: LocalSwapId, kind: ActionKind, result: anyhow::Result<()>) {
+    let succeeded = match result {
+        Ok(()) => {
+            tracing::info!("executed {} for swap {}", kind, id);
+            true
+        }
+        Err(e) => {
+            tracing::warn!("failed to execute {} for swap {}: {:?}", kind, id, e);
+            false
+        }
+    };
+
+    let executed_at = OffsetDateTime::now_utc();
+    if let Err(e) = storage
+        .db
+        .do_in_transaction(|conn| WalletExecution::record(conn, id, kind, succeeded, executed_at))
+        .await
+    {
+        tracing::warn!(
+            "failed to record execution of {} for swap {}: {:#}",
+            kind,
+            id,
+            e
+        );
+    }
+}
@@ -11,14 +11,17 @@ pub trait Actions {
 }
 
 pub mod bitcoin {
-    use crate::{asset, ledger};
+    use crate::{asset, bitcoin::SatPerVbyte, ledger};
     use bitcoin::{
         secp256k1::{self, Secp256k1},
         OutPoint,
     };
     use blockchain_contracts::bitcoin::witness::PrimedInput;
+    use std::convert::TryInto;
 
-    pub use bitcoin::{Address, Amount, Transaction};
+    pub use bitcoin::{
+        util::psbt::PartiallySignedTransaction, Address, Amount, Transaction, TxOut,
+    };
     pub use blockchain_contracts::bitcoin::witness::{PrimedTransaction, UnlockParameters};
 
     #[derive(Debug, Clone, PartialEq)]
@@ -28,6 +31,26 @@ pub mod bitcoin {
         pub network: ledger::Bitcoin,
     }
 
+    impl SendToAddress {
+        /// Creates an unsigned PSBT paying the amount to the address.
+        ///
+        /// The PSBT is only a template: Selecting and signing inputs as well as
+        /// adding a change output is up to the wallet processing it.
+        pub fn to_psbt(&self) -> anyhow::Result<PartiallySignedTransaction> {
+            let transaction = Transaction {
+                version: 2,
+                lock_time: 0,
+                input: vec![],
+                output: vec![TxOut {
+                    value: self.amount.as_sat(),
+                    script_pubkey: self.to.script_pubkey(),
+                }],
+            };
+
+            Ok(PartiallySignedTransaction::from_unsigned_tx(transaction)?)
+        }
+    }
+
     #[derive(Debug, Clone, PartialEq)]
     pub struct SpendOutput {
         // Remember: One man's input is another man's output!
@@ -56,23 +79,80 @@ pub mod bitcoin {
         }
     }
 
-    pub fn sign_with_fixed_rate<C>(
+    pub fn sign_with_rate<C>(
         secp: &Secp256k1<C>,
         primed_transaction: PrimedTransaction,
+        rate: SatPerVbyte,
     ) -> anyhow::Result<Transaction>
     where
         C: secp256k1::Signing,
     {
-        let rate = 10;
         primed_transaction
-            .sign_with_rate(secp, rate)
+            .sign_with_rate(secp, rate.as_u64().try_into()?)
             .map_err(|_| anyhow::anyhow!("failed to sign with {} rate", rate))
     }
 
+    /// Spending an HTLC output with a transaction that is yet to be signed.
+    ///
+    /// The fee rate is only chosen when signing, i.e. when the action is
+    /// executed or handed out.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct SpendHtlc {
+        pub primed_transaction: PrimedTransaction,
+        pub network: ledger::Bitcoin,
+    }
+
+    impl SpendHtlc {
+        pub fn sign<C>(
+            self,
+            secp: &Secp256k1<C>,
+            fee_rate: SatPerVbyte,
+        ) -> anyhow::Result<BroadcastSignedTransaction>
+        where
+            C: secp256k1::Signing,
+        {
+            let transaction = sign_with_rate(secp, self.primed_transaction, fee_rate)?;
+
+            Ok(BroadcastSignedTransaction {
+                transaction,
+                network: self.network,
+                fee_rate,
+            })
+        }
+    }
+
     #[derive(Debug, Clone, PartialEq)]
     pub struct BroadcastSignedTransaction {
         pub transaction: Transaction,
         pub network: ledger::Bitcoin,
+        pub fee_rate: SatPerVbyte,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::str::FromStr;
+
+        #[test]
+        fn fund_psbt_pays_amount_to_address() {
+            let to = Address::from_str("bcrt1qcqslz7lfn34dl096t5uwurff9spen5h4v2pmap").unwrap();
+            let action = SendToAddress {
+                to: to.clone(),
+                amount: asset::Bitcoin::from_sat(100_000),
+                network: ledger::Bitcoin::Regtest,
+            };
+
+            let psbt = action.to_psbt().unwrap();
+
+            assert!(psbt.global.unsigned_tx.input.is_empty());
+            assert_eq!(
+                psbt.global.unsigned_tx.output,
+                vec![TxOut {
+                    value: 100_000,
+                    script_pubkey: to.script_pubkey(),
+                }]
+            );
+        }
     }
 }
 
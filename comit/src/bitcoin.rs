@@ -6,6 +6,13 @@
 //!       libraries
 //!     - Common functionality that is not (yet) available upstream
 
+mod fee_rate;
+
+pub use self::fee_rate::{
+    BitcoindFeeRateEstimator, EstimateFeeRate, FeeRateEstimator, NoFeeRateEstimate, SatPerVbyte,
+    StaticFeeRate, FALLBACK_FEE_RATE, MAX_BUMPED_FEE_RATE,
+};
+
 use crate::{
     btsieve::{BlockByHash, LatestBlock},
     Timestamp,
@@ -0,0 +1,213 @@
+use crate::btsieve::jsonrpc;
+use anyhow::Context;
+use async_trait::async_trait;
+use bitcoin::Amount;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The fee rate we used to sign all transactions with before fee rates were
+/// made configurable.
+///
+/// Useful as a last resort if estimating a fee rate fails.
+pub const FALLBACK_FEE_RATE: SatPerVbyte = SatPerVbyte(10);
+
+/// We never bump the fee rate of a transaction beyond this.
+pub const MAX_BUMPED_FEE_RATE: SatPerVbyte = SatPerVbyte(500);
+
+/// How long an estimate from bitcoind is reused before asking again.
+///
+/// Estimates only change with new blocks, hence there is no point in asking
+/// bitcoind for every transaction we sign.
+const ESTIMATE_TTL: Duration = Duration::from_secs(60);
+
+/// A fee rate denominated in satoshi per virtual byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SatPerVbyte(u64);
+
+impl SatPerVbyte {
+    pub const fn new(sat_per_vbyte: u64) -> Self {
+        Self(sat_per_vbyte)
+    }
+
+    pub fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Converts a fee rate denominated in BTC per kilo virtual byte, which is
+    /// what bitcoind reports, rounding up to the next full satoshi.
+    pub fn from_btc_per_kvb(btc_per_kvb: f64) -> anyhow::Result<Self> {
+        let sat_per_kvb = Amount::from_btc(btc_per_kvb)
+            .with_context(|| format!("invalid fee rate {} BTC/kvB", btc_per_kvb))?
+            .as_sat();
+
+        Ok(Self((sat_per_kvb + 999) / 1000))
+    }
+
+    /// Computes the fee rate for a transaction replacing one that was signed
+    /// with this fee rate.
+    ///
+    /// BIP125 requires the replacement to pay a higher fee than the original.
+    /// We bump by at least 25% to not need too many replacements for getting
+    /// a stuck transaction confirmed but go straight to the current estimate
+    /// if that is even higher.
+    pub fn bump(self, estimate: SatPerVbyte) -> Self {
+        let minimum = self.0 + u64::max(1, (self.0 + 3) / 4);
+
+        Self(u64::max(minimum, estimate.0))
+    }
+}
+
+impl From<u64> for SatPerVbyte {
+    fn from(sat_per_vbyte: u64) -> Self {
+        Self(sat_per_vbyte)
+    }
+}
+
+impl fmt::Display for SatPerVbyte {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} sat/vbyte", self.0)
+    }
+}
+
+#[async_trait]
+pub trait EstimateFeeRate {
+    async fn estimate_fee_rate(&self) -> anyhow::Result<SatPerVbyte>;
+}
+
+/// Always "estimates" the same, configured fee rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StaticFeeRate(pub SatPerVbyte);
+
+#[async_trait]
+impl EstimateFeeRate for StaticFeeRate {
+    async fn estimate_fee_rate(&self) -> anyhow::Result<SatPerVbyte> {
+        Ok(self.0)
+    }
+}
+
+/// Estimates fee rates using bitcoind's `estimatesmartfee` RPC.
+///
+/// Estimates are cached for a minute.
+#[derive(Debug)]
+pub struct BitcoindFeeRateEstimator {
+    client: jsonrpc::Client,
+    target_blocks: u16,
+    cache: Mutex<Option<(Instant, SatPerVbyte)>>,
+}
+
+impl BitcoindFeeRateEstimator {
+    /// Creates a new estimator that aims for a transaction to be confirmed
+    /// within `target_blocks` blocks.
+    ///
+    /// The `node_url` needs to point to the JSON-RPC interface of bitcoind,
+    /// including any credentials.
+    pub fn new(node_url: reqwest::Url, target_blocks: u16) -> Self {
+        Self {
+            client: jsonrpc::Client::new(node_url),
+            target_blocks,
+            cache: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl EstimateFeeRate for BitcoindFeeRateEstimator {
+    async fn estimate_fee_rate(&self) -> anyhow::Result<SatPerVbyte> {
+        let cached = *self.cache.lock().expect("no poisoned lock");
+        if let Some((estimated_at, fee_rate)) = cached {
+            if estimated_at.elapsed() < ESTIMATE_TTL {
+                return Ok(fee_rate);
+            }
+        }
+
+        let response = self
+            .client
+            .send::<_, EstimateSmartFeeResponse>(jsonrpc::Request::new(
+                "estimatesmartfee",
+                vec![self.target_blocks],
+            ))
+            .await?;
+
+        let btc_per_kvb = response.feerate.ok_or_else(|| {
+            NoFeeRateEstimate(self.target_blocks, response.errors.unwrap_or_default())
+        })?;
+
+        let fee_rate = SatPerVbyte::from_btc_per_kvb(btc_per_kvb)?;
+        *self.cache.lock().expect("no poisoned lock") = Some((Instant::now(), fee_rate));
+
+        Ok(fee_rate)
+    }
+}
+
+/// Allows the fee rate estimation strategy to be chosen at runtime.
+#[derive(Debug)]
+pub enum FeeRateEstimator {
+    Static(StaticFeeRate),
+    Bitcoind(BitcoindFeeRateEstimator),
+}
+
+#[async_trait]
+impl EstimateFeeRate for FeeRateEstimator {
+    async fn estimate_fee_rate(&self) -> anyhow::Result<SatPerVbyte> {
+        match self {
+            FeeRateEstimator::Static(estimator) => estimator.estimate_fee_rate().await,
+            FeeRateEstimator::Bitcoind(estimator) => estimator.estimate_fee_rate().await,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct EstimateSmartFeeResponse {
+    feerate: Option<f64>,
+    errors: Option<Vec<String>>,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("bitcoind could not estimate a fee rate for a confirmation within {0} blocks: {1:?}")]
+pub struct NoFeeRateEstimate(pub u16, pub Vec<String>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_btc_per_kvb_rounding_up() {
+        let fee_rate = SatPerVbyte::from_btc_per_kvb(0.000_105_01).unwrap();
+
+        assert_eq!(fee_rate, SatPerVbyte::new(11));
+    }
+
+    #[test]
+    fn bump_increases_fee_rate_by_at_least_a_quarter() {
+        assert_eq!(
+            SatPerVbyte::new(10).bump(SatPerVbyte::new(5)),
+            SatPerVbyte::new(13)
+        );
+        assert_eq!(
+            SatPerVbyte::new(1).bump(SatPerVbyte::new(1)),
+            SatPerVbyte::new(2)
+        );
+    }
+
+    #[test]
+    fn bump_uses_estimate_if_higher() {
+        assert_eq!(
+            SatPerVbyte::new(10).bump(SatPerVbyte::new(40)),
+            SatPerVbyte::new(40)
+        );
+    }
+
+    #[test]
+    fn deserializes_response_without_feerate() {
+        let json = r#"{"errors":["Insufficient data or no feerate found"],"blocks":2}"#;
+
+        let response = serde_json::from_str::<EstimateSmartFeeResponse>(json).unwrap();
+
+        assert_eq!(response.feerate, None);
+    }
+}
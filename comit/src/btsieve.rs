@@ -1,12 +1,17 @@
 pub mod bitcoin;
 pub mod ethereum;
-mod jsonrpc;
+pub mod jsonrpc;
 
 use crate::Never;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use genawaiter::sync::{Co, Gen};
-use std::{collections::HashSet, future::Future, hash::Hash};
+use std::{
+    collections::HashSet,
+    future::Future,
+    hash::Hash,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 #[async_trait]
 pub trait LatestBlock: Send + Sync + 'static {
@@ -23,6 +28,31 @@ pub trait BlockByHash: Send + Sync + 'static {
     async fn block_by_hash(&self, block_hash: Self::BlockHash) -> anyhow::Result<Self::Block>;
 }
 
+/// Counts how often a cache could answer a request without asking the node.
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl CacheStats {
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn hit(&self) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn miss(&self) {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
 /// Checks if a given block predates a certain timestamp.
 pub trait Predates {
     fn predates(&self, timestamp: DateTime<Utc>) -> bool;
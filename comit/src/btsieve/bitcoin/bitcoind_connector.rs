@@ -11,9 +11,18 @@ use serde::{de, export::fmt, Deserialize, Deserializer};
 
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub struct ChainInfo {
-    bestblockhash: BlockHash,
+    pub bestblockhash: BlockHash,
     #[serde(deserialize_with = "deserialize_bitcoind_values")]
     pub chain: ledger::Bitcoin,
+    /// Height of the best fully validated block.
+    #[serde(default)]
+    pub blocks: u32,
+    /// Height of the best known header, may be ahead of `blocks` while
+    /// syncing.
+    #[serde(default)]
+    pub headers: u32,
+    #[serde(default)]
+    pub initialblockdownload: bool,
 }
 
 #[derive(Debug)]
@@ -206,6 +215,23 @@ mod tests {
         assert_eq!(info.chain, Bitcoin::Regtest);
     }
 
+    #[test]
+    fn can_deserialize_sync_state_from_chain_info() {
+        let chain_info = r#"{
+    "chain": "regtest",
+    "blocks": 210,
+    "headers": 215,
+    "bestblockhash": "00000000000000c473d592c8637824b8362d522af18bfb1d0e92107b96ecdc5c",
+    "initialblockdownload": true
+  }
+  "#;
+        let info = serde_json::from_str::<ChainInfo>(chain_info).unwrap();
+
+        assert_eq!(info.blocks, 210);
+        assert_eq!(info.headers, 215);
+        assert!(info.initialblockdownload);
+    }
+
     #[test]
     fn can_decode_block_from_bitcoind_http_interface() {
         // the line break here is on purpose, as it is returned like that from bitcoind
@@ -1,4 +1,4 @@
-use crate::btsieve::{BlockByHash, LatestBlock};
+use crate::btsieve::{BlockByHash, CacheStats, LatestBlock};
 use async_trait::async_trait;
 use bitcoin::{Block, BlockHash as Hash, BlockHash};
 use derivative::Derivative;
@@ -12,6 +12,7 @@ pub struct Cache<C> {
     pub connector: C,
     #[derivative(Debug = "ignore")]
     pub block_cache: Arc<Mutex<LruCache<BlockHash, Block>>>,
+    pub block_cache_stats: Arc<CacheStats>,
 }
 
 impl<C> Cache<C> {
@@ -20,6 +21,7 @@ impl<C> Cache<C> {
         Cache {
             connector,
             block_cache,
+            block_cache_stats: Arc::default(),
         }
     }
 }
@@ -55,8 +57,10 @@ where
     async fn block_by_hash(&self, block_hash: Self::BlockHash) -> anyhow::Result<Self::Block> {
         if let Some(block) = self.block_cache.lock().await.get(&block_hash) {
             tracing::trace!("Found block in cache: {:x}", block_hash);
+            self.block_cache_stats.hit();
             return Ok(block.clone());
         }
+        self.block_cache_stats.miss();
 
         let block = self.connector.block_by_hash(block_hash).await?;
         tracing::trace!("Fetched block from connector: {:x}", block_hash);
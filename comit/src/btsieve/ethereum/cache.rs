@@ -1,7 +1,7 @@
 use crate::{
     btsieve::{
         ethereum::{self, Hash, ReceiptByHash},
-        BlockByHash, LatestBlock,
+        BlockByHash, CacheStats, LatestBlock,
     },
     ethereum::TransactionReceipt,
 };
@@ -24,6 +24,8 @@ pub struct Cache<C> {
     pub block_cache: Arc<Mutex<LruCache<Hash, Block>>>,
     #[derivative(Debug = "ignore")]
     pub receipt_cache: Arc<Mutex<LruCache<Hash, TransactionReceipt>>>,
+    pub block_cache_stats: Arc<CacheStats>,
+    pub receipt_cache_stats: Arc<CacheStats>,
 }
 
 impl<C> Cache<C> {
@@ -38,6 +40,8 @@ impl<C> Cache<C> {
             connector,
             block_cache,
             receipt_cache,
+            block_cache_stats: Arc::default(),
+            receipt_cache_stats: Arc::default(),
         }
     }
 }
@@ -72,8 +76,10 @@ where
     async fn block_by_hash(&self, block_hash: Self::BlockHash) -> anyhow::Result<Self::Block> {
         if let Some(block) = self.block_cache.lock().await.get(&block_hash) {
             tracing::trace!("Found block in cache: {}", block_hash);
+            self.block_cache_stats.hit();
             return Ok(block.clone());
         }
+        self.block_cache_stats.miss();
 
         let block = self.connector.block_by_hash(block_hash).await?;
         tracing::trace!("Fetched block from connector: {}", block_hash);
@@ -95,8 +101,10 @@ where
     async fn receipt_by_hash(&self, transaction_hash: Hash) -> anyhow::Result<TransactionReceipt> {
         if let Some(receipt) = self.receipt_cache.lock().await.get(&transaction_hash) {
             tracing::trace!("Found receipt in cache: {}", transaction_hash);
+            self.receipt_cache_stats.hit();
             return Ok(receipt.clone());
         }
+        self.receipt_cache_stats.miss();
 
         let receipt = self.connector.receipt_by_hash(transaction_hash).await?;
 
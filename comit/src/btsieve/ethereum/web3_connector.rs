@@ -1,6 +1,6 @@
 use crate::{
     btsieve::{ethereum::ReceiptByHash, jsonrpc, BlockByHash, LatestBlock},
-    ethereum::{ChainId, Hash, TransactionReceipt},
+    ethereum::{ChainId, Hash, TransactionReceipt, U256},
 };
 use async_trait::async_trait;
 
@@ -26,6 +26,31 @@ impl Web3Connector {
 
         Ok(ChainId::from(version.parse::<u32>()?))
     }
+
+    pub async fn block_number(&self) -> anyhow::Result<U256> {
+        let number = self
+            .client
+            .send::<Vec<()>, U256>(jsonrpc::Request::new("eth_blockNumber", vec![]))
+            .await?;
+
+        tracing::trace!("Fetched block number from web3: {}", number);
+
+        Ok(number)
+    }
+
+    /// Whether the node is still catching up with the network.
+    pub async fn is_syncing(&self) -> anyhow::Result<bool> {
+        // `eth_syncing` returns either `false` or an object describing the
+        // progress of the sync.
+        let syncing = self
+            .client
+            .send::<Vec<()>, serde_json::Value>(jsonrpc::Request::new("eth_syncing", vec![]))
+            .await?;
+
+        tracing::trace!("Fetched syncing status from web3: {}", syncing);
+
+        Ok(syncing != serde_json::Value::Bool(false))
+    }
 }
 
 #[async_trait]
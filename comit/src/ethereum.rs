@@ -1,3 +1,6 @@
+pub mod gas;
+pub mod nonce;
+
 use crate::{btsieve::LatestBlock, Timestamp};
 pub use ethbloom::{Bloom as H2048, Input};
 use hex::FromHexError;
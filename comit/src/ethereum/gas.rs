@@ -0,0 +1,204 @@
+use crate::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Wei per Gwei.
+const GWEI: u64 = 1_000_000_000;
+
+/// How long we wait for a transaction to be mined before replacing it with one
+/// paying a higher gas price.
+pub const DEFAULT_STUCK_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How the gas price of our transactions is chosen.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum GasPriceStrategy {
+    /// Always start with the given gas price.
+    Fixed { gwei: u64 },
+    /// Take the gas price suggested by the node, scaled by the given
+    /// percentage, e.g. 120 for paying 20% more than suggested.
+    Node { multiplier_percent: u64 },
+    /// Like `Node` but never pay more than the given gas price, not even when
+    /// replacing a stuck transaction.
+    Capped {
+        multiplier_percent: u64,
+        max_gwei: u64,
+    },
+}
+
+impl Default for GasPriceStrategy {
+    fn default() -> Self {
+        GasPriceStrategy::Node {
+            multiplier_percent: 100,
+        }
+    }
+}
+
+impl GasPriceStrategy {
+    /// Computes the gas price in wei based on the one suggested by the node.
+    pub fn gas_price(&self, node_gas_price: u64) -> u64 {
+        match *self {
+            GasPriceStrategy::Fixed { gwei } => gwei.saturating_mul(GWEI),
+            GasPriceStrategy::Node { multiplier_percent } => {
+                scale(node_gas_price, multiplier_percent)
+            }
+            GasPriceStrategy::Capped {
+                multiplier_percent,
+                max_gwei,
+            } => u64::min(
+                scale(node_gas_price, multiplier_percent),
+                max_gwei.saturating_mul(GWEI),
+            ),
+        }
+    }
+
+    /// Computes the gas price in wei of a transaction replacing one that paid
+    /// `previous`.
+    ///
+    /// Nodes only accept a replacement if it pays at least 10% more than the
+    /// transaction it replaces, we bump by 12.5% to be on the safe side.
+    /// Returns `None` if the bumped gas price exceeds the cap.
+    pub fn bump(&self, previous: u64, node_gas_price: u64) -> Option<u64> {
+        let bumped = u64::max(
+            previous.saturating_add(previous / 8).saturating_add(1),
+            self.gas_price(node_gas_price),
+        );
+
+        match *self {
+            GasPriceStrategy::Capped { max_gwei, .. } if bumped > max_gwei.saturating_mul(GWEI) => {
+                None
+            }
+            _ => Some(bumped),
+        }
+    }
+}
+
+fn scale(gas_price: u64, percent: u64) -> u64 {
+    gas_price.saturating_mul(percent) / 100
+}
+
+/// Decides on the gas price of our transactions and when to replace them if
+/// they are not getting mined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GasPolicy {
+    pub strategy: GasPriceStrategy,
+    pub stuck_transaction_timeout: Duration,
+}
+
+impl Default for GasPolicy {
+    fn default() -> Self {
+        Self {
+            strategy: GasPriceStrategy::default(),
+            stuck_transaction_timeout: DEFAULT_STUCK_TRANSACTION_TIMEOUT,
+        }
+    }
+}
+
+impl GasPolicy {
+    /// What to do about a transaction sent `since_sent` ago that is not yet
+    /// mined and needs to be mined before `deadline`.
+    ///
+    /// The closer we get to the deadline, the less patient we are: We never
+    /// wait longer than half of the remaining time. Once the deadline has
+    /// passed, paying more is pointless and we give up.
+    pub fn on_pending(
+        &self,
+        since_sent: Duration,
+        deadline: Option<Timestamp>,
+        now: Timestamp,
+    ) -> OnPending {
+        let timeout = match deadline {
+            None => self.stuck_transaction_timeout,
+            Some(deadline) if deadline <= now => return OnPending::GiveUp,
+            Some(deadline) => {
+                let remaining =
+                    Duration::from_secs(u64::from(u32::from(deadline) - u32::from(now)));
+                Duration::min(self.stuck_transaction_timeout, remaining / 2)
+            }
+        };
+
+        if since_sent >= timeout {
+            OnPending::Replace
+        } else {
+            OnPending::Wait
+        }
+    }
+}
+
+/// What to do about a transaction that is not yet mined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnPending {
+    Wait,
+    Replace,
+    GiveUp,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_strategy_scales_suggested_gas_price() {
+        let strategy = GasPriceStrategy::Node {
+            multiplier_percent: 120,
+        };
+
+        assert_eq!(strategy.gas_price(10 * GWEI), 12 * GWEI);
+    }
+
+    #[test]
+    fn capped_strategy_never_exceeds_cap() {
+        let strategy = GasPriceStrategy::Capped {
+            multiplier_percent: 200,
+            max_gwei: 15,
+        };
+
+        assert_eq!(strategy.gas_price(10 * GWEI), 15 * GWEI);
+        assert_eq!(strategy.bump(15 * GWEI, 10 * GWEI), None);
+    }
+
+    #[test]
+    fn bump_pays_at_least_ten_percent_more() {
+        let strategy = GasPriceStrategy::Fixed { gwei: 10 };
+
+        let bumped = strategy.bump(10 * GWEI, 0).unwrap();
+
+        assert!(bumped >= 11 * GWEI);
+    }
+
+    #[test]
+    fn bump_follows_rising_node_gas_price() {
+        let strategy = GasPriceStrategy::default();
+
+        let bumped = strategy.bump(10 * GWEI, 20 * GWEI).unwrap();
+
+        assert_eq!(bumped, 20 * GWEI);
+    }
+
+    #[test]
+    fn replaces_sooner_when_deadline_is_near() {
+        let policy = GasPolicy::default();
+        let now = Timestamp::from(1_000_000);
+        let deadline = now.plus(120);
+
+        assert_eq!(
+            policy.on_pending(Duration::from_secs(60), Some(deadline), now),
+            OnPending::Replace
+        );
+        assert_eq!(
+            policy.on_pending(Duration::from_secs(60), None, now),
+            OnPending::Wait
+        );
+    }
+
+    #[test]
+    fn gives_up_after_deadline() {
+        let policy = GasPolicy::default();
+        let now = Timestamp::from(1_000_000);
+
+        assert_eq!(
+            policy.on_pending(Duration::from_secs(3600), Some(now), now),
+            OnPending::GiveUp
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// The part of the nonce bookkeeping that needs to survive restarts.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Nonces {
+    /// The lowest nonce that has never been handed out.
+    pub next: u32,
+    /// Nonces that were handed out but never made it to the node.
+    pub released: BTreeSet<u32>,
+}
+
+/// Keeps track of the nonces handed out for the transactions of an account.
+///
+/// Asking the node for the transaction count right before signing is racy if
+/// several transactions are sent at the same time. Hence, nonces are handed
+/// out one after the other and nonces of transactions that never made it to
+/// the node are handed out again.
+#[derive(Debug, Default)]
+pub struct NonceTracker {
+    nonces: Nonces,
+    /// Nonces that were handed out and whose transactions are not yet mined.
+    in_flight: BTreeSet<u32>,
+}
+
+impl NonceTracker {
+    pub fn new(nonces: Nonces) -> Self {
+        Self {
+            nonces,
+            in_flight: BTreeSet::new(),
+        }
+    }
+
+    pub fn nonces(&self) -> &Nonces {
+        &self.nonces
+    }
+
+    /// Reconciles our view with the node's count of pending transactions.
+    pub fn sync(&mut self, pending_count: u32) {
+        let nonces = &mut self.nonces;
+
+        // Nonces below the pending count have been used, be it by us or not
+        nonces.released = nonces.released.split_off(&pending_count);
+        nonces.next = u32::max(nonces.next, pending_count);
+
+        // With none of our transactions in flight, all nonces the node doesn't
+        // know about are gaps, e.g. because we crashed before broadcasting
+        if self.in_flight.is_empty() {
+            nonces.released.extend(pending_count..nonces.next);
+        }
+    }
+
+    /// Hands out the nonce for the next transaction.
+    ///
+    /// The nonce must be given back through either `release` or `confirm`.
+    pub fn allocate(&mut self) -> u32 {
+        let nonce = match self.nonces.released.iter().next().copied() {
+            Some(nonce) => {
+                self.nonces.released.remove(&nonce);
+                nonce
+            }
+            None => {
+                let nonce = self.nonces.next;
+                self.nonces.next += 1;
+                nonce
+            }
+        };
+        self.in_flight.insert(nonce);
+
+        nonce
+    }
+
+    /// Gives back a nonce whose transaction could not be broadcast.
+    pub fn release(&mut self, nonce: u32) {
+        if self.in_flight.remove(&nonce) {
+            self.nonces.released.insert(nonce);
+        }
+    }
+
+    /// Gives back a nonce whose transaction has been mined.
+    pub fn confirm(&mut self, nonce: u32) {
+        self.in_flight.remove(&nonce);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_allocations_get_distinct_nonces() {
+        let mut tracker = NonceTracker::default();
+
+        tracker.sync(5);
+        let first = tracker.allocate();
+        tracker.sync(5);
+        let second = tracker.allocate();
+
+        assert_eq!(first, 5);
+        assert_eq!(second, 6);
+    }
+
+    #[test]
+    fn released_nonce_is_handed_out_again() {
+        let mut tracker = NonceTracker::default();
+        tracker.sync(0);
+        let first = tracker.allocate();
+        let _second = tracker.allocate();
+
+        tracker.release(first);
+        tracker.sync(0);
+
+        assert_eq!(tracker.allocate(), first);
+    }
+
+    #[test]
+    fn released_nonce_used_by_someone_else_is_dropped() {
+        let mut tracker = NonceTracker::default();
+        tracker.sync(0);
+        let first = tracker.allocate();
+        tracker.release(first);
+
+        tracker.sync(1);
+
+        assert_eq!(tracker.allocate(), 1);
+    }
+
+    #[test]
+    fn gaps_left_before_restart_are_recovered() {
+        let mut tracker = NonceTracker::new(Nonces {
+            next: 10,
+            released: BTreeSet::new(),
+        });
+
+        tracker.sync(8);
+
+        assert_eq!(tracker.allocate(), 8);
+        assert_eq!(tracker.allocate(), 9);
+        assert_eq!(tracker.allocate(), 10);
+    }
+
+    #[test]
+    fn no_gaps_assumed_while_transactions_are_in_flight() {
+        let mut tracker = NonceTracker::default();
+        tracker.sync(3);
+        let _in_flight = tracker.allocate();
+
+        tracker.sync(3);
+
+        assert_eq!(tracker.allocate(), 4);
+    }
+}
@@ -13,6 +13,7 @@ use crate::{
 };
 use async_trait::async_trait;
 use num::integer;
+use serde::{Deserialize, Serialize};
 use std::{cmp, fmt};
 use time::Duration;
 
@@ -63,6 +64,78 @@ pub fn to_timestamps(
     (alpha.into(), beta.into())
 }
 
+/// Proposed expiries may be at most this many times the standard offsets away
+/// from the start of the swap, otherwise they lock up funds for too long.
+const MAX_OFFSET_FACTOR: i32 = 2;
+
+/// The reasons why a pair of expiries is not safe to use.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, thiserror::Error)]
+#[serde(rename_all = "snake_case")]
+pub enum UnsafeExpiries {
+    #[error("beta expiry does not leave Alice enough time to complete the swap")]
+    BetaExpiryTooEarly,
+    #[error("alpha expiry does not leave Bob enough time to complete the swap")]
+    AlphaExpiryTooEarly,
+    #[error("alpha expiry is too close to the beta expiry for Bob to redeem safely")]
+    SafetyWindowTooShort,
+    #[error("expiries lock up funds for too long")]
+    TooLate,
+}
+
+/// Check that a pair of expiries proposed for a herc20-hbit swap that started
+/// at `start_at` is safe for both Alice and Bob.
+pub fn validate_herc20_hbit(
+    network: Network,
+    start_at: Timestamp,
+    alpha_expiry: AlphaExpiry,
+    beta_expiry: BetaExpiry,
+) -> Result<(), UnsafeExpiries> {
+    let config = Config::herc20_hbit(network);
+    validate(&config, start_at, alpha_expiry, beta_expiry)
+}
+
+/// Check that a pair of expiries proposed for a hbit-herc20 swap that started
+/// at `start_at` is safe for both Alice and Bob.
+pub fn validate_hbit_herc20(
+    network: Network,
+    start_at: Timestamp,
+    alpha_expiry: AlphaExpiry,
+    beta_expiry: BetaExpiry,
+) -> Result<(), UnsafeExpiries> {
+    let config = Config::hbit_herc20(network);
+    validate(&config, start_at, alpha_expiry, beta_expiry)
+}
+
+// Mirrors the rules in `expiry_offsets`, the standard offsets always pass.
+fn validate(
+    config: &Config,
+    start_at: Timestamp,
+    alpha_expiry: AlphaExpiry,
+    beta_expiry: BetaExpiry,
+) -> Result<(), UnsafeExpiries> {
+    let alpha_offset = timestamp::duration_between(start_at, alpha_expiry.0);
+    let beta_offset = timestamp::duration_between(start_at, beta_expiry.0);
+
+    if beta_offset < happy_path_swap_period_for_alice(config) {
+        return Err(UnsafeExpiries::BetaExpiryTooEarly);
+    }
+    if alpha_offset < happy_path_swap_period_for_bob(config) {
+        return Err(UnsafeExpiries::AlphaExpiryTooEarly);
+    }
+    if alpha_offset - beta_offset < config.bobs_safety_window() {
+        return Err(UnsafeExpiries::SafetyWindowTooShort);
+    }
+
+    let (max_alpha_offset, max_beta_offset) = expiry_offsets(config);
+    if alpha_offset > Duration::from(max_alpha_offset) * MAX_OFFSET_FACTOR
+        || beta_offset > Duration::from(max_beta_offset) * MAX_OFFSET_FACTOR
+    {
+        return Err(UnsafeExpiries::TooLate);
+    }
+
+    Ok(())
+}
+
 /// Current time as a UNIX timestamp from the perspective of the implementer.
 ///
 /// Intended for getting the current time from the underlying blockchain.
@@ -903,6 +976,46 @@ mod tests {
         assert_eq!(b, 35.seconds().into());
     }
 
+    #[test]
+    fn standard_expiries_are_valid() {
+        let start_at = Timestamp::now();
+
+        let (alpha, beta) = expiry_offsets_hbit_herc20(Network::Main);
+        let (alpha, beta) = to_timestamps(start_at, alpha, beta);
+        assert_that(&validate_hbit_herc20(Network::Main, start_at, alpha, beta)).is_ok();
+
+        let (alpha, beta) = expiry_offsets_herc20_hbit(Network::Main);
+        let (alpha, beta) = to_timestamps(start_at, alpha, beta);
+        assert_that(&validate_herc20_hbit(Network::Main, start_at, alpha, beta)).is_ok();
+    }
+
+    #[test]
+    fn unsafe_expiries_are_rejected() {
+        let start_at = Timestamp::now();
+        let (alpha, beta) = expiry_offsets_herc20_hbit(Network::Main);
+        let (alpha, beta) = (Duration::from(alpha), Duration::from(beta));
+
+        let validate = |alpha: Duration, beta: Duration| {
+            validate_herc20_hbit(
+                Network::Main,
+                start_at,
+                start_at.add_duration(alpha).into(),
+                start_at.add_duration(beta).into(),
+            )
+        };
+
+        assert_eq!(
+            validate(alpha, beta - 1.seconds()),
+            Err(UnsafeExpiries::BetaExpiryTooEarly)
+        );
+        assert_eq!(
+            validate(alpha, alpha),
+            Err(UnsafeExpiries::SafetyWindowTooShort)
+        );
+        assert_eq!(validate(alpha * 3, beta * 2), Err(UnsafeExpiries::TooLate));
+        assert_eq!(validate(alpha + 1.hours(), beta + 1.hours()), Ok(()));
+    }
+
     #[tokio::test]
     async fn alice_can_complete_an_hbit_herc20_swap() {
         let start_at = Timestamp::now();
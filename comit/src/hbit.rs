@@ -1,9 +1,7 @@
 //! Htlc Bitcoin atomic swap protocol.
 
 use crate::{
-    actions::bitcoin::{
-        sign_with_fixed_rate, BroadcastSignedTransaction, SendToAddress, SpendOutput,
-    },
+    actions::bitcoin::{SendToAddress, SpendHtlc, SpendOutput},
     asset,
     btsieve::{
         bitcoin::{watch_for_created_outpoint, watch_for_spent_outpoint},
@@ -214,6 +212,10 @@ where
     Ok(Refunded { transaction })
 }
 
+/// The highest sequence number that still signals replaceability as per
+/// BIP125.
+pub const RBF_SEQUENCE: u32 = 0xFFFF_FFFD;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Params {
     pub network: ledger::Bitcoin,
@@ -244,11 +246,11 @@ impl Params {
         fund_location: htlc_location::Bitcoin,
         transient_refund_sk: SecretKey,
         refund_address: Address,
-    ) -> anyhow::Result<BroadcastSignedTransaction>
+    ) -> SpendHtlc
     where
         C: Signing,
     {
-        self.build_spend_action(&secp, fund_amount, fund_location, refund_address, |htlc| {
+        self.build_spend_action(fund_amount, fund_location, refund_address, |htlc| {
             htlc.unlock_after_timeout(&secp, transient_refund_sk)
         })
     }
@@ -261,26 +263,22 @@ impl Params {
         transient_redeem_sk: SecretKey,
         redeem_address: Address,
         secret: Secret,
-    ) -> anyhow::Result<BroadcastSignedTransaction>
+    ) -> SpendHtlc
     where
         C: Signing,
     {
-        self.build_spend_action(&secp, fund_amount, fund_location, redeem_address, |htlc| {
+        self.build_spend_action(fund_amount, fund_location, redeem_address, |htlc| {
             htlc.unlock_with_secret(secp, transient_redeem_sk, secret.into_raw_secret())
         })
     }
 
-    fn build_spend_action<C>(
+    fn build_spend_action(
         &self,
-        secp: &Secp256k1<C>,
         fund_amount: asset::Bitcoin,
         fund_location: htlc_location::Bitcoin,
         spend_address: Address,
         unlock_fn: impl Fn(Htlc) -> UnlockParameters,
-    ) -> anyhow::Result<BroadcastSignedTransaction>
-    where
-        C: Signing,
-    {
+    ) -> SpendHtlc {
         let network = self.network;
         let primed_transaction = {
             let htlc = build_bitcoin_htlc(
@@ -289,18 +287,20 @@ impl Params {
                 self.expiry,
                 self.secret_hash,
             );
-            let input_parameters = unlock_fn(htlc);
+            let mut input_parameters = unlock_fn(htlc);
+            // Signal replaceability so the spend can be fee-bumped if it gets stuck.
+            input_parameters.sequence = u32::min(input_parameters.sequence, RBF_SEQUENCE);
+
             let spend_output =
                 SpendOutput::new(fund_location, fund_amount, input_parameters, network);
 
             spend_output.spend_to(spend_address)
         };
-        let transaction = sign_with_fixed_rate(&secp, primed_transaction)?;
 
-        Ok(BroadcastSignedTransaction {
-            transaction,
+        SpendHtlc {
+            primed_transaction,
             network,
-        })
+        }
     }
 }
 
@@ -33,6 +33,7 @@ pub mod order;
 pub mod orderpool;
 #[cfg(test)]
 pub mod proptest;
+pub mod reputation;
 mod secret;
 mod secret_hash;
 mod timestamp;
@@ -161,6 +162,16 @@ impl From<Network> for ledger::Bitcoin {
     }
 }
 
+impl From<ledger::Bitcoin> for Network {
+    fn from(network: ledger::Bitcoin) -> Self {
+        match network {
+            ledger::Bitcoin::Mainnet => Network::Main,
+            ledger::Bitcoin::Testnet => Network::Test,
+            ledger::Bitcoin::Regtest => Network::Dev,
+        }
+    }
+}
+
 impl From<Network> for ethereum::ChainId {
     fn from(network: Network) -> Self {
         match network {
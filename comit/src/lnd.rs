@@ -1,4 +1,5 @@
 use crate::{
+    actions::lnd::{AddHoldInvoice, SendPayment, SettleInvoice},
     asset,
     halbit::{
         Accepted, Cancelled, Opened, Params, Settled, WaitForAccepted, WaitForCancelled,
@@ -11,12 +12,13 @@ use reqwest::{
     header::{HeaderMap, HeaderValue},
     StatusCode, Url,
 };
-use serde::{de, export::fmt, Deserialize, Deserializer};
+use serde::{de, export::fmt, Deserialize, Deserializer, Serialize};
 use std::{
     convert::{TryFrom, TryInto},
     fmt::Debug,
     io::Read,
     path::PathBuf,
+    sync::{Arc, Mutex},
     time::Duration,
 };
 
@@ -414,6 +416,15 @@ struct LndError {
     code: u32,
 }
 
+/// LND rejects requests the macaroon doesn't grant with one of these messages,
+/// depending on whether the macaroon lacks the permission or is not valid at
+/// all.
+fn is_permission_denied(error: &Error) -> bool {
+    error.downcast_ref::<LndError>().map_or(false, |e| {
+        e.message.contains("permission denied") || e.message.contains("verification failed")
+    })
+}
+
 #[async_trait::async_trait]
 impl WaitForOpened for LndConnectorAsReceiver {
     async fn wait_for_opened(&self, params: &Params) -> anyhow::Result<Opened> {
@@ -517,6 +528,401 @@ impl WaitForCancelled for LndConnectorAsReceiver {
     }
 }
 
+/// LND connector for executing lightning actions on behalf of the user.
+///
+/// In contrast to the other connectors, this one needs to be constructed from
+/// params that hold a macaroon with write permissions for invoices and
+/// payments, e.g. the admin macaroon.
+#[derive(Clone, Debug)]
+pub struct LndActionExecutor {
+    lnd_url: Url,
+    certificate: Certificate,
+    macaroon: Macaroon,
+    /// Whether the macaroon grants the permissions we need, once we checked.
+    can_execute_actions: Arc<Mutex<Option<bool>>>,
+}
+
+impl From<LndConnectorParams> for LndActionExecutor {
+    fn from(params: LndConnectorParams) -> Self {
+        Self {
+            lnd_url: params.lnd_url,
+            certificate: params.certificate,
+            macaroon: params.macaroon,
+            can_execute_actions: Arc::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct AddHoldInvoiceRequest {
+    hash: String,
+    value: String,
+    expiry: String,
+    cltv_expiry: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct SettleInvoiceRequest {
+    preimage: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct SendPaymentRequest {
+    dest: String,
+    amt: String,
+    payment_hash: String,
+    final_cltv_delta: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct SendPaymentResponse {
+    payment_error: Option<String>,
+}
+
+impl LndActionExecutor {
+    /// Adds a hold invoice for the given secret hash.
+    pub async fn add_hold_invoice(&self, action: AddHoldInvoice) -> anyhow::Result<()> {
+        let body = AddHoldInvoiceRequest {
+            hash: base64::encode(action.secret_hash.as_raw()),
+            value: action.amount.as_sat().to_string(),
+            expiry: action.expiry.to_string(),
+            cltv_expiry: action.cltv_expiry.to_string(),
+        };
+
+        let _ = self.post("/v2/invoices/hodl", &body).await?;
+
+        Ok(())
+    }
+
+    /// Settles a previously added hold invoice by revealing the secret.
+    pub async fn settle_invoice(&self, action: SettleInvoice) -> anyhow::Result<()> {
+        let body = SettleInvoiceRequest {
+            preimage: base64::encode(action.secret.as_raw_secret()),
+        };
+
+        let _ = self.post("/v2/invoices/settle", &body).await?;
+
+        Ok(())
+    }
+
+    /// Sends a payment locked to the given secret hash.
+    ///
+    /// LND only responds to this request once the payment has either been
+    /// settled or failed. For hold invoices, this may take as long as the
+    /// receiver waits before settling, hence callers should not block on it.
+    pub async fn send_payment(&self, action: SendPayment) -> anyhow::Result<()> {
+        let body = SendPaymentRequest {
+            dest: base64::encode(action.to_public_key.to_bytes()),
+            amt: action.amount.as_sat().to_string(),
+            payment_hash: base64::encode(action.secret_hash.as_raw()),
+            final_cltv_delta: action.final_cltv_delta.into(),
+        };
+
+        let response = self
+            .post("/v1/channels/transactions", &body)
+            .await?
+            .json::<SendPaymentResponse>()
+            .await
+            .context("failed to deserialize response as payment result")?;
+
+        match response.payment_error {
+            Some(error) if !error.is_empty() => bail!(PaymentFailed(error)),
+            _ => Ok(()),
+        }
+    }
+
+    /// The status of the latest payment we sent for the given secret hash, if
+    /// any.
+    ///
+    /// LND refuses to pay the same hash again unless the earlier payment
+    /// failed, hence this is to be checked before sending a payment again.
+    pub async fn payment_status(
+        &self,
+        secret_hash: SecretHash,
+    ) -> anyhow::Result<Option<PaymentStatus>> {
+        let url = self
+            .lnd_url
+            .join("/v1/payments?include_incomplete=true")
+            .expect("append valid string to url");
+        let response = client(&self.certificate, &self.macaroon)?
+            .get(url.clone())
+            .send()
+            .await
+            .with_context(|| GetRequestFailed(url))?
+            .json::<PaymentsResponse>()
+            .await
+            .context("failed to deserialize response as list of payments")?;
+
+        let status = response
+            .payments
+            .unwrap_or_default()
+            .into_iter()
+            .rev()
+            .find(|payment| payment.payment_hash == secret_hash)
+            .map(|payment| payment.status);
+
+        Ok(status)
+    }
+
+    async fn post<B>(&self, path: &str, body: &B) -> anyhow::Result<reqwest::Response>
+    where
+        B: Serialize + Debug,
+    {
+        let url = self.lnd_url.join(path).expect("append valid string to url");
+        let response = client(&self.certificate, &self.macaroon)?
+            .post(url.clone())
+            .json(body)
+            .send()
+            .await
+            .with_context(|| PostRequestFailed(url))?;
+
+        if !response.status().is_success() {
+            let status_code = response.status();
+            let lnd_error = response.json::<LndError>().await.with_context(|| {
+                format!(
+                    "encountered {} while executing {} but couldn't deserialize error response",
+                    status_code, path
+                )
+            })?;
+
+            bail!(lnd_error)
+        }
+
+        Ok(response)
+    }
+
+    /// Whether the macaroon allows us to add and settle invoices and to send
+    /// payments.
+    ///
+    /// LND bakes the permissions a macaroon grants into its identifier, hence
+    /// we can read them off the macaroon without making any requests. The
+    /// macaroon doesn't change, so neither does the result.
+    pub async fn can_execute_actions(&self) -> anyhow::Result<bool> {
+        let mut can_execute_actions = self
+            .can_execute_actions
+            .lock()
+            .expect("no other thread panicked while holding the lock");
+
+        if let Some(can_execute_actions) = *can_execute_actions {
+            return Ok(can_execute_actions);
+        }
+
+        let macaroon = hex::decode(&self.macaroon.0)?;
+        let can = grants_action_permissions(&macaroon_permissions(&macaroon)?);
+        *can_execute_actions = Some(can);
+
+        Ok(can)
+    }
+}
+
+/// Whether the permissions allow adding and settling hold invoices and
+/// sending payments.
+fn grants_action_permissions(permissions: &[(String, String)]) -> bool {
+    [("invoices", "write"), ("offchain", "write")]
+        .iter()
+        .all(|&(entity, action)| {
+            permissions.iter().any(|(granted_entity, granted_action)| {
+                granted_entity == entity && granted_action == action
+            })
+        })
+}
+
+/// The version of the identifiers of macaroons baked by LND.
+const LND_MACAROON_ID_VERSION: u8 = 3;
+
+/// Reads the permissions off a macaroon baked by LND, as (entity, action)
+/// pairs.
+///
+/// Macaroons are serialized in the binary V2 format. LND's identifiers are a
+/// version byte followed by a protobuf encoded `MacaroonId` whose third field
+/// holds the permissions as `Op { entity = 1; repeated actions = 2 }`.
+fn macaroon_permissions(macaroon: &[u8]) -> anyhow::Result<Vec<(String, String)>> {
+    let mut reader = macaroon;
+
+    anyhow::ensure!(
+        read_bytes(&mut reader, 1)?[0] == 2,
+        "macaroon is not in the V2 format"
+    );
+    let identifier = loop {
+        match read_varint(&mut reader)? {
+            // location
+            1 => {
+                let len = read_varint(&mut reader)?;
+                read_bytes(&mut reader, len)?;
+            }
+            // identifier
+            2 => {
+                let len = read_varint(&mut reader)?;
+                break read_bytes(&mut reader, len)?;
+            }
+            field => bail!("unexpected field {} in macaroon", field),
+        }
+    };
+
+    let (version, mut macaroon_id) = identifier
+        .split_first()
+        .context("macaroon identifier is empty")?;
+    anyhow::ensure!(
+        *version == LND_MACAROON_ID_VERSION,
+        "macaroon identifier has unknown version {}",
+        version
+    );
+
+    let mut permissions = Vec::new();
+    while let Some((field, mut op)) = read_protobuf_field(&mut macaroon_id)? {
+        if field != 3 {
+            continue;
+        }
+
+        let mut entity = String::new();
+        let mut actions = Vec::new();
+        while let Some((field, value)) = read_protobuf_field(&mut op)? {
+            match field {
+                1 => entity = String::from_utf8(value.to_vec())?,
+                2 => actions.push(String::from_utf8(value.to_vec())?),
+                _ => {}
+            }
+        }
+
+        permissions.extend(actions.into_iter().map(|action| (entity.clone(), action)));
+    }
+
+    Ok(permissions)
+}
+
+/// Reads the next field of a protobuf message, skipping fields that are not
+/// length-delimited.
+fn read_protobuf_field<'a>(message: &mut &'a [u8]) -> anyhow::Result<Option<(u64, &'a [u8])>> {
+    while !message.is_empty() {
+        let key = read_varint(message)?;
+        let field = key >> 3;
+
+        match key & 0x07 {
+            0 => {
+                read_varint(message)?;
+            }
+            1 => {
+                read_bytes(message, 8)?;
+            }
+            2 => {
+                let len = read_varint(message)?;
+                return Ok(Some((field, read_bytes(message, len)?)));
+            }
+            5 => {
+                read_bytes(message, 4)?;
+            }
+            wire_type => bail!("unsupported protobuf wire type {}", wire_type),
+        }
+    }
+
+    Ok(None)
+}
+
+fn read_varint(buf: &mut &[u8]) -> anyhow::Result<u64> {
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let byte = read_bytes(buf, 1)?[0];
+        value |= u64::from(byte & 0x7f) << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+
+    bail!("varint is too long")
+}
+
+fn read_bytes<'a>(buf: &mut &'a [u8], len: u64) -> anyhow::Result<&'a [u8]> {
+    let len = usize::try_from(len)?;
+    anyhow::ensure!(buf.len() >= len, "unexpected end of macaroon");
+
+    let (bytes, rest) = buf.split_at(len);
+    *buf = rest;
+
+    Ok(bytes)
+}
+
+/// The parts of LND's node information we are interested in.
+// ref: https://api.lightning.community/#getinfo
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct NodeInfo {
+    pub block_height: u32,
+    #[serde(default)]
+    pub synced_to_chain: bool,
+}
+
+/// LND connector for checking whether the LND node is usable for swaps.
+#[derive(Clone, Debug)]
+pub struct LndDiagnostics {
+    lnd_url: Url,
+    certificate: Certificate,
+    macaroon: Macaroon,
+}
+
+impl From<LndConnectorParams> for LndDiagnostics {
+    fn from(params: LndConnectorParams) -> Self {
+        Self {
+            lnd_url: params.lnd_url,
+            certificate: params.certificate,
+            macaroon: params.macaroon,
+        }
+    }
+}
+
+impl LndDiagnostics {
+    pub async fn node_info(&self) -> anyhow::Result<NodeInfo> {
+        let node_info = self
+            .get("/v1/getinfo")
+            .await?
+            .json::<NodeInfo>()
+            .await
+            .context("failed to deserialize response as node info")?;
+
+        Ok(node_info)
+    }
+
+    /// Whether the macaroon allows us to read invoices and payments, which is
+    /// what we need to follow lightning HTLCs.
+    pub async fn can_read_invoices_and_payments(&self) -> anyhow::Result<bool> {
+        for path in &[
+            "/v1/invoices?num_max_invoices=1",
+            "/v1/payments?max_payments=1",
+        ] {
+            match self.get(path).await {
+                Ok(_) => {}
+                Err(e) if is_permission_denied(&e) => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(true)
+    }
+
+    async fn get(&self, path: &str) -> anyhow::Result<reqwest::Response> {
+        let url = self.lnd_url.join(path).expect("append valid string to url");
+        let response = client(&self.certificate, &self.macaroon)?
+            .get(url.clone())
+            .send()
+            .await
+            .with_context(|| GetRequestFailed(url))?;
+
+        if !response.status().is_success() {
+            let status_code = response.status();
+            let lnd_error = response.json::<LndError>().await.with_context(|| {
+                format!(
+                    "encountered {} while fetching {} but couldn't deserialize error response",
+                    status_code, path
+                )
+            })?;
+
+            bail!(lnd_error)
+        }
+
+        Ok(response)
+    }
+}
+
 fn client(certificate: &Certificate, macaroon: &Macaroon) -> anyhow::Result<reqwest::Client> {
     let cert = certificate.0.clone();
     let mut default_headers = HeaderMap::with_capacity(1);
@@ -551,6 +957,14 @@ fn client(certificate: &Certificate, macaroon: &Macaroon) -> anyhow::Result<reqw
 #[error("GET request to {0} failed")]
 pub struct GetRequestFailed(Url);
 
+#[derive(Debug, thiserror::Error)]
+#[error("POST request to {0} failed")]
+pub struct PostRequestFailed(Url);
+
+#[derive(Debug, thiserror::Error)]
+#[error("payment failed: {0}")]
+pub struct PaymentFailed(String);
+
 pub fn deserialize_amount<'de, D>(deserializer: D) -> Result<asset::Bitcoin, D::Error>
 where
     D: Deserializer<'de>,
@@ -647,6 +1061,80 @@ where
 mod tests {
     use super::*;
     use spectral::prelude::*;
+
+    fn varint(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while value >= 0x80 {
+            bytes.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+
+        bytes
+    }
+
+    fn length_delimited(key: u64, value: &[u8]) -> Vec<u8> {
+        let mut bytes = varint(key);
+        bytes.extend(varint(value.len() as u64));
+        bytes.extend(value);
+
+        bytes
+    }
+
+    /// Bakes a macaroon the way LND does, without the caveats and signature
+    /// we don't look at.
+    fn macaroon(permissions: &[(&str, &str)]) -> Vec<u8> {
+        let protobuf_field = |number: u64, value: &[u8]| length_delimited((number << 3) | 2, value);
+
+        let mut id = vec![LND_MACAROON_ID_VERSION];
+        id.extend(protobuf_field(1, b"nonce"));
+        id.extend(protobuf_field(2, b"0"));
+        for (entity, action) in permissions {
+            let mut op = protobuf_field(1, entity.as_bytes());
+            op.extend(protobuf_field(2, action.as_bytes()));
+            id.extend(protobuf_field(3, &op));
+        }
+
+        let mut macaroon = vec![2];
+        macaroon.extend(length_delimited(1, b"lnd"));
+        macaroon.extend(length_delimited(2, &id));
+        macaroon.push(0);
+
+        macaroon
+    }
+
+    #[test]
+    fn admin_macaroon_grants_action_permissions() {
+        let macaroon = macaroon(&[
+            ("info", "read"),
+            ("invoices", "read"),
+            ("invoices", "write"),
+            ("offchain", "read"),
+            ("offchain", "write"),
+        ]);
+
+        let permissions = macaroon_permissions(&macaroon).unwrap();
+
+        assert!(permissions.contains(&("invoices".to_owned(), "write".to_owned())));
+        assert!(grants_action_permissions(&permissions));
+    }
+
+    #[test]
+    fn readonly_macaroon_does_not_grant_action_permissions() {
+        let macaroon = macaroon(&[("info", "read"), ("invoices", "read"), ("offchain", "read")]);
+
+        let permissions = macaroon_permissions(&macaroon).unwrap();
+
+        assert!(!grants_action_permissions(&permissions));
+    }
+
+    #[test]
+    fn macaroons_not_baked_by_lnd_are_rejected() {
+        let mut macaroon = macaroon(&[]);
+        macaroon[0] = 1;
+
+        assert!(macaroon_permissions(&macaroon).is_err());
+    }
     #[test]
     fn deserialize_ln_invoice_preimage_present() {
         let r_preimage = [
@@ -1,4 +1,5 @@
 pub mod comit;
+pub mod discovery;
 pub mod oneshot_behaviour;
 pub mod oneshot_protocol;
 pub mod orderbook;
@@ -0,0 +1,176 @@
+use anyhow::Context as _;
+use libp2p::{
+    core::multiaddr::Protocol,
+    kad::{record::store::MemoryStore, Kademlia, KademliaConfig, KademliaEvent},
+    mdns::{MdnsEvent, TokioMdns},
+    swarm::{toggle::Toggle, NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters},
+    Multiaddr, NetworkBehaviour, PeerId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    task::{Context, Poll},
+};
+
+/// The protocol name of our DHT, we don't want to end up in the IPFS DHT.
+const KADEMLIA_PROTOCOL_NAME: &[u8] = b"/comit/kad/1.0.0";
+
+/// Configures how we discover other peers.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Config {
+    /// Find peers in the local network using mDNS.
+    pub mdns: bool,
+    /// Find peers through a Kademlia DHT.
+    pub kademlia: bool,
+    /// The nodes we initially connect to in order to join the DHT.
+    ///
+    /// Each address has to end with the peer id of the node, e.g.
+    /// `/ip4/1.2.3.4/tcp/9939/p2p/QmXYZ...`.
+    pub bootstrap_nodes: Vec<Multiaddr>,
+}
+
+#[derive(Debug)]
+pub enum BehaviourOutEvent {
+    /// We learned the address of a peer.
+    Discovered { peer: PeerId, address: Multiaddr },
+}
+
+/// A [NetworkBehaviour] for discovering other peers without having to dial
+/// them manually.
+///
+/// Both mechanisms are optional: mDNS finds peers in the local network and a
+/// Kademlia DHT, joined through the configured bootstrap nodes, finds peers on
+/// the internet.
+#[derive(NetworkBehaviour)]
+#[behaviour(poll_method = "poll", out_event = "BehaviourOutEvent")]
+#[allow(missing_debug_implementations)]
+pub struct Discovery {
+    mdns: Toggle<TokioMdns>,
+    kademlia: Toggle<Kademlia<MemoryStore>>,
+
+    #[behaviour(ignore)]
+    events: VecDeque<BehaviourOutEvent>,
+}
+
+impl Discovery {
+    pub fn new(me: PeerId, config: &Config) -> anyhow::Result<Self> {
+        let mdns = if config.mdns {
+            Some(TokioMdns::new().context("failed to start mDNS")?)
+        } else {
+            None
+        };
+
+        let kademlia = if config.kademlia {
+            let mut kademlia_config = KademliaConfig::default();
+            kademlia_config.set_protocol_name(KADEMLIA_PROTOCOL_NAME);
+
+            let store = MemoryStore::new(me.clone());
+            let mut kademlia = Kademlia::with_config(me, store, kademlia_config);
+
+            for node in &config.bootstrap_nodes {
+                let (peer, address) = split_peer_id(node.clone())?;
+                kademlia.add_address(&peer, address);
+            }
+
+            if kademlia.bootstrap().is_err() {
+                tracing::warn!("no bootstrap nodes configured, waiting for peers to connect");
+            }
+
+            Some(kademlia)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            mdns: mdns.into(),
+            kademlia: kademlia.into(),
+            events: VecDeque::default(),
+        })
+    }
+
+    fn poll<BIE>(
+        &mut self,
+        _: &mut Context<'_>,
+        _: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<BIE, BehaviourOutEvent>> {
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
+        Poll::Pending
+    }
+}
+
+impl NetworkBehaviourEventProcess<MdnsEvent> for Discovery {
+    fn inject_event(&mut self, event: MdnsEvent) {
+        if let MdnsEvent::Discovered(discovered) = event {
+            for (peer, address) in discovered {
+                tracing::debug!("discovered {} at {} through mDNS", peer, address);
+                self.events
+                    .push_back(BehaviourOutEvent::Discovered { peer, address });
+            }
+        }
+    }
+}
+
+impl NetworkBehaviourEventProcess<KademliaEvent> for Discovery {
+    fn inject_event(&mut self, event: KademliaEvent) {
+        if let KademliaEvent::RoutingUpdated {
+            peer, addresses, ..
+        } = event
+        {
+            for address in addresses.into_vec() {
+                tracing::debug!("discovered {} at {} through the DHT", peer, address);
+                self.events.push_back(BehaviourOutEvent::Discovered {
+                    peer: peer.clone(),
+                    address,
+                });
+            }
+        }
+    }
+}
+
+/// Splits an address of the form `.../p2p/<peer id>` into the peer id and the
+/// address of the peer.
+fn split_peer_id(address: Multiaddr) -> anyhow::Result<(PeerId, Multiaddr)> {
+    let mut node_address = address.clone();
+
+    match node_address.pop() {
+        Some(Protocol::P2p(hash)) => {
+            let peer = PeerId::from_multihash(hash)
+                .map_err(|_| anyhow::anyhow!("invalid peer id in bootstrap node address"))?;
+
+            Ok((peer, node_address))
+        }
+        _ => anyhow::bail!(
+            "bootstrap node address {} does not end with a peer id",
+            address
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libp2p::identity::Keypair;
+
+    #[test]
+    fn splits_peer_id_off_bootstrap_node_address() {
+        let peer = PeerId::from(Keypair::generate_ed25519().public());
+        let address: Multiaddr = "/ip4/127.0.0.1/tcp/9939".parse().unwrap();
+
+        let (actual_peer, actual_address) =
+            split_peer_id(address.clone().with(Protocol::P2p(peer.clone().into()))).unwrap();
+
+        assert_eq!(actual_peer, peer);
+        assert_eq!(actual_address, address);
+    }
+
+    #[test]
+    fn rejects_bootstrap_node_address_without_peer_id() {
+        let address: Multiaddr = "/ip4/127.0.0.1/tcp/9939".parse().unwrap();
+
+        assert!(split_peer_id(address).is_err());
+    }
+}
@@ -2,7 +2,7 @@ mod makerbook;
 mod order_source;
 
 use crate::{
-    orderpool::{Match, OrderPool},
+    orderpool::{Match, OrderPool, OrderUpdate},
     BtcDaiOrder, OrderId,
 };
 use libp2p::{
@@ -10,12 +10,15 @@ use libp2p::{
     swarm::{NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters},
     NetworkBehaviour, PeerId,
 };
-use makerbook::Makerbook;
+use makerbook::{Makerbook, SignedOrderRemoval, SignedReservation};
 use order_source::*;
 use std::{
     collections::VecDeque,
     task::{Context, Poll},
 };
+use time::OffsetDateTime;
+
+pub use order_source::{InvalidOrder, SignedOrder, ORDER_TTL};
 
 /// The Orderbook libp2p network behaviour.
 #[derive(NetworkBehaviour)]
@@ -29,16 +32,20 @@ pub struct Orderbook {
     events: VecDeque<BehaviourOutEvent>,
     #[behaviour(ignore)]
     orderpool: OrderPool,
+    /// Our identity, used to sign the orders we push to other peers.
+    #[behaviour(ignore)]
+    key: Keypair,
 }
 
 impl Orderbook {
     /// Construct a new orderbook for this node using the node's peer ID.
     pub fn new(me: PeerId, key: Keypair) -> Orderbook {
         Orderbook {
-            makerbook: Makerbook::new(key),
-            order_source: OrderSource::default(),
+            makerbook: Makerbook::new(key.clone()),
+            order_source: OrderSource::new(key.clone()),
             events: VecDeque::new(),
             orderpool: OrderPool::new(me),
+            key,
         }
     }
 
@@ -53,6 +60,11 @@ impl Orderbook {
         self.orderpool.clear_own_orders();
     }
 
+    /// Connect to a peer that might be a maker, e.g. one we discovered.
+    pub fn connect(&mut self, peer: PeerId) {
+        self.makerbook.connect(peer);
+    }
+
     /// Publish this order so it is visible to other peers.
     pub fn publish(&mut self, order: BtcDaiOrder) {
         self.orderpool.publish(order);
@@ -63,6 +75,16 @@ impl Orderbook {
         self.orderpool.cancel(id);
     }
 
+    /// Ignore the orders of this maker, e.g. because of their bad reputation.
+    pub fn block(&mut self, maker: PeerId) {
+        self.orderpool.block(maker);
+    }
+
+    /// Undoes `block()`.
+    pub fn unblock(&mut self, maker: &PeerId) {
+        self.orderpool.unblock(maker);
+    }
+
     pub fn orderpool(&self) -> &OrderPool {
         &self.orderpool
     }
@@ -75,6 +97,24 @@ impl Orderbook {
         &mut self.orderpool
     }
 
+    fn push_order_update(&mut self, update: OrderUpdate) {
+        match update {
+            OrderUpdate::Published(order) => {
+                let id = order.id;
+                let expires_at = OffsetDateTime::now_utc() + ORDER_TTL;
+
+                match SignedOrder::sign(order, expires_at, &self.key) {
+                    Ok(order) => self.makerbook.publish_order(order),
+                    Err(e) => tracing::warn!("failed to sign order {}: {:#}", id, e),
+                }
+            }
+            OrderUpdate::Removed(id) => match SignedOrderRemoval::sign(id, &self.key) {
+                Ok(removal) => self.makerbook.remove_order(removal),
+                Err(e) => tracing::warn!("failed to sign removal of order {}: {:#}", id, e),
+            },
+        }
+    }
+
     fn my_poll<BIE>(
         &mut self,
         _: &mut Context<'_>,
@@ -91,6 +131,19 @@ impl Orderbook {
                 .push_back(BehaviourOutEvent::OrderMatch(r#match));
         }
 
+        for reservation in self.orderpool.take_reservation_updates() {
+            let id = reservation.order;
+
+            match SignedReservation::sign(reservation, &self.key) {
+                Ok(reservation) => self.makerbook.announce_reservation(reservation),
+                Err(e) => tracing::warn!("failed to sign reservation for order {}: {:#}", id, e),
+            }
+        }
+
+        for update in self.orderpool.take_order_updates() {
+            self.push_order_update(update);
+        }
+
         Poll::Pending
     }
 }
@@ -103,6 +156,10 @@ pub struct OrderNotFound(OrderId);
 #[derive(Debug)]
 pub enum BehaviourOutEvent {
     OrderMatch(Match),
+    /// The given peer sent us malformed messages or invalid orders.
+    ProtocolViolation {
+        peer: PeerId,
+    },
 }
 
 impl NetworkBehaviourEventProcess<makerbook::BehaviourOutEvent> for Orderbook {
@@ -112,6 +169,41 @@ impl NetworkBehaviourEventProcess<makerbook::BehaviourOutEvent> for Orderbook {
                 self.order_source.stop_getting_orders_from(&peer);
                 self.orderpool.remove_all_from(&peer)
             }
+            makerbook::BehaviourOutEvent::Reservation { peer, reservation } => {
+                match reservation.verify_maker(&peer) {
+                    Ok(reservation) => self.orderpool.receive_reservation(&peer, reservation),
+                    Err(e) => {
+                        tracing::warn!("ignoring reservation announced by {}: {}", peer, e);
+                        self.events
+                            .push_back(BehaviourOutEvent::ProtocolViolation { peer });
+                    }
+                }
+            }
+            makerbook::BehaviourOutEvent::OrderPublished { peer, order } => {
+                match order.verify_maker(&peer) {
+                    Ok((order, expires_at)) => {
+                        self.orderpool.receive_order(peer, order, expires_at)
+                    }
+                    Err(e) => {
+                        tracing::warn!("ignoring order pushed by {}: {}", peer, e);
+                        self.events
+                            .push_back(BehaviourOutEvent::ProtocolViolation { peer });
+                    }
+                }
+            }
+            makerbook::BehaviourOutEvent::OrderRemoved { peer, order } => {
+                match order.verify_maker(&peer) {
+                    Ok(order) => self.orderpool.remove_order(&peer, order),
+                    Err(e) => {
+                        tracing::warn!("ignoring order removal announced by {}: {}", peer, e);
+                        self.events
+                            .push_back(BehaviourOutEvent::ProtocolViolation { peer });
+                    }
+                }
+            }
+            makerbook::BehaviourOutEvent::MalformedMessage { peer } => self
+                .events
+                .push_back(BehaviourOutEvent::ProtocolViolation { peer }),
         }
     }
 }
@@ -1,3 +1,8 @@
+use crate::{
+    network::orderbook::{InvalidOrder, SignedOrder},
+    orderpool::Reservation,
+    OrderId,
+};
 use byteorder::{BigEndian, ByteOrder};
 use conquer_once::Lazy;
 use libp2p::{
@@ -5,14 +10,16 @@ use libp2p::{
         Gossipsub, GossipsubConfigBuilder, GossipsubEvent, GossipsubMessage, GossipsubRpc,
         MessageAuthenticity, MessageId, Topic,
     },
-    identity::Keypair,
+    identity::{error::DecodingError, Keypair, PublicKey},
     swarm::{
         DialPeerCondition, NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters,
     },
     NetworkBehaviour, PeerId,
 };
+use serde::{de::DeserializeOwned, Serialize};
 use std::{
     collections::{hash_map::DefaultHasher, VecDeque},
+    convert::TryFrom,
     hash::{Hash, Hasher},
     task::{Context, Poll},
     time::Duration,
@@ -27,6 +34,20 @@ pub enum BehaviourOutEvent {
     ///
     /// Connections to this peer can be closed as a result of this event.
     Logout { peer: PeerId },
+    /// The given peer announced a reservation for one of its orders.
+    Reservation {
+        peer: PeerId,
+        reservation: SignedReservation,
+    },
+    /// The given peer published a new order or changed one of its orders.
+    OrderPublished { peer: PeerId, order: SignedOrder },
+    /// The given peer removed one of its orders.
+    OrderRemoved {
+        peer: PeerId,
+        order: SignedOrderRemoval,
+    },
+    /// The given peer published a message we could not make sense of.
+    MalformedMessage { peer: PeerId },
 }
 
 /// A [NetworkBehaviour] for discovering peers that are likely to trade with us.
@@ -82,6 +103,46 @@ impl Makerbook {
         }
     }
 
+    /// Announce how much of one of our orders is reserved.
+    pub fn announce_reservation(&mut self, reservation: SignedReservation) {
+        let message = serde_json::to_vec(&wire::Message::Reservation {
+            reservation: reservation.0.into(),
+        })
+        .expect("serialization doesn't panic");
+        if self.gossipsub.publish(&COMIT_MAKERS, message).is_err() {
+            tracing::warn!("announcing reservation failed");
+        }
+    }
+
+    /// Push a new or changed order to all subscribers.
+    pub fn publish_order(&mut self, order: SignedOrder) {
+        let message = serde_json::to_vec(&wire::Message::Order { order })
+            .expect("serialization doesn't panic");
+        if self.gossipsub.publish(&COMIT_MAKERS, message).is_err() {
+            tracing::warn!("publishing order failed");
+        }
+    }
+
+    /// Tell all subscribers that one of our orders is gone.
+    pub fn remove_order(&mut self, order: SignedOrderRemoval) {
+        let message = serde_json::to_vec(&wire::Message::OrderRemoved {
+            order: order.0.into(),
+        })
+        .expect("serialization doesn't panic");
+        if self.gossipsub.publish(&COMIT_MAKERS, message).is_err() {
+            tracing::warn!("removing order failed");
+        }
+    }
+
+    /// Connect to a peer we discovered by other means than gossipsub so that
+    /// we learn about its orders and it learns about ours.
+    pub fn connect(&mut self, peer: PeerId) {
+        self.actions.push_back(NetworkBehaviourAction::DialPeer {
+            peer_id: peer,
+            condition: DialPeerCondition::Disconnected,
+        });
+    }
+
     fn poll(
         &mut self,
         _: &mut Context<'_>,
@@ -102,7 +163,16 @@ impl NetworkBehaviourEventProcess<GossipsubEvent> for Makerbook {
             let message = match serde_json::from_slice::<wire::Message>(&message.data) {
                 Ok(message) => message,
                 Err(e) => {
-                    tracing::debug!("receives malformed message from {}: {:?}", relayed_from, e);
+                    tracing::debug!(
+                        "received malformed message of {} from {}: {:?}",
+                        source,
+                        relayed_from,
+                        e
+                    );
+                    self.actions
+                        .push_back(NetworkBehaviourAction::GenerateEvent(
+                            BehaviourOutEvent::MalformedMessage { peer: source },
+                        ));
                     return;
                 }
             };
@@ -132,11 +202,189 @@ impl NetworkBehaviourEventProcess<GossipsubEvent> for Makerbook {
                             BehaviourOutEvent::Logout { peer: source },
                         ))
                 }
+                wire::Message::Reservation { reservation } => {
+                    let event = match Signed::try_from(reservation) {
+                        Ok(reservation) => BehaviourOutEvent::Reservation {
+                            peer: source,
+                            reservation: SignedReservation(reservation),
+                        },
+                        Err(e) => {
+                            tracing::debug!(
+                                "received reservation of {} with malformed public key: {}",
+                                source,
+                                e
+                            );
+                            BehaviourOutEvent::MalformedMessage { peer: source }
+                        }
+                    };
+                    self.actions
+                        .push_back(NetworkBehaviourAction::GenerateEvent(event))
+                }
+                wire::Message::Order { order } => {
+                    self.actions
+                        .push_back(NetworkBehaviourAction::GenerateEvent(
+                            BehaviourOutEvent::OrderPublished {
+                                peer: source,
+                                order,
+                            },
+                        ))
+                }
+                wire::Message::OrderRemoved { order } => {
+                    let event = match Signed::try_from(order) {
+                        Ok(order) => BehaviourOutEvent::OrderRemoved {
+                            peer: source,
+                            order: SignedOrderRemoval(order),
+                        },
+                        Err(e) => {
+                            tracing::debug!(
+                                "received order removal of {} with malformed public key: {}",
+                                source,
+                                e
+                            );
+                            BehaviourOutEvent::MalformedMessage { peer: source }
+                        }
+                    };
+                    self.actions
+                        .push_back(NetworkBehaviourAction::GenerateEvent(event))
+                }
             }
         }
     }
 }
 
+/// A reservation signed by the maker of the reserved order.
+///
+/// Gossip is relayed by other peers, hence makers sign their reservations the
+/// same way they sign their orders. Otherwise, anyone could block the orders
+/// of a maker by announcing reservations on their behalf.
+#[derive(Debug, Clone)]
+pub struct SignedReservation(Signed);
+
+impl SignedReservation {
+    pub fn sign(reservation: Reservation, key: &Keypair) -> anyhow::Result<Self> {
+        let signed = Signed::sign(
+            &wire::ReservationPayload {
+                order: reservation.order,
+                quantity: reservation.quantity,
+                expires_at: reservation.expires_at,
+            },
+            key,
+        )?;
+
+        Ok(Self(signed))
+    }
+
+    /// Checks that the reservation was signed by the given maker and returns
+    /// it.
+    pub fn verify_maker(&self, maker: &PeerId) -> Result<Reservation, InvalidOrder> {
+        let wire::ReservationPayload {
+            order,
+            quantity,
+            expires_at,
+        } = self.0.verify_maker(maker)?;
+
+        Ok(Reservation {
+            order,
+            quantity,
+            expires_at,
+        })
+    }
+}
+
+/// The removal of an order, signed by its maker.
+///
+/// Like reservations, removals are signed so that nobody but the maker can
+/// take an order off the orderbook of other peers.
+#[derive(Debug, Clone)]
+pub struct SignedOrderRemoval(Signed);
+
+impl SignedOrderRemoval {
+    pub fn sign(order: OrderId, key: &Keypair) -> anyhow::Result<Self> {
+        let signed = Signed::sign(&wire::OrderRemovalPayload { order }, key)?;
+
+        Ok(Self(signed))
+    }
+
+    /// Checks that the removal was signed by the given maker and returns the
+    /// removed order.
+    pub fn verify_maker(&self, maker: &PeerId) -> Result<OrderId, InvalidOrder> {
+        let wire::OrderRemovalPayload { order } = self.0.verify_maker(maker)?;
+
+        Ok(order)
+    }
+}
+
+/// A payload signed by the peer that published it.
+#[derive(Debug, Clone)]
+struct Signed {
+    /// The encoded payload, exactly as signed by the peer.
+    payload: Vec<u8>,
+    signer: PublicKey,
+    signature: Vec<u8>,
+}
+
+impl Signed {
+    fn sign(payload: &impl Serialize, key: &Keypair) -> anyhow::Result<Self> {
+        let payload = serde_json::to_vec(payload)?;
+        let signature = key.sign(&payload)?;
+
+        Ok(Self {
+            payload,
+            signer: key.public(),
+            signature,
+        })
+    }
+
+    fn verify_maker<T>(&self, maker: &PeerId) -> Result<T, InvalidOrder>
+    where
+        T: DeserializeOwned,
+    {
+        let signer = self.signer.clone().into_peer_id();
+        if signer != *maker {
+            return Err(InvalidOrder::Maker(signer));
+        }
+        if !self.signer.verify(&self.payload, &self.signature) {
+            return Err(InvalidOrder::Signature);
+        }
+
+        Ok(serde_json::from_slice(&self.payload)?)
+    }
+}
+
+impl TryFrom<wire::SignedPayload> for Signed {
+    type Error = DecodingError;
+
+    fn try_from(wire: wire::SignedPayload) -> Result<Self, Self::Error> {
+        let wire::SignedPayload {
+            payload,
+            public_key,
+            signature,
+        } = wire;
+
+        Ok(Self {
+            payload,
+            signer: PublicKey::from_protobuf_encoding(&public_key)?,
+            signature,
+        })
+    }
+}
+
+impl From<Signed> for wire::SignedPayload {
+    fn from(model: Signed) -> Self {
+        let Signed {
+            payload,
+            signer,
+            signature,
+        } = model;
+
+        Self {
+            payload,
+            public_key: signer.into_protobuf_encoding(),
+            signature,
+        }
+    }
+}
+
 fn content_based_id(message: &GossipsubMessage) -> MessageId {
     let mut s = DefaultHasher::new();
     message.data.hash(&mut s);
@@ -150,8 +398,10 @@ fn content_based_id(message: &GossipsubMessage) -> MessageId {
 }
 
 mod wire {
+    use crate::{asset, network::orderbook::SignedOrder, OrderId};
     use serde::{Deserialize, Serialize};
     use std::fmt;
+    use time::OffsetDateTime;
 
     /// All messages sent to the `/comit/makers` topic.
     #[derive(Debug, Serialize, Deserialize)]
@@ -162,6 +412,43 @@ mod wire {
         /// Informs all subscribers that the source peers is going offline and
         /// no longer available for trading the given trading pair.
         Logout { trading_pair: TradingPair },
+        /// Informs all subscribers how much of one of its orders the source
+        /// peer reserved for swaps that are being set up.
+        Reservation { reservation: SignedPayload },
+        /// Pushes a new or changed order of the source peer to all
+        /// subscribers.
+        Order { order: SignedOrder },
+        /// Informs all subscribers that the source peer cancelled or filled
+        /// one of its orders.
+        OrderRemoved { order: SignedPayload },
+    }
+
+    /// A payload signed by the source peer.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct SignedPayload {
+        #[serde(with = "hex")]
+        pub payload: Vec<u8>,
+        /// The protobuf encoding of the source peer's public key.
+        #[serde(with = "hex")]
+        pub public_key: Vec<u8>,
+        #[serde(with = "hex")]
+        pub signature: Vec<u8>,
+    }
+
+    /// The part of a reservation that is covered by the signature.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct ReservationPayload {
+        pub order: OrderId,
+        #[serde(with = "asset::bitcoin::sats_as_string")]
+        pub quantity: asset::Bitcoin,
+        #[serde(with = "time::serde::timestamp")]
+        pub expires_at: OffsetDateTime,
+    }
+
+    /// The part of an order removal that is covered by the signature.
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct OrderRemovalPayload {
+        pub order: OrderId,
     }
 
     /// Defines the set of trading pairs that we support.
@@ -179,3 +466,74 @@ mod wire {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset;
+    use time::OffsetDateTime;
+
+    #[test]
+    fn signed_reservation_verifies_after_going_over_the_wire() {
+        let key = Keypair::generate_ed25519();
+        let reservation = reservation();
+
+        let signed = SignedReservation::sign(reservation, &key).unwrap();
+        let bytes = serde_json::to_vec(&wire::SignedPayload::from(signed.0)).unwrap();
+        let received = serde_json::from_slice::<wire::SignedPayload>(&bytes).unwrap();
+        let received = SignedReservation(Signed::try_from(received).unwrap());
+
+        assert_eq!(
+            received.verify_maker(&key.public().into_peer_id()).unwrap(),
+            reservation
+        );
+    }
+
+    #[test]
+    fn reservations_signed_by_someone_else_do_not_verify() {
+        let key = Keypair::generate_ed25519();
+        let signed = SignedReservation::sign(reservation(), &key).unwrap();
+
+        assert!(matches!(
+            signed.verify_maker(&PeerId::random()),
+            Err(InvalidOrder::Maker(_))
+        ));
+    }
+
+    #[test]
+    fn tampered_reservation_does_not_verify() {
+        let key = Keypair::generate_ed25519();
+        let mut signed = SignedReservation::sign(reservation(), &key).unwrap();
+        let last = signed.0.payload.len() - 1;
+        signed.0.payload[last] ^= 1;
+
+        assert!(matches!(
+            signed.verify_maker(&key.public().into_peer_id()),
+            Err(InvalidOrder::Signature)
+        ));
+    }
+
+    #[test]
+    fn order_removals_signed_by_someone_else_do_not_verify() {
+        let key = Keypair::generate_ed25519();
+        let order = OrderId::random();
+        let signed = SignedOrderRemoval::sign(order, &key).unwrap();
+
+        assert_eq!(
+            signed.verify_maker(&key.public().into_peer_id()).unwrap(),
+            order
+        );
+        assert!(matches!(
+            signed.verify_maker(&PeerId::random()),
+            Err(InvalidOrder::Maker(_))
+        ));
+    }
+
+    fn reservation() -> Reservation {
+        Reservation {
+            order: OrderId::random(),
+            quantity: asset::Bitcoin::from_sat(50_000_000),
+            expires_at: OffsetDateTime::from_unix_timestamp(1_600_000_000),
+        }
+    }
+}
@@ -7,6 +7,7 @@ use futures::{AsyncRead, AsyncWrite};
 use libp2p::{
     core::{
         connection::{ConnectionId, ListenerId},
+        identity::{error::DecodingError, Keypair, PublicKey},
         upgrade, ConnectedPoint,
     },
     request_response::{
@@ -17,26 +18,44 @@ use libp2p::{
     swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters},
     Multiaddr, PeerId,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashSet, VecDeque},
+    convert::TryFrom,
     error::Error,
     io,
     task::{Context, Poll},
     time::{Duration, Instant},
 };
-use time::NumericalDuration;
+use time::{NumericalDuration, OffsetDateTime};
 
-/// Wait at least this long before re-getting orders from a maker.
-const POLLING_INTERVAL: Duration = Duration::from_secs(5);
+/// Wait at least this long before re-getting a snapshot of all orders from a
+/// maker.
+///
+/// Makers push changes to their orders as they happen, the snapshot only keeps
+/// us consistent in case we missed some of them. It must be shorter than the
+/// [`ORDER_TTL`], otherwise orders that didn't change would expire.
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long the orders we hand out are valid.
+///
+/// Our orders are signed with a fresh expiry every time they are requested or
+/// pushed. Hence, the orders of a maker that stops responding go stale after
+/// this duration.
+pub const ORDER_TTL: Duration = Duration::from_secs(60);
+
+/// The maximum size of a get orders response.
+const MAX_RESPONSE_SIZE: usize = 64 * 1024;
 
 /// A [NetworkBehaviour] that acts as a source for orders.
 ///
-/// Orders are pulled regularly from a given set of makers. Every connection
-/// established will be tried as a potential order source.
+/// Snapshots of all orders are pulled regularly from a given set of makers.
+/// Every connection established will be tried as a potential order source.
 #[allow(missing_debug_implementations)]
 pub struct OrderSource {
     get_orders: RequestResponse<GetBtcDaiOrdersCodec>,
+    /// Our identity, used to sign the orders we hand out.
+    key: Keypair,
     /// Makers we will attempt to get updated orders from.
     active_makers: HashSet<PeerId>,
     last_polled_makers_at: Instant,
@@ -45,6 +64,23 @@ pub struct OrderSource {
 }
 
 impl OrderSource {
+    pub fn new(key: Keypair) -> Self {
+        let config = RequestResponseConfig::default();
+        let behaviour = RequestResponse::new(
+            GetBtcDaiOrdersCodec::default(),
+            vec![(GetBtcDaiOrdersProtocol, ProtocolSupport::Full)],
+            config,
+        );
+
+        Self {
+            get_orders: behaviour,
+            key,
+            active_makers: HashSet::default(),
+            last_polled_makers_at: Instant::now(),
+            actions: VecDeque::default(),
+        }
+    }
+
     /// Start getting orders from this peer.
     pub fn start_getting_orders_from(&mut self, maker: PeerId) {
         self.active_makers.insert(maker);
@@ -55,12 +91,27 @@ impl OrderSource {
     }
 
     /// Respond to a get orders request.
+    ///
+    /// Every order is signed with an expiry of [`ORDER_TTL`] from now.
     pub fn send_orders(&mut self, handle: ResponseHandle, orders: Vec<BtcDaiOrder>) {
+        let expires_at = OffsetDateTime::now_utc() + ORDER_TTL;
+        let key = &self.key;
+
+        let orders = orders
+            .into_iter()
+            .filter_map(|order| {
+                let id = order.id;
+                SignedOrder::sign(order, expires_at, key)
+                    .map_err(|e| tracing::warn!("failed to sign order {}: {:#}", id, e))
+                    .ok()
+            })
+            .collect();
+
         self.get_orders.send_response(handle.0, orders);
     }
 
     fn is_time_to_update_orders(&self) -> bool {
-        Instant::now().duration_since(self.last_polled_makers_at) > POLLING_INTERVAL
+        Instant::now().duration_since(self.last_polled_makers_at) > SNAPSHOT_INTERVAL
     }
 }
 
@@ -202,6 +253,8 @@ impl NetworkBehaviour for OrderSource {
                 } => {
                     tracing::debug!("fetched {} orders from {}", orders.len(), peer_id);
 
+                    let orders = verify_orders(&peer_id, orders);
+
                     return Poll::Ready(NetworkBehaviourAction::GenerateEvent(
                         BehaviourOutEvent::RetrievedOrders {
                             maker: peer_id,
@@ -257,32 +310,17 @@ impl NetworkBehaviour for OrderSource {
     }
 }
 
-impl Default for OrderSource {
-    fn default() -> Self {
-        let config = RequestResponseConfig::default();
-        let behaviour = RequestResponse::new(
-            GetBtcDaiOrdersCodec::default(),
-            vec![(GetBtcDaiOrdersProtocol, ProtocolSupport::Full)],
-            config,
-        );
-
-        Self {
-            get_orders: behaviour,
-            active_makers: HashSet::default(),
-            last_polled_makers_at: Instant::now(),
-            actions: VecDeque::default(),
-        }
-    }
-}
-
 #[derive(Debug)]
 pub enum BehaviourOutEvent {
     /// Our orders are being requested by another peer.
     GetOrdersRequest { response_handle: ResponseHandle },
     /// We retrieved orders from the given maker.
+    ///
+    /// Only orders carrying a valid signature of the maker are included,
+    /// together with the time until which the maker honours them.
     RetrievedOrders {
         maker: PeerId,
-        orders: Vec<BtcDaiOrder>,
+        orders: Vec<(BtcDaiOrder, OffsetDateTime)>,
     },
     /// The given maker disconnected.
     ///
@@ -296,14 +334,102 @@ pub enum BehaviourOutEvent {
 ///
 /// This type allows us to keep the `wire` module private to this module.
 #[derive(Debug)]
-pub struct ResponseHandle(ResponseChannel<Vec<BtcDaiOrder>>);
+pub struct ResponseHandle(ResponseChannel<Vec<SignedOrder>>);
+
+/// Keeps the orders that carry a valid signature of the given maker.
+fn verify_orders(maker: &PeerId, orders: Vec<SignedOrder>) -> Vec<(BtcDaiOrder, OffsetDateTime)> {
+    orders
+        .into_iter()
+        .filter_map(|signed| {
+            signed
+                .verify_maker(maker)
+                .map_err(|e| tracing::warn!("ignoring order from {}: {}", maker, e))
+                .ok()
+        })
+        .collect()
+}
+
+/// An order signed by its maker.
+///
+/// The signature covers the order as well as the time until which the maker
+/// honours it. Signed orders can therefore be relayed and cached by anyone
+/// without the receiver having to trust them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "wire::SignedOrder", into = "wire::SignedOrder")]
+pub struct SignedOrder {
+    /// The encoded order and expiry, exactly as signed by the maker.
+    payload: Vec<u8>,
+    maker: PublicKey,
+    signature: Vec<u8>,
+}
+
+impl SignedOrder {
+    pub fn sign(
+        order: BtcDaiOrder,
+        expires_at: OffsetDateTime,
+        key: &Keypair,
+    ) -> anyhow::Result<Self> {
+        let payload = serde_json::to_vec(&wire::OrderPayload {
+            order: wire::BtcDaiOrder::from_model(order),
+            expires_at,
+        })?;
+        let signature = key.sign(&payload)?;
+
+        Ok(Self {
+            payload,
+            maker: key.public(),
+            signature,
+        })
+    }
+
+    /// The peer id of the maker that signed this order.
+    pub fn maker(&self) -> PeerId {
+        self.maker.clone().into_peer_id()
+    }
+
+    /// Checks the maker's signature and returns the order together with the
+    /// time until which the maker honours it.
+    pub fn verify(&self) -> Result<(BtcDaiOrder, OffsetDateTime), InvalidOrder> {
+        if !self.maker.verify(&self.payload, &self.signature) {
+            return Err(InvalidOrder::Signature);
+        }
+
+        let payload = serde_json::from_slice::<wire::OrderPayload>(&self.payload)?;
+
+        Ok((payload.order.into_model(), payload.expires_at))
+    }
+
+    /// Like [`SignedOrder::verify`] but additionally checks that the order was
+    /// signed by the given maker.
+    pub fn verify_maker(
+        &self,
+        maker: &PeerId,
+    ) -> Result<(BtcDaiOrder, OffsetDateTime), InvalidOrder> {
+        let signer = self.maker();
+        if signer != *maker {
+            return Err(InvalidOrder::Maker(signer));
+        }
+
+        self.verify()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidOrder {
+    #[error("signature does not match the order")]
+    Signature,
+    #[error("order was signed by {0} instead of its maker")]
+    Maker(PeerId),
+    #[error("malformed order")]
+    Malformed(#[from] serde_json::Error),
+}
 
 #[derive(Debug, Clone, Copy)]
 pub struct GetBtcDaiOrdersProtocol;
 
 impl ProtocolName for GetBtcDaiOrdersProtocol {
     fn protocol_name(&self) -> &[u8] {
-        b"/comit/get-orders/btc-dai/1.0.0"
+        b"/comit/get-orders/btc-dai/2.0.0"
     }
 }
 
@@ -315,7 +441,7 @@ impl RequestResponseCodec for GetBtcDaiOrdersCodec {
     type Protocol = GetBtcDaiOrdersProtocol;
     type Request = ();
     // TODO: Allow a response of "I am not a maker" to stop asking them.
-    type Response = Vec<BtcDaiOrder>;
+    type Response = Vec<SignedOrder>;
 
     /// Reads a get orders request from the given I/O stream.
     async fn read_request<T>(&mut self, _: &Self::Protocol, _: &mut T) -> io::Result<Self::Request>
@@ -334,13 +460,13 @@ impl RequestResponseCodec for GetBtcDaiOrdersCodec {
     where
         T: AsyncRead + Unpin + Send,
     {
-        let message = upgrade::read_one(io, 1024)
+        let message = upgrade::read_one(io, MAX_RESPONSE_SIZE)
             .await
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
         let mut de = serde_json::Deserializer::from_slice(&message);
-        let orders = Vec::<wire::BtcDaiOrder>::deserialize(&mut de)?;
+        let orders = Vec::<SignedOrder>::deserialize(&mut de)?;
 
-        Ok(orders.into_iter().map(|wire| wire.into_model()).collect())
+        Ok(orders)
     }
 
     /// Writes a get orders request to the given I/O stream.
@@ -367,12 +493,7 @@ impl RequestResponseCodec for GetBtcDaiOrdersCodec {
     where
         T: AsyncWrite + Unpin + Send,
     {
-        let bytes = serde_json::to_vec(
-            &orders
-                .into_iter()
-                .map(wire::BtcDaiOrder::from_model)
-                .collect::<Vec<_>>(),
-        )?;
+        let bytes = serde_json::to_vec(&orders)?;
         upgrade::write_one(io, &bytes).await?;
 
         Ok(())
@@ -397,6 +518,25 @@ mod wire {
         pub price: Erc20Quantity,
     }
 
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    pub struct SignedOrder {
+        #[serde(with = "hex")]
+        pub payload: Vec<u8>,
+        /// The protobuf encoding of the maker's public key.
+        #[serde(with = "hex")]
+        pub public_key: Vec<u8>,
+        #[serde(with = "hex")]
+        pub signature: Vec<u8>,
+    }
+
+    /// The part of a signed order that is covered by the signature.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    pub struct OrderPayload {
+        pub order: BtcDaiOrder,
+        #[serde(with = "time::serde::timestamp")]
+        pub expires_at: OffsetDateTime,
+    }
+
     #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
     #[serde(rename_all = "snake_case")]
     pub enum SwapProtocol {
@@ -454,6 +594,40 @@ impl wire::BtcDaiOrder {
     }
 }
 
+impl TryFrom<wire::SignedOrder> for SignedOrder {
+    type Error = DecodingError;
+
+    fn try_from(wire: wire::SignedOrder) -> Result<Self, Self::Error> {
+        let wire::SignedOrder {
+            payload,
+            public_key,
+            signature,
+        } = wire;
+
+        Ok(SignedOrder {
+            payload,
+            maker: PublicKey::from_protobuf_encoding(&public_key)?,
+            signature,
+        })
+    }
+}
+
+impl From<SignedOrder> for wire::SignedOrder {
+    fn from(model: SignedOrder) -> Self {
+        let SignedOrder {
+            payload,
+            maker,
+            signature,
+        } = model;
+
+        Self {
+            payload,
+            public_key: maker.into_protobuf_encoding(),
+            signature,
+        }
+    }
+}
+
 impl wire::SwapProtocol {
     fn into_model(self) -> SwapProtocol {
         match self {
@@ -499,7 +673,10 @@ impl wire::SwapProtocol {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::proptest;
+    use crate::{
+        order::{btc, dai_per_btc},
+        proptest,
+    };
 
     proptest::proptest! {
         #[test]
@@ -511,4 +688,55 @@ mod tests {
             assert_eq!(order, round_tripped);
         }
     }
+
+    proptest::proptest! {
+        #[test]
+        fn signed_order_verifies_after_going_over_the_wire(
+            order in proptest::order::btc_dai_order(),
+        ) {
+            let key = Keypair::generate_ed25519();
+            let expires_at = OffsetDateTime::from_unix_timestamp(1_600_000_000);
+
+            let signed = SignedOrder::sign(order.clone(), expires_at, &key).unwrap();
+            let bytes = serde_json::to_vec(&signed).unwrap();
+            let received = serde_json::from_slice::<SignedOrder>(&bytes).unwrap();
+
+            assert_eq!(received.maker(), key.public().into_peer_id());
+            assert_eq!(received.verify().unwrap(), (order, expires_at));
+        }
+    }
+
+    #[test]
+    fn snapshots_are_taken_before_orders_expire() {
+        assert!(SNAPSHOT_INTERVAL < ORDER_TTL);
+    }
+
+    #[test]
+    fn tampered_order_does_not_verify() {
+        let key = Keypair::generate_ed25519();
+
+        let mut signed = SignedOrder::sign(order(), OffsetDateTime::now_utc(), &key).unwrap();
+        let last = signed.payload.len() - 1;
+        signed.payload[last] ^= 1;
+
+        assert!(matches!(signed.verify(), Err(InvalidOrder::Signature)));
+    }
+
+    #[test]
+    fn orders_signed_by_someone_else_are_ignored() {
+        let maker = PeerId::random();
+        let key = Keypair::generate_ed25519();
+        let signed = SignedOrder::sign(order(), OffsetDateTime::now_utc(), &key).unwrap();
+
+        let orders = verify_orders(&maker, vec![signed]);
+
+        assert!(orders.is_empty());
+    }
+
+    fn order() -> BtcDaiOrder {
+        BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), SwapProtocol::HbitHerc20 {
+            hbit_expiry_offset: 0.seconds().into(),
+            herc20_expiry_offset: 0.seconds().into(),
+        })
+    }
 }
@@ -1,5 +1,6 @@
 pub mod announce;
 pub mod bitcoin_identity;
+pub mod cancel_swap;
 pub mod ethereum_identity;
 pub mod finalize;
 pub mod lightning_identity;
@@ -0,0 +1,476 @@
+use crate::SecretHash;
+use futures::prelude::*;
+use libp2p::{
+    core::{
+        identity::{error::DecodingError, Keypair, PublicKey},
+        upgrade,
+    },
+    request_response::{
+        ProtocolName, ProtocolSupport, RequestId, RequestResponse, RequestResponseCodec,
+        RequestResponseConfig, RequestResponseEvent, RequestResponseMessage, ResponseChannel,
+    },
+    swarm::{NetworkBehaviourAction, NetworkBehaviourEventProcess, PollParameters},
+    NetworkBehaviour, PeerId,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryFrom,
+    io,
+    task::{Context, Poll},
+};
+use tokio::sync::oneshot;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BehaviourOutEvent {
+    /// The peer told us that they are not going to fund the swap.
+    ///
+    /// The peer waits for us to [`respond`](CancelSwap::respond) whether we
+    /// accept the cancellation.
+    Cancelled {
+        peer: PeerId,
+        secret_hash: SecretHash,
+    },
+    /// The peer sent us a cancellation that was not signed by them.
+    ProtocolViolation { peer: PeerId },
+}
+
+/// A `NetworkBehaviour` for cancelling swaps that were set up but not funded
+/// yet.
+///
+/// Swaps are identified by their secret hash because it is the only
+/// identifier both parties share regardless of how the swap was set up. The
+/// cancellation is signed by the sender so it can be kept as a proof that the
+/// peer withdrew from the swap. The peer answers whether it accepts the
+/// cancellation, the swap is only cancelled if it does.
+#[derive(NetworkBehaviour)]
+#[behaviour(out_event = "BehaviourOutEvent", poll_method = "poll")]
+#[allow(missing_debug_implementations)]
+pub struct CancelSwap {
+    cancel_swap: RequestResponse<Codec>,
+    #[behaviour(ignore)]
+    key: Keypair,
+    #[behaviour(ignore)]
+    events: VecDeque<BehaviourOutEvent>,
+    /// Our cancellations the peer did not answer yet.
+    #[behaviour(ignore)]
+    outbound: HashMap<RequestId, oneshot::Sender<Response>>,
+    /// The cancellations of peers we did not answer yet.
+    #[behaviour(ignore)]
+    inbound: HashMap<(PeerId, SecretHash), ResponseChannel<Response>>,
+}
+
+/// Whether the peer accepted a cancellation.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Accepted,
+    Rejected { reason: String },
+}
+
+impl CancelSwap {
+    pub fn new(key: Keypair) -> Self {
+        Self {
+            cancel_swap: RequestResponse::new(
+                Codec::default(),
+                vec![(CancelSwapProtocol, ProtocolSupport::Full)],
+                RequestResponseConfig::default(),
+            ),
+            key,
+            events: VecDeque::default(),
+            outbound: HashMap::default(),
+            inbound: HashMap::default(),
+        }
+    }
+
+    /// Tell the given peer that we are not going to fund the swap with this
+    /// secret hash.
+    ///
+    /// The returned receiver yields the peer's answer. It fails if the peer
+    /// does not answer, e.g. because the request timed out.
+    pub fn cancel(
+        &mut self,
+        peer: &PeerId,
+        secret_hash: SecretHash,
+    ) -> anyhow::Result<oneshot::Receiver<Response>> {
+        let cancellation = SignedCancellation::sign(secret_hash, &self.key)?;
+
+        tracing::info!("Cancelling swap with {}", peer);
+        let request_id = self.cancel_swap.send_request(peer, cancellation);
+
+        let (sender, receiver) = oneshot::channel();
+        self.outbound.insert(request_id, sender);
+
+        Ok(receiver)
+    }
+
+    /// Answer the cancellation of the swap with this secret hash that we
+    /// received from the given peer.
+    pub fn respond(&mut self, peer: &PeerId, secret_hash: SecretHash, response: Response) {
+        match self.inbound.remove(&(peer.clone(), secret_hash)) {
+            Some(channel) => self.cancel_swap.send_response(channel, response),
+            None => tracing::warn!("no cancellation of {} to respond to", peer),
+        }
+    }
+
+    fn receive(
+        &mut self,
+        peer: PeerId,
+        cancellation: SignedCancellation,
+        channel: ResponseChannel<Response>,
+    ) {
+        match cancellation.verify_sender(&peer) {
+            Ok(secret_hash) => {
+                self.inbound.insert((peer.clone(), secret_hash), channel);
+                self.events
+                    .push_back(BehaviourOutEvent::Cancelled { peer, secret_hash })
+            }
+            Err(e) => {
+                tracing::warn!("ignoring cancellation from {}: {}", peer, e);
+                self.cancel_swap.send_response(channel, Response::Rejected {
+                    reason: e.to_string(),
+                });
+                self.events
+                    .push_back(BehaviourOutEvent::ProtocolViolation { peer })
+            }
+        }
+    }
+
+    fn poll<InEvent>(
+        &mut self,
+        _: &mut Context<'_>,
+        _: &mut impl PollParameters,
+    ) -> Poll<NetworkBehaviourAction<InEvent, BehaviourOutEvent>> {
+        if let Some(event) = self.events.pop_front() {
+            return Poll::Ready(NetworkBehaviourAction::GenerateEvent(event));
+        }
+
+        Poll::Pending
+    }
+}
+
+impl NetworkBehaviourEventProcess<RequestResponseEvent<SignedCancellation, Response>>
+    for CancelSwap
+{
+    fn inject_event(&mut self, event: RequestResponseEvent<SignedCancellation, Response>) {
+        match event {
+            RequestResponseEvent::Message {
+                peer,
+                message:
+                    RequestResponseMessage::Request {
+                        request, channel, ..
+                    },
+            } => self.receive(peer, request, channel),
+            RequestResponseEvent::Message {
+                message:
+                    RequestResponseMessage::Response {
+                        request_id,
+                        response,
+                    },
+                ..
+            } => {
+                if let Some(sender) = self.outbound.remove(&request_id) {
+                    let _ = sender.send(response);
+                }
+            }
+            RequestResponseEvent::OutboundFailure {
+                request_id, error, ..
+            } => {
+                tracing::warn!("outbound failure: {:?}", error);
+                // Dropping the sender tells the receiver that no answer is coming
+                self.outbound.remove(&request_id);
+            }
+            RequestResponseEvent::InboundFailure { error, .. } => {
+                tracing::warn!("inbound failure: {:?}", error);
+            }
+        }
+    }
+}
+
+/// A cancellation of a swap, signed by the party that withdraws from it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    try_from = "wire::SignedCancellation",
+    into = "wire::SignedCancellation"
+)]
+pub struct SignedCancellation {
+    /// The encoded cancellation, exactly as signed by the sender.
+    payload: Vec<u8>,
+    sender: PublicKey,
+    signature: Vec<u8>,
+}
+
+impl SignedCancellation {
+    pub fn sign(secret_hash: SecretHash, key: &Keypair) -> anyhow::Result<Self> {
+        let payload = serde_json::to_vec(&wire::CancellationPayload { secret_hash })?;
+        let signature = key.sign(&payload)?;
+
+        Ok(Self {
+            payload,
+            sender: key.public(),
+            signature,
+        })
+    }
+
+    /// The peer id of the party that signed this cancellation.
+    pub fn sender(&self) -> PeerId {
+        self.sender.clone().into_peer_id()
+    }
+
+    /// Checks that the cancellation was signed by the given peer and returns
+    /// the secret hash of the cancelled swap.
+    pub fn verify_sender(&self, sender: &PeerId) -> Result<SecretHash, InvalidCancellation> {
+        let signer = self.sender();
+        if signer != *sender {
+            return Err(InvalidCancellation::Sender(signer));
+        }
+
+        if !self.sender.verify(&self.payload, &self.signature) {
+            return Err(InvalidCancellation::Signature);
+        }
+
+        let payload = serde_json::from_slice::<wire::CancellationPayload>(&self.payload)?;
+
+        Ok(payload.secret_hash)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidCancellation {
+    #[error("signature does not match the cancellation")]
+    Signature,
+    #[error("cancellation was signed by {0} instead of the sender")]
+    Sender(PeerId),
+    #[error("malformed cancellation")]
+    Malformed(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CancelSwapProtocol;
+
+impl ProtocolName for CancelSwapProtocol {
+    fn protocol_name(&self) -> &[u8] {
+        b"/comit/cancel-swap/1.0.0"
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Codec;
+
+#[async_trait::async_trait]
+impl RequestResponseCodec for Codec {
+    type Protocol = CancelSwapProtocol;
+    type Request = SignedCancellation;
+    type Response = Response;
+
+    /// Reads a cancellation from the given I/O stream.
+    async fn read_request<T>(&mut self, _: &Self::Protocol, io: &mut T) -> io::Result<Self::Request>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let message = upgrade::read_one(io, 1024)
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut de = serde_json::Deserializer::from_slice(&message);
+        let req = SignedCancellation::deserialize(&mut de)?;
+
+        Ok(req)
+    }
+
+    /// Reads the answer to a cancellation from the given I/O stream.
+    async fn read_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+    ) -> io::Result<Self::Response>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        let message = upgrade::read_one(io, 1024)
+            .await
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut de = serde_json::Deserializer::from_slice(&message);
+        let res = Response::deserialize(&mut de)?;
+
+        Ok(res)
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        req: Self::Request,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = serde_json::to_vec(&req)?;
+        upgrade::write_one(io, &bytes).await?;
+
+        Ok(())
+    }
+
+    async fn write_response<T>(
+        &mut self,
+        _: &Self::Protocol,
+        io: &mut T,
+        res: Self::Response,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        let bytes = serde_json::to_vec(&res)?;
+        upgrade::write_one(io, &bytes).await?;
+
+        Ok(())
+    }
+}
+
+/// A dedicated module for the types that represent our messages "on the wire".
+mod wire {
+    use crate::SecretHash;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    pub struct SignedCancellation {
+        #[serde(with = "hex")]
+        pub payload: Vec<u8>,
+        /// The protobuf encoding of the sender's public key.
+        #[serde(with = "hex")]
+        pub public_key: Vec<u8>,
+        #[serde(with = "hex")]
+        pub signature: Vec<u8>,
+    }
+
+    /// The part of a cancellation that is covered by the signature.
+    #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+    pub struct CancellationPayload {
+        pub secret_hash: SecretHash,
+    }
+}
+
+impl TryFrom<wire::SignedCancellation> for SignedCancellation {
+    type Error = DecodingError;
+
+    fn try_from(wire: wire::SignedCancellation) -> Result<Self, Self::Error> {
+        let wire::SignedCancellation {
+            payload,
+            public_key,
+            signature,
+        } = wire;
+
+        Ok(SignedCancellation {
+            payload,
+            sender: PublicKey::from_protobuf_encoding(&public_key)?,
+            signature,
+        })
+    }
+}
+
+impl From<SignedCancellation> for wire::SignedCancellation {
+    fn from(model: SignedCancellation) -> Self {
+        let SignedCancellation {
+            payload,
+            sender,
+            signature,
+        } = model;
+
+        Self {
+            payload,
+            public_key: sender.into_protobuf_encoding(),
+            signature,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        network::test::{connect, new_swarm},
+        Secret,
+    };
+    use std::time::Duration;
+    use tokio::time;
+
+    fn secret_hash() -> SecretHash {
+        SecretHash::new(Secret::from(*b"hello world, you are beautiful!!"))
+    }
+
+    #[tokio::test]
+    async fn cancellation_is_received_and_answered_by_peer() {
+        let (mut alice_swarm, _, alice_id) = new_swarm(|_, key| CancelSwap::new(key));
+        let (mut bob_swarm, _, bob_id) = new_swarm(|_, key| CancelSwap::new(key));
+        connect(&mut alice_swarm, &mut bob_swarm).await;
+
+        let mut answer = bob_swarm
+            .cancel(&alice_id, secret_hash())
+            .expect("bob failed to cancel");
+
+        let alice_event = time::timeout(Duration::from_secs(10), async {
+            loop {
+                tokio::select! {
+                    event = alice_swarm.next() => break event,
+                    _ = bob_swarm.next_event() => {}
+                }
+            }
+        })
+        .await
+        .expect("alice to receive the cancellation within 10 seconds");
+
+        assert!(matches!(
+            alice_event,
+            BehaviourOutEvent::Cancelled { secret_hash: received, .. } if received == secret_hash()
+        ));
+
+        alice_swarm.respond(&bob_id, secret_hash(), Response::Accepted);
+        let answer = time::timeout(Duration::from_secs(10), async {
+            loop {
+                tokio::select! {
+                    answer = &mut answer => break answer,
+                    _ = alice_swarm.next_event() => {}
+                    _ = bob_swarm.next_event() => {}
+                }
+            }
+        })
+        .await
+        .expect("bob to receive the answer within 10 seconds");
+
+        assert_eq!(answer.unwrap(), Response::Accepted);
+    }
+
+    #[test]
+    fn cancellation_signed_by_someone_else_is_rejected() {
+        let key = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519().public().into_peer_id();
+
+        let cancellation = SignedCancellation::sign(secret_hash(), &key).unwrap();
+
+        assert!(matches!(
+            cancellation.verify_sender(&other),
+            Err(InvalidCancellation::Sender(_))
+        ));
+        assert_eq!(
+            cancellation
+                .verify_sender(&key.public().into_peer_id())
+                .unwrap(),
+            secret_hash()
+        );
+    }
+
+    #[test]
+    fn cancellation_roundtrips_through_the_wire_format() {
+        let key = Keypair::generate_ed25519();
+        let cancellation = SignedCancellation::sign(secret_hash(), &key).unwrap();
+
+        let json = serde_json::to_string(&cancellation).unwrap();
+        let deserialized = serde_json::from_str::<SignedCancellation>(&json).unwrap();
+
+        assert_eq!(
+            deserialized
+                .verify_sender(&key.public().into_peer_id())
+                .unwrap(),
+            secret_hash()
+        );
+    }
+}
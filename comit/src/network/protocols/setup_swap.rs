@@ -1,5 +1,8 @@
 use crate::{
-    asset, ethereum::ChainId, hbit, herc20, identity, ledger, Role, SecretHash, Timestamp,
+    asset,
+    ethereum::ChainId,
+    expiries::{self, UnsafeExpiries},
+    hbit, herc20, identity, ledger, Network, OrderId, Role, SecretHash, Timestamp,
 };
 use anyhow::Result;
 use futures::prelude::*;
@@ -19,8 +22,15 @@ use std::{
     io,
     marker::PhantomData,
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
+/// Negotiations that don't lead to a swap within this time are dropped.
+///
+/// This matches how long makers reserve the quantity of an order for a swap
+/// that is being set up.
+const NEGOTIATION_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
 #[derive(Clone, Copy, Debug, thiserror::Error)]
 #[error("Already have role dependent parameters for this set of common parameters")]
 pub struct AlreadyHaveRoleParams;
@@ -35,6 +45,37 @@ pub enum BehaviourOutEvent<C> {
         have: RoleDependentParams,
         received: RoleDependentParams,
     },
+    /// We and the peer could not agree on the expiries, no swap was set up.
+    ExpiriesRejected {
+        peer: PeerId,
+        rejected_by: Role,
+        reason: UnsafeExpiries,
+        context: C,
+    },
+    /// The peer did not complete the negotiation of a swap we proposed within
+    /// [`NEGOTIATION_TIMEOUT`], no swap was set up.
+    TimedOut {
+        peer: PeerId,
+        context: C,
+    },
+    /// The peer wants to take one of our orders, see [`SetupSwap::take`].
+    ///
+    /// Their parameters are kept, the swap is set up as soon as we
+    /// [`send`](SetupSwap::send) ours.
+    TakeRequested {
+        peer: PeerId,
+        take: Take,
+        common: CommonParams,
+        swap_protocol: SwapProtocol,
+        their_role: Role,
+    },
+    /// The peer declined to set up a swap we proposed, see
+    /// [`SetupSwap::decline`].
+    Declined {
+        peer: PeerId,
+        reason: String,
+        context: C,
+    },
 }
 
 #[derive(Clone, Debug)]
@@ -106,12 +147,23 @@ impl<C> BehaviourOutEvent<C> {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum SwapProtocol {
     HbitHerc20,
     Herc20Hbit,
 }
 
+/// A `NetworkBehaviour` for setting up swaps with other peers.
+///
+/// Both parties send their role dependent parameters together with the common
+/// parameters they expect. If the expiries they propose differ, Alice decides:
+/// She either accepts Bob's expiries if they are safe for her or insists on her
+/// own ones with a single counter-proposal. Bob either accepts this
+/// counter-proposal or rejects it, there is no further round.
+///
+/// Usually both parties send their parameters because they matched each
+/// other's orders. A taker may instead [`take`](SetupSwap::take) a maker's
+/// order directly, the maker then decides whether to send its parameters.
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "BehaviourOutEvent<C>", poll_method = "poll")]
 #[allow(missing_debug_implementations)]
@@ -120,10 +172,10 @@ pub struct SetupSwap<C: Clone + Send + 'static> {
     herc20_hbit: RequestResponse<Codec<Herc20HbitProtocol>>,
     #[behaviour(ignore)]
     events: VecDeque<BehaviourOutEvent<C>>,
+    /// Negotiations are kept per peer so that one peer cannot interfere with
+    /// the negotiation of another peer.
     #[behaviour(ignore)]
-    swap_data: HashMap<CommonParams, RoleDependentParams>,
-    #[behaviour(ignore)]
-    context: HashMap<CommonParams, C>,
+    negotiations: HashMap<(PeerId, NegotiationId), Negotiation<C>>,
 }
 
 impl<C: Clone + Send + 'static> Default for SetupSwap<C> {
@@ -140,160 +192,465 @@ impl<C: Clone + Send + 'static> Default for SetupSwap<C> {
                 RequestResponseConfig::default(),
             ),
             events: Default::default(),
-            swap_data: Default::default(),
-            context: Default::default(),
+            negotiations: Default::default(),
         }
     }
 }
 
 impl<C: Clone + Send + 'static> SetupSwap<C> {
+    /// Propose a swap to the given peer.
+    ///
+    /// The expiries in `common` are validated relative to `start_of_swap` if
+    /// the peer proposes different ones.
+    #[allow(clippy::too_many_arguments)]
     pub fn send(
         &mut self,
         to: &PeerId,
+        id: NegotiationId,
         to_send: RoleDependentParams,
         common: CommonParams,
         swap_protocol: SwapProtocol,
+        start_of_swap: Timestamp,
         context: C,
     ) -> Result<()> {
-        match (self.swap_data.get(&common), &to_send) {
-            (Some(RoleDependentParams::Alice(alice)), RoleDependentParams::Bob(bob)) => {
-                self.events
-                    .push_back(BehaviourOutEvent::new_executable_swap(
-                        Role::Bob,
-                        common.clone(),
-                        alice,
-                        &bob,
-                        swap_protocol,
-                        to.clone(),
-                        context,
-                    ));
-            }
-            (Some(RoleDependentParams::Bob(bob)), RoleDependentParams::Alice(alice)) => {
-                self.events
-                    .push_back(BehaviourOutEvent::new_executable_swap(
-                        Role::Alice,
-                        common.clone(),
-                        &alice,
-                        bob,
-                        swap_protocol,
-                        to.clone(),
-                        context,
-                    ));
-            }
-            (None, role) => {
-                self.swap_data.insert(common.clone(), *role);
-                self.context.insert(common.clone(), context);
-            }
-            (Some(_), _) => return Err(anyhow::Error::from(AlreadyHaveRoleParams)),
+        self.propose(
+            to,
+            id,
+            to_send,
+            common,
+            swap_protocol,
+            start_of_swap,
+            context,
+            None,
+        )
+    }
+
+    /// Propose a swap to the maker of the order we want to take.
+    ///
+    /// Unlike with [`send`](SetupSwap::send), the maker did not match this
+    /// swap itself and only sends its parameters if it agrees to fill
+    /// `take.order` with the quantities in `common`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn take(
+        &mut self,
+        maker: &PeerId,
+        to_send: RoleDependentParams,
+        common: CommonParams,
+        swap_protocol: SwapProtocol,
+        start_of_swap: Timestamp,
+        take: Take,
+        context: C,
+    ) -> Result<()> {
+        self.propose(
+            maker,
+            NegotiationId::from(take),
+            to_send,
+            common,
+            swap_protocol,
+            start_of_swap,
+            context,
+            Some(take),
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn propose(
+        &mut self,
+        to: &PeerId,
+        id: NegotiationId,
+        to_send: RoleDependentParams,
+        common: CommonParams,
+        swap_protocol: SwapProtocol,
+        start_of_swap: Timestamp,
+        context: C,
+        take: Option<Take>,
+    ) -> Result<()> {
+        let negotiation = self.negotiations.entry((to.clone(), id)).or_default();
+
+        let have_role_params = match &negotiation.theirs {
+            Some(theirs) => theirs.params.role() == to_send.role(),
+            None => false,
+        };
+        if negotiation.ours.is_some() || have_role_params {
+            return Err(anyhow::Error::from(AlreadyHaveRoleParams));
         }
 
+        negotiation.ours = Some(Proposal {
+            common: common.clone(),
+            params: to_send,
+            swap_protocol,
+            start_of_swap,
+            context,
+        });
+
         tracing::info!("Setting up swap with {}", to);
 
-        let _ = match swap_protocol {
-            SwapProtocol::Herc20Hbit => self
-                .herc20_hbit
-                .send_request(to, to_send.to_message(common)),
-            SwapProtocol::HbitHerc20 => self
-                .hbit_herc20
-                .send_request(to, to_send.to_message(common)),
+        let message = match take {
+            Some(take) => Outgoing::Take(common, to_send, take),
+            None => Outgoing::Params(common, to_send),
         };
+        self.send_message(to, swap_protocol, id, message);
+        self.negotiate(to, id);
+
         Ok(())
     }
 
-    fn alice_receive_hbit_herc20(&mut self, from: PeerId, common: CommonParams, bob: BobParams) {
-        self.alice_receive(from, common, bob, SwapProtocol::HbitHerc20);
-    }
+    /// Tell the peer that we will not set up the swap on `common`, e.g.
+    /// because we don't fill the order they want to take.
+    ///
+    /// Our side of the negotiation, if any, is dropped without an event.
+    pub fn decline(
+        &mut self,
+        peer: &PeerId,
+        id: NegotiationId,
+        common: &CommonParams,
+        swap_protocol: SwapProtocol,
+        reason: impl Into<String>,
+    ) {
+        let reason = reason.into();
+        self.negotiations.remove(&(peer.clone(), id));
 
-    fn alice_receive_herc20_hbit(&mut self, from: PeerId, common: CommonParams, bob: BobParams) {
-        self.alice_receive(from, common, bob, SwapProtocol::Herc20Hbit);
+        tracing::info!("Declining to set up swap with {}: {}", peer, reason);
+
+        self.send_message(
+            peer,
+            swap_protocol,
+            id,
+            Outgoing::Declined(common.clone(), reason),
+        );
     }
 
-    fn bob_receive_hbit_herc20(&mut self, from: PeerId, common: CommonParams, alice: AliceParams) {
-        self.bob_receive(from, common, alice, SwapProtocol::HbitHerc20);
+    /// Give up on the swaps we proposed whose context matches `is_aborted`,
+    /// the peers are told that we declined.
+    ///
+    /// Returns whether any negotiation was aborted.
+    pub fn abort(&mut self, is_aborted: impl Fn(&C) -> bool) -> bool {
+        let aborted = self
+            .negotiations
+            .iter()
+            .filter_map(|((peer, id), negotiation)| match &negotiation.ours {
+                Some(ours) if is_aborted(&ours.context) => {
+                    Some((peer.clone(), *id, ours.common.clone(), ours.swap_protocol))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for (peer, id, common, swap_protocol) in &aborted {
+            self.decline(
+                peer,
+                *id,
+                common,
+                *swap_protocol,
+                "gave up setting up the swap",
+            );
+        }
+
+        !aborted.is_empty()
     }
 
-    fn bob_receive_herc20_hbit(&mut self, from: PeerId, common: CommonParams, alice: AliceParams) {
-        self.bob_receive(from, common, alice, SwapProtocol::Herc20Hbit);
+    fn receive<U>(&mut self, from: PeerId, swap_protocol: SwapProtocol, message: Message<U>) {
+        match message {
+            Message::Alice {
+                id,
+                common,
+                alice,
+                take,
+                ..
+            } => self.receive_params(
+                from,
+                id,
+                swap_protocol,
+                common,
+                RoleDependentParams::Alice(alice),
+                take,
+            ),
+            Message::Bob {
+                id,
+                common,
+                bob,
+                take,
+                ..
+            } => self.receive_params(
+                from,
+                id,
+                swap_protocol,
+                common,
+                RoleDependentParams::Bob(bob),
+                take,
+            ),
+            Message::CounterProposal {
+                id,
+                common,
+                alice,
+                reason,
+                ..
+            } => self.receive_counter_proposal(from, id, swap_protocol, common, alice, reason),
+            Message::Rejected { id, reason, .. } => self.receive_rejection(from, id, reason),
+            Message::Declined { id, reason, .. } => self.receive_decline(from, id, reason),
+        }
     }
-    fn alice_receive(
+
+    fn receive_params(
         &mut self,
         from: PeerId,
-        common: CommonParams,
-        bob: BobParams,
+        id: NegotiationId,
         swap_protocol: SwapProtocol,
+        common: CommonParams,
+        received: RoleDependentParams,
+        take: Option<Take>,
     ) {
-        match self.swap_data.get(&common) {
-            Some(RoleDependentParams::Alice(alice)) => {
-                // todo: remove unwrap
-                let context = self.context.get(&common).cloned().unwrap();
-                self.events
-                    .push_back(BehaviourOutEvent::new_executable_swap(
-                        Role::Alice,
-                        common,
-                        alice,
-                        &bob,
-                        swap_protocol,
-                        from,
-                        context,
-                    ));
-            }
-            Some(RoleDependentParams::Bob(have)) => {
+        let negotiation = self.negotiations.entry((from.clone(), id)).or_default();
+
+        if let (Some(take), None) = (take, &negotiation.ours) {
+            self.events.push_back(BehaviourOutEvent::TakeRequested {
+                peer: from.clone(),
+                take,
+                common: common.clone(),
+                swap_protocol,
+                their_role: received.role(),
+            });
+        }
+
+        if let Some(ours) = &negotiation.ours {
+            if ours.params.role() == received.role() {
                 self.events
                     .push_back(BehaviourOutEvent::AlreadyHaveRoleParams {
                         peer: from,
-                        have: RoleDependentParams::Bob(*have),
-                        received: RoleDependentParams::Bob(bob),
+                        have: ours.params,
+                        received,
                     });
-            }
-            None => {
-                self.swap_data
-                    .insert(common.clone(), RoleDependentParams::Bob(bob));
+                return;
             }
         }
+
+        // Alice may send her parameters again once she accepted Bob's expiries.
+        negotiation.theirs = Some(TheirProposal {
+            common,
+            params: received,
+            swap_protocol,
+        });
+        self.negotiate(&from, id);
     }
 
-    fn bob_receive(
+    fn receive_counter_proposal(
         &mut self,
         from: PeerId,
+        id: NegotiationId,
+        swap_protocol: SwapProtocol,
         common: CommonParams,
         alice: AliceParams,
-        swap_protocol: SwapProtocol,
+        reason: UnsafeExpiries,
     ) {
-        match self.swap_data.get(&common) {
-            Some(RoleDependentParams::Alice(have)) => {
-                self.events
-                    .push_back(BehaviourOutEvent::AlreadyHaveRoleParams {
-                        peer: from,
-                        have: RoleDependentParams::Alice(*have),
-                        received: RoleDependentParams::Alice(alice),
-                    });
+        let key = (from.clone(), id);
+        let ours = match self.negotiations.get(&key) {
+            Some(Negotiation {
+                ours: Some(ours), ..
+            }) => ours,
+            _ => {
+                tracing::warn!("received counter-proposal from {} for unknown swap", from);
+                return;
             }
-            Some(RoleDependentParams::Bob(bob)) => {
-                // todo: remove unwrap
-                let context = self.context.get(&common).cloned().unwrap();
-                self.events
-                    .push_back(BehaviourOutEvent::new_executable_swap(
-                        Role::Bob,
-                        common,
-                        &alice,
-                        bob,
-                        swap_protocol,
-                        from,
-                        context,
-                    ))
+        };
+        let bob = match ours.params {
+            RoleDependentParams::Bob(bob) => bob,
+            RoleDependentParams::Alice(_) => {
+                tracing::warn!("received counter-proposal from {} but we are Alice", from);
+                return;
+            }
+        };
+        if !ours.has_same_terms(&common, swap_protocol) {
+            tracing::warn!("received counter-proposal from {} on other terms", from);
+            return;
+        }
+
+        tracing::info!(
+            "{} rejected our expiries and counter-proposed: {}",
+            from,
+            reason
+        );
+
+        match ours.validate(&common) {
+            Ok(()) => {
+                let event = ours.executable_swap(
+                    common.clone(),
+                    RoleDependentParams::Alice(alice),
+                    from.clone(),
+                );
+
+                self.events.push_back(event);
+                self.negotiations.remove(&key);
+                self.send_message(
+                    &from,
+                    swap_protocol,
+                    id,
+                    Outgoing::Params(common, RoleDependentParams::Bob(bob)),
+                );
             }
-            None => {
-                self.swap_data
-                    .insert(common.clone(), RoleDependentParams::Alice(alice));
+            Err(reason) => self.reject(&from, id, reason),
+        }
+    }
+
+    fn receive_rejection(&mut self, from: PeerId, id: NegotiationId, reason: UnsafeExpiries) {
+        if let Some(Negotiation {
+            ours: Some(ours), ..
+        }) = self.negotiations.remove(&(from.clone(), id))
+        {
+            tracing::warn!("{} rejected the expiries: {}", from, reason);
+
+            let rejected_by = match ours.params.role() {
+                Role::Alice => Role::Bob,
+                Role::Bob => Role::Alice,
+            };
+            self.events.push_back(BehaviourOutEvent::ExpiriesRejected {
+                peer: from,
+                rejected_by,
+                reason,
+                context: ours.context,
+            });
+        }
+    }
+
+    fn receive_decline(&mut self, from: PeerId, id: NegotiationId, reason: String) {
+        match self.negotiations.remove(&(from.clone(), id)) {
+            Some(Negotiation {
+                ours: Some(ours), ..
+            }) => {
+                tracing::warn!("{} declined to set up the swap: {}", from, reason);
+
+                self.events.push_back(BehaviourOutEvent::Declined {
+                    peer: from,
+                    reason,
+                    context: ours.context,
+                });
             }
+            _ => tracing::info!("{} gave up setting up a swap: {}", from, reason),
         }
     }
 
+    /// Set up the swap once we have the parameters of both parties, resolving
+    /// diverging expiries if necessary.
+    fn negotiate(&mut self, peer: &PeerId, id: NegotiationId) {
+        let key = (peer.clone(), id);
+        let negotiation = match self.negotiations.get_mut(&key) {
+            Some(negotiation) => negotiation,
+            None => return,
+        };
+        let (ours, theirs) = match (&negotiation.ours, &negotiation.theirs) {
+            (Some(ours), Some(theirs)) => (ours, theirs),
+            _ => return,
+        };
+
+        // Only the expiries are up for negotiation.
+        if !ours.has_same_terms(&theirs.common, theirs.swap_protocol) {
+            tracing::warn!("{} proposed other terms for the swap, ignoring them", peer);
+            negotiation.theirs = None;
+            return;
+        }
+        let swap_protocol = ours.swap_protocol;
+        let (common, theirs) = (&theirs.common, &theirs.params);
+
+        if ours.common == *common {
+            let event = ours.executable_swap(common.clone(), *theirs, peer.clone());
+
+            self.events.push_back(event);
+            self.negotiations.remove(&key);
+            return;
+        }
+
+        // Only Alice decides on diverging expiries, Bob waits for her decision.
+        let alice = match ours.params {
+            RoleDependentParams::Alice(alice) => alice,
+            RoleDependentParams::Bob(_) => return,
+        };
+
+        match ours.validate(common) {
+            Ok(()) => {
+                tracing::info!("Accepting the expiries proposed by {}", peer);
+
+                let event = ours.executable_swap(common.clone(), *theirs, peer.clone());
+                let message = Outgoing::Params(common.clone(), ours.params);
+
+                self.events.push_back(event);
+                self.negotiations.remove(&key);
+                self.send_message(peer, swap_protocol, id, message);
+            }
+            Err(reason) if !negotiation.counter_proposed => {
+                tracing::info!("Counter-proposing expiries to {}: {}", peer, reason);
+
+                let message = Outgoing::CounterProposal(ours.common.clone(), alice, reason);
+
+                negotiation.counter_proposed = true;
+                self.send_message(peer, swap_protocol, id, message);
+            }
+            Err(reason) => self.reject(peer, id, reason),
+        }
+    }
+
+    fn reject(&mut self, peer: &PeerId, id: NegotiationId, reason: UnsafeExpiries) {
+        if let Some(Negotiation {
+            ours: Some(ours), ..
+        }) = self.negotiations.remove(&(peer.clone(), id))
+        {
+            tracing::warn!("Rejecting the expiries proposed by {}: {}", peer, reason);
+
+            self.events.push_back(BehaviourOutEvent::ExpiriesRejected {
+                peer: peer.clone(),
+                rejected_by: ours.params.role(),
+                reason,
+                context: ours.context,
+            });
+            self.send_message(
+                peer,
+                ours.swap_protocol,
+                id,
+                Outgoing::Rejected(ours.common, reason),
+            );
+        }
+    }
+
+    fn send_message(
+        &mut self,
+        to: &PeerId,
+        swap_protocol: SwapProtocol,
+        id: NegotiationId,
+        message: Outgoing,
+    ) {
+        let _ = match swap_protocol {
+            SwapProtocol::Herc20Hbit => self.herc20_hbit.send_request(to, message.into_message(id)),
+            SwapProtocol::HbitHerc20 => self.hbit_herc20.send_request(to, message.into_message(id)),
+        };
+    }
+
+    /// Drop the negotiations that did not lead to a swap in time.
+    fn remove_timed_out(&mut self) {
+        let events = &mut self.events;
+
+        self.negotiations.retain(|(peer, _), negotiation| {
+            if negotiation.started_at.elapsed() < NEGOTIATION_TIMEOUT {
+                return true;
+            }
+
+            tracing::warn!("Setting up swap with {} timed out", peer);
+
+            if let Some(ours) = &negotiation.ours {
+                events.push_back(BehaviourOutEvent::TimedOut {
+                    peer: peer.clone(),
+                    context: ours.context.clone(),
+                });
+            }
+
+            false
+        });
+    }
+
     fn poll<InEvent>(
         &mut self,
         _: &mut Context<'_>,
         _: &mut impl PollParameters,
     ) -> Poll<NetworkBehaviourAction<InEvent, BehaviourOutEvent<C>>> {
+        self.remove_timed_out();
+
         if let Some(event) = self.events.pop_front() {
             if let BehaviourOutEvent::ExecutableSwap(swap) = &event {
                 tracing::info!("Successfully set up swap with {}", swap.peer_id);
@@ -318,14 +675,7 @@ impl<C: Clone + Send + 'static>
                     RequestResponseMessage::Request {
                         request: message, ..
                     },
-            } => match message {
-                Message::Alice { alice, common, .. } => {
-                    self.bob_receive_hbit_herc20(peer, common, alice)
-                }
-                Message::Bob { bob, common, .. } => {
-                    self.alice_receive_hbit_herc20(peer, common, bob)
-                }
-            },
+            } => self.receive(peer, SwapProtocol::HbitHerc20, message),
             RequestResponseEvent::OutboundFailure { error, .. } => {
                 tracing::warn!("outbound failure: {:?}", error);
             }
@@ -349,14 +699,7 @@ impl<C: Clone + Send + 'static>
                     RequestResponseMessage::Request {
                         request: message, ..
                     },
-            } => match message {
-                Message::Alice { alice, common, .. } => {
-                    self.bob_receive_herc20_hbit(peer, common, alice)
-                }
-                Message::Bob { bob, common, .. } => {
-                    self.alice_receive_herc20_hbit(peer, common, bob)
-                }
-            },
+            } => self.receive(peer, SwapProtocol::Herc20Hbit, message),
             RequestResponseEvent::OutboundFailure { error, .. } => {
                 tracing::warn!("outbound failure: {:?}", error);
             }
@@ -368,12 +711,123 @@ impl<C: Clone + Send + 'static>
     }
 }
 
+/// The parameters of a swap that are not up for negotiation, both parties
+/// need to propose the same ones.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Terms {
+    erc20: asset::Erc20,
+    bitcoin: asset::Bitcoin,
+    ethereum_chain_id: ChainId,
+    bitcoin_network: ledger::Bitcoin,
+    swap_protocol: SwapProtocol,
+}
+
+impl Terms {
+    fn new(common: &CommonParams, swap_protocol: SwapProtocol) -> Self {
+        Terms {
+            erc20: common.erc20.clone(),
+            bitcoin: common.bitcoin,
+            ethereum_chain_id: common.ethereum_chain_id,
+            bitcoin_network: common.bitcoin_network,
+            swap_protocol,
+        }
+    }
+}
+
+struct Negotiation<C> {
+    ours: Option<Proposal<C>>,
+    theirs: Option<TheirProposal>,
+    counter_proposed: bool,
+    started_at: Instant,
+}
+
+impl<C> Default for Negotiation<C> {
+    fn default() -> Self {
+        Negotiation {
+            ours: None,
+            theirs: None,
+            counter_proposed: false,
+            started_at: Instant::now(),
+        }
+    }
+}
+
+struct Proposal<C> {
+    common: CommonParams,
+    params: RoleDependentParams,
+    swap_protocol: SwapProtocol,
+    start_of_swap: Timestamp,
+    context: C,
+}
+
+struct TheirProposal {
+    common: CommonParams,
+    params: RoleDependentParams,
+    swap_protocol: SwapProtocol,
+}
+
+impl<C: Clone> Proposal<C> {
+    fn has_same_terms(&self, common: &CommonParams, swap_protocol: SwapProtocol) -> bool {
+        Terms::new(&self.common, self.swap_protocol) == Terms::new(common, swap_protocol)
+    }
+
+    /// Check whether the expiries in `common` are safe for the swap we
+    /// proposed.
+    fn validate(&self, common: &CommonParams) -> Result<(), UnsafeExpiries> {
+        let network = Network::from(common.bitcoin_network);
+        let ethereum_expiry = Timestamp::from(common.ethereum_absolute_expiry);
+        let bitcoin_expiry = Timestamp::from(common.bitcoin_absolute_expiry);
+
+        match self.swap_protocol {
+            SwapProtocol::HbitHerc20 => expiries::validate_hbit_herc20(
+                network,
+                self.start_of_swap,
+                bitcoin_expiry.into(),
+                ethereum_expiry.into(),
+            ),
+            SwapProtocol::Herc20Hbit => expiries::validate_herc20_hbit(
+                network,
+                self.start_of_swap,
+                ethereum_expiry.into(),
+                bitcoin_expiry.into(),
+            ),
+        }
+    }
+
+    fn executable_swap(
+        &self,
+        common: CommonParams,
+        theirs: RoleDependentParams,
+        peer_id: PeerId,
+    ) -> BehaviourOutEvent<C> {
+        let (our_role, alice, bob) = match (self.params, theirs) {
+            (RoleDependentParams::Alice(alice), RoleDependentParams::Bob(bob)) => {
+                (Role::Alice, alice, bob)
+            }
+            (RoleDependentParams::Bob(bob), RoleDependentParams::Alice(alice)) => {
+                (Role::Bob, alice, bob)
+            }
+            _ => unreachable!("we never store role parameters of the same role twice"),
+        };
+
+        BehaviourOutEvent::new_executable_swap(
+            our_role,
+            common,
+            &alice,
+            &bob,
+            self.swap_protocol,
+            peer_id,
+            self.context.clone(),
+        )
+    }
+}
+
 #[derive(Debug, Clone, Copy, Default)]
 pub struct HbitHerc20Protocol;
 
 impl ProtocolName for HbitHerc20Protocol {
     fn protocol_name(&self) -> &[u8] {
-        b"/comit/setup-swap/hbit-herc20/1.0.0"
+        b"/comit/setup-swap/hbit-herc20/2.0.0"
     }
 }
 
@@ -382,7 +836,7 @@ pub struct Herc20HbitProtocol;
 
 impl ProtocolName for Herc20HbitProtocol {
     fn protocol_name(&self) -> &[u8] {
-        b"/comit/setup-swap/herc20-hbit/1.0.0"
+        b"/comit/setup-swap/herc20-hbit/2.0.0"
     }
 }
 
@@ -413,6 +867,35 @@ pub struct BobParams {
     pub bitcoin_identity: identity::Bitcoin,
 }
 
+/// Identifies the order a taker wants to take, see [`SetupSwap::take`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Take {
+    /// The maker's order.
+    pub order: OrderId,
+    /// The order the taker created to record its side of the swap.
+    pub taker_order: OrderId,
+}
+
+/// Identifies the negotiation of a swap by the two orders it fills, one of
+/// each party.
+///
+/// Both parties know these orders from matching or taking them, hence
+/// concurrent swaps on the same terms with the same peer are kept apart.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct NegotiationId(OrderId, OrderId);
+
+impl NegotiationId {
+    pub fn new(ours: OrderId, theirs: OrderId) -> Self {
+        NegotiationId(OrderId::min(ours, theirs), OrderId::max(ours, theirs))
+    }
+}
+
+impl From<Take> for NegotiationId {
+    fn from(take: Take) -> Self {
+        NegotiationId::new(take.taker_order, take.order)
+    }
+}
+
 #[derive(Debug, Copy, Clone)]
 pub enum RoleDependentParams {
     Alice(AliceParams),
@@ -420,18 +903,10 @@ pub enum RoleDependentParams {
 }
 
 impl RoleDependentParams {
-    fn to_message<U>(self, common: CommonParams) -> Message<U> {
+    fn role(&self) -> Role {
         match self {
-            RoleDependentParams::Alice(alice) => Message::Alice {
-                _marker: PhantomData,
-                common,
-                alice,
-            },
-            RoleDependentParams::Bob(bob) => Message::Bob {
-                _marker: PhantomData,
-                common,
-                bob,
-            },
+            RoleDependentParams::Alice(_) => Role::Alice,
+            RoleDependentParams::Bob(_) => Role::Bob,
         }
     }
 }
@@ -440,16 +915,107 @@ impl RoleDependentParams {
 pub enum Message<U> {
     Alice {
         _marker: PhantomData<U>,
+        id: NegotiationId,
         common: CommonParams,
         alice: AliceParams,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        take: Option<Take>,
     },
     Bob {
         _marker: PhantomData<U>,
+        id: NegotiationId,
         common: CommonParams,
         bob: BobParams,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        take: Option<Take>,
+    },
+    /// Alice refuses the expiries proposed by Bob and insists on her own.
+    CounterProposal {
+        _marker: PhantomData<U>,
+        id: NegotiationId,
+        common: CommonParams,
+        alice: AliceParams,
+        reason: UnsafeExpiries,
+    },
+    /// The proposed expiries were rejected, no swap will be set up.
+    Rejected {
+        _marker: PhantomData<U>,
+        id: NegotiationId,
+        common: CommonParams,
+        reason: UnsafeExpiries,
+    },
+    /// The sender will not set up this swap.
+    Declined {
+        _marker: PhantomData<U>,
+        id: NegotiationId,
+        common: CommonParams,
+        reason: String,
     },
 }
 
+/// A message to send, independent of the protocol it is sent over.
+enum Outgoing {
+    Params(CommonParams, RoleDependentParams),
+    Take(CommonParams, RoleDependentParams, Take),
+    CounterProposal(CommonParams, AliceParams, UnsafeExpiries),
+    Rejected(CommonParams, UnsafeExpiries),
+    Declined(CommonParams, String),
+}
+
+impl Outgoing {
+    fn into_message<U>(self, id: NegotiationId) -> Message<U> {
+        match self {
+            Outgoing::Params(common, RoleDependentParams::Alice(alice)) => Message::Alice {
+                _marker: PhantomData,
+                id,
+                common,
+                alice,
+                take: None,
+            },
+            Outgoing::Params(common, RoleDependentParams::Bob(bob)) => Message::Bob {
+                _marker: PhantomData,
+                id,
+                common,
+                bob,
+                take: None,
+            },
+            Outgoing::Take(common, RoleDependentParams::Alice(alice), take) => Message::Alice {
+                _marker: PhantomData,
+                id,
+                common,
+                alice,
+                take: Some(take),
+            },
+            Outgoing::Take(common, RoleDependentParams::Bob(bob), take) => Message::Bob {
+                _marker: PhantomData,
+                id,
+                common,
+                bob,
+                take: Some(take),
+            },
+            Outgoing::CounterProposal(common, alice, reason) => Message::CounterProposal {
+                _marker: PhantomData,
+                id,
+                common,
+                alice,
+                reason,
+            },
+            Outgoing::Rejected(common, reason) => Message::Rejected {
+                _marker: PhantomData,
+                id,
+                common,
+                reason,
+            },
+            Outgoing::Declined(common, reason) => Message::Declined {
+                _marker: PhantomData,
+                id,
+                common,
+                reason,
+            },
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl<U> RequestResponseCodec for Codec<U>
 where
@@ -517,11 +1083,12 @@ where
 mod tests {
     use super::*;
     use crate::{
+        expiries::{AlphaExpiry, BetaExpiry},
         network::test::{await_events_or_timeout, connect, new_swarm},
         Secret,
     };
     use bitcoin::secp256k1;
-    use std::{future::Future, str::FromStr};
+    use std::{future::Future, str::FromStr, time::Duration};
 
     #[tokio::test]
     async fn given_bob_sends_when_alice_sends_one_then_swap_is_confirmed() {
@@ -529,18 +1096,6 @@ mod tests {
         let (mut bob_swarm, _, bob_id) = new_swarm(|_, _| SetupSwap::default());
         connect(&mut alice_swarm, &mut bob_swarm).await;
 
-        let ethereum_identity = identity::Ethereum::random();
-        let bitcoin_identity = identity::Bitcoin::from(
-            secp256k1::PublicKey::from_str(
-                "02c2a8efce029526d364c2cf39d89e3cdda05e5df7b2cbfc098b4e3d02b70b5275",
-            )
-            .unwrap(),
-        );
-        let secret_hash = SecretHash::new(
-            Secret::from_str("68d627971643a6f97f27c58957826fcba853ec2077fd10ec6b93d8e61deb4c66")
-                .expect("could not convert string to secret"),
-        );
-
         let common = CommonParams {
             erc20: asset::Erc20::new(identity::Ethereum::random(), asset::Erc20Quantity::zero()),
             bitcoin: asset::Bitcoin::from_sat(0),
@@ -550,31 +1105,29 @@ mod tests {
             bitcoin_network: ledger::Bitcoin::Regtest,
         };
 
+        let id = negotiation_id();
         let alice_context = 1;
         let bob_context = 2;
 
         bob_swarm
             .send(
                 &alice_id,
-                RoleDependentParams::Bob(BobParams {
-                    ethereum_identity,
-                    bitcoin_identity,
-                }),
+                id,
+                bob_params(),
                 common.clone(),
                 SwapProtocol::HbitHerc20,
+                Timestamp::from(0),
                 bob_context,
             )
             .expect("bob failed to send");
         alice_swarm
             .send(
                 &bob_id,
-                RoleDependentParams::Alice(AliceParams {
-                    ethereum_identity,
-                    bitcoin_identity,
-                    secret_hash,
-                }),
+                id,
+                alice_params(),
                 common,
                 SwapProtocol::HbitHerc20,
+                Timestamp::from(0),
                 alice_context,
             )
             .expect("alice failed to send");
@@ -588,12 +1141,420 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    async fn given_bob_proposes_later_safe_expiries_then_alice_accepts_them() {
+        let (mut alice_swarm, _, alice_id) = new_swarm(|_, _| SetupSwap::default());
+        let (mut bob_swarm, _, bob_id) = new_swarm(|_, _| SetupSwap::default());
+        connect(&mut alice_swarm, &mut bob_swarm).await;
+
+        let start_of_swap = Timestamp::now();
+        let (alpha, beta) = standard_expiries(start_of_swap);
+        let later = |expiry: Timestamp| expiry.plus(10);
+        let id = negotiation_id();
+
+        bob_swarm
+            .send(
+                &alice_id,
+                id,
+                bob_params(),
+                hbit_herc20_params(later(alpha.into()).into(), later(beta.into()).into()),
+                SwapProtocol::HbitHerc20,
+                start_of_swap,
+                2,
+            )
+            .expect("bob failed to send");
+        alice_swarm
+            .send(
+                &bob_id,
+                id,
+                alice_params(),
+                hbit_herc20_params(alpha, beta),
+                SwapProtocol::HbitHerc20,
+                start_of_swap,
+                1,
+            )
+            .expect("alice failed to send");
+
+        let (alice_hbit, _) =
+            assert_both_confirmed(alice_swarm.next(), bob_swarm.next(), 1, 2).await;
+        assert_eq!(alice_hbit.expiry, later(alpha.into()));
+    }
+
+    #[tokio::test]
+    async fn given_bob_proposes_unsafe_expiries_then_alice_counter_proposes_hers() {
+        let (mut alice_swarm, _, alice_id) = new_swarm(|_, _| SetupSwap::default());
+        let (mut bob_swarm, _, bob_id) = new_swarm(|_, _| SetupSwap::default());
+        connect(&mut alice_swarm, &mut bob_swarm).await;
+
+        let start_of_swap = Timestamp::now();
+        let (alpha, beta) = standard_expiries(start_of_swap);
+        let id = negotiation_id();
+
+        bob_swarm
+            .send(
+                &alice_id,
+                id,
+                bob_params(),
+                hbit_herc20_params(Timestamp::from(beta).into(), beta),
+                SwapProtocol::HbitHerc20,
+                start_of_swap,
+                2,
+            )
+            .expect("bob failed to send");
+        alice_swarm
+            .send(
+                &bob_id,
+                id,
+                alice_params(),
+                hbit_herc20_params(alpha, beta),
+                SwapProtocol::HbitHerc20,
+                start_of_swap,
+                1,
+            )
+            .expect("alice failed to send");
+
+        let (alice_hbit, _) =
+            assert_both_confirmed(alice_swarm.next(), bob_swarm.next(), 1, 2).await;
+        assert_eq!(alice_hbit.expiry, Timestamp::from(alpha));
+    }
+
+    #[tokio::test]
+    async fn given_bob_finds_counter_proposal_unsafe_then_both_are_told_why() {
+        let (mut alice_swarm, _, alice_id) = new_swarm(|_, _| SetupSwap::default());
+        let (mut bob_swarm, _, bob_id) = new_swarm(|_, _| SetupSwap::default());
+        connect(&mut alice_swarm, &mut bob_swarm).await;
+
+        // Bob considers the swap to start a day later than Alice does.
+        let alice_start_of_swap = Timestamp::now();
+        let bob_start_of_swap = alice_start_of_swap.plus(24 * 60 * 60);
+        let (alice_alpha, alice_beta) = standard_expiries(alice_start_of_swap);
+        let (bob_alpha, bob_beta) = standard_expiries(bob_start_of_swap);
+        let id = negotiation_id();
+
+        bob_swarm
+            .send(
+                &alice_id,
+                id,
+                bob_params(),
+                hbit_herc20_params(bob_alpha, bob_beta),
+                SwapProtocol::HbitHerc20,
+                bob_start_of_swap,
+                2,
+            )
+            .expect("bob failed to send");
+        alice_swarm
+            .send(
+                &bob_id,
+                id,
+                alice_params(),
+                hbit_herc20_params(alice_alpha, alice_beta),
+                SwapProtocol::HbitHerc20,
+                alice_start_of_swap,
+                1,
+            )
+            .expect("alice failed to send");
+
+        match await_events_or_timeout(alice_swarm.next(), bob_swarm.next()).await {
+            (
+                BehaviourOutEvent::ExpiriesRejected {
+                    rejected_by: Role::Bob,
+                    reason: alice_reason,
+                    context: 1,
+                    ..
+                },
+                BehaviourOutEvent::ExpiriesRejected {
+                    rejected_by: Role::Bob,
+                    reason: bob_reason,
+                    context: 2,
+                    ..
+                },
+            ) => {
+                assert_eq!(alice_reason, UnsafeExpiries::BetaExpiryTooEarly);
+                assert_eq!(bob_reason, UnsafeExpiries::BetaExpiryTooEarly);
+            }
+            (alice_event, bob_event) => panic!(
+                "expected both parties to learn about the rejection but alice emitted {:?} and bob emitted {:?}",
+                alice_event, bob_event
+            ),
+        }
+    }
+
+    #[tokio::test]
+    async fn given_bob_takes_order_when_alice_sends_then_swap_is_confirmed() {
+        let (mut alice_swarm, _, alice_id) = new_swarm(|_, _| SetupSwap::default());
+        let (mut bob_swarm, _, bob_id) = new_swarm(|_, _| SetupSwap::default());
+        connect(&mut alice_swarm, &mut bob_swarm).await;
+
+        let start_of_swap = Timestamp::now();
+        let (alpha, beta) = standard_expiries(start_of_swap);
+        let take = Take {
+            order: OrderId::random(),
+            taker_order: OrderId::random(),
+        };
+        let id = NegotiationId::from(take);
+
+        bob_swarm
+            .take(
+                &alice_id,
+                bob_params(),
+                hbit_herc20_params(alpha, beta),
+                SwapProtocol::HbitHerc20,
+                start_of_swap,
+                take,
+                2,
+            )
+            .expect("bob failed to take");
+
+        // Bob's swarm needs to be polled as well for his message to go out.
+        let take_requested = tokio::time::timeout(
+            Duration::from_secs(10),
+            future::select(alice_swarm.next(), bob_swarm.next()),
+        )
+        .await
+        .expect("alice to emit an event within 10 seconds");
+        match take_requested {
+            future::Either::Left((
+                BehaviourOutEvent::TakeRequested {
+                    peer,
+                    take: requested,
+                    their_role: Role::Bob,
+                    ..
+                },
+                _,
+            )) => {
+                assert_eq!(peer, bob_id);
+                assert_eq!(requested, take);
+            }
+            future::Either::Left((event, _)) => panic!(
+                "expected alice to be asked to take her order but got {:?}",
+                event
+            ),
+            future::Either::Right((event, _)) => panic!(
+                "expected alice to emit an event but bob emitted {:?}",
+                event
+            ),
+        }
+
+        alice_swarm
+            .send(
+                &bob_id,
+                id,
+                alice_params(),
+                hbit_herc20_params(alpha, beta),
+                SwapProtocol::HbitHerc20,
+                start_of_swap,
+                1,
+            )
+            .expect("alice failed to send");
+
+        assert_both_confirmed(alice_swarm.next(), bob_swarm.next(), 1, 2).await;
+    }
+
+    #[test]
+    fn rejection_from_another_peer_does_not_abort_negotiation() {
+        let mut setup_swap = SetupSwap::default();
+        let bob_id = PeerId::random();
+        let (alpha, beta) = standard_expiries(Timestamp::from(0));
+        let id = negotiation_id();
+
+        setup_swap
+            .send(
+                &bob_id,
+                id,
+                alice_params(),
+                hbit_herc20_params(alpha, beta),
+                SwapProtocol::HbitHerc20,
+                Timestamp::from(0),
+                1,
+            )
+            .expect("alice failed to send");
+        setup_swap.receive_rejection(PeerId::random(), id, UnsafeExpiries::TooLate);
+
+        assert!(setup_swap.events.is_empty());
+        assert!(setup_swap.negotiations.contains_key(&(bob_id, id)));
+    }
+
+    #[test]
+    fn concurrent_negotiations_on_same_terms_are_kept_apart() {
+        let mut setup_swap = SetupSwap::default();
+        let bob_id = PeerId::random();
+        let (alpha, beta) = standard_expiries(Timestamp::from(0));
+        let first = negotiation_id();
+        let second = negotiation_id();
+
+        for (id, context) in vec![(first, 1), (second, 2)] {
+            setup_swap
+                .send(
+                    &bob_id,
+                    id,
+                    alice_params(),
+                    hbit_herc20_params(alpha, beta),
+                    SwapProtocol::HbitHerc20,
+                    Timestamp::from(0),
+                    context,
+                )
+                .expect("alice failed to send");
+        }
+        setup_swap.receive_params(
+            bob_id,
+            second,
+            SwapProtocol::HbitHerc20,
+            hbit_herc20_params(alpha, beta),
+            bob_params(),
+            None,
+        );
+
+        match setup_swap.events.pop_front() {
+            Some(BehaviourOutEvent::ExecutableSwap(ExecutableSwap { context: 2, .. })) => {}
+            event => panic!("expected the second swap to be set up but got {:?}", event),
+        }
+        assert_eq!(setup_swap.negotiations.len(), 1);
+    }
+
+    #[test]
+    fn negotiation_is_dropped_after_timeout() {
+        let mut setup_swap = SetupSwap::default();
+        let bob_id = PeerId::random();
+        let (alpha, beta) = standard_expiries(Timestamp::from(0));
+        let id = negotiation_id();
+
+        setup_swap
+            .send(
+                &bob_id,
+                id,
+                alice_params(),
+                hbit_herc20_params(alpha, beta),
+                SwapProtocol::HbitHerc20,
+                Timestamp::from(0),
+                1,
+            )
+            .expect("alice failed to send");
+        for negotiation in setup_swap.negotiations.values_mut() {
+            negotiation.started_at -= NEGOTIATION_TIMEOUT;
+        }
+        setup_swap.remove_timed_out();
+
+        assert!(setup_swap.negotiations.is_empty());
+        match setup_swap.events.pop_front() {
+            Some(BehaviourOutEvent::TimedOut { peer, context: 1 }) => assert_eq!(peer, bob_id),
+            event => panic!("expected negotiation to time out but got {:?}", event),
+        }
+    }
+
+    #[test]
+    fn aborted_negotiation_is_dropped_without_event() {
+        let mut setup_swap = SetupSwap::default();
+        let (alpha, beta) = standard_expiries(Timestamp::from(0));
+
+        for context in 1..=2 {
+            let id = negotiation_id();
+            setup_swap
+                .send(
+                    &PeerId::random(),
+                    id,
+                    alice_params(),
+                    hbit_herc20_params(alpha, beta),
+                    SwapProtocol::HbitHerc20,
+                    Timestamp::from(0),
+                    context,
+                )
+                .expect("alice failed to send");
+        }
+
+        assert!(setup_swap.abort(|context| *context == 1));
+        assert!(!setup_swap.abort(|context| *context == 1));
+
+        assert!(setup_swap.events.is_empty());
+        assert_eq!(setup_swap.negotiations.len(), 1);
+    }
+
+    #[test]
+    fn decline_of_our_proposal_is_reported() {
+        let mut setup_swap = SetupSwap::default();
+        let bob_id = PeerId::random();
+        let (alpha, beta) = standard_expiries(Timestamp::from(0));
+        let id = negotiation_id();
+
+        setup_swap
+            .send(
+                &bob_id,
+                id,
+                alice_params(),
+                hbit_herc20_params(alpha, beta),
+                SwapProtocol::HbitHerc20,
+                Timestamp::from(0),
+                1,
+            )
+            .expect("alice failed to send");
+        setup_swap.receive_decline(bob_id.clone(), id, "order is gone".to_owned());
+
+        assert!(setup_swap.negotiations.is_empty());
+        match setup_swap.events.pop_front() {
+            Some(BehaviourOutEvent::Declined {
+                peer, context: 1, ..
+            }) => assert_eq!(peer, bob_id),
+            event => panic!("expected swap to be declined but got {:?}", event),
+        }
+    }
+
+    fn negotiation_id() -> NegotiationId {
+        NegotiationId::new(OrderId::random(), OrderId::random())
+    }
+
+    fn standard_expiries(start_of_swap: Timestamp) -> (AlphaExpiry, BetaExpiry) {
+        let (alpha, beta) = expiries::expiry_offsets_hbit_herc20(Network::Dev);
+        expiries::to_timestamps(start_of_swap, alpha, beta)
+    }
+
+    fn hbit_herc20_params(alpha: AlphaExpiry, beta: BetaExpiry) -> CommonParams {
+        CommonParams {
+            erc20: asset::Erc20::new(
+                identity::Ethereum::from_str("0xc5549e335b2786520f4c5d706c76c9ee69d0a028").unwrap(),
+                asset::Erc20Quantity::zero(),
+            ),
+            bitcoin: asset::Bitcoin::from_sat(0),
+            ethereum_absolute_expiry: Timestamp::from(beta).into(),
+            bitcoin_absolute_expiry: Timestamp::from(alpha).into(),
+            ethereum_chain_id: ChainId::GETH_DEV,
+            bitcoin_network: ledger::Bitcoin::Regtest,
+        }
+    }
+
+    fn alice_params() -> RoleDependentParams {
+        let secret_hash = SecretHash::new(
+            Secret::from_str("68d627971643a6f97f27c58957826fcba853ec2077fd10ec6b93d8e61deb4c66")
+                .expect("could not convert string to secret"),
+        );
+
+        RoleDependentParams::Alice(AliceParams {
+            ethereum_identity: identity::Ethereum::random(),
+            bitcoin_identity: bitcoin_identity(),
+            secret_hash,
+        })
+    }
+
+    fn bob_params() -> RoleDependentParams {
+        RoleDependentParams::Bob(BobParams {
+            ethereum_identity: identity::Ethereum::random(),
+            bitcoin_identity: bitcoin_identity(),
+        })
+    }
+
+    fn bitcoin_identity() -> identity::Bitcoin {
+        identity::Bitcoin::from(
+            secp256k1::PublicKey::from_str(
+                "02c2a8efce029526d364c2cf39d89e3cdda05e5df7b2cbfc098b4e3d02b70b5275",
+            )
+            .unwrap(),
+        )
+    }
+
     async fn assert_both_confirmed<C: PartialEq + Debug>(
         alice_event: impl Future<Output = BehaviourOutEvent<C>>,
         bob_event: impl Future<Output = BehaviourOutEvent<C>>,
         expected_alice_context: C,
         expected_bob_context: C,
-    ) {
+    ) -> (hbit::Params, herc20::Params) {
         match await_events_or_timeout(alice_event, bob_event).await {
             (
                 BehaviourOutEvent::ExecutableSwap(ExecutableSwap {
@@ -609,6 +1570,7 @@ mod tests {
                 assert_eq!(expected_alice_context, alice_context);
                 assert_eq!(expected_bob_context, bob_context);
 
+                (alice_hbit, alice_herc20)
             }
             (alice_event, bob_event) => panic!("expected both parties to confirm the swap but alice emitted {:?} and bob emitted {:?}", alice_event, bob_event),
         }
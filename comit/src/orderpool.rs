@@ -6,24 +6,101 @@ use anyhow::Result;
 use libp2p::PeerId;
 use lru::LruCache;
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     iter,
-    iter::FromIterator,
-    ops::AddAssign,
+    time::Duration,
 };
 use time::OffsetDateTime;
 
+/// How long we reserve the matched quantity of two orders for setting up a
+/// swap.
+///
+/// If the swap is not set up within this time, the reservation lapses and the
+/// quantity becomes available for other matches again.
+pub const RESERVATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The order is not in the order pool, e.g. because it was filled or
+/// cancelled.
+///
+/// Our own orders cannot be taken, they are not found either.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("order {0} is not in the order pool")]
+pub struct UnknownOrder(pub OrderId);
+
+/// Less of the order is available than was asked for, the rest is filled or
+/// reserved for other swaps.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("cannot fill {requested} of order {order} because only {available} are available")]
+pub struct InsufficientQuantity {
+    pub order: OrderId,
+    pub requested: asset::Bitcoin,
+    pub available: asset::Bitcoin,
+}
+
+/// Swaps smaller than the minimum fill are not worth their fees.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("{quantity} is below the minimum fill of {min_fill}")]
+pub struct BelowMinFill {
+    pub quantity: asset::Bitcoin,
+    pub min_fill: asset::Bitcoin,
+}
+
+/// A swap was set up for a quantity of one of our orders we never reserved.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+#[error("none of the reservations for order {order} is for {quantity}")]
+pub struct UnreservedQuantity {
+    pub order: OrderId,
+    pub quantity: asset::Bitcoin,
+}
+
+/// One of our orders cannot be amended as requested.
+#[derive(Clone, Copy, Debug, PartialEq, thiserror::Error)]
+pub enum InvalidAmendment {
+    #[error("order {0} cannot be amended to a quantity of zero, cancel it instead")]
+    ZeroQuantity(OrderId),
+    #[error("order {0} cannot be amended to a price of zero")]
+    ZeroPrice(OrderId),
+    #[error("cannot reduce order {order} to {requested} because {reserved} are reserved for swaps being set up")]
+    BelowReserved {
+        order: OrderId,
+        requested: asset::Bitcoin,
+        reserved: asset::Bitcoin,
+    },
+}
+
 /// A collection of orders gathered from several makers.
 #[derive(Debug)]
 pub struct OrderPool {
     inner: HashMap<PeerId, HashMap<OrderId, BtcDaiOrder>>,
 
-    reserved_quantities: HashMap<OrderId, asset::Bitcoin>,
+    /// Until when the makers of the orders we received honour them.
+    ///
+    /// Our own orders never expire.
+    expiries: HashMap<OrderId, OffsetDateTime>,
+
+    /// Quantities we reserved for swaps that are being set up.
+    reserved: HashMap<OrderId, Vec<Reserved>>,
+    /// Quantities other makers reserved of their orders.
+    announced: HashMap<OrderId, Reservation>,
+    /// Our orders whose reservations changed since we last announced them.
+    unannounced: HashSet<OrderId>,
+    /// Our orders that changed since we last pushed them to other peers.
+    changed: HashSet<OrderId>,
+    /// Makers whose orders we don't want to match, e.g. because of their bad
+    /// reputation.
+    blocked: HashSet<PeerId>,
+
     /// Our own id.
     ///
     /// Allows us to filter out our own orders.
     me: PeerId,
 
+    /// The smallest quantity we are willing to swap when matching orders.
+    ///
+    /// Prevents orders from being filled by swaps whose fees would eat up most
+    /// of the traded amount.
+    min_fill: asset::Bitcoin,
+
     /// A cache for storing which orders don't match.
     no_match_cache: LruCache<NoMatch, ()>,
 }
@@ -34,24 +111,93 @@ pub struct OrderPool {
 ///
 /// 1. We don't consume as much memory because only the hash of this struct is
 /// stored. 2. It allows our cache to automatically invalidate itself if the
-/// quantity of any of the orders change.
+/// quantity of any of the orders or their reservations change.
 #[derive(Debug, PartialEq, Eq, Hash)]
 struct NoMatch {
     ours: BtcDaiOrder,
     theirs: BtcDaiOrder,
+    reserved_ours: asset::Bitcoin,
+    reserved_theirs: asset::Bitcoin,
+}
+
+/// A change to one of our orders that needs to be pushed to other peers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderUpdate {
+    /// The order is new or its quantity changed.
+    Published(BtcDaiOrder),
+    /// The order was cancelled or filled completely.
+    Removed(OrderId),
+}
+
+/// A quantity we reserved of an order for a swap that is being set up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Reserved {
+    quantity: asset::Bitcoin,
+    expires_at: OffsetDateTime,
+}
+
+/// Announces how much of one of their orders a maker reserved for swaps that
+/// are being set up.
+///
+/// The quantity is the total of all reservations for this order. A smaller
+/// quantity than announced before releases the difference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reservation {
+    pub order: OrderId,
+    pub quantity: asset::Bitcoin,
+    /// When the reservation lapses unless it is announced again.
+    pub expires_at: OffsetDateTime,
 }
 
 impl OrderPool {
     pub fn new(me: PeerId) -> Self {
         Self {
             inner: Default::default(),
-            reserved_quantities: Default::default(),
+            expiries: Default::default(),
+            reserved: Default::default(),
+            announced: Default::default(),
+            unannounced: Default::default(),
+            changed: Default::default(),
+            blocked: Default::default(),
             me,
+            min_fill: asset::Bitcoin::ZERO,
             no_match_cache: LruCache::new(100), /* cap this at a 100 entries to avoid unbounded
                                                  * memory growth */
         }
     }
 
+    /// Set the smallest quantity we are willing to swap when matching orders.
+    pub fn set_min_fill(&mut self, min_fill: asset::Bitcoin) {
+        self.min_fill = min_fill;
+        // Pairs that were too small before might match now
+        self.no_match_cache.clear();
+    }
+
+    /// Stop accepting orders of this maker and drop the ones we have.
+    pub fn block(&mut self, maker: PeerId) {
+        if maker == self.me {
+            return;
+        }
+
+        self.remove_all_from(&maker);
+        if self.blocked.insert(maker.clone()) {
+            tracing::info!("blocked orders of {}", maker);
+        }
+    }
+
+    /// Accept orders of this maker again.
+    ///
+    /// Their orders show up again with the next snapshot or push we receive.
+    pub fn unblock(&mut self, maker: &PeerId) {
+        if self.blocked.remove(maker) {
+            tracing::info!("unblocked orders of {}", maker);
+        }
+    }
+
+    pub fn is_blocked(&self, maker: &PeerId) -> bool {
+        self.blocked.contains(maker)
+    }
+
     /// Get the peer id of the maker of this order.
     pub fn maker_id(&self, id: OrderId) -> Option<PeerId> {
         for (maker, orders) in self.inner.iter() {
@@ -62,32 +208,235 @@ impl OrderPool {
         None
     }
 
+    /// Publish one of our orders.
+    ///
+    /// Orders smaller than the minimum fill could never be matched, hence they
+    /// are not published.
     pub fn publish(&mut self, order: BtcDaiOrder) {
         let id = order.id;
+        if order.quantity.to_inner() < self.min_fill {
+            tracing::warn!(
+                "not publishing order {} because {}",
+                id,
+                BelowMinFill {
+                    quantity: order.quantity.to_inner(),
+                    min_fill: self.min_fill,
+                }
+            );
+            return;
+        }
+
         self.inner
             .entry(self.me.clone())
             .or_default()
             .insert(id, order);
+        self.changed.insert(id);
 
         tracing::info!("published order {}", id);
     }
 
-    /// Receive other people's orders.
+    /// Receive a single order pushed by its maker.
+    pub fn receive_order(&mut self, maker: PeerId, order: BtcDaiOrder, expires_at: OffsetDateTime) {
+        if maker == self.me || self.blocked.contains(&maker) {
+            return;
+        }
+
+        self.expiries.insert(order.id, expires_at);
+        self.inner.entry(maker).or_default().insert(order.id, order);
+    }
+
+    /// Remove an order because its maker told us so.
+    pub fn remove_order(&mut self, maker: &PeerId, id: OrderId) {
+        if *maker == self.me {
+            return;
+        }
+
+        if let Some(orders) = self.inner.get_mut(maker) {
+            if orders.remove(&id).is_some() {
+                self.expiries.remove(&id);
+                self.announced.remove(&id);
+            }
+        }
+    }
+
+    /// Receive other people's orders together with their expiry.
     ///
     /// This replaces all current orders of this peer with the newly received
     /// ones.
-    pub fn receive(&mut self, maker: PeerId, orders: Vec<BtcDaiOrder>) {
-        let map = HashMap::from_iter(orders.into_iter().map(|o| (o.id, o)));
+    pub fn receive(&mut self, maker: PeerId, orders: Vec<(BtcDaiOrder, OffsetDateTime)>) {
+        if self.blocked.contains(&maker) {
+            return;
+        }
+
+        if let Some(previous) = self.inner.remove(&maker) {
+            for id in previous.keys() {
+                self.expiries.remove(id);
+            }
+        }
+
+        let mut map = HashMap::with_capacity(orders.len());
+        for (order, expires_at) in orders {
+            self.expiries.insert(order.id, expires_at);
+            map.insert(order.id, order);
+        }
 
         self.inner.insert(maker, map);
     }
 
     pub fn remove_all_from(&mut self, maker: &PeerId) {
-        self.inner.remove(maker);
+        if let Some(orders) = self.inner.remove(maker) {
+            for id in orders.keys() {
+                self.expiries.remove(id);
+                self.announced.remove(id);
+            }
+        }
+    }
+
+    /// Remove all orders of other people and all reservations that expired at
+    /// the given time.
+    pub fn remove_expired(&mut self, now: OffsetDateTime) {
+        let expiries = &mut self.expiries;
+
+        for orders in self.inner.values_mut() {
+            orders.retain(|id, _| match expiries.get(id).copied() {
+                Some(expires_at) if expires_at <= now => {
+                    tracing::debug!("order {} expired at {}", id, expires_at);
+                    expiries.remove(id);
+                    false
+                }
+                _ => true,
+            });
+        }
+
+        let unannounced = &mut self.unannounced;
+        let ours = self.inner.get(&self.me);
+
+        self.reserved.retain(|id, reservations| {
+            let before = reservations.len();
+            reservations.retain(|reserved| reserved.expires_at > now);
+
+            if reservations.len() != before {
+                tracing::debug!("reservation for order {} timed out", id);
+
+                if ours.map_or(false, |orders| orders.contains_key(id)) {
+                    unannounced.insert(*id);
+                }
+            }
+
+            !reservations.is_empty()
+        });
+
+        self.announced
+            .retain(|_, reservation| reservation.expires_at > now);
+    }
+
+    /// Honour a reservation a maker announced for one of their orders.
+    ///
+    /// Only the maker of an order can reserve quantities of it.
+    pub fn receive_reservation(&mut self, maker: &PeerId, reservation: Reservation) {
+        let id = reservation.order;
+
+        if *maker == self.me || self.maker_id(id).as_ref() != Some(maker) {
+            tracing::debug!(
+                "ignoring reservation for order {} from {} because they are not its maker",
+                id,
+                maker
+            );
+            return;
+        }
+
+        if reservation.quantity == asset::Bitcoin::ZERO {
+            self.announced.remove(&id);
+        } else {
+            self.announced.insert(id, reservation);
+        }
+    }
+
+    /// Take the reservations of our orders that changed since they were last
+    /// taken, so they can be announced to other peers.
+    pub fn take_reservation_updates(&mut self) -> Vec<Reservation> {
+        let now = OffsetDateTime::now_utc();
+        let reserved = &self.reserved;
+
+        self.unannounced
+            .drain()
+            .map(|order| {
+                let reservations = reserved.get(&order).map(Vec::as_slice).unwrap_or_default();
+
+                Reservation {
+                    order,
+                    quantity: reservations
+                        .iter()
+                        .fold(asset::Bitcoin::ZERO, |total, r| total + r.quantity),
+                    expires_at: reservations
+                        .iter()
+                        .map(|r| r.expires_at)
+                        .max()
+                        .unwrap_or(now),
+                }
+            })
+            .collect()
+    }
+
+    /// Amend the price and/or the remaining quantity of one of our orders.
+    ///
+    /// The order keeps its id, hence other peers update it in place. Its
+    /// quantity cannot be reduced below what is reserved for swaps that are
+    /// being set up. Returns the order as it was before.
+    pub fn amend(
+        &mut self,
+        id: OrderId,
+        price: Option<Price<asset::Bitcoin, Erc20Quantity>>,
+        quantity: Option<Quantity<asset::Bitcoin>>,
+    ) -> Result<BtcDaiOrder> {
+        self.remove_expired(OffsetDateTime::now_utc());
+
+        let reserved = reserved_quantity(&self.reserved, &self.announced, id);
+        let order = self
+            .inner
+            .get_mut(&self.me)
+            .and_then(|orders| orders.get_mut(&id))
+            .ok_or(UnknownOrder(id))?;
+
+        if let Some(quantity) = quantity {
+            let requested = quantity.to_inner();
+
+            if requested == asset::Bitcoin::ZERO {
+                anyhow::bail!(InvalidAmendment::ZeroQuantity(id))
+            }
+            if requested < reserved {
+                anyhow::bail!(InvalidAmendment::BelowReserved {
+                    order: id,
+                    requested,
+                    reserved,
+                })
+            }
+            if requested < self.min_fill {
+                anyhow::bail!(BelowMinFill {
+                    quantity: requested,
+                    min_fill: self.min_fill,
+                })
+            }
+        }
+
+        let previous = order.clone();
+        if let Some(price) = price {
+            order.price = price;
+        }
+        if let Some(quantity) = quantity {
+            order.quantity = quantity;
+        }
+        self.changed.insert(id);
+
+        tracing::info!("amended order {}", id);
+
+        Ok(previous)
     }
 
     pub fn clear_own_orders(&mut self) {
-        self.inner.remove(&self.me);
+        if let Some(orders) = self.inner.remove(&self.me) {
+            self.changed.extend(orders.keys());
+        }
     }
 
     pub fn cancel(&mut self, id: OrderId) {
@@ -96,11 +445,26 @@ impl OrderPool {
 
     pub fn remove_ours(&mut self, id: OrderId) -> Option<BtcDaiOrder> {
         if let Some(map) = self.inner.get_mut(&self.me) {
+            self.changed.insert(id);
             return map.remove(&id);
         }
         None
     }
 
+    /// Take the changes to our orders since they were last taken, so they can
+    /// be pushed to other peers.
+    pub fn take_order_updates(&mut self) -> Vec<OrderUpdate> {
+        let ours = self.inner.get(&self.me);
+
+        self.changed
+            .drain()
+            .map(|id| match ours.and_then(|orders| orders.get(&id)) {
+                Some(order) => OrderUpdate::Published(order.clone()),
+                None => OrderUpdate::Removed(id),
+            })
+            .collect()
+    }
+
     pub fn all(&self) -> impl Iterator<Item = (&PeerId, &BtcDaiOrder)> {
         self.inner
             .iter()
@@ -136,35 +500,69 @@ impl OrderPool {
     /// While this was in progress, the OrderPool had "reserved" a certain
     /// quantity for this order. Now that we setup a swap successfully, we can
     /// clear this reservation and actually update the amount of the order.
+    ///
+    /// A remainder below the minimum fill could never be matched, hence the
+    /// order is closed instead.
+    ///
+    /// Fails without touching the order if none of its reservations is for the
+    /// given quantity.
     pub fn notify_swap_setup_successful(
         &mut self,
         order_id: OrderId,
         quantity: Quantity<asset::Bitcoin>,
     ) -> Result<()> {
         let quantity = quantity.to_inner();
-
-        if let Some(reserved_quantity) = self.reserved_quantities.get_mut(&order_id) {
-            if *reserved_quantity < quantity {
-                anyhow::bail!(
-                    "attempted to un-reserve {} but only {} were reserved",
-                    reserved_quantity,
-                    *reserved_quantity
-                );
+        let is_ours = self.is_ours(order_id);
+
+        if let Entry::Occupied(mut entry) = self.reserved.entry(order_id) {
+            let reservations = entry.get_mut();
+            let index = reservations
+                .iter()
+                .position(|r| r.quantity == quantity)
+                .ok_or(UnreservedQuantity {
+                    order: order_id,
+                    quantity,
+                })?;
+            reservations.remove(index);
+            if reservations.is_empty() {
+                entry.remove();
             }
-
-            *reserved_quantity -= quantity;
         } else {
-            tracing::warn!("we never reserved anything for order {}", order_id);
+            tracing::warn!("no reservation of {} left for order {}", quantity, order_id);
+        }
+
+        // The orders we create for taking someone else's order are never
+        // published, there is nothing to tell other peers about them.
+        if is_ours {
+            self.unannounced.insert(order_id);
+            self.changed.insert(order_id);
         }
 
         if let Some(our_orders) = self.inner.get_mut(&self.me) {
             if let Entry::Occupied(mut entry) = our_orders.entry(order_id) {
                 let order = entry.get_mut();
-
-                if order.quantity.to_inner() == quantity {
-                    entry.remove();
-                } else {
-                    order.quantity = Quantity::new(order.quantity.to_inner() - quantity);
+                let remaining = order.quantity.to_inner().checked_sub(quantity);
+
+                match remaining {
+                    Some(remaining) if remaining == asset::Bitcoin::ZERO => {
+                        entry.remove();
+                    }
+                    Some(remaining) if remaining < self.min_fill => {
+                        tracing::info!(
+                            "closing order {} because its remainder of {} is below the minimum fill of {}",
+                            order_id,
+                            remaining,
+                            self.min_fill
+                        );
+                        entry.remove();
+                    }
+                    Some(remaining) => order.quantity = Quantity::new(remaining),
+                    None => anyhow::bail!(
+                        "attempted to fill {} of order {} but only {} are left",
+                        quantity,
+                        order_id,
+                        order.quantity.to_inner()
+                    ),
                 }
             }
         }
@@ -172,11 +570,206 @@ impl OrderPool {
         Ok(())
     }
 
+    /// Release the quantity we reserved of an order for a swap that could not
+    /// be set up, it can be matched again right away.
+    pub fn notify_swap_setup_failed(
+        &mut self,
+        order_id: OrderId,
+        quantity: Quantity<asset::Bitcoin>,
+    ) -> Result<()> {
+        let quantity = quantity.to_inner();
+        let reservations = self.reserved.get_mut(&order_id);
+        let index = reservations
+            .as_ref()
+            .and_then(|reservations| reservations.iter().position(|r| r.quantity == quantity))
+            .ok_or(UnreservedQuantity {
+                order: order_id,
+                quantity,
+            })?;
+
+        if let Some(reservations) = reservations {
+            reservations.remove(index);
+            if reservations.is_empty() {
+                self.reserved.remove(&order_id);
+            }
+        }
+
+        if self.is_ours(order_id) {
+            self.unannounced.insert(order_id);
+        }
+
+        Ok(())
+    }
+
     pub fn is_ours(&self, id: OrderId) -> bool {
         self.ours().any(|o| o.id == id)
     }
 
+    /// Take one of their orders directly instead of waiting for one of ours
+    /// to match it.
+    ///
+    /// Reserves the given quantity of the order, or everything that is left of
+    /// it if no quantity is given. Returns the order recording our side of the
+    /// swap, which is never published, together with the match to set up the
+    /// swap for.
+    pub fn take(
+        &mut self,
+        id: OrderId,
+        quantity: Option<Quantity<asset::Bitcoin>>,
+    ) -> Result<(BtcDaiOrder, Match)> {
+        self.remove_expired(OffsetDateTime::now_utc());
+
+        let (peer, theirs) = self
+            .theirs()
+            .find(|(_, order)| order.id == id)
+            .map(|(maker, order)| (maker.clone(), order.clone()))
+            .ok_or(UnknownOrder(id))?;
+
+        let available = theirs
+            .quantity
+            .to_inner()
+            .checked_sub(reserved_quantity(&self.reserved, &self.announced, id))
+            .unwrap_or(asset::Bitcoin::ZERO);
+        let quantity = quantity.map_or(available, |quantity| quantity.to_inner());
+
+        if quantity == asset::Bitcoin::ZERO || quantity > available {
+            anyhow::bail!(InsufficientQuantity {
+                order: id,
+                requested: quantity,
+                available,
+            })
+        }
+
+        if quantity < self.min_fill {
+            anyhow::bail!(BelowMinFill {
+                quantity,
+                min_fill: self.min_fill,
+            })
+        }
+
+        let position = match theirs.position {
+            Position::Buy => Position::Sell,
+            Position::Sell => Position::Buy,
+        };
+        let ours = BtcDaiOrder::new(
+            position,
+            Quantity::new(quantity),
+            theirs.price.clone(),
+            theirs.swap_protocol,
+        );
+
+        let reserved = Reserved {
+            quantity,
+            expires_at: OffsetDateTime::now_utc() + RESERVATION_TIMEOUT,
+        };
+        self.reserved.entry(ours.id).or_default().push(reserved);
+        self.reserved.entry(id).or_default().push(reserved);
+
+        let r#match = Match {
+            peer,
+            price: theirs.price.clone(),
+            quantity: ours.quantity,
+            ours: ours.id,
+            theirs: id,
+            our_position: ours.position,
+            swap_protocol: ours.swap_protocol,
+            match_reference_point: make_reference_point(&ours, &theirs),
+        };
+
+        Ok((ours, r#match))
+    }
+
+    /// Reserve a quantity of one of our orders for a taker who wants to take
+    /// it directly, see [`take`](OrderPool::take).
+    ///
+    /// The taker has to pay our price for it. The reference point is the one
+    /// the taker derived the expiries from, it has to be recent for the
+    /// expiries to be safe.
+    pub fn reserve_for_taker(
+        &mut self,
+        taker: PeerId,
+        id: OrderId,
+        taker_order: OrderId,
+        quantity: Quantity<asset::Bitcoin>,
+        quote: &Erc20Quantity,
+        match_reference_point: OffsetDateTime,
+    ) -> Result<Match> {
+        let now = OffsetDateTime::now_utc();
+        self.remove_expired(now);
+
+        if self.blocked.contains(&taker) {
+            anyhow::bail!("{} is blocked and cannot take order {}", taker, id)
+        }
+
+        let ours = self
+            .ours()
+            .find(|order| order.id == id)
+            .cloned()
+            .ok_or(UnknownOrder(id))?;
+
+        if quantity * ours.price.clone() != *quote {
+            anyhow::bail!(
+                "{} did not offer our price of {} for order {}",
+                taker,
+                ours.price.wei_per_sat(),
+                id
+            )
+        }
+
+        if match_reference_point < now - RESERVATION_TIMEOUT
+            || match_reference_point > now + RESERVATION_TIMEOUT
+        {
+            anyhow::bail!(
+                "reference point {} of {} for order {} is not recent",
+                match_reference_point,
+                taker,
+                id
+            )
+        }
+
+        let quantity = quantity.to_inner();
+        let available = ours
+            .quantity
+            .to_inner()
+            .checked_sub(reserved_quantity(&self.reserved, &self.announced, id))
+            .unwrap_or(asset::Bitcoin::ZERO);
+
+        if quantity == asset::Bitcoin::ZERO || quantity > available {
+            anyhow::bail!(InsufficientQuantity {
+                order: id,
+                requested: quantity,
+                available,
+            })
+        }
+
+        if quantity < self.min_fill {
+            anyhow::bail!(BelowMinFill {
+                quantity,
+                min_fill: self.min_fill,
+            })
+        }
+
+        self.reserved.entry(id).or_default().push(Reserved {
+            quantity,
+            expires_at: now + RESERVATION_TIMEOUT,
+        });
+        self.unannounced.insert(id);
+
+        Ok(Match {
+            peer: taker,
+            price: ours.price,
+            quantity: Quantity::new(quantity),
+            ours: id,
+            theirs: taker_order,
+            our_position: ours.position,
+            swap_protocol: ours.swap_protocol,
+            match_reference_point,
+        })
+    }
+
     pub fn matches(&mut self) -> Vec<Match> {
+        self.remove_expired(OffsetDateTime::now_utc());
+
         let me = &self.me;
 
         let mut matches = Vec::new();
@@ -201,26 +794,30 @@ impl OrderPool {
             })
             .flatten();
 
+        let expires_at = OffsetDateTime::now_utc() + RESERVATION_TIMEOUT;
+
         for ours in ours {
             for (peer, theirs) in theirs.clone() {
-                let reserved_ours = self
-                    .reserved_quantities
-                    .get(&ours.id)
-                    .unwrap_or(&asset::Bitcoin::ZERO);
-                let reserved_theirs = self
-                    .reserved_quantities
-                    .get(&theirs.id)
-                    .unwrap_or(&asset::Bitcoin::ZERO);
+                let reserved_ours = reserved_quantity(&self.reserved, &self.announced, ours.id);
+                let reserved_theirs = reserved_quantity(&self.reserved, &self.announced, theirs.id);
 
                 // TODO: Avoid the .clone() here somehow
                 if self.no_match_cache.contains(&NoMatch {
                     ours: ours.clone(),
                     theirs: theirs.clone(),
+                    reserved_ours,
+                    reserved_theirs,
                 }) {
                     continue;
                 }
 
-                if let Some(r#match) = match_orders(ours, theirs, reserved_ours, reserved_theirs) {
+                if let Some(r#match) = match_orders(
+                    ours,
+                    theirs,
+                    &reserved_ours,
+                    &reserved_theirs,
+                    &self.min_fill,
+                ) {
                     let quantity = r#match.quantity;
 
                     matches.push(Match {
@@ -234,24 +831,22 @@ impl OrderPool {
                         match_reference_point: make_reference_point(ours, theirs),
                     });
 
-                    // TODO: Expose these reservations to other parties
-                    // Take care that we don't do it immediately, we probably want to wait until we
-                    // successfully set up a swap
-                    self.reserved_quantities
-                        .entry(ours.id)
-                        .or_default()
-                        .add_assign(quantity.to_inner());
-
-                    // TODO: We should reset this once we receive orders again from them
-                    self.reserved_quantities
-                        .entry(theirs.id)
-                        .or_default()
-                        .add_assign(quantity.to_inner());
+                    let reserved = Reserved {
+                        quantity: quantity.to_inner(),
+                        expires_at,
+                    };
+                    self.reserved.entry(ours.id).or_default().push(reserved);
+                    self.reserved.entry(theirs.id).or_default().push(reserved);
+
+                    // Other takers must not match the same quantity of our order
+                    self.unannounced.insert(ours.id);
                 } else {
                     self.no_match_cache.put(
                         NoMatch {
                             ours: ours.clone(),
                             theirs: theirs.clone(),
+                            reserved_ours,
+                            reserved_theirs,
                         },
                         (),
                     );
@@ -263,6 +858,27 @@ impl OrderPool {
     }
 }
 
+/// The quantity of an order that is reserved, be it by us or by its maker.
+///
+/// The reservation announced by the maker of an order already covers the
+/// quantity we reserved when matching it, hence the larger of the two counts.
+fn reserved_quantity(
+    reserved: &HashMap<OrderId, Vec<Reserved>>,
+    announced: &HashMap<OrderId, Reservation>,
+    id: OrderId,
+) -> asset::Bitcoin {
+    let ours = reserved
+        .get(&id)
+        .into_iter()
+        .flatten()
+        .fold(asset::Bitcoin::ZERO, |total, r| total + r.quantity);
+    let theirs = announced
+        .get(&id)
+        .map_or(asset::Bitcoin::ZERO, |r| r.quantity);
+
+    ours.max(theirs)
+}
+
 fn make_reference_point(left: &BtcDaiOrder, right: &BtcDaiOrder) -> OffsetDateTime {
     left.created_at.max(right.created_at)
 }
@@ -289,12 +905,17 @@ impl Match {
     }
 }
 
-#[tracing::instrument(level = "debug", fields(left = %left.id, right = %right.id, %reserved_left, %reserved_right))]
+/// Matches two orders for the quantity they have in common.
+///
+/// Only the quantities that are not reserved yet can be matched. Matches whose
+/// quantity falls below `min_fill` are rejected.
+#[tracing::instrument(level = "debug", fields(left = %left.id, right = %right.id, %reserved_left, %reserved_right, %min_fill))]
 fn match_orders(
     left: &BtcDaiOrder,
     right: &BtcDaiOrder,
     reserved_left: &asset::Bitcoin,
     reserved_right: &asset::Bitcoin,
+    min_fill: &asset::Bitcoin,
 ) -> Option<InternalMatch> {
     use Position::*;
 
@@ -322,21 +943,32 @@ fn match_orders(
         return None;
     }
 
-    // TODO: partial order matching
-    if left.quantity != right.quantity {
-        tracing::trace!("orders with different quantities don't match");
-        return None;
-    }
-
-    let remaining_left = left.quantity.to_inner() - *reserved_left;
-    let remaining_right = right.quantity.to_inner() - *reserved_right;
+    let remaining_left = left
+        .quantity
+        .to_inner()
+        .checked_sub(*reserved_left)
+        .unwrap_or(asset::Bitcoin::ZERO);
+    let remaining_right = right
+        .quantity
+        .to_inner()
+        .checked_sub(*reserved_right)
+        .unwrap_or(asset::Bitcoin::ZERO);
 
     if remaining_left == asset::Bitcoin::ZERO || remaining_right == asset::Bitcoin::ZERO {
         tracing::trace!("cannot fill order because of existing reserved funds");
         return None;
     }
 
-    let quantity = remaining_left;
+    let quantity = remaining_left.min(remaining_right);
+
+    if quantity < *min_fill {
+        tracing::trace!(
+            "overlapping quantity {} is below the minimum fill of {}",
+            quantity,
+            min_fill
+        );
+        return None;
+    }
 
     tracing::info!("matched with {} at price {}", quantity, price.wei_per_sat());
 
@@ -369,7 +1001,7 @@ mod tests {
         let buy = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), herc20_hbit());
         let sell = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), herc20_hbit());
 
-        let r#match = match_orders(&buy, &sell, &Bitcoin::ZERO, &Bitcoin::ZERO);
+        let r#match = match_orders(&buy, &sell, &Bitcoin::ZERO, &Bitcoin::ZERO, &Bitcoin::ZERO);
 
         assert_that(&r#match).is_some().is_equal_to(&InternalMatch {
             price: dai_per_btc(9000),
@@ -382,7 +1014,13 @@ mod tests {
         let sell_1 = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), herc20_hbit());
         let sell_2 = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), herc20_hbit());
 
-        let r#match = match_orders(&sell_1, &sell_2, &Bitcoin::ZERO, &Bitcoin::ZERO);
+        let r#match = match_orders(
+            &sell_1,
+            &sell_2,
+            &Bitcoin::ZERO,
+            &Bitcoin::ZERO,
+            &Bitcoin::ZERO,
+        );
 
         assert_that(&r#match).is_none();
     }
@@ -392,7 +1030,7 @@ mod tests {
         let sell = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), herc20_hbit());
         let buy = BtcDaiOrder::buy(btc(1.0), dai_per_btc(8500), herc20_hbit());
 
-        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO);
+        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO, &Bitcoin::ZERO);
 
         assert_that(&r#match).is_none();
     }
@@ -402,7 +1040,7 @@ mod tests {
         let sell = BtcDaiOrder::sell(btc(1.0), dai_per_btc(8500), herc20_hbit());
         let buy = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), herc20_hbit());
 
-        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO);
+        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO, &Bitcoin::ZERO);
 
         assert_that(&r#match).is_some().is_equal_to(&InternalMatch {
             price: dai_per_btc(8500),
@@ -410,13 +1048,31 @@ mod tests {
         });
     }
 
-    // only temporary until we take care of partial matching properly
     #[test]
-    fn given_different_quantities_then_no_match() {
+    fn given_different_quantities_then_matches_overlapping_quantity() {
         let sell = BtcDaiOrder::sell(btc(0.5), dai_per_btc(9000), herc20_hbit());
         let buy = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), herc20_hbit());
 
-        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO);
+        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO, &Bitcoin::ZERO);
+
+        assert_that(&r#match).is_some().is_equal_to(&InternalMatch {
+            price: dai_per_btc(9000),
+            quantity: btc(0.5),
+        });
+    }
+
+    #[test]
+    fn given_overlapping_quantity_below_min_fill_then_no_match() {
+        let sell = BtcDaiOrder::sell(btc(0.001), dai_per_btc(9000), herc20_hbit());
+        let buy = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), herc20_hbit());
+
+        let r#match = match_orders(
+            &sell,
+            &buy,
+            &Bitcoin::ZERO,
+            &Bitcoin::ZERO,
+            &btc(0.01).to_inner(),
+        );
 
         assert_that(&r#match).is_none();
     }
@@ -426,7 +1082,13 @@ mod tests {
         let sell = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), herc20_hbit());
         let buy = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), herc20_hbit());
 
-        let r#match = match_orders(&sell, &buy, &btc(0.5).to_inner(), &Bitcoin::ZERO);
+        let r#match = match_orders(
+            &sell,
+            &buy,
+            &btc(0.5).to_inner(),
+            &Bitcoin::ZERO,
+            &Bitcoin::ZERO,
+        );
 
         assert_that(&r#match).is_some().is_equal_to(&InternalMatch {
             price: dai_per_btc(9000),
@@ -439,7 +1101,13 @@ mod tests {
         let sell = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), herc20_hbit());
         let buy = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), herc20_hbit());
 
-        let r#match = match_orders(&sell, &buy, &Bitcoin::ONE_BTC, &Bitcoin::ZERO);
+        let r#match = match_orders(
+            &sell,
+            &buy,
+            &Bitcoin::ONE_BTC,
+            &Bitcoin::ZERO,
+            &Bitcoin::ZERO,
+        );
 
         assert_that(&r#match).is_none();
     }
@@ -449,7 +1117,7 @@ mod tests {
         let sell = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), herc20_hbit());
         let buy = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20());
 
-        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO);
+        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO, &Bitcoin::ZERO);
 
         assert_that(&r#match).is_none();
     }
@@ -465,7 +1133,7 @@ mod tests {
             herc20_expiry_offset: 1.hours().into(),
         });
 
-        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO);
+        let r#match = match_orders(&sell, &buy, &Bitcoin::ZERO, &Bitcoin::ZERO, &Bitcoin::ZERO);
 
         assert_that(&r#match).is_none();
     }
@@ -500,11 +1168,14 @@ mod tests {
             left in proptest::order::btc_dai_order(),
             right in proptest::order::btc_dai_order(),
             reserved_left in proptest::asset::bitcoin(),
-            reserved_right in proptest::asset::bitcoin()
+            reserved_right in proptest::asset::bitcoin(),
+            min_fill in proptest::asset::bitcoin()
         ) {
 
-            let first_match = match_orders(&left, &right, &reserved_left, &reserved_right);
-            let second_match = match_orders(&right, &left, &reserved_right, &reserved_left);
+            let first_match =
+                match_orders(&left, &right, &reserved_left, &reserved_right, &min_fill);
+            let second_match =
+                match_orders(&right, &left, &reserved_right, &reserved_left, &min_fill);
 
             assert_eq!(first_match, second_match);
         }
@@ -515,10 +1186,9 @@ mod tests {
         let mut pool = OrderPool::new(PeerId::random());
 
         pool.publish(BtcDaiOrder::buy(btc(0.5), dai_per_btc(9000), hbit_herc20()));
-        pool.receive(PeerId::random(), vec![BtcDaiOrder::sell(
-            btc(0.5),
-            dai_per_btc(9000),
-            hbit_herc20(),
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.5), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
         )]);
 
         let matches_1 = pool.matches();
@@ -535,10 +1205,9 @@ mod tests {
 
         let our_order = BtcDaiOrder::buy(btc(0.5), dai_per_btc(9000), hbit_herc20());
         pool.publish(our_order.clone());
-        pool.receive(PeerId::random(), vec![BtcDaiOrder::sell(
-            btc(0.5),
-            dai_per_btc(9000),
-            hbit_herc20(),
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.5), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
         )]);
         pool.matches();
 
@@ -548,6 +1217,425 @@ mod tests {
         assert_that(&pool.ours().next()).is_none();
     }
 
+    #[test]
+    fn given_partial_match_when_notified_about_successful_swap_then_remainder_stays_in_pool() {
+        let mut pool = OrderPool::new(PeerId::random());
+
+        let our_order = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(our_order.clone());
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.4), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+        let matches = pool.matches();
+
+        pool.notify_swap_setup_successful(our_order.id, btc(0.4))
+            .unwrap();
+
+        assert_that(&matches).matching_contains(|m| m.quantity == btc(0.4));
+        assert_that(&pool.ours().next())
+            .is_some()
+            .map(|order| &order.quantity)
+            .is_equal_to(&btc(0.6));
+    }
+
+    #[test]
+    fn given_partial_match_when_remainder_is_below_min_fill_then_order_is_closed() {
+        let mut pool = OrderPool::new(PeerId::random());
+        pool.set_min_fill(btc(0.1).to_inner());
+
+        let our_order = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(our_order.clone());
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.95), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+        pool.matches();
+
+        pool.notify_swap_setup_successful(our_order.id, btc(0.95))
+            .unwrap();
+
+        assert_that(&pool.ours().next()).is_none();
+        assert_that(&pool.take_order_updates()).contains(OrderUpdate::Removed(our_order.id));
+    }
+
+    #[test]
+    fn given_a_reservation_when_notified_about_another_quantity_then_order_is_untouched() {
+        let mut pool = OrderPool::new(PeerId::random());
+
+        let our_order = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(our_order.clone());
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.4), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+        pool.matches();
+
+        let result = pool.notify_swap_setup_successful(our_order.id, btc(0.5));
+
+        assert!(result.unwrap_err().is::<UnreservedQuantity>());
+        assert_that(&pool.ours().next())
+            .is_some()
+            .map(|order| &order.quantity)
+            .is_equal_to(&btc(1.0));
+
+        pool.notify_swap_setup_successful(our_order.id, btc(0.4))
+            .unwrap();
+    }
+
+    #[test]
+    fn given_failed_swap_setup_then_reservation_is_released() {
+        let mut pool = OrderPool::new(PeerId::random());
+
+        let our_order = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(our_order.clone());
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.4), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+        pool.matches();
+        pool.take_reservation_updates();
+
+        pool.notify_swap_setup_failed(our_order.id, btc(0.4))
+            .unwrap();
+
+        assert!(pool.reserved.get(&our_order.id).is_none());
+        assert_that(&pool.take_reservation_updates()).has_length(1);
+        assert!(pool
+            .notify_swap_setup_failed(our_order.id, btc(0.4))
+            .unwrap_err()
+            .is::<UnreservedQuantity>());
+    }
+
+    #[test]
+    fn orders_below_min_fill_are_neither_published_nor_taken() {
+        let mut pool = OrderPool::new(PeerId::random());
+        pool.set_min_fill(btc(0.1).to_inner());
+        let their_order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.receive(PeerId::random(), vec![(their_order.clone(), in_one_hour())]);
+
+        pool.publish(BtcDaiOrder::buy(btc(0.05), dai_per_btc(9000), hbit_herc20()));
+        let result = pool.take(their_order.id, Some(btc(0.05)));
+
+        assert_that(&pool.ours().next()).is_none();
+        assert_that(&result.unwrap_err().is::<BelowMinFill>()).is_true();
+    }
+
+    #[test]
+    fn given_expired_order_then_no_match() {
+        let mut pool = OrderPool::new(PeerId::random());
+
+        pool.publish(BtcDaiOrder::buy(btc(0.5), dai_per_btc(9000), hbit_herc20()));
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.5), dai_per_btc(9000), hbit_herc20()),
+            OffsetDateTime::now_utc() - 1.seconds(),
+        )]);
+
+        let matches = pool.matches();
+
+        assert_that(&matches).has_length(0);
+        assert_that(&pool.theirs().next()).is_none();
+    }
+
+    #[test]
+    fn remove_expired_keeps_our_orders_and_fresh_orders() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let now = OffsetDateTime::now_utc();
+
+        pool.publish(BtcDaiOrder::buy(btc(0.5), dai_per_btc(9000), hbit_herc20()));
+        pool.receive(PeerId::random(), vec![
+            (
+                BtcDaiOrder::sell(btc(0.5), dai_per_btc(9000), hbit_herc20()),
+                now - 1.seconds(),
+            ),
+            (
+                BtcDaiOrder::sell(btc(0.5), dai_per_btc(9100), hbit_herc20()),
+                now + 1.minutes(),
+            ),
+        ]);
+
+        pool.remove_expired(now);
+
+        assert_that(&pool.ours().count()).is_equal_to(1);
+        assert_that(&pool.theirs().count()).is_equal_to(1);
+    }
+
+    #[test]
+    fn given_reservation_announced_by_maker_then_only_matches_remaining_quantity() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let maker = PeerId::random();
+        let their_order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+
+        pool.publish(BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20()));
+        pool.receive(maker.clone(), vec![(their_order.clone(), in_one_hour())]);
+        pool.receive_reservation(&maker, Reservation {
+            order: their_order.id,
+            quantity: btc(0.4).to_inner(),
+            expires_at: in_one_hour(),
+        });
+
+        let matches = pool.matches();
+
+        assert_that(&matches).matching_contains(|m| m.quantity == btc(0.6));
+    }
+
+    #[test]
+    fn given_reservation_announced_by_someone_else_than_the_maker_then_ignores_it() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let their_order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+
+        pool.publish(BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20()));
+        pool.receive(PeerId::random(), vec![(their_order.clone(), in_one_hour())]);
+        pool.receive_reservation(&PeerId::random(), Reservation {
+            order: their_order.id,
+            quantity: btc(1.0).to_inner(),
+            expires_at: in_one_hour(),
+        });
+
+        let matches = pool.matches();
+
+        assert_that(&matches).matching_contains(|m| m.quantity == btc(1.0));
+    }
+
+    #[test]
+    fn given_a_match_then_announces_and_releases_reservation_of_our_order() {
+        let mut pool = OrderPool::new(PeerId::random());
+
+        let our_order = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(our_order.clone());
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.4), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+
+        pool.matches();
+        let reserved = pool.take_reservation_updates();
+        pool.notify_swap_setup_successful(our_order.id, btc(0.4))
+            .unwrap();
+        let released = pool.take_reservation_updates();
+
+        assert_that(&reserved)
+            .matching_contains(|r| r.order == our_order.id && r.quantity == btc(0.4).to_inner());
+        assert_that(&released)
+            .matching_contains(|r| r.order == our_order.id && r.quantity == Bitcoin::ZERO);
+    }
+
+    #[test]
+    fn given_swap_setup_does_not_complete_then_reservation_times_out() {
+        let mut pool = OrderPool::new(PeerId::random());
+
+        pool.publish(BtcDaiOrder::buy(btc(0.5), dai_per_btc(9000), hbit_herc20()));
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.5), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+
+        let matches_1 = pool.matches();
+        pool.remove_expired(OffsetDateTime::now_utc() + RESERVATION_TIMEOUT);
+        let matches_2 = pool.matches();
+
+        assert_that(&matches_1).has_length(1);
+        assert_that(&matches_2).has_length(1);
+    }
+
+    #[test]
+    fn changes_to_our_orders_are_taken_as_updates() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let published = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        let cancelled = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9500), hbit_herc20());
+        pool.publish(cancelled.clone());
+        pool.take_order_updates();
+
+        pool.publish(published.clone());
+        pool.cancel(cancelled.id);
+        let updates = pool.take_order_updates();
+
+        assert_that(&updates).contains(OrderUpdate::Published(published));
+        assert_that(&updates).contains(OrderUpdate::Removed(cancelled.id));
+        assert_that(&pool.take_order_updates()).has_length(0);
+    }
+
+    #[test]
+    fn pushed_orders_are_added_and_removed_only_by_their_maker() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let maker = PeerId::random();
+        let order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+
+        pool.receive_order(maker.clone(), order.clone(), in_one_hour());
+        pool.remove_order(&PeerId::random(), order.id);
+        let after_foreign_removal = pool.theirs().count();
+        pool.remove_order(&maker, order.id);
+
+        assert_that(&after_foreign_removal).is_equal_to(1);
+        assert_that(&pool.theirs().next()).is_none();
+    }
+
+    #[test]
+    fn orders_of_blocked_makers_are_dropped_and_ignored() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let maker = PeerId::random();
+        pool.publish(BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20()));
+        pool.receive(maker.clone(), vec![(
+            BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+
+        pool.block(maker.clone());
+        pool.receive_order(
+            maker.clone(),
+            BtcDaiOrder::sell(btc(0.5), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        );
+
+        assert_that(&pool.theirs().next()).is_none();
+        assert_that(&pool.matches()).has_length(0);
+
+        pool.unblock(&maker);
+        pool.receive(maker, vec![(
+            BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+
+        assert_that(&pool.matches()).has_length(1);
+    }
+
+    #[test]
+    fn given_their_order_when_taking_it_then_reserves_it_for_an_opposite_order() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let maker = PeerId::random();
+        let their_order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.receive(maker.clone(), vec![(their_order.clone(), in_one_hour())]);
+
+        let (our_order, r#match) = pool.take(their_order.id, Some(btc(0.4))).unwrap();
+
+        assert_that(&our_order.position).is_equal_to(Position::Buy);
+        assert_that(&our_order.price).is_equal_to(dai_per_btc(9000));
+        assert_that(&our_order.quantity).is_equal_to(btc(0.4));
+        assert_that(&r#match.peer).is_equal_to(maker);
+        assert_that(&r#match.ours).is_equal_to(our_order.id);
+        assert_that(&r#match.theirs).is_equal_to(their_order.id);
+        assert_that(&pool.take(their_order.id, None))
+            .is_ok()
+            .map(|(order, _)| &order.quantity)
+            .is_equal_to(&btc(0.6));
+        assert_that(&pool.take(their_order.id, None)).is_err();
+        assert_that(&pool.ours().next()).is_none();
+    }
+
+    #[test]
+    fn our_own_orders_cannot_be_taken() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let our_order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(our_order.clone());
+
+        let result = pool.take(our_order.id, None);
+
+        assert_that(&result.map_err(|e| e.downcast::<UnknownOrder>().unwrap()))
+            .is_err_containing(UnknownOrder(our_order.id));
+    }
+
+    #[test]
+    fn given_taker_offers_our_price_then_reserves_quantity_of_our_order() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let our_order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(our_order.clone());
+        let now = OffsetDateTime::now_utc();
+
+        let r#match = pool.reserve_for_taker(
+            PeerId::random(),
+            our_order.id,
+            OrderId::random(),
+            btc(0.4),
+            &(btc(0.4) * dai_per_btc(9000)),
+            now,
+        );
+        let too_much = pool.reserve_for_taker(
+            PeerId::random(),
+            our_order.id,
+            OrderId::random(),
+            btc(0.7),
+            &(btc(0.7) * dai_per_btc(9000)),
+            now,
+        );
+
+        assert_that(&r#match)
+            .is_ok()
+            .map(|m| &m.quantity)
+            .is_equal_to(&btc(0.4));
+        assert_that(&too_much).is_err();
+        assert_that(&pool.take_reservation_updates())
+            .matching_contains(|r| r.order == our_order.id && r.quantity == btc(0.4).to_inner());
+    }
+
+    #[test]
+    fn given_taker_offers_less_than_our_price_then_does_not_reserve() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let our_order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(our_order.clone());
+
+        let r#match = pool.reserve_for_taker(
+            PeerId::random(),
+            our_order.id,
+            OrderId::random(),
+            btc(1.0),
+            &(btc(1.0) * dai_per_btc(8500)),
+            OffsetDateTime::now_utc(),
+        );
+
+        assert_that(&r#match).is_err();
+        assert_that(&pool.take_reservation_updates()).has_length(0);
+    }
+
+    #[test]
+    fn given_our_order_when_amended_then_keeps_id_and_pushes_update() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let order = BtcDaiOrder::sell(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(order.clone());
+        pool.take_order_updates();
+
+        let previous = pool
+            .amend(order.id, Some(dai_per_btc(9100)), Some(btc(0.5)))
+            .unwrap();
+        let amended = pool.ours().next().cloned().unwrap();
+
+        assert_that(&previous).is_equal_to(&order);
+        assert_that(&amended.id).is_equal_to(order.id);
+        assert_that(&amended.price).is_equal_to(dai_per_btc(9100));
+        assert_that(&amended.quantity).is_equal_to(btc(0.5));
+        assert_that(&pool.take_order_updates()).contains(OrderUpdate::Published(amended));
+    }
+
+    #[test]
+    fn given_reserved_quantity_then_order_cannot_be_reduced_below_it() {
+        let mut pool = OrderPool::new(PeerId::random());
+        let order = BtcDaiOrder::buy(btc(1.0), dai_per_btc(9000), hbit_herc20());
+        pool.publish(order.clone());
+        pool.receive(PeerId::random(), vec![(
+            BtcDaiOrder::sell(btc(0.6), dai_per_btc(9000), hbit_herc20()),
+            in_one_hour(),
+        )]);
+        pool.matches();
+
+        let below_reserved = pool.amend(order.id, None, Some(btc(0.5)));
+        let above_reserved = pool.amend(order.id, None, Some(btc(0.7)));
+
+        assert_that(&below_reserved.map_err(|e| e.downcast::<InvalidAmendment>().unwrap()))
+            .is_err_containing(InvalidAmendment::BelowReserved {
+                order: order.id,
+                requested: btc(0.5).to_inner(),
+                reserved: btc(0.6).to_inner(),
+            });
+        assert_that(&above_reserved).is_ok();
+        assert_that(&pool.ours().next())
+            .is_some()
+            .map(|order| &order.quantity)
+            .is_equal_to(&btc(0.7));
+    }
+
+    fn in_one_hour() -> OffsetDateTime {
+        OffsetDateTime::now_utc() + 1.hours()
+    }
+
     fn hbit_herc20() -> SwapProtocol {
         SwapProtocol::HbitHerc20 {
             hbit_expiry_offset: 0.seconds().into(),
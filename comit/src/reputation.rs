@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+/// How a peer behaved in the swaps and protocols we ran with them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Reputation {
+    /// Swaps that were redeemed.
+    pub completed: u32,
+    /// Swaps that were refunded.
+    pub refunded: u32,
+    /// Swaps that were set up but never funded before they expired.
+    pub expired: u32,
+    /// Swaps that failed for any other reason.
+    pub failed: u32,
+    /// Malformed messages or invalid orders the peer sent us.
+    pub violations: u32,
+    /// Whether the peer was banned explicitly, regardless of its score.
+    pub banned: bool,
+}
+
+/// Something a peer did that affects its reputation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum_macros::Display, strum_macros::EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum Outcome {
+    Completed,
+    Refunded,
+    Expired,
+    Failed,
+    ProtocolViolation,
+}
+
+impl Reputation {
+    pub fn record(&mut self, outcome: Outcome) {
+        let counter = match outcome {
+            Outcome::Completed => &mut self.completed,
+            Outcome::Refunded => &mut self.refunded,
+            Outcome::Expired => &mut self.expired,
+            Outcome::Failed => &mut self.failed,
+            Outcome::ProtocolViolation => &mut self.violations,
+        };
+
+        *counter = counter.saturating_add(1);
+    }
+
+    /// A single number summarizing the reputation, higher is better.
+    ///
+    /// Every completed swap counts one point. Refunded and expired swaps cost
+    /// two points because they lock up our funds until the HTLCs expire.
+    /// Failed swaps and protocol violations cost one point, they may as well
+    /// be caused by bugs or network issues.
+    pub fn score(&self) -> i64 {
+        i64::from(self.completed)
+            - 2 * i64::from(self.refunded)
+            - 2 * i64::from(self.expired)
+            - i64::from(self.failed)
+            - i64::from(self.violations)
+    }
+
+    /// Whether we are willing to trade with a peer of this reputation.
+    ///
+    /// Banned peers are never acceptable, all others are acceptable if there
+    /// is no minimum score or their score reaches it.
+    pub fn is_acceptable(&self, min_score: Option<i64>) -> bool {
+        !self.banned && min_score.map_or(true, |min_score| self.score() >= min_score)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refunds_weigh_more_than_completed_swaps() {
+        let mut reputation = Reputation::default();
+
+        reputation.record(Outcome::Completed);
+        reputation.record(Outcome::Completed);
+        reputation.record(Outcome::Refunded);
+        reputation.record(Outcome::ProtocolViolation);
+
+        assert_eq!(reputation.score(), -1);
+        assert!(reputation.is_acceptable(Some(-1)));
+        assert!(!reputation.is_acceptable(Some(0)));
+    }
+
+    #[test]
+    fn banned_peers_are_never_acceptable() {
+        let reputation = Reputation {
+            completed: 100,
+            banned: true,
+            ..Reputation::default()
+        };
+
+        assert!(!reputation.is_acceptable(None));
+    }
+}
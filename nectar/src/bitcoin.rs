@@ -8,6 +8,10 @@ pub use wallet::Wallet;
 
 pub const SATS_IN_BITCOIN_EXP: u16 = 8;
 
+/// The number of blocks within which we want our redeem and refund
+/// transactions to be confirmed, unless configured otherwise.
+pub const DEFAULT_FEE_RATE_TARGET_BLOCKS: u16 = 6;
+
 #[cfg(test)]
 pub mod amount {
     use super::*;
@@ -302,6 +302,38 @@ impl Client {
         Ok(txid)
     }
 
+    pub async fn get_transaction(
+        &self,
+        wallet_name: &str,
+        txid: Txid,
+    ) -> anyhow::Result<GetTransactionResponse> {
+        self.rpc_client
+            .send_with_path(
+                format!("/wallet/{}", wallet_name),
+                jsonrpc::Request::new(
+                    "gettransaction",
+                    vec![jsonrpc::serialize(txid)?],
+                    JSONRPC_VERSION.into(),
+                ),
+            )
+            .await
+            .context("failed to get transaction")
+    }
+
+    pub async fn estimate_smart_fee(
+        &self,
+        conf_target: u16,
+    ) -> anyhow::Result<EstimateSmartFeeResponse> {
+        self.rpc_client
+            .send(jsonrpc::Request::new(
+                "estimatesmartfee",
+                vec![jsonrpc::serialize(conf_target)?],
+                JSONRPC_VERSION.into(),
+            ))
+            .await
+            .context("failed to estimate smart fee")
+    }
+
     #[cfg(test)]
     pub async fn dump_wallet(
         &self,
@@ -401,6 +433,19 @@ struct BlockchainInfo {
 #[derive(Debug, Deserialize)]
 pub struct BlockHash(String);
 
+#[derive(Debug, Deserialize)]
+pub struct GetTransactionResponse {
+    /// Negative if the transaction conflicts with a confirmed one.
+    pub confirmations: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EstimateSmartFeeResponse {
+    /// Estimated fee rate in BTC/kvB, absent if bitcoind lacks the data.
+    pub feerate: Option<f64>,
+    pub errors: Option<Vec<String>>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateWalletResponse {
     name: String,
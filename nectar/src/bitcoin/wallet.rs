@@ -9,6 +9,7 @@ use ::bitcoin::{
     PrivateKey, Transaction, Txid,
 };
 use bitcoin::{util::bip32::DerivationPath, OutPoint};
+use comit::bitcoin::{NoFeeRateEstimate, SatPerVbyte};
 use std::str::FromStr;
 use url::Url;
 
@@ -252,6 +253,30 @@ impl Wallet {
         Ok(txid)
     }
 
+    /// The number of confirmations of a transaction relevant to this wallet.
+    pub async fn transaction_confirmations(&self, txid: Txid) -> anyhow::Result<i64> {
+        let response = self
+            .bitcoind_client
+            .get_transaction(&self.name, txid)
+            .await?;
+
+        Ok(response.confirmations)
+    }
+
+    /// Estimates the fee rate needed for a transaction to confirm within
+    /// `target_blocks` blocks.
+    pub async fn estimate_fee_rate(&self, target_blocks: u16) -> anyhow::Result<SatPerVbyte> {
+        let response = self
+            .bitcoind_client
+            .estimate_smart_fee(target_blocks)
+            .await?;
+        let btc_per_kvb = response
+            .feerate
+            .ok_or_else(|| NoFeeRateEstimate(target_blocks, response.errors.unwrap_or_default()))?;
+
+        SatPerVbyte::from_btc_per_kvb(btc_per_kvb)
+    }
+
     #[cfg(test)]
     pub async fn dump(&self, filename: &std::path::Path) -> anyhow::Result<()> {
         self.bitcoind_client.dump_wallet(&self.name, filename).await
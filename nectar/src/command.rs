@@ -3,6 +3,7 @@ use structopt::StructOpt;
 
 mod balance;
 mod deposit;
+mod reputation;
 mod resume_only;
 mod trade;
 mod wallet_info;
@@ -17,11 +18,13 @@ use crate::{
     swap::SwapKind,
 };
 use chrono::{DateTime, Utc};
+use libp2p::PeerId;
 use num::BigUint;
 use std::str::FromStr;
 
 pub use balance::balance;
 pub use deposit::deposit;
+pub use reputation::{ban, reputation};
 pub use resume_only::resume_only;
 pub use trade::trade;
 pub use wallet_info::wallet_info;
@@ -64,6 +67,12 @@ pub enum Command {
     Withdraw(Withdraw),
     /// Only resume ongoing swaps, do not publish or accept new orders
     ResumeOnly,
+    /// Print the reputation of all takers we know, nectar must not be running
+    Reputation,
+    /// Never trade with this taker again, nectar must not be running
+    Ban { peer: PeerId },
+    /// Trade with a banned taker again, nectar must not be running
+    Unban { peer: PeerId },
 }
 
 pub fn dump_config(settings: Settings) -> anyhow::Result<()> {
@@ -0,0 +1,59 @@
+use crate::{config::Settings, swap::Database};
+use libp2p::PeerId;
+
+pub fn reputation(settings: &Settings) -> anyhow::Result<String> {
+    let db = open(settings)?;
+
+    let mut reputations = db.reputations()?;
+    reputations.sort_by_key(|(_, reputation)| std::cmp::Reverse(reputation.score()));
+
+    let mut lines = vec![
+        "Peer id | Score | Completed | Refunded | Expired | Failed | Violations | Banned"
+            .to_string(),
+    ];
+    lines.extend(reputations.into_iter().map(|(peer, reputation)| {
+        format!(
+            "{} | {} | {} | {} | {} | {} | {} | {}",
+            peer,
+            reputation.score(),
+            reputation.completed,
+            reputation.refunded,
+            reputation.expired,
+            reputation.failed,
+            reputation.violations,
+            reputation.banned
+        )
+    }));
+
+    Ok(lines.join("\n"))
+}
+
+pub async fn ban(settings: &Settings, peer: PeerId, banned: bool) -> anyhow::Result<String> {
+    let db = open(settings)?;
+    let reputation = db.set_banned(&peer, banned).await?;
+
+    Ok(format!(
+        "{} {}, score: {}",
+        if reputation.banned {
+            "Banned"
+        } else {
+            "Unbanned"
+        },
+        peer,
+        reputation.score()
+    ))
+}
+
+/// The database can only be opened by one process at a time, hence nectar must
+/// not be running while using these commands.
+fn open(settings: &Settings) -> anyhow::Result<Database> {
+    #[cfg(not(test))]
+    let db = Database::new(&settings.data.dir.join("database"))?;
+    #[cfg(test)]
+    let db = {
+        let _ = settings;
+        Database::new_test()?
+    };
+
+    Ok(db)
+}
@@ -25,9 +25,10 @@ pub async fn resume_only(
     let (executor, mut finished_swap_receiver) = SwapExecutor::new(
         db.clone(),
         Arc::new(bitcoin_wallet),
-        Arc::new(ethereum_wallet),
+        Arc::new(ethereum_wallet.with_database(Arc::clone(&db))),
         Arc::new(BitcoindConnector::new(settings.bitcoin.bitcoind.node_url)?),
         Arc::new(Web3Connector::new(settings.ethereum.node_url)),
+        settings.bitcoin.fee_rate_target_blocks,
     );
 
     for swap in db.all_swaps()? {
@@ -27,8 +27,13 @@ pub async fn trade(
     ethereum_wallet: ethereum::Wallet,
     network: comit::Network,
 ) -> anyhow::Result<()> {
+    #[cfg(not(test))]
+    let db = Arc::new(Database::new(&settings.data.dir.join("database"))?);
+    #[cfg(test)]
+    let db = Arc::new(Database::new_test()?);
+
     let bitcoin_wallet = Arc::new(bitcoin_wallet);
-    let ethereum_wallet = Arc::new(ethereum_wallet);
+    let ethereum_wallet = Arc::new(ethereum_wallet.with_database(Arc::clone(&db)));
 
     let mut maker = init_maker(
         Arc::clone(&bitcoin_wallet),
@@ -39,12 +44,13 @@ pub async fn trade(
     .await
     .context("Could not initialise Maker")?;
 
-    #[cfg(not(test))]
-    let db = Arc::new(Database::new(&settings.data.dir.join("database"))?);
-    #[cfg(test)]
-    let db = Arc::new(Database::new_test()?);
-
     let mut swarm = new_swarm(network::Seed::new(seed.bytes()), &settings)?;
+    if let Some(min_fill_sats) = settings.network.min_fill_sats {
+        swarm
+            .orderbook
+            .orderpool_mut()
+            .set_min_fill(comit::asset::Bitcoin::from_sat(min_fill_sats));
+    }
 
     let initial_sell_order = maker
         .new_sell_order()
@@ -83,6 +89,7 @@ pub async fn trade(
         Arc::clone(&ethereum_wallet),
         bitcoin_connector,
         ethereum_connector,
+        settings.bitcoin.fee_rate_target_blocks,
     );
 
     respawn_swaps(Arc::clone(&db), &mut maker, swap_executor.clone())
@@ -90,6 +97,12 @@ pub async fn trade(
 
     let history = History::new(settings.data.dir.join("history.csv").as_path())?;
 
+    for (peer, reputation) in db.reputations()? {
+        if !reputation.is_acceptable(settings.network.min_reputation) {
+            swarm.orderbook.block(peer);
+        }
+    }
+
     let event_loop = EventLoop::new(
         maker,
         swarm,
@@ -98,6 +111,7 @@ pub async fn trade(
         bitcoin_wallet,
         ethereum_wallet,
         swap_executor,
+        settings.network.min_reputation,
     );
 
     event_loop
@@ -307,6 +321,9 @@ mod tests {
                 listen: vec!["/ip4/98.97.96.95/tcp/20500"
                     .parse()
                     .expect("invalid multiaddr")],
+                min_reputation: None,
+                min_fill_sats: None,
+                discovery: Default::default(),
             },
             data: Data {
                 dir: Default::default(),
@@ -321,6 +338,7 @@ mod tests {
                     ChainId::GETH_DEV,
                     ethereum_blockchain.token_contract(),
                 ),
+                gas_policy: ethereum::GasPolicy::default(),
             },
         };
 
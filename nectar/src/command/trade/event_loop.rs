@@ -1,12 +1,13 @@
 use crate::{
     bitcoin,
     command::{into_history_trade, FinishedSwap},
+    database::{Commitment, Load},
     ethereum::{self, dai},
     history::History,
     maker::{PublishOrders, TakeRequestDecision},
     network::{self, ActivePeer, SetupSwapContext, Swarm},
     order::BtcDaiOrderForm,
-    swap::{hbit, Database, SwapExecutor, SwapKind, SwapParams},
+    swap::{hbit, herc20, Database, SwapExecutor, SwapKind, SwapParams},
     Maker, MidMarketRate, SwapId,
 };
 use anyhow::{bail, Context, Result};
@@ -14,14 +15,16 @@ use chrono::{NaiveDateTime, Utc};
 use comit::{
     identity,
     network::{
-        orderbook,
-        setup_swap::{self, BobParams, CommonParams, RoleDependentParams},
+        cancel_swap, discovery, orderbook,
+        setup_swap::{self, BobParams, CommonParams, NegotiationId, RoleDependentParams},
     },
     order::SwapProtocol,
     orderpool::Match,
-    Position,
+    reputation::Outcome,
+    Position, Quantity, SecretHash, Timestamp,
 };
 use futures::{channel::mpsc::Receiver, FutureExt, StreamExt};
+use libp2p::PeerId;
 use std::sync::Arc;
 
 pub(super) struct EventLoop {
@@ -32,9 +35,11 @@ pub(super) struct EventLoop {
     bitcoin_wallet: Arc<bitcoin::Wallet>,
     ethereum_wallet: Arc<ethereum::Wallet>,
     swap_executor: SwapExecutor,
+    min_reputation: Option<i64>,
 }
 
 impl EventLoop {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         maker: Maker,
         swarm: Swarm,
@@ -43,6 +48,7 @@ impl EventLoop {
         bitcoin_wallet: Arc<bitcoin::Wallet>,
         ethereum_wallet: Arc<ethereum::Wallet>,
         swap_executor: SwapExecutor,
+        min_reputation: Option<i64>,
     ) -> Self {
         Self {
             maker,
@@ -52,6 +58,7 @@ impl EventLoop {
             bitcoin_wallet,
             ethereum_wallet,
             swap_executor,
+            min_reputation,
         }
     }
 
@@ -197,6 +204,16 @@ impl EventLoop {
             network::BehaviourOutEvent::SetupSwap(event) => {
                 self.handle_setup_swap_event(event).await
             }
+            network::BehaviourOutEvent::CancelSwap(event) => {
+                self.handle_cancel_swap_event(event).await
+            }
+            network::BehaviourOutEvent::Discovery(discovery::BehaviourOutEvent::Discovered {
+                peer,
+                ..
+            }) => {
+                self.swarm.orderbook.connect(peer);
+                Ok(())
+            }
         }
     }
 
@@ -207,6 +224,25 @@ impl EventLoop {
         match event {
             setup_swap::BehaviourOutEvent::ExecutableSwap(exec_swap) => {
                 let swap_id = exec_swap.context.swap_id;
+
+                // The swap is set up, hence what we reserved of our order for it
+                // is filled now.
+                if let Err(e) = self
+                    .swarm
+                    .orderbook
+                    .orderpool_mut()
+                    .notify_swap_setup_successful(
+                        exec_swap.context.order,
+                        Quantity::new(exec_swap.hbit.asset),
+                    )
+                {
+                    tracing::error!(
+                        "failed to notify orderpool about successful setup of swap {}: {:#}",
+                        swap_id,
+                        e
+                    );
+                }
+
                 let start_of_swap = chrono::DateTime::from_utc(
                     NaiveDateTime::from_timestamp(exec_swap.context.match_ref_point.timestamp(), 0),
                     Utc,
@@ -242,7 +278,185 @@ impl EventLoop {
             setup_swap::BehaviourOutEvent::AlreadyHaveRoleParams { peer, .. } => {
                 bail!("already received role params from {}", peer)
             }
+            setup_swap::BehaviourOutEvent::TakeRequested {
+                peer,
+                take,
+                common,
+                swap_protocol,
+                ..
+            } => {
+                // Our orders are only filled by matching them, tell the taker
+                // instead of letting it wait for the negotiation to time out.
+                tracing::warn!("declining request of {} to take order {}", peer, take.order);
+                self.swarm.setup_swap.decline(
+                    &peer,
+                    take.into(),
+                    &common,
+                    swap_protocol,
+                    "orders of this maker are only filled by matching",
+                );
+            }
+            setup_swap::BehaviourOutEvent::ExpiriesRejected {
+                peer,
+                rejected_by,
+                reason,
+                context,
+            } => {
+                self.abandon_setup_swap(&peer, &context).await?;
+
+                bail!(
+                    "failed to set up swap {} with {}, {} rejected the expiries: {}",
+                    context.swap_id,
+                    peer,
+                    rejected_by,
+                    reason
+                )
+            }
+            setup_swap::BehaviourOutEvent::TimedOut { peer, context } => {
+                self.abandon_setup_swap(&peer, &context).await?;
+
+                bail!(
+                    "failed to set up swap {} with {}, the negotiation timed out",
+                    context.swap_id,
+                    peer
+                )
+            }
+            setup_swap::BehaviourOutEvent::Declined {
+                peer,
+                reason,
+                context,
+            } => {
+                self.abandon_setup_swap(&peer, &context).await?;
+
+                bail!(
+                    "failed to set up swap {} with {}, they declined: {}",
+                    context.swap_id,
+                    peer,
+                    reason
+                )
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Undo what we did for a swap that was never set up.
+    async fn abandon_setup_swap(
+        &mut self,
+        peer: &PeerId,
+        context: &SetupSwapContext,
+    ) -> Result<()> {
+        self.maker
+            .free_funds(context.reserved_dai.clone(), context.reserved_btc);
+
+        self.database
+            .remove_active_peer(&ActivePeer {
+                peer_id: peer.clone(),
+            })
+            .await
+            .context("Failed to remove active peer")
+    }
+
+    async fn handle_cancel_swap_event(
+        &mut self,
+        event: cancel_swap::BehaviourOutEvent,
+    ) -> Result<()> {
+        match event {
+            cancel_swap::BehaviourOutEvent::Cancelled { peer, secret_hash } => {
+                let result = self.handle_cancelled_swap(peer.clone(), secret_hash).await;
+                let response = match &result {
+                    Ok(()) => cancel_swap::Response::Accepted,
+                    Err(e) => cancel_swap::Response::Rejected {
+                        reason: e.to_string(),
+                    },
+                };
+                self.swarm.cancel_swap.respond(&peer, secret_hash, response);
+
+                result
+            }
+            cancel_swap::BehaviourOutEvent::ProtocolViolation { peer } => {
+                let reputation = self
+                    .database
+                    .record_outcome(&peer, Outcome::ProtocolViolation)
+                    .await
+                    .with_context(|| format!("could not record protocol violation of {}", peer))?;
+
+                if !reputation.is_acceptable(self.min_reputation) {
+                    self.swarm.orderbook.block(peer);
+                }
+
+                Ok(())
+            }
         }
+    }
+
+    async fn handle_cancelled_swap(&mut self, peer: PeerId, secret_hash: SecretHash) -> Result<()> {
+        let swap = self
+            .database
+            .all_swaps()?
+            .into_iter()
+            .find(|swap| {
+                let params = swap.params();
+                params.secret_hash == secret_hash && params.taker.peer_id() == peer
+            })
+            .with_context(|| format!("{} cancelled a swap we do not know about", peer))?;
+        let swap_id = swap.swap_id();
+
+        // We only act on the beta ledger once the taker funded the alpha HTLC,
+        // hence having done so means it is too late to cancel the swap.
+        let beta_acted_on = match swap {
+            SwapKind::HbitHerc20(_) => {
+                Load::<herc20::Deployed>::load(self.database.as_ref(), swap_id)?.is_some()
+            }
+            SwapKind::Herc20Hbit(_) => {
+                Load::<hbit::Funded>::load(self.database.as_ref(), swap_id)?.is_some()
+            }
+        };
+        if beta_acted_on {
+            bail!(
+                "ignoring cancellation of swap {} from {}, it is already funded",
+                swap_id,
+                peer
+            );
+        }
+
+        // The swap might be about to broadcast our funding transaction, it
+        // has to keep running so we refund if need be.
+        let commitment = self
+            .database
+            .commit_swap_to(swap_id, Commitment::Cancellation)
+            .await
+            .with_context(|| format!("could not commit swap {} to its cancellation", swap_id))?;
+        if commitment == Commitment::Funding {
+            bail!(
+                "ignoring cancellation of swap {} from {}, we already started funding it",
+                swap_id,
+                peer
+            );
+        }
+
+        self.swap_executor.abort(swap_id);
+
+        let (dai, btc) = match swap {
+            SwapKind::HbitHerc20(params) => (Some(params.herc20_params.asset.into()), None),
+            SwapKind::Herc20Hbit(params) => (None, Some(params.hbit_params.shared.asset)),
+        };
+
+        self.database
+            .remove_swap(&swap_id)
+            .await
+            .context("Unable to delete swap from db")?;
+        self.database
+            .remove_active_peer(&ActivePeer {
+                peer_id: peer.clone(),
+            })
+            .await
+            .context("Unable to remove from active takers")?;
+
+        // Only free funds if the swap was removed from the db
+        self.maker.free_funds(dai, btc);
+
+        tracing::info!("swap {} was cancelled by {}", swap_id, peer);
 
         Ok(())
     }
@@ -257,8 +471,24 @@ impl EventLoop {
                 swap_protocol,
                 match_reference_point: match_ref_point,
                 ours,
+                theirs,
                 ..
             }) => {
+                let reputation = self
+                    .database
+                    .reputation(&peer)
+                    .with_context(|| format!("could not load reputation of taker {}", peer))?;
+
+                if !reputation.is_acceptable(self.min_reputation) {
+                    self.swarm.orderbook.block(peer.clone());
+                    bail!(
+                        "ignoring take order request from taker with score {}, taker: {}, order: {}",
+                        reputation.score(),
+                        peer,
+                        ours,
+                    );
+                }
+
                 let taker = ActivePeer {
                     peer_id: peer.clone(),
                 };
@@ -300,6 +530,10 @@ impl EventLoop {
                     identity::Bitcoin::from_secret_key(&crate::SECP, &bitcoin_transient_sk);
 
                 let erc20_quantity = quantity * price.clone();
+                let (reserved_dai, reserved_btc) = match our_position {
+                    Position::Buy => (Some(dai::Amount::from(erc20_quantity.clone())), None),
+                    Position::Sell => (None, Some(quantity.to_inner())),
+                };
 
                 let form = BtcDaiOrderForm {
                     position: our_position,
@@ -358,6 +592,10 @@ impl EventLoop {
                         }
                     };
 
+                #[allow(clippy::cast_sign_loss)]
+                #[allow(clippy::cast_possible_truncation)]
+                let start_of_swap = Timestamp::from(match_ref_point.timestamp() as u32);
+
                 let decision = self
                     .maker
                     .process_taken_order(form)
@@ -369,6 +607,7 @@ impl EventLoop {
                             .setup_swap
                             .send(
                                 &peer,
+                                NegotiationId::new(ours, theirs),
                                 RoleDependentParams::Bob(BobParams {
                                     bitcoin_identity,
                                     ethereum_identity,
@@ -385,10 +624,14 @@ impl EventLoop {
                                     bitcoin_network,
                                 },
                                 swap_protocol,
+                                start_of_swap,
                                 SetupSwapContext {
                                     swap_id,
+                                    order: ours,
                                     match_ref_point,
                                     bitcoin_transient_key_index: index,
+                                    reserved_dai,
+                                    reserved_btc,
                                 },
                             )
                             .context("Sending setup swap message yielded error")?;
@@ -407,6 +650,17 @@ impl EventLoop {
                     TakeRequestDecision::RateNotProfitable => bail!("Rate not profitable"),
                 };
             }
+            orderbook::BehaviourOutEvent::ProtocolViolation { peer } => {
+                let reputation = self
+                    .database
+                    .record_outcome(&peer, Outcome::ProtocolViolation)
+                    .await
+                    .with_context(|| format!("could not record protocol violation of {}", peer))?;
+
+                if !reputation.is_acceptable(self.min_reputation) {
+                    self.swarm.orderbook.block(peer);
+                }
+            }
         }
 
         Ok(())
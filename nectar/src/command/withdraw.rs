@@ -1,4 +1,10 @@
-use crate::{bitcoin, command::Withdraw, ethereum, ethereum::STANDARD_ETH_TRANSFER_GAS_LIMIT};
+use crate::{
+    bitcoin,
+    command::Withdraw,
+    ethereum,
+    ethereum::{DEFAULT_MINING_DEADLINE_SECS, STANDARD_ETH_TRANSFER_GAS_LIMIT},
+};
+use comit::Timestamp;
 use std::borrow::Borrow;
 
 pub async fn withdraw(
@@ -19,7 +25,12 @@ pub async fn withdraw(
         }
         Withdraw::Dai { amount, to_address } => {
             let tx_id = ethereum_wallet
-                .transfer_dai(to_address, amount.clone(), ethereum_wallet.chain_id())
+                .transfer_dai(
+                    to_address,
+                    amount.clone(),
+                    ethereum_wallet.chain_id(),
+                    Timestamp::now().plus(DEFAULT_MINING_DEADLINE_SECS),
+                )
                 .await?;
             Ok(format!(
                 "{} transferred to {}\nTransaction id: {}",
@@ -34,6 +45,7 @@ pub async fn withdraw(
                     Some(STANDARD_ETH_TRANSFER_GAS_LIMIT),
                     None,
                     ethereum_wallet.chain_id(),
+                    Timestamp::now().plus(DEFAULT_MINING_DEADLINE_SECS),
                 )
                 .await?;
             Ok(format!(
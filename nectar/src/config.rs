@@ -7,6 +7,7 @@ pub mod validation;
 use crate::{bitcoin, ethereum::dai};
 use ::serde::{Deserialize, Serialize};
 use anyhow::anyhow;
+use comit::network::discovery;
 use libp2p::Multiaddr;
 use std::path::PathBuf;
 use url::Url;
@@ -22,6 +23,13 @@ pub struct Data {
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Network {
     pub listen: Vec<Multiaddr>,
+    /// Takers whose reputation score is lower than this are not traded with.
+    pub min_reputation: Option<i64>,
+    /// The smallest quantity in satoshi we are willing to swap when one of
+    /// our orders is matched.
+    pub min_fill_sats: Option<u64>,
+    #[serde(default)]
+    pub discovery: discovery::Config,
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -91,17 +99,38 @@ mod tests {
             r#"
             listen = ["/ip4/0.0.0.0/tcp/9939", "/ip4/127.0.0.1/tcp/9939"]
             "#,
+            r#"
+            listen = ["/ip4/0.0.0.0/tcp/9939"]
+            min_reputation = -5
+            [discovery]
+            mdns = true
+            "#,
         ];
 
         let expected = vec![
             Network {
                 listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
+                min_reputation: None,
+                min_fill_sats: None,
+                discovery: Default::default(),
             },
             Network {
                 listen: (vec![
                     "/ip4/0.0.0.0/tcp/9939".parse().unwrap(),
                     "/ip4/127.0.0.1/tcp/9939".parse().unwrap(),
                 ]),
+                min_reputation: None,
+                min_fill_sats: None,
+                discovery: Default::default(),
+            },
+            Network {
+                listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
+                min_reputation: Some(-5),
+                min_fill_sats: None,
+                discovery: discovery::Config {
+                    mdns: true,
+                    ..Default::default()
+                },
             },
         ];
 
@@ -130,6 +159,9 @@ mod tests {
             }),
             network: Some(Network {
                 listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
+                min_reputation: None,
+                min_fill_sats: None,
+                discovery: Default::default(),
             }),
             data: Some(Data {
                 dir: "/Users/froyer/Library/Application Support/nectar"
@@ -141,6 +173,7 @@ mod tests {
             }),
             bitcoin: Some(file::Bitcoin {
                 network: ledger::Bitcoin::Regtest,
+                fee_rate_target_blocks: None,
                 bitcoind: Some(Bitcoind {
                     node_url: "http://localhost:18443/".parse().unwrap(),
                 }),
@@ -149,6 +182,8 @@ mod tests {
                 chain_id: ChainId::MAINNET,
                 node_url: Some("http://localhost:8545/".parse().unwrap()),
                 local_dai_contract_address: None,
+                stuck_transaction_timeout_secs: None,
+                gas_price: None,
             }),
         };
 
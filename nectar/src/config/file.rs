@@ -1,6 +1,7 @@
 use crate::{
     bitcoin,
     config::{Bitcoind, Data, MaxSell, Network},
+    ethereum::GasPriceStrategy,
     Spread,
 };
 use comit::{ethereum::ChainId, ledger};
@@ -43,6 +44,7 @@ pub struct Fees {
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Bitcoin {
     pub network: ledger::Bitcoin,
+    pub fee_rate_target_blocks: Option<u16>,
     pub bitcoind: Option<Bitcoind>,
 }
 
@@ -53,6 +55,8 @@ pub struct Ethereum {
     #[serde(default)]
     #[serde(with = "crate::config::serde::ethereum_address")]
     pub local_dai_contract_address: Option<comit::ethereum::Address>,
+    pub stuck_transaction_timeout_secs: Option<u64>,
+    pub gas_price: Option<GasPriceStrategy>,
 }
 
 impl File {
@@ -187,6 +191,12 @@ node_url = "http://localhost:18443/"
 chain_id = 1337
 node_url = "http://localhost:8545/"
 local_dai_contract_address = "0x6A9865aDE2B6207dAAC49f8bCba9705dEB0B0e6D"
+stuck_transaction_timeout_secs = 300
+
+[ethereum.gas_price]
+strategy = "capped"
+multiplier_percent = 120
+max_gwei = 200
 "#;
         let expected = File {
             maker: Some(Maker {
@@ -202,6 +212,9 @@ local_dai_contract_address = "0x6A9865aDE2B6207dAAC49f8bCba9705dEB0B0e6D"
             }),
             network: Some(Network {
                 listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
+                min_reputation: None,
+                min_fill_sats: None,
+                discovery: Default::default(),
             }),
             data: Some(Data {
                 dir: PathBuf::from("/tmp/nectar/"),
@@ -211,6 +224,7 @@ local_dai_contract_address = "0x6A9865aDE2B6207dAAC49f8bCba9705dEB0B0e6D"
             }),
             bitcoin: Some(Bitcoin {
                 network: ledger::Bitcoin::Regtest,
+                fee_rate_target_blocks: None,
                 bitcoind: Some(Bitcoind {
                     node_url: "http://localhost:18443".parse().unwrap(),
                 }),
@@ -223,6 +237,11 @@ local_dai_contract_address = "0x6A9865aDE2B6207dAAC49f8bCba9705dEB0B0e6D"
                         .parse()
                         .unwrap(),
                 ),
+                stuck_transaction_timeout_secs: Some(300),
+                gas_price: Some(GasPriceStrategy::Capped {
+                    multiplier_percent: 120,
+                    max_gwei: 200,
+                }),
             }),
         };
 
@@ -253,6 +272,9 @@ local_dai_contract_address = "0x6A9865aDE2B6207dAAC49f8bCba9705dEB0B0e6D"
             }),
             network: Some(Network {
                 listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
+                min_reputation: None,
+                min_fill_sats: None,
+                discovery: Default::default(),
             }),
             data: Some(Data {
                 dir: PathBuf::from("/tmp/nectar/"),
@@ -262,6 +284,7 @@ local_dai_contract_address = "0x6A9865aDE2B6207dAAC49f8bCba9705dEB0B0e6D"
             }),
             bitcoin: Some(Bitcoin {
                 network: ledger::Bitcoin::Regtest,
+                fee_rate_target_blocks: None,
                 bitcoind: Some(Bitcoind {
                     node_url: "http://localhost:18443".parse().unwrap(),
                 }),
@@ -274,6 +297,11 @@ local_dai_contract_address = "0x6A9865aDE2B6207dAAC49f8bCba9705dEB0B0e6D"
                         .parse()
                         .unwrap(),
                 ),
+                stuck_transaction_timeout_secs: Some(300),
+                gas_price: Some(GasPriceStrategy::Capped {
+                    multiplier_percent: 120,
+                    max_gwei: 200,
+                }),
             }),
         };
 
@@ -291,6 +319,11 @@ bitcoin = 0.01
 [network]
 listen = ["/ip4/0.0.0.0/tcp/9939"]
 
+[network.discovery]
+mdns = false
+kademlia = false
+bootstrap_nodes = []
+
 [data]
 dir = "/tmp/nectar/"
 
@@ -307,6 +340,12 @@ node_url = "http://localhost:18443/"
 chain_id = 1337
 node_url = "http://localhost:8545/"
 local_dai_contract_address = "0x6a9865ade2b6207daac49f8bcba9705deb0b0e6d"
+stuck_transaction_timeout_secs = 300
+
+[ethereum.gas_price]
+strategy = "capped"
+multiplier_percent = 120
+max_gwei = 200
 "#;
 
         let serialized = toml::to_string(&file);
@@ -357,18 +396,21 @@ local_dai_contract_address = "0x6a9865ade2b6207daac49f8bcba9705deb0b0e6d"
         let expected = vec![
             Bitcoin {
                 network: ledger::Bitcoin::Mainnet,
+                fee_rate_target_blocks: None,
                 bitcoind: Some(Bitcoind {
                     node_url: Url::parse("http://example.com:8332").unwrap(),
                 }),
             },
             Bitcoin {
                 network: ledger::Bitcoin::Testnet,
+                fee_rate_target_blocks: None,
                 bitcoind: Some(Bitcoind {
                     node_url: Url::parse("http://example.com:18332").unwrap(),
                 }),
             },
             Bitcoin {
                 network: ledger::Bitcoin::Regtest,
+                fee_rate_target_blocks: None,
                 bitcoind: Some(Bitcoind {
                     node_url: Url::parse("http://example.com:18443").unwrap(),
                 }),
@@ -6,6 +6,7 @@ use crate::{
 use anyhow::{Context, Result};
 use comit::ledger;
 use log::LevelFilter;
+use std::time::Duration;
 use url::Url;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -21,6 +22,9 @@ pub struct Settings {
 #[derive(Clone, Debug, PartialEq)]
 pub struct Bitcoin {
     pub network: ledger::Bitcoin,
+    /// The number of blocks within which we want our redeem and refund
+    /// transactions to be confirmed.
+    pub fee_rate_target_blocks: u16,
     pub bitcoind: Bitcoind,
 }
 
@@ -28,6 +32,7 @@ impl Bitcoin {
     pub fn new(network: ledger::Bitcoin) -> Self {
         Self {
             network,
+            fee_rate_target_blocks: bitcoin::DEFAULT_FEE_RATE_TARGET_BLOCKS,
             bitcoind: Bitcoind::new(network),
         }
     }
@@ -46,9 +51,19 @@ impl Bitcoin {
         }
 
         let network = bitcoin.network;
+        let fee_rate_target_blocks = bitcoin
+            .fee_rate_target_blocks
+            .unwrap_or(bitcoin::DEFAULT_FEE_RATE_TARGET_BLOCKS);
+        if fee_rate_target_blocks == 0 {
+            anyhow::bail!("fee_rate_target_blocks must be at least 1");
+        }
         let bitcoind = bitcoin.bitcoind.unwrap_or_else(|| Bitcoind::new(network));
 
-        Ok(Bitcoin { network, bitcoind })
+        Ok(Bitcoin {
+            network,
+            fee_rate_target_blocks,
+            bitcoind,
+        })
     }
 }
 
@@ -74,6 +89,7 @@ impl From<Bitcoin> for file::Bitcoin {
     fn from(bitcoin: Bitcoin) -> Self {
         file::Bitcoin {
             network: bitcoin.network,
+            fee_rate_target_blocks: Some(bitcoin.fee_rate_target_blocks),
             bitcoind: Some(bitcoin.bitcoind),
         }
     }
@@ -83,6 +99,7 @@ impl From<Bitcoin> for file::Bitcoin {
 pub struct Ethereum {
     pub node_url: Url,
     pub chain: ethereum::Chain,
+    pub gas_policy: ethereum::GasPolicy,
 }
 
 impl Ethereum {
@@ -92,7 +109,11 @@ impl Ethereum {
             .parse()
             .expect("to be valid static string");
 
-        Ok(Ethereum { node_url, chain })
+        Ok(Ethereum {
+            node_url,
+            chain,
+            gas_policy: ethereum::GasPolicy::default(),
+        })
     }
 
     fn from_file(ethereum: file::Ethereum, comit_network: Option<comit::Network>) -> Result<Self> {
@@ -123,12 +144,28 @@ impl Ethereum {
             (chain_id, None) => ethereum::Chain::from_public_chain_id(chain_id)?,
         };
 
-        Ok(Ethereum { node_url, chain })
+        let gas_policy = ethereum::GasPolicy {
+            strategy: ethereum.gas_price.unwrap_or_default(),
+            stuck_transaction_timeout: ethereum.stuck_transaction_timeout_secs.map_or(
+                ethereum::DEFAULT_STUCK_TRANSACTION_TIMEOUT,
+                Duration::from_secs,
+            ),
+        };
+
+        Ok(Ethereum {
+            node_url,
+            chain,
+            gas_policy,
+        })
     }
 }
 
 impl From<Ethereum> for file::Ethereum {
     fn from(ethereum: Ethereum) -> Self {
+        let stuck_transaction_timeout_secs =
+            Some(ethereum.gas_policy.stuck_transaction_timeout.as_secs());
+        let gas_price = Some(ethereum.gas_policy.strategy);
+
         match ethereum.chain {
             ethereum::Chain::Local {
                 chain_id,
@@ -137,11 +174,15 @@ impl From<Ethereum> for file::Ethereum {
                 chain_id: chain_id.into(),
                 node_url: Some(ethereum.node_url),
                 local_dai_contract_address: Some(dai_contract_address),
+                stuck_transaction_timeout_secs,
+                gas_price,
             },
             _ => file::Ethereum {
                 chain_id: ethereum.chain.chain_id(),
                 node_url: Some(ethereum.node_url),
                 local_dai_contract_address: None,
+                stuck_transaction_timeout_secs,
+                gas_price,
             },
         }
     }
@@ -152,6 +193,7 @@ impl Default for Ethereum {
         Self {
             node_url: Url::parse("http://localhost:8545").expect("static string to be a valid url"),
             chain: ethereum::Chain::Mainnet,
+            gas_policy: ethereum::GasPolicy::default(),
         }
     }
 }
@@ -321,6 +363,9 @@ impl Settings {
 
                 Network {
                     listen: vec![default_socket],
+                    min_reputation: None,
+                    min_fill_sats: None,
+                    discovery: Default::default(),
                 }
             }),
             data: {
@@ -392,6 +437,9 @@ mod tests {
             .map(|settings| &settings.network)
             .is_equal_to(Network {
                 listen: vec!["/ip4/0.0.0.0/tcp/9939".parse().unwrap()],
+                min_reputation: None,
+                min_fill_sats: None,
+                discovery: Default::default(),
             })
     }
 
@@ -406,6 +454,7 @@ mod tests {
             .map(|settings| &settings.bitcoin)
             .is_equal_to(Bitcoin {
                 network: ledger::Bitcoin::Mainnet,
+                fee_rate_target_blocks: 6,
                 bitcoind: Bitcoind {
                     node_url: "http://localhost:8332".parse().unwrap(),
                 },
@@ -424,6 +473,7 @@ mod tests {
             let config_file = File {
                 bitcoin: Some(file::Bitcoin {
                     network,
+                    fee_rate_target_blocks: None,
                     bitcoind: None,
                 }),
                 ..File::default()
@@ -436,6 +486,7 @@ mod tests {
                 .map(|settings| &settings.bitcoin)
                 .is_equal_to(Bitcoin {
                     network,
+                    fee_rate_target_blocks: 6,
                     bitcoind: Bitcoind {
                         node_url: url.parse().unwrap(),
                     },
@@ -455,6 +506,7 @@ mod tests {
             .is_equal_to(Ethereum {
                 node_url: "http://localhost:8545".parse().unwrap(),
                 chain: ethereum::Chain::Mainnet,
+                gas_policy: ethereum::GasPolicy::default(),
             })
     }
 }
@@ -2,14 +2,19 @@ use self::{
     hbit::{HbitFunded, HbitRedeemed, HbitRefunded},
     herc20::{Herc20Deployed, Herc20Funded, Herc20Redeemed, Herc20Refunded},
 };
-use crate::{network, network::ActivePeer, swap, swap::SwapKind, SwapId};
+use crate::{ethereum, network, network::ActivePeer, swap, swap::SwapKind, SwapId};
 use anyhow::{anyhow, Context};
 use chrono::{DateTime, Utc};
+use comit::reputation::{Outcome, Reputation};
+use libp2p::PeerId;
 use serde::{Deserialize, Serialize};
 
 #[cfg(test)]
 use crate::StaticStub;
-use std::{collections::HashSet, iter::FromIterator};
+use std::{
+    collections::{HashMap, HashSet},
+    iter::FromIterator,
+};
 
 mod hbit;
 mod herc20;
@@ -160,6 +165,12 @@ impl Database {
             .remove(key)
             .with_context(|| format!("Could not delete swap {}", swap_id))
             .map(|_| ())?;
+        self.db
+            .remove(Self::swap_outcome_key(swap_id)?)
+            .with_context(|| format!("Could not delete outcome of swap {}", swap_id))?;
+        self.db
+            .remove(Self::commitment_key(swap_id)?)
+            .with_context(|| format!("Could not delete commitment of swap {}", swap_id))?;
 
         self.db
             .flush_async()
@@ -180,6 +191,215 @@ impl Database {
     }
 }
 
+/// These methods persist the nonces handed out for our Ethereum transactions.
+impl Database {
+    const ETHEREUM_NONCES_KEY: &'static str = "ethereum_nonces";
+
+    pub fn ethereum_nonces(&self) -> anyhow::Result<Option<ethereum::Nonces>> {
+        let nonces = self
+            .db
+            .get(serialize(&Self::ETHEREUM_NONCES_KEY)?)?
+            .map(|nonces| deserialize(&nonces))
+            .transpose()
+            .context("Could not deserialize Ethereum nonces")?;
+
+        Ok(nonces)
+    }
+
+    pub async fn save_ethereum_nonces(&self, nonces: &ethereum::Nonces) -> anyhow::Result<()> {
+        self.db
+            .insert(serialize(&Self::ETHEREUM_NONCES_KEY)?, serialize(nonces)?)?;
+
+        self.db
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")
+    }
+}
+
+/// These methods keep track of how other peers behaved in the past.
+impl Database {
+    const REPUTATIONS_KEY: &'static str = "reputations";
+
+    pub fn reputations(&self) -> anyhow::Result<Vec<(PeerId, Reputation)>> {
+        self.load_reputations()?
+            .into_iter()
+            .map(|(peer, reputation)| {
+                let peer = peer
+                    .parse::<PeerId>()
+                    .with_context(|| format!("Could not parse peer id {}", peer))?;
+
+                Ok((peer, reputation))
+            })
+            .collect()
+    }
+
+    pub fn reputation(&self, peer: &PeerId) -> anyhow::Result<Reputation> {
+        let reputation = self
+            .load_reputations()?
+            .remove(&peer.to_base58())
+            .unwrap_or_default();
+
+        Ok(reputation)
+    }
+
+    pub async fn record_outcome(
+        &self,
+        peer: &PeerId,
+        outcome: Outcome,
+    ) -> anyhow::Result<Reputation> {
+        self.modify_reputation_with(peer, |reputation| reputation.record(outcome))
+            .await
+    }
+
+    pub async fn set_banned(&self, peer: &PeerId, banned: bool) -> anyhow::Result<Reputation> {
+        self.modify_reputation_with(peer, |reputation| reputation.banned = banned)
+            .await
+    }
+
+    /// The reputations of all peers are stored under a single key, hence they
+    /// are updated atomically so that outcomes recorded concurrently are not
+    /// lost. The operation may be applied more than once.
+    async fn modify_reputation_with(
+        &self,
+        peer: &PeerId,
+        operation_fn: impl Fn(&mut Reputation),
+    ) -> anyhow::Result<Reputation> {
+        let peer = peer.to_base58();
+        let mut corrupted = None;
+
+        let updated = self
+            .db
+            .update_and_fetch(serialize(&Self::REPUTATIONS_KEY)?, |old| {
+                let reputations = old
+                    .map(deserialize::<HashMap<String, Reputation>>)
+                    .transpose();
+                let mut reputations = match reputations {
+                    Ok(reputations) => reputations.unwrap_or_default(),
+                    Err(e) => {
+                        // Leave the corrupted value for inspection.
+                        corrupted = Some(e);
+                        return old.map(|bytes| bytes.to_vec());
+                    }
+                };
+                corrupted = None;
+
+                operation_fn(reputations.entry(peer.clone()).or_default());
+
+                Some(serialize(&reputations).expect("Can always serialize reputations"))
+            })?;
+
+        if let Some(e) = corrupted {
+            return Err(e).context("Could not deserialize reputations");
+        }
+
+        self.db.flush_async().await.context("Could not flush db")?;
+
+        let reputations = updated
+            .map(|reputations| deserialize::<HashMap<String, Reputation>>(&reputations))
+            .transpose()
+            .context("Could not deserialize reputations")?
+            .unwrap_or_default();
+
+        Ok(reputations.get(&peer).copied().unwrap_or_default())
+    }
+
+    /// Records the outcome of a swap unless one was recorded for it before.
+    ///
+    /// Unfinished swaps are resumed on every start, hence the same swap may
+    /// end more than once. Returns `None` if the outcome was recorded before.
+    pub async fn record_swap_outcome(
+        &self,
+        swap_id: SwapId,
+        peer: &PeerId,
+        outcome: Outcome,
+    ) -> anyhow::Result<Option<Reputation>> {
+        let recorded = self
+            .db
+            .compare_and_swap(
+                Self::swap_outcome_key(&swap_id)?,
+                Option::<Vec<u8>>::None,
+                Some(serialize(&outcome.to_string())?),
+            )
+            .context("Could not write in the DB")?;
+        if recorded.is_err() {
+            return Ok(None);
+        }
+
+        self.record_outcome(peer, outcome).await.map(Some)
+    }
+
+    fn swap_outcome_key(swap_id: &SwapId) -> anyhow::Result<Vec<u8>> {
+        serialize(&format!("outcome-{}", swap_id))
+    }
+
+    fn load_reputations(&self) -> anyhow::Result<HashMap<String, Reputation>> {
+        let reputations = self
+            .db
+            .get(serialize(&Self::REPUTATIONS_KEY)?)?
+            .map(|reputations| deserialize(&reputations))
+            .transpose()
+            .context("Could not deserialize reputations")?
+            .unwrap_or_default();
+
+        Ok(reputations)
+    }
+}
+
+/// Whether we went ahead with a swap or the taker cancelled it, whichever
+/// happened first.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Commitment {
+    Funding,
+    Cancellation,
+}
+
+impl Database {
+    /// Commits the swap to being funded or cancelled unless it is already
+    /// committed to either.
+    ///
+    /// Returns the commitment that is in place afterwards, which is the given
+    /// one only if it came first.
+    pub async fn commit_swap_to(
+        &self,
+        swap_id: SwapId,
+        commitment: Commitment,
+    ) -> anyhow::Result<Commitment> {
+        let committed = self
+            .db
+            .compare_and_swap(
+                Self::commitment_key(&swap_id)?,
+                Option::<Vec<u8>>::None,
+                Some(serialize(&commitment)?),
+            )
+            .context("Could not write in the DB")?;
+
+        let commitment = match committed {
+            Ok(()) => commitment,
+            Err(sled::CompareAndSwapError {
+                current: Some(current),
+                ..
+            }) => deserialize(&current).context("Could not deserialize commitment")?,
+            Err(sled::CompareAndSwapError { current: None, .. }) => {
+                anyhow::bail!("Commitment of swap {} vanished", swap_id)
+            }
+        };
+
+        self.db
+            .flush_async()
+            .await
+            .map(|_| ())
+            .context("Could not flush db")?;
+
+        Ok(commitment)
+    }
+
+    fn commitment_key(swap_id: &SwapId) -> anyhow::Result<Vec<u8>> {
+        serialize(&format!("commitment-{}", swap_id))
+    }
+}
+
 /// These methods are used to prevent a peer from having more than one ongoing
 /// swap with nectar An active peer refers to one that has an ongoing swap with
 /// nectar.
@@ -362,6 +582,7 @@ impl From<SwapKind> for Swap {
 mod tests {
     use super::*;
     use quickcheck::{Arbitrary, StdThreadGen};
+    use std::sync::Arc;
 
     #[quickcheck_async::tokio]
     async fn save_and_retrieve_swaps(swap_1: SwapKind, swap_2: SwapKind) -> bool {
@@ -443,4 +664,106 @@ mod tests {
         assert_eq!(db.fetch_inc_bitcoin_transient_key_index().await.unwrap(), 0);
         assert_eq!(db.fetch_inc_bitcoin_transient_key_index().await.unwrap(), 1);
     }
+
+    #[tokio::test]
+    async fn save_and_retrieve_ethereum_nonces() {
+        let db = Database::new_test().unwrap();
+        assert_eq!(db.ethereum_nonces().unwrap(), None);
+
+        let nonces = ethereum::Nonces {
+            next: 42,
+            released: vec![40].into_iter().collect(),
+        };
+        db.save_ethereum_nonces(&nonces).await.unwrap();
+
+        assert_eq!(db.ethereum_nonces().unwrap(), Some(nonces));
+    }
+
+    #[tokio::test]
+    async fn record_outcomes_and_ban_peers() {
+        let db = Database::new_test().unwrap();
+        let peer = PeerId::random();
+
+        db.record_outcome(&peer, Outcome::Completed).await.unwrap();
+        db.record_outcome(&peer, Outcome::Refunded).await.unwrap();
+        db.set_banned(&peer, true).await.unwrap();
+
+        let expected = Reputation {
+            completed: 1,
+            refunded: 1,
+            banned: true,
+            ..Reputation::default()
+        };
+        assert_eq!(db.reputation(&peer).unwrap(), expected);
+        assert_eq!(db.reputations().unwrap(), vec![(peer, expected)]);
+        assert_eq!(
+            db.reputation(&PeerId::random()).unwrap(),
+            Reputation::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn outcome_of_a_swap_is_recorded_once() {
+        let db = Database::new_test().unwrap();
+        let swap_id = SwapId::default();
+        let peer = PeerId::random();
+
+        let first = db
+            .record_swap_outcome(swap_id, &peer, Outcome::Failed)
+            .await
+            .unwrap();
+        let second = db
+            .record_swap_outcome(swap_id, &peer, Outcome::Failed)
+            .await
+            .unwrap();
+
+        assert_eq!(first.map(|reputation| reputation.failed), Some(1));
+        assert_eq!(second, None);
+        assert_eq!(db.reputation(&peer).unwrap().failed, 1);
+        assert!(db.all_swaps().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn first_commitment_of_a_swap_wins() {
+        let db = Database::new_test().unwrap();
+        let swap_id = SwapId::default();
+
+        let funding = db
+            .commit_swap_to(swap_id, Commitment::Funding)
+            .await
+            .unwrap();
+        let cancellation = db
+            .commit_swap_to(swap_id, Commitment::Cancellation)
+            .await
+            .unwrap();
+
+        assert_eq!(funding, Commitment::Funding);
+        assert_eq!(cancellation, Commitment::Funding);
+        assert!(db.all_swaps().unwrap().is_empty());
+    }
+
+    #[test]
+    fn concurrently_recorded_outcomes_are_not_lost() {
+        let db = Arc::new(Database::new_test().unwrap());
+        let peer = PeerId::random();
+
+        let threads = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                let peer = peer.clone();
+
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        futures::executor::block_on(db.record_outcome(&peer, Outcome::Failed))
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        assert_eq!(db.reputation(&peer).unwrap().failed, 40);
+    }
 }
@@ -1,14 +1,23 @@
 pub mod dai;
 mod geth;
+mod nonce;
 mod wallet;
 
-pub use comit::ethereum::{Address, ChainId, Hash};
+pub use comit::ethereum::{
+    gas::{GasPolicy, GasPriceStrategy, OnPending, DEFAULT_STUCK_TRANSACTION_TIMEOUT},
+    nonce::Nonces,
+    Address, ChainId, Hash,
+};
 pub use geth::Client;
 pub use wallet::Wallet;
 
 pub const STANDARD_ETH_TRANSFER_GAS_LIMIT: u64 = 21_000;
 pub const DAI_TRANSFER_GAS_LIMIT: u64 = 100_000;
 
+/// How long we wait for transactions without a deadline of their own, such as
+/// withdrawals and refunds, to be mined before giving up on them.
+pub const DEFAULT_MINING_DEADLINE_SECS: u32 = 60 * 60;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Chain {
     Mainnet,
@@ -91,12 +91,14 @@ impl Client {
         Ok(receipt)
     }
 
+    /// Returns the transaction count of the account, including transactions
+    /// that are pending in the node's mempool.
     pub async fn get_transaction_count(&self, account: Address) -> anyhow::Result<u32> {
         let count: String = self
             .rpc_client
             .send(jsonrpc::Request::new(
                 "eth_getTransactionCount",
-                vec![jsonrpc::serialize(account)?, jsonrpc::serialize("latest")?],
+                vec![jsonrpc::serialize(account)?, jsonrpc::serialize("pending")?],
                 JSONRPC_VERSION.into(),
             ))
             .await
@@ -159,7 +161,8 @@ impl Client {
         Ok(amount)
     }
 
-    pub async fn gas_price(&self) -> anyhow::Result<clarity::Uint256> {
+    /// Returns the gas price suggested by the node in wei.
+    pub async fn gas_price(&self) -> anyhow::Result<u64> {
         let amount = self
             .rpc_client
             .send::<Vec<()>, String>(jsonrpc::Request::new(
@@ -169,7 +172,7 @@ impl Client {
             ))
             .await
             .context("failed to get gas price")?;
-        let amount = clarity::Uint256::from_str_radix(&amount[2..], 16)?;
+        let amount = u64::from_str_radix(&amount[2..], 16)?;
 
         Ok(amount)
     }
@@ -0,0 +1,89 @@
+use crate::{
+    database::Database,
+    ethereum::{geth::Client, Address, Nonces},
+};
+use comit::ethereum::nonce::NonceTracker;
+use futures::lock::Mutex;
+use std::sync::Arc;
+
+/// Hands out the nonces of our Ethereum transactions, see [`NonceTracker`].
+///
+/// If a database is configured, the nonces are persisted so that they survive
+/// restarts.
+#[derive(Debug)]
+pub struct NonceManager {
+    client: Client,
+    account: Address,
+    database: Option<Arc<Database>>,
+    tracker: Mutex<Option<NonceTracker>>,
+}
+
+impl NonceManager {
+    pub fn new(client: Client, account: Address, database: Option<Arc<Database>>) -> Self {
+        Self {
+            client,
+            account,
+            database,
+            tracker: Mutex::new(None),
+        }
+    }
+
+    /// Hands out the nonce for the next transaction.
+    ///
+    /// The nonce must be given back through either `release` or `confirm`.
+    pub async fn allocate(&self) -> anyhow::Result<u32> {
+        let mut guard = self.tracker.lock().await;
+
+        if guard.is_none() {
+            let nonces = self.load()?;
+            *guard = Some(NonceTracker::new(nonces));
+        }
+        let tracker = guard.as_mut().expect("tracker was initialised above");
+
+        let pending_count = self.client.get_transaction_count(self.account).await?;
+        tracker.sync(pending_count);
+        let nonce = tracker.allocate();
+
+        self.save(tracker.nonces()).await?;
+
+        Ok(nonce)
+    }
+
+    /// Gives back a nonce whose transaction could not be broadcast.
+    pub async fn release(&self, nonce: u32) -> anyhow::Result<()> {
+        let mut guard = self.tracker.lock().await;
+
+        if let Some(tracker) = guard.as_mut() {
+            tracker.release(nonce);
+            self.save(tracker.nonces()).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Gives back a nonce whose transaction has been mined.
+    pub async fn confirm(&self, nonce: u32) {
+        let mut guard = self.tracker.lock().await;
+
+        if let Some(tracker) = guard.as_mut() {
+            tracker.confirm(nonce);
+        }
+    }
+
+    fn load(&self) -> anyhow::Result<Nonces> {
+        let nonces = match &self.database {
+            Some(database) => database.ethereum_nonces()?.unwrap_or_default(),
+            None => Nonces::default(),
+        };
+
+        Ok(nonces)
+    }
+
+    async fn save(&self, nonces: &Nonces) -> anyhow::Result<()> {
+        if let Some(database) = &self.database {
+            database.save_ethereum_nonces(nonces).await?;
+        }
+
+        Ok(())
+    }
+}
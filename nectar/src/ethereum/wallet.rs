@@ -2,8 +2,10 @@ use crate::{
     ethereum::{
         self, dai, ether,
         geth::{Client, EstimateGasRequest},
-        Address, ChainId, Hash, DAI_TRANSFER_GAS_LIMIT,
+        nonce::NonceManager,
+        Address, ChainId, GasPolicy, Hash, OnPending, DAI_TRANSFER_GAS_LIMIT,
     },
+    swap::Database,
     Seed,
 };
 use anyhow::{Context, Result};
@@ -13,10 +15,14 @@ use comit::{
     actions::ethereum::{CallContract, DeployContract},
     asset::Erc20,
     ethereum::{Transaction, TransactionReceipt},
+    Timestamp,
 };
 use conquer_once::Lazy;
 use num::BigUint;
-use std::time::Duration;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use url::Url;
 
 /// Ethereum Standard - m/44'/60'/0'/0/0
@@ -31,6 +37,8 @@ pub struct Wallet {
     private_key: clarity::PrivateKey,
     geth_client: Client,
     chain: ethereum::Chain,
+    gas_policy: GasPolicy,
+    nonces: Arc<NonceManager>,
 }
 
 impl Wallet {
@@ -38,10 +46,17 @@ impl Wallet {
         let geth_client = Client::new(url);
 
         let private_key = Self::private_key_from_seed(&seed)?;
+        let nonces = NonceManager::new(
+            geth_client.clone(),
+            account_from_private_key(&private_key),
+            None,
+        );
         let wallet = Self {
             geth_client,
             private_key,
             chain,
+            gas_policy: GasPolicy::default(),
+            nonces: Arc::new(nonces),
         };
 
         wallet.assert_chain(chain.chain_id()).await?;
@@ -61,10 +76,33 @@ impl Wallet {
         // deploy it. We will replace this placeholder once that happens
         let placeholder_dai_contract_address = Address::default();
         let chain = ethereum::Chain::new(chain_id, placeholder_dai_contract_address);
+        let nonces = NonceManager::new(
+            geth_client.clone(),
+            account_from_private_key(&private_key),
+            None,
+        );
         Self {
             private_key,
             geth_client,
             chain,
+            gas_policy: GasPolicy::default(),
+            nonces: Arc::new(nonces),
+        }
+    }
+
+    pub fn with_gas_policy(self, gas_policy: GasPolicy) -> Self {
+        Self { gas_policy, ..self }
+    }
+
+    /// Persists the nonces handed out for our transactions in the database.
+    ///
+    /// Must be called before sending any transactions.
+    pub fn with_database(self, database: Arc<Database>) -> Self {
+        let nonces = NonceManager::new(self.geth_client.clone(), self.account(), Some(database));
+
+        Self {
+            nonces: Arc::new(nonces),
+            ..self
         }
     }
 
@@ -97,12 +135,7 @@ impl Wallet {
     }
 
     pub fn account(&self) -> Address {
-        let pk = self.private_key.to_public_key().expect("cannot fail");
-
-        let mut bytes = [0u8; 20];
-        bytes.copy_from_slice(pk.as_bytes());
-
-        Address::from(bytes)
+        account_from_private_key(&self.private_key)
     }
 
     pub fn private_key(&self) -> clarity::PrivateKey {
@@ -125,29 +158,28 @@ impl Wallet {
             chain_id,
             ..
         }: DeployContract,
+        deadline: Timestamp,
     ) -> anyhow::Result<DeployedContract> {
         self.assert_chain(chain_id).await?;
 
-        let nonce = self.get_transaction_count().await?;
         let gas_price = self.gas_price().await?;
 
+        let nonce = self.nonces.allocate().await?;
         let transaction = clarity::Transaction {
             nonce: nonce.into(),
-            gas_price,
+            gas_price: gas_price.into(),
             gas_limit: gas_limit.into(),
             to: clarity::Address::default(),
             value: 0u64.into(),
             data,
             signature: None,
         };
-        let transaction_hex = self.sign(transaction)?;
 
-        let hash = self
-            .geth_client
-            .send_raw_transaction(transaction_hex)
+        let (hash, receipt) = self
+            .send_until_mined(nonce, transaction, gas_price, deadline)
             .await?;
 
-        let contract_address = match self.wait_until_transaction_receipt(hash).await? {
+        let contract_address = match receipt {
             TransactionReceipt {
                 successful: true,
                 contract_address: Some(contract_address),
@@ -177,10 +209,10 @@ impl Wallet {
         gas_limit: Option<u64>,
         data: Option<Vec<u8>>,
         chain_id: ChainId,
+        deadline: Timestamp,
     ) -> anyhow::Result<Hash> {
         self.assert_chain(chain_id).await?;
 
-        let nonce = self.get_transaction_count().await?;
         let gas_price = self.gas_price().await?;
 
         let gas_limit = match gas_limit {
@@ -189,7 +221,7 @@ impl Wallet {
                 self.gas_limit(EstimateGasRequest {
                     from: None,
                     to: Some(to),
-                    gas_price: Some(gas_price.clone()),
+                    gas_price: Some(gas_price.into()),
                     value: Some(value.clone().into()),
                     data: data.clone(),
                 })
@@ -197,24 +229,23 @@ impl Wallet {
             }
         };
 
+        let to = to_clarity_address(to)?;
+
+        let nonce = self.nonces.allocate().await?;
         let transaction = clarity::Transaction {
             nonce: nonce.into(),
-            gas_price,
+            gas_price: gas_price.into(),
             gas_limit,
-            to: to_clarity_address(to)?,
+            to,
             value: value.into(),
             data: data.unwrap_or_default(),
             signature: None,
         };
-        let transaction_hex = self.sign(transaction)?;
 
-        let hash = self
-            .geth_client
-            .send_raw_transaction(transaction_hex)
+        let (hash, _) = self
+            .send_until_mined(nonce, transaction, gas_price, deadline)
             .await?;
 
-        let _ = self.wait_until_transaction_receipt(hash).await?;
-
         Ok(hash)
     }
 
@@ -223,10 +254,10 @@ impl Wallet {
         to: Address,
         value: dai::Amount,
         chain_id: ChainId,
+        deadline: Timestamp,
     ) -> anyhow::Result<Hash> {
         self.assert_chain(chain_id).await?;
 
-        let nonce = self.get_transaction_count().await?;
         let gas_price = self.gas_price().await?;
 
         let to = to_clarity_address(to)?;
@@ -237,24 +268,21 @@ impl Wallet {
             clarity::abi::Token::Uint(Uint256::from_bytes_le(value.to_bytes().as_slice())),
         ]);
 
+        let nonce = self.nonces.allocate().await?;
         let transaction = clarity::Transaction {
             nonce: nonce.into(),
-            gas_price,
+            gas_price: gas_price.into(),
             gas_limit: DAI_TRANSFER_GAS_LIMIT.into(),
             to: dai_contract_addr,
             value: 0u16.into(),
             data,
             signature: None,
         };
-        let transaction_hex = self.sign(transaction)?;
 
-        let hash = self
-            .geth_client
-            .send_raw_transaction(transaction_hex)
+        let (hash, _) = self
+            .send_until_mined(nonce, transaction, gas_price, deadline)
             .await?;
 
-        let _ = self.wait_until_transaction_receipt(hash).await?;
-
         Ok(hash)
     }
 
@@ -267,67 +295,155 @@ impl Wallet {
             chain_id,
             ..
         }: CallContract,
+        deadline: Timestamp,
     ) -> anyhow::Result<Hash> {
         self.assert_chain(chain_id).await?;
 
-        let nonce = self.get_transaction_count().await?;
         let gas_price = self.gas_price().await?;
+        let to = to_clarity_address(to)?;
 
+        let nonce = self.nonces.allocate().await?;
         let transaction = clarity::Transaction {
             nonce: nonce.into(),
-            gas_price,
+            gas_price: gas_price.into(),
             gas_limit: gas_limit.into(),
-            to: to_clarity_address(to)?,
+            to,
             value: 0u32.into(),
             data: data.unwrap_or_default(),
             signature: None,
         };
-        let transaction_hex = self.sign(transaction)?;
 
-        let hash = self
-            .geth_client
-            .send_raw_transaction(transaction_hex)
+        let (hash, _) = self
+            .send_until_mined(nonce, transaction, gas_price, deadline)
             .await?;
 
-        let _ = self.wait_until_transaction_receipt(hash).await?;
-
         Ok(hash)
     }
 
-    pub async fn get_transaction_by_hash(
+    /// Sends the transaction and waits until it is mined.
+    ///
+    /// The nonce of the transaction is confirmed once it is mined and released
+    /// on any error, see [`NonceManager`]. Releasing the nonce of a transaction
+    /// that is still pending is fine because the nonce manager drops released
+    /// nonces the node already knows about.
+    async fn send_until_mined(
         &self,
-        transaction_hash: Hash,
-    ) -> anyhow::Result<Transaction> {
-        self.geth_client
-            .get_transaction_by_hash(transaction_hash)
+        nonce: u32,
+        transaction: clarity::Transaction,
+        gas_price: u64,
+        deadline: Timestamp,
+    ) -> anyhow::Result<(Hash, TransactionReceipt)> {
+        match self
+            .wait_until_mined(nonce, transaction, gas_price, deadline)
             .await
+        {
+            Ok(mined) => {
+                self.nonces.confirm(nonce).await;
+                Ok(mined)
+            }
+            Err(e) => {
+                if let Err(release_error) = self.nonces.release(nonce).await {
+                    tracing::warn!("failed to release nonce {}: {:#}", nonce, release_error);
+                }
+                Err(e)
+            }
+        }
     }
 
-    pub async fn wait_until_transaction_receipt(
+    /// If the transaction is not mined in time, it is replaced with one using
+    /// the same nonce but paying a higher gas price. Any of the sent
+    /// transactions may end up being mined, hence we return the hash and
+    /// receipt of the one that was. Should none of them be mined before the
+    /// deadline or the gas price reach the cap of our strategy, we give up.
+    async fn wait_until_mined(
         &self,
-        transaction_hash: Hash,
-    ) -> anyhow::Result<TransactionReceipt> {
-        let start_time = std::time::Instant::now();
-        let max_retry_time = Duration::from_millis(60_000);
+        nonce: u32,
+        mut transaction: clarity::Transaction,
+        mut gas_price: u64,
+        deadline: Timestamp,
+    ) -> anyhow::Result<(Hash, TransactionReceipt)> {
+        let hash = self.sign_and_send(transaction.clone()).await?;
+        let mut hashes = vec![hash];
+        let mut sent_at = Instant::now();
 
         loop {
-            if std::time::Instant::now() > start_time + max_retry_time {
-                anyhow::bail!(
-                    "failed to find transaction receipt for transaction {}",
-                    transaction_hash
-                )
+            for hash in hashes.iter() {
+                match self.get_transaction_receipt(*hash).await {
+                    Ok(Some(receipt)) => return Ok((*hash, receipt)),
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!("failed to get receipt of {}: {:#}", hash, e),
+                }
             }
 
-            if let Some(transaction_receipt) =
-                self.get_transaction_receipt(transaction_hash).await?
-            {
-                return Ok(transaction_receipt);
+            let on_pending =
+                self.gas_policy
+                    .on_pending(sent_at.elapsed(), Some(deadline), Timestamp::now());
+
+            if on_pending == OnPending::GiveUp {
+                anyhow::bail!(NotMinedBeforeDeadline { nonce, deadline })
             }
 
-            tokio::time::delay_for(Duration::from_millis(1_000)).await;
+            if on_pending == OnPending::Replace {
+                match self.replace(nonce, &mut transaction, gas_price).await {
+                    Ok((hash, bumped)) => {
+                        gas_price = bumped;
+                        hashes.push(hash);
+                    }
+                    Err(e) if e.is::<GasPriceAtCap>() => return Err(e),
+                    // The original transaction may have been mined in the meantime
+                    Err(e) => tracing::warn!("failed to replace transaction: {:#}", e),
+                }
+
+                sent_at = Instant::now();
+            }
+
+            tokio::time::delay_for(Duration::from_secs(1)).await;
         }
     }
 
+    /// Re-sends the transaction with a higher gas price, unless that would
+    /// exceed the cap of our gas price strategy.
+    async fn replace(
+        &self,
+        nonce: u32,
+        transaction: &mut clarity::Transaction,
+        gas_price: u64,
+    ) -> anyhow::Result<(Hash, u64)> {
+        let node_gas_price = self.geth_client.gas_price().await?;
+
+        let bumped = self
+            .gas_policy
+            .strategy
+            .bump(gas_price, node_gas_price)
+            .ok_or(GasPriceAtCap { nonce, gas_price })?;
+
+        transaction.gas_price = bumped.into();
+        let hash = self.sign_and_send(transaction.clone()).await?;
+
+        tracing::info!(
+            "replaced stuck transaction with {} paying {} wei per gas",
+            hash,
+            bumped
+        );
+
+        Ok((hash, bumped))
+    }
+
+    async fn sign_and_send(&self, transaction: clarity::Transaction) -> anyhow::Result<Hash> {
+        let transaction_hex = self.sign(transaction)?;
+
+        self.geth_client.send_raw_transaction(transaction_hex).await
+    }
+
+    pub async fn get_transaction_by_hash(
+        &self,
+        transaction_hash: Hash,
+    ) -> anyhow::Result<Transaction> {
+        self.geth_client
+            .get_transaction_by_hash(transaction_hash)
+            .await
+    }
+
     pub async fn erc20_balance(&self, token_contract: Address) -> anyhow::Result<Erc20> {
         self.geth_client
             .erc20_balance(self.account(), token_contract)
@@ -355,10 +471,6 @@ impl Wallet {
             .await
     }
 
-    async fn get_transaction_count(&self) -> anyhow::Result<u32> {
-        self.geth_client.get_transaction_count(self.account()).await
-    }
-
     async fn assert_chain(&self, expected: ChainId) -> anyhow::Result<()> {
         let actual = self.geth_client.chain_id().await?;
 
@@ -369,8 +481,12 @@ impl Wallet {
         Ok(())
     }
 
-    async fn gas_price(&self) -> anyhow::Result<clarity::Uint256> {
-        self.geth_client.gas_price().await
+    /// Returns the gas price in wei to start with as per our gas price
+    /// strategy.
+    async fn gas_price(&self) -> anyhow::Result<u64> {
+        let node_gas_price = self.geth_client.gas_price().await?;
+
+        Ok(self.gas_policy.strategy.gas_price(node_gas_price))
     }
 
     async fn gas_limit(&self, request: EstimateGasRequest) -> anyhow::Result<clarity::Uint256> {
@@ -399,7 +515,8 @@ impl Wallet {
         &mut self,
         deployment_data: DeployContract,
     ) -> anyhow::Result<()> {
-        let deployed_contract = self.deploy_contract(deployment_data).await?;
+        let deadline = Timestamp::now().plus(ethereum::DEFAULT_MINING_DEADLINE_SECS);
+        let deployed_contract = self.deploy_contract(deployment_data, deadline).await?;
 
         // Set correct value for DAI token contract address after deployment
         self.chain =
@@ -542,6 +659,7 @@ mod tests {
                 Address::random(),
                 dai::Amount::from_dai_trunc(1.0).unwrap(),
                 chain_id,
+                Timestamp::now().plus(ethereum::DEFAULT_MINING_DEADLINE_SECS),
             )
             .await
             .unwrap();
@@ -600,18 +718,44 @@ mod tests {
         };
 
         wallet
-            .deploy_contract(DeployContract {
-                data: htlc_params.bytecode(),
-                amount: asset::Ether::zero(),
-                gas_limit: 160_000,
-                chain_id,
-            })
+            .deploy_contract(
+                DeployContract {
+                    data: htlc_params.bytecode(),
+                    amount: asset::Ether::zero(),
+                    gas_limit: 160_000,
+                    chain_id,
+                },
+                Timestamp::now().plus(ethereum::DEFAULT_MINING_DEADLINE_SECS),
+            )
             .await
             .unwrap();
     }
 }
 
+fn account_from_private_key(private_key: &clarity::PrivateKey) -> Address {
+    let pk = private_key.to_public_key().expect("cannot fail");
+
+    let mut bytes = [0u8; 20];
+    bytes.copy_from_slice(pk.as_bytes());
+
+    Address::from(bytes)
+}
+
 fn to_clarity_address(to: Address) -> Result<clarity::Address> {
     clarity::Address::from_slice(to.as_bytes())
         .context("failed to create private key from byte slice")
 }
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("transaction with nonce {nonce} was not mined before the deadline {deadline:?}")]
+pub struct NotMinedBeforeDeadline {
+    pub nonce: u32,
+    pub deadline: Timestamp,
+}
+
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("transaction with nonce {nonce} is stuck at the gas price cap of {gas_price} wei")]
+pub struct GasPriceAtCap {
+    pub nonce: u32,
+    pub gas_price: u64,
+}
@@ -42,13 +42,16 @@ mod arbitrary;
 
 use crate::{
     command::{
-        balance, deposit, dump_config, resume_only, trade, wallet_info, withdraw, Command, Options,
+        balance, ban, deposit, dump_config, reputation, resume_only, trade, wallet_info, withdraw,
+        Command, Options,
     },
     config::{read_config, Settings},
     fs::default_config_path,
+    swap::Database,
 };
 use anyhow::{Context, Result};
 use conquer_once::Lazy;
+use std::sync::Arc;
 
 pub use maker::Maker;
 pub use mid_market_rate::MidMarketRate;
@@ -93,7 +96,8 @@ async fn main() -> Result<()> {
         settings.ethereum.node_url.clone(),
         settings.ethereum.chain,
     )
-    .await;
+    .await
+    .map(|wallet| wallet.with_gas_policy(settings.ethereum.gas_policy));
 
     match options.cmd {
         Command::Trade => trade(
@@ -135,8 +139,13 @@ async fn main() -> Result<()> {
             println!("{}", deposit);
         }
         Command::Withdraw(arguments) => {
+            // Withdrawals use the same persisted Ethereum nonces as trading,
+            // hence nectar must not be running at the same time
+            let db = Database::new(&settings.data.dir.join("database")).expect("open database");
             let tx_id = withdraw(
-                ethereum_wallet.expect("could not initialise ethereum wallet"),
+                ethereum_wallet
+                    .expect("could not initialise ethereum wallet")
+                    .with_database(Arc::new(db)),
                 bitcoin_wallet.expect("could not initialise bitcoin wallet"),
                 arguments,
             )
@@ -152,6 +161,18 @@ async fn main() -> Result<()> {
         )
         .await
         .expect("Wrapping up"),
+        Command::Reputation => {
+            let reputation = reputation(&settings).expect("get reputations");
+            println!("{}", reputation);
+        }
+        Command::Ban { peer } => {
+            let stdout = ban(&settings, peer, true).await.expect("ban peer");
+            println!("{}", stdout);
+        }
+        Command::Unban { peer } => {
+            let stdout = ban(&settings, peer, false).await.expect("unban peer");
+            println!("{}", stdout);
+        }
     };
 
     Ok(())
@@ -1,6 +1,9 @@
-use crate::SwapId;
+use crate::{bitcoin, ethereum::dai, SwapId};
 use ::bitcoin::hashes::{sha256, Hash, HashEngine};
-use comit::network::{orderbook, setup_swap};
+use comit::{
+    network::{cancel_swap, discovery, orderbook, setup_swap},
+    OrderId,
+};
 use futures::Future;
 use libp2p::{
     identity::{ed25519, Keypair},
@@ -17,7 +20,7 @@ pub const SEED_LENGTH: usize = 32;
 pub fn new_swarm(seed: Seed, settings: &crate::config::Settings) -> anyhow::Result<Swarm> {
     use anyhow::Context as _;
 
-    let behaviour = Nectar::new(seed);
+    let behaviour = Nectar::new(seed, &settings.network.discovery)?;
 
     let local_key_pair = behaviour.identity();
     let local_peer_id = behaviour.peer_id();
@@ -41,6 +44,8 @@ pub fn new_swarm(seed: Seed, settings: &crate::config::Settings) -> anyhow::Resu
 pub enum BehaviourOutEvent {
     Orderbook(orderbook::BehaviourOutEvent),
     SetupSwap(setup_swap::BehaviourOutEvent<SetupSwapContext>),
+    CancelSwap(cancel_swap::BehaviourOutEvent),
+    Discovery(discovery::BehaviourOutEvent),
 }
 
 impl From<orderbook::BehaviourOutEvent> for BehaviourOutEvent {
@@ -55,35 +60,56 @@ impl From<setup_swap::BehaviourOutEvent<SetupSwapContext>> for BehaviourOutEvent
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+impl From<cancel_swap::BehaviourOutEvent> for BehaviourOutEvent {
+    fn from(event: cancel_swap::BehaviourOutEvent) -> Self {
+        BehaviourOutEvent::CancelSwap(event)
+    }
+}
+
+impl From<discovery::BehaviourOutEvent> for BehaviourOutEvent {
+    fn from(event: discovery::BehaviourOutEvent) -> Self {
+        BehaviourOutEvent::Discovery(event)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SetupSwapContext {
     pub swap_id: SwapId,
+    /// The order of ours that was matched.
+    pub order: OrderId,
     pub bitcoin_transient_key_index: u32,
     pub match_ref_point: OffsetDateTime,
+    /// The funds we reserved for the swap, to be freed if it is not set up.
+    pub reserved_dai: Option<dai::Amount>,
+    pub reserved_btc: Option<bitcoin::Amount>,
 }
 
-/// A `NetworkBehaviour` that delegates to the `Orderbook` and `SetupSwap`
-/// behaviours.
+/// A `NetworkBehaviour` that delegates to the `Orderbook`, `SetupSwap`,
+/// `CancelSwap` and `Discovery` behaviours.
 #[derive(NetworkBehaviour)]
 #[behaviour(out_event = "BehaviourOutEvent", event_process = false)]
 #[allow(missing_debug_implementations)]
 pub struct Nectar {
     pub orderbook: orderbook::Orderbook,
     pub setup_swap: setup_swap::SetupSwap<SetupSwapContext>,
+    pub cancel_swap: cancel_swap::CancelSwap,
+    pub discovery: discovery::Discovery,
     #[behaviour(ignore)]
     identity: Keypair,
 }
 
 impl Nectar {
-    fn new(seed: Seed) -> Self {
+    fn new(seed: Seed, discovery: &discovery::Config) -> anyhow::Result<Self> {
         let identity = seed.derive_libp2p_identity();
         let peer_id = PeerId::from(identity.public());
 
-        Self {
-            orderbook: comit::network::Orderbook::new(peer_id, identity.clone()),
+        Ok(Self {
+            orderbook: comit::network::Orderbook::new(peer_id.clone(), identity.clone()),
+            discovery: discovery::Discovery::new(peer_id, discovery)?,
+            cancel_swap: cancel_swap::CancelSwap::new(identity.clone()),
             identity,
             setup_swap: Default::default(),
-        }
+        })
     }
 
     pub fn identity(&self) -> Keypair {
@@ -8,12 +8,28 @@ mod bob;
 mod comit;
 pub mod ethereum;
 
-use crate::{command::FinishedSwap, network::ActivePeer, swap::bob::Bob, SwapId};
-use ::comit::btsieve::{bitcoin::BitcoindConnector, ethereum::Web3Connector};
+use crate::{
+    command::FinishedSwap,
+    database::Load,
+    network::ActivePeer,
+    swap::{action::AbortConditionMet, bob::Bob, comit::SwapFailedShouldRefund},
+    SwapId,
+};
+use ::comit::{
+    btsieve::{bitcoin::BitcoindConnector, ethereum::Web3Connector},
+    reputation::Outcome,
+};
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
-use futures::{channel::mpsc, SinkExt};
-use std::sync::Arc;
+use futures::{
+    channel::mpsc,
+    future::{self, abortable, AbortHandle, Either},
+    Future, SinkExt,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 use tracing_futures::Instrument;
 
 pub use self::comit::{hbit, herc20};
@@ -337,6 +353,7 @@ mod tests {
                 bitcoin::Wallet {
                     inner: Arc::new(bitcoin_wallet),
                     connector: Arc::clone(&bitcoin_connector),
+                    fee_rate_target_blocks: crate::bitcoin::DEFAULT_FEE_RATE_TARGET_BLOCKS,
                 },
                 ethereum::Wallet {
                     inner: Arc::new(ethereum_wallet),
@@ -377,6 +394,7 @@ mod tests {
                 bitcoin::Wallet {
                     inner: Arc::new(bitcoin_wallet),
                     connector: Arc::clone(&bitcoin_connector),
+                    fee_rate_target_blocks: crate::bitcoin::DEFAULT_FEE_RATE_TARGET_BLOCKS,
                 },
                 ethereum::Wallet {
                     inner: Arc::new(ethereum_wallet),
@@ -541,6 +559,8 @@ pub struct SwapExecutor {
     finished_swap_sender: mpsc::Sender<FinishedSwap>,
     bitcoin_connector: Arc<BitcoindConnector>,
     ethereum_connector: Arc<Web3Connector>,
+    bitcoin_fee_rate_target_blocks: u16,
+    executions: Arc<Mutex<HashMap<SwapId, AbortHandle>>>,
 }
 
 impl SwapExecutor {
@@ -550,6 +570,7 @@ impl SwapExecutor {
         ethereum_wallet: Arc<crate::ethereum::Wallet>,
         bitcoin_connector: Arc<BitcoindConnector>,
         ethereum_connector: Arc<Web3Connector>,
+        bitcoin_fee_rate_target_blocks: u16,
     ) -> (Self, mpsc::Receiver<FinishedSwap>) {
         // buffer increases by 1 for every clone of `Sender` and we use every sender
         // only once, hence making the initial buffer size 0 is good enough
@@ -563,6 +584,8 @@ impl SwapExecutor {
             finished_swap_sender,
             bitcoin_connector,
             ethereum_connector,
+            bitcoin_fee_rate_target_blocks,
+            executions: Arc::default(),
         };
 
         (executor, finished_swap_receiver)
@@ -571,11 +594,13 @@ impl SwapExecutor {
 
 impl SwapExecutor {
     pub fn execute(&self, swap: SwapKind) {
+        let swap_id = swap.swap_id();
         let execution = execute(
             swap,
             bitcoin::Wallet {
                 inner: self.bitcoin_wallet.clone(),
                 connector: self.bitcoin_connector.clone(),
+                fee_rate_target_blocks: self.bitcoin_fee_rate_target_blocks,
             },
             self.bitcoin_connector.clone(),
             ethereum::Wallet {
@@ -587,12 +612,44 @@ impl SwapExecutor {
             self.finished_swap_sender.clone(),
         );
 
+        let (execution, handle) = abortable(execution);
+        self.executions
+            .lock()
+            .expect("no other thread panicked while holding the lock")
+            .insert(swap_id, handle);
+
+        let executions = self.executions.clone();
         tokio::spawn(async move {
-            if let Err(e) = execution.await {
-                tracing::warn!("swap execution failed: {:#}", e);
+            match execution.await {
+                Ok(Err(e)) => tracing::warn!("swap execution failed: {:#}", e),
+                Ok(Ok(())) => {}
+                Err(_aborted) => tracing::info!("swap execution of {} was aborted", swap_id),
             }
+
+            executions
+                .lock()
+                .expect("no other thread panicked while holding the lock")
+                .remove(&swap_id);
         });
     }
+
+    /// Stop executing the given swap.
+    ///
+    /// Returns `false` if the swap is not being executed.
+    pub fn abort(&self, swap_id: SwapId) -> bool {
+        match self
+            .executions
+            .lock()
+            .expect("no other thread panicked while holding the lock")
+            .remove(&swap_id)
+        {
+            Some(handle) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
+    }
 }
 
 async fn execute(
@@ -604,7 +661,8 @@ async fn execute(
     db: Arc<Database>,
     mut sender: mpsc::Sender<FinishedSwap>,
 ) -> Result<()> {
-    match swap.clone() {
+    let active_peer = swap.params().taker;
+    let result = match swap.clone() {
         SwapKind::HbitHerc20(SwapParams {
             hbit_params,
             herc20_params,
@@ -613,17 +671,18 @@ async fn execute(
             swap_id,
             ..
         }) => {
+            let beta_expiry = herc20_params.expiry;
             let bob = Bob {
                 alpha_wallet: bitcoin_wallet,
                 beta_wallet: ethereum_wallet,
-                db,
+                db: db.clone(),
                 swap_id,
                 secret_hash,
                 utc_start_of_swap: start_of_swap,
                 beta_expiry: herc20_params.expiry,
             };
 
-            comit::hbit_herc20_bob(
+            let swap = comit::hbit_herc20_bob(
                 bob,
                 bitcoin_connector.as_ref(),
                 ethereum_connector.as_ref(),
@@ -631,8 +690,16 @@ async fn execute(
                 herc20_params,
                 start_of_swap,
             )
-            .instrument(tracing::error_span!("hbit_herc20_bob", %swap_id))
-            .await?
+            .instrument(tracing::error_span!("hbit_herc20_bob", %swap_id));
+
+            unless_expired::<herc20::Deployed, _, _>(
+                swap,
+                ethereum_connector.as_ref(),
+                beta_expiry,
+                db.as_ref(),
+                swap_id,
+            )
+            .await
         }
         SwapKind::Herc20Hbit(SwapParams {
             hbit_params,
@@ -642,17 +709,18 @@ async fn execute(
             swap_id,
             ..
         }) => {
+            let beta_expiry = hbit_params.shared.expiry;
             let bob = Bob {
                 alpha_wallet: ethereum_wallet,
                 beta_wallet: bitcoin_wallet,
-                db,
+                db: db.clone(),
                 swap_id,
                 secret_hash,
                 utc_start_of_swap: start_of_swap,
                 beta_expiry: herc20_params.expiry,
             };
 
-            comit::herc20_hbit_bob(
+            let swap = comit::herc20_hbit_bob(
                 bob,
                 ethereum_connector.as_ref(),
                 bitcoin_connector.as_ref(),
@@ -660,13 +728,28 @@ async fn execute(
                 hbit_params,
                 start_of_swap,
             )
-            .instrument(tracing::error_span!("herc20_hbit_bob", %swap_id))
-            .await?
+            .instrument(tracing::error_span!("herc20_hbit_bob", %swap_id));
+
+            unless_expired::<hbit::Funded, _, _>(
+                swap,
+                bitcoin_connector.as_ref(),
+                beta_expiry,
+                db.as_ref(),
+                swap_id,
+            )
+            .await
         }
     };
 
-    let active_peer = swap.params().taker;
     let swap_id = swap.swap_id();
+    let taker = active_peer.peer_id();
+    if let Some(outcome) = swap_outcome(&result) {
+        if let Err(e) = db.record_swap_outcome(swap_id, &taker, outcome).await {
+            tracing::warn!("failed to record {} of {}: {:#}", outcome, taker, e);
+        }
+    }
+    result?;
+
     sender
         .send(FinishedSwap::new(swap, active_peer, chrono::Utc::now()))
         .await
@@ -676,3 +759,73 @@ async fn execute(
 
     Ok(())
 }
+
+/// Executes the swap unless the beta HTLC expires before we acted on it.
+///
+/// Once we acted on the beta ledger, the swap has to run its course so that we
+/// refund if necessary.
+async fn unless_expired<E, S, BC>(
+    swap: S,
+    beta_connector: &BC,
+    beta_expiry: comit::Timestamp,
+    db: &Database,
+    swap_id: SwapId,
+) -> Result<()>
+where
+    S: Future<Output = Result<()>>,
+    BC: LedgerTime,
+    Database: Load<E>,
+{
+    let expired = async {
+        poll_beta_has_expired(beta_connector, beta_expiry).await?;
+
+        if Load::<E>::load(db, swap_id)?.is_some() {
+            future::pending::<()>().await;
+        }
+
+        Err(anyhow::Error::from(SwapExpired))
+    };
+
+    futures::pin_mut!(swap, expired);
+    match future::select(swap, expired).await {
+        Either::Left((result, _)) | Either::Right((result, _)) => result,
+    }
+}
+
+/// The taker did not fund the alpha HTLC early enough for us to act on the
+/// beta ledger before it expired.
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+#[error("the swap expired before the taker funded it")]
+pub struct SwapExpired;
+
+/// The taker cancelled the swap before we acted on the beta ledger.
+#[derive(Clone, Copy, Debug, thiserror::Error)]
+#[error("the swap was cancelled before we funded it")]
+pub struct SwapCancelled;
+
+/// How the execution of a swap affects the reputation of the taker.
+///
+/// Cancelled swaps are taken care of when handling the cancellation.
+fn swap_outcome(result: &Result<()>) -> Option<Outcome> {
+    let error = match result {
+        Ok(()) => return Some(Outcome::Completed),
+        Err(error) => error,
+    };
+
+    if error.is::<SwapCancelled>() {
+        return None;
+    }
+
+    if error.is::<SwapExpired>() || error.is::<AbortConditionMet>() {
+        return Some(Outcome::Expired);
+    }
+
+    let refunded = error.is::<SwapFailedShouldRefund<hbit::Funded>>()
+        || error.is::<SwapFailedShouldRefund<herc20::Deployed>>();
+
+    if refunded {
+        Some(Outcome::Refunded)
+    } else {
+        Some(Outcome::Failed)
+    }
+}
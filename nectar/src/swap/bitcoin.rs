@@ -1,18 +1,31 @@
 use crate::swap::{hbit, LedgerTime};
 use comit::{
-    bitcoin::median_time_past,
+    bitcoin::{median_time_past, SatPerVbyte, FALLBACK_FEE_RATE, MAX_BUMPED_FEE_RATE},
     btsieve::{bitcoin::BitcoindConnector, BlockByHash, LatestBlock},
     Secret, Timestamp,
 };
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub use crate::bitcoin::Amount;
 pub use ::bitcoin::{secp256k1::SecretKey, Address, Block, BlockHash, OutPoint, Transaction};
 
+/// The expected time between two Bitcoin blocks.
+const BLOCK_TIME: Duration = Duration::from_secs(10 * 60);
+
+/// How often we check whether one of our redeem or refund transactions got
+/// confirmed.
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Debug, Clone)]
 pub struct Wallet {
     pub inner: Arc<crate::bitcoin::Wallet>,
     pub connector: Arc<comit::btsieve::bitcoin::BitcoindConnector>,
+    /// The number of blocks within which we want our redeem and refund
+    /// transactions to be confirmed.
+    pub fee_rate_target_blocks: u16,
 }
 
 #[async_trait::async_trait]
@@ -47,8 +60,9 @@ impl hbit::ExecuteRedeem for Wallet {
             params.transient_sk,
             redeem_address,
             secret,
-        )?;
-        let transaction = self.spend(action).await?;
+        );
+        // Once the HTLC expired, our counterparty can refund it.
+        let transaction = self.spend(action, Some(params.shared.expiry)).await?;
 
         Ok(hbit::Redeemed {
             transaction,
@@ -85,27 +99,218 @@ impl hbit::ExecuteRefund for Wallet {
             fund_event.location,
             params.transient_sk,
             refund_address,
-        )?;
-        let transaction = self.spend(action).await?;
+        );
+        let transaction = self.spend(action, None).await?;
 
         Ok(hbit::Refunded { transaction })
     }
 }
 
 impl Wallet {
+    /// Estimates the fee rate for spending from an HTLC within the given
+    /// number of blocks.
+    ///
+    /// Estimating may fail, for example on regtest where bitcoind lacks the
+    /// data to do so. We would rather spend the HTLC with a conservative fee
+    /// rate than not at all, hence we fall back to a default in this case.
+    async fn fee_rate(&self, target_blocks: u16) -> SatPerVbyte {
+        match self.inner.estimate_fee_rate(target_blocks).await {
+            Ok(fee_rate) => fee_rate,
+            Err(e) => {
+                tracing::warn!(
+                    "failed to estimate fee rate, falling back to {}: {:#}",
+                    FALLBACK_FEE_RATE,
+                    e
+                );
+                FALLBACK_FEE_RATE
+            }
+        }
+    }
+
+    /// Spends from an HTLC, signing the transaction with an estimated fee
+    /// rate.
+    ///
+    /// We keep replacing the transaction with ones paying a higher fee rate
+    /// until one of them is confirmed and return the one that was. If the
+    /// transaction has to be confirmed before the given expiry, we bump more
+    /// aggressively as the expiry gets close.
     async fn spend(
         &self,
-        action: hbit::BroadcastSignedTransaction,
+        action: hbit::SpendHtlc,
+        expiry: Option<Timestamp>,
     ) -> anyhow::Result<bitcoin::Transaction> {
-        let _txid = self
+        let fee_rate = self.fee_rate(self.fee_rate_target_blocks).await;
+        let signed = action.clone().sign(&crate::SECP, fee_rate)?;
+        self.broadcast(&signed).await?;
+
+        self.bump_fee_until_confirmed(signed, action, expiry).await
+    }
+
+    async fn broadcast(
+        &self,
+        action: &hbit::BroadcastSignedTransaction,
+    ) -> anyhow::Result<bitcoin::Txid> {
+        let txid = self
             .inner
             .send_raw_transaction(action.transaction.clone(), action.network.into())
             .await?;
 
-        Ok(action.transaction)
+        tracing::info!(
+            "broadcast transaction {} with a fee rate of {}",
+            txid,
+            action.fee_rate
+        );
+
+        Ok(txid)
+    }
+
+    /// How long we wait for a redeem or refund transaction to be confirmed
+    /// before we replace it with one paying a higher fee rate.
+    ///
+    /// This corresponds to the expected time for mining the number of blocks
+    /// we target when estimating the fee rate.
+    fn fee_bump_window(&self) -> Duration {
+        BLOCK_TIME * u32::from(self.fee_rate_target_blocks)
+    }
+
+    /// Whether the expiry is within the fee bump window, i.e. a transaction
+    /// that is to be confirmed before it needs to be confirmed soon.
+    async fn expires_soon(&self, expiry: Timestamp) -> bool {
+        match median_time_past(self.connector.as_ref()).await {
+            Ok(now) => {
+                u64::from(u32::from(now)) + self.fee_bump_window().as_secs()
+                    >= u64::from(u32::from(expiry))
+            }
+            Err(e) => {
+                tracing::warn!("failed to get median time past: {:#}", e);
+                false
+            }
+        }
+    }
+
+    /// Waits for one of the transactions spending the HTLC to be confirmed,
+    /// replacing the latest one with a higher fee rate whenever none got
+    /// confirmed within the fee bump window.
+    ///
+    /// Once the expiry is within the fee bump window, we replace the latest
+    /// transaction after every block instead and target the next block.
+    ///
+    /// The fee rate is capped at [`MAX_BUMPED_FEE_RATE`], once we reached it we keep
+    /// waiting for the last replacement to be confirmed.
+    async fn bump_fee_until_confirmed(
+        &self,
+        mut latest: hbit::BroadcastSignedTransaction,
+        action: hbit::SpendHtlc,
+        expiry: Option<Timestamp>,
+    ) -> anyhow::Result<bitcoin::Transaction> {
+        let mut broadcast = vec![latest.transaction.clone()];
+        let mut broadcast_at = Instant::now();
+
+        loop {
+            tokio::time::delay_for(CONFIRMATION_POLL_INTERVAL).await;
+
+            match self.confirmed(&broadcast).await {
+                Ok(Some(transaction)) => return Ok(transaction),
+                Ok(None) => {}
+                Err(e) if e.is::<HtlcSpentByOtherTransaction>() => return Err(e),
+                Err(e) => {
+                    tracing::warn!("failed to get confirmations of spend transactions: {:#}", e);
+                    continue;
+                }
+            }
+
+            let urgent = match expiry {
+                Some(expiry) => self.expires_soon(expiry).await,
+                None => false,
+            };
+            let (window, target_blocks) = if urgent {
+                (BLOCK_TIME, 1)
+            } else {
+                (self.fee_bump_window(), self.fee_rate_target_blocks)
+            };
+
+            if broadcast_at.elapsed() < window {
+                continue;
+            }
+
+            if latest.fee_rate >= MAX_BUMPED_FEE_RATE {
+                tracing::warn!(
+                    "transaction {} is not confirmed but its fee rate is already at the maximum of {}",
+                    latest.transaction.txid(),
+                    MAX_BUMPED_FEE_RATE
+                );
+                broadcast_at = Instant::now();
+                continue;
+            }
+
+            let estimate = self.fee_rate(target_blocks).await;
+            let bumped = std::cmp::min(latest.fee_rate.bump(estimate), MAX_BUMPED_FEE_RATE);
+            let result = async {
+                let signed = action.clone().sign(&crate::SECP, bumped)?;
+                self.broadcast(&signed).await?;
+
+                Ok::<_, anyhow::Error>(signed)
+            }
+            .await;
+
+            match result {
+                Ok(replacement) => {
+                    tracing::info!(
+                        "replaced unconfirmed transaction {} with {}",
+                        latest.transaction.txid(),
+                        replacement.transaction.txid()
+                    );
+                    broadcast.push(replacement.transaction.clone());
+                    broadcast_at = Instant::now();
+                    latest = replacement;
+                }
+                Err(e) => tracing::warn!(
+                    "failed to bump fee of {}: {:#}",
+                    latest.transaction.txid(),
+                    e
+                ),
+            }
+        }
+    }
+
+    /// Returns the transaction out of the given ones that got confirmed, if
+    /// any.
+    ///
+    /// All of them spend the same HTLC, hence at most one can be confirmed.
+    /// Should all of them conflict with a confirmed transaction, the HTLC was
+    /// spent by someone else.
+    async fn confirmed(
+        &self,
+        transactions: &[bitcoin::Transaction],
+    ) -> anyhow::Result<Option<bitcoin::Transaction>> {
+        let mut conflicted = 0;
+
+        for transaction in transactions {
+            let confirmations = self
+                .inner
+                .transaction_confirmations(transaction.txid())
+                .await?;
+
+            if confirmations > 0 {
+                return Ok(Some(transaction.clone()));
+            }
+            if confirmations < 0 {
+                conflicted += 1;
+            }
+        }
+
+        if conflicted == transactions.len() {
+            anyhow::bail!(HtlcSpentByOtherTransaction)
+        }
+
+        Ok(None)
     }
 }
 
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("the HTLC was spent by a transaction other than ours")]
+pub struct HtlcSpentByOtherTransaction;
+
 #[async_trait::async_trait]
 impl LatestBlock for Wallet {
     type Block = bitcoin::Block;
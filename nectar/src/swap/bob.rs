@@ -4,8 +4,10 @@
 //! component has to be prepared to execute actions using wallets.
 
 use crate::{
+    database::Commitment,
     swap::{
         action::try_do_it_once, bitcoin, ethereum, hbit, herc20, poll_beta_has_expired, Database,
+        SwapCancelled,
     },
     SwapId,
 };
@@ -24,12 +26,29 @@ pub struct Bob<AW, BW> {
     pub beta_expiry: Timestamp,
 }
 
+impl<AW, BW> Bob<AW, BW> {
+    /// Our first action on the beta ledger commits the swap to being funded,
+    /// unless the taker cancelled it before.
+    async fn commit_to_funding(&self) -> anyhow::Result<()> {
+        match self
+            .db
+            .commit_swap_to(self.swap_id, Commitment::Funding)
+            .await?
+        {
+            Commitment::Funding => Ok(()),
+            Commitment::Cancellation => Err(anyhow::Error::from(SwapCancelled)),
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl<AW> herc20::ExecuteDeploy for Bob<AW, ethereum::Wallet>
 where
     AW: Send + Sync,
 {
     async fn execute_deploy(&self, params: herc20::Params) -> anyhow::Result<herc20::Deployed> {
+        self.commit_to_funding().await?;
+
         let action = self.beta_wallet.execute_deploy(params);
         let poll_beta_has_expired = poll_beta_has_expired(&self.beta_wallet, self.beta_expiry);
 
@@ -126,6 +145,8 @@ where
     AW: Send + Sync,
 {
     async fn execute_fund(&self, params: &hbit::Params) -> anyhow::Result<hbit::Funded> {
+        self.commit_to_funding().await?;
+
         let action = self.beta_wallet.execute_fund(params);
         let poll_beta_has_expired = poll_beta_has_expired(&self.beta_wallet, self.beta_expiry);
 
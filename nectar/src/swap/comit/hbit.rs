@@ -5,7 +5,7 @@ use chrono::{DateTime, Utc};
 use comit::asset;
 
 pub use comit::{
-    actions::bitcoin::{BroadcastSignedTransaction, SendToAddress},
+    actions::bitcoin::{BroadcastSignedTransaction, SendToAddress, SpendHtlc},
     btsieve::{BlockByHash, LatestBlock},
     hbit::*,
     htlc_location, transaction, Secret, SecretHash, Timestamp,
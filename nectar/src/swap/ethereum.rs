@@ -21,7 +21,7 @@ pub struct Wallet {
 impl herc20::ExecuteDeploy for Wallet {
     async fn execute_deploy(&self, params: herc20::Params) -> anyhow::Result<herc20::Deployed> {
         let action = params.build_deploy_action();
-        let deployed_contract = self.inner.deploy_contract(action).await?;
+        let deployed_contract = self.inner.deploy_contract(action, params.expiry).await?;
 
         Ok(deployed_contract.into())
     }
@@ -36,7 +36,7 @@ impl herc20::ExecuteFund for Wallet {
         utc_start_of_swap: DateTime<Utc>,
     ) -> anyhow::Result<herc20::Funded> {
         let action = params.build_fund_action(deploy_event.location);
-        let _data = self.inner.call_contract(action).await?;
+        let _data = self.inner.call_contract(action, params.expiry).await?;
 
         let event = herc20::watch_for_funded(
             self.connector.as_ref(),
@@ -60,7 +60,7 @@ impl herc20::ExecuteRedeem for Wallet {
         utc_start_of_swap: DateTime<Utc>,
     ) -> anyhow::Result<herc20::Redeemed> {
         let action = params.build_redeem_action(deploy_event.location, secret);
-        let _data = self.inner.call_contract(action).await?;
+        let _data = self.inner.call_contract(action, params.expiry).await?;
 
         let event =
             herc20::watch_for_redeemed(self.connector.as_ref(), utc_start_of_swap, deploy_event)
@@ -89,8 +89,11 @@ impl herc20::ExecuteRefund for Wallet {
             tokio::time::delay_for(Duration::from_secs(1)).await;
         }
 
+        // Once we can refund, there is no deadline anymore but we still give up
+        // eventually to not hold on to the nonce forever
         let action = params.build_refund_action(deploy_event.location);
-        let _data = self.inner.call_contract(action).await?;
+        let deadline = Timestamp::now().plus(crate::ethereum::DEFAULT_MINING_DEADLINE_SECS);
+        let _data = self.inner.call_contract(action, deadline).await?;
 
         let event =
             herc20::watch_for_refunded(self.connector.as_ref(), utc_start_of_swap, deploy_event)
@@ -4,6 +4,7 @@ use clarity::{PrivateKey, Uint256};
 use comit::{
     actions::ethereum::DeployContract,
     asset::{Erc20, Erc20Quantity, Ether},
+    Timestamp,
 };
 use std::str::FromStr;
 use tempfile::TempDir;
@@ -131,7 +132,14 @@ impl<'c> Blockchain<'c> {
     ) -> anyhow::Result<()> {
         let _ = self
             .dev_account_wallet
-            .send_transaction(to, ether, Some(100_000), None, chain_id)
+            .send_transaction(
+                to,
+                ether,
+                Some(100_000),
+                None,
+                chain_id,
+                Timestamp::now().plus(ethereum::DEFAULT_MINING_DEADLINE_SECS),
+            )
             .await?;
 
         Ok(())
@@ -153,6 +161,7 @@ impl<'c> Blockchain<'c> {
                 Some(100_000),
                 Some(transfer),
                 chain_id,
+                Timestamp::now().plus(ethereum::DEFAULT_MINING_DEADLINE_SECS),
             )
             .await?;
 